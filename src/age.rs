@@ -0,0 +1,68 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encrypts and decrypts files in the age format, with the `age` program, for exports
+//! that people without Rooster can open.
+
+use super::safe_vec::SafeVec;
+use super::session;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Environment variable with the age program to use, `age` by default.
+pub const AGE_ENV_VAR: &'static str = "ROOSTER_AGE";
+
+fn age() -> Command {
+    session::command(env::var(AGE_ENV_VAR).unwrap_or_else(|_| "age".to_string()))
+}
+
+/// Runs age with `input` on stdin and returns what it printed on stdout. Its errors, and
+/// its passphrase prompt, go to the terminal.
+fn run(mut command: Command, input: &[u8]) -> Result<SafeVec, String> {
+    let mut child = try!(command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit()).spawn().map_err(|err| {
+        format!("I could not run age ({}), is it installed? See https://age-encryption.org", err)
+    }));
+    if let Some(mut stdin) = child.stdin.take() {
+        try!(stdin.write_all(input).map_err(|err| format!("I could not talk to age ({})", err)));
+    }
+    let output = try!(child.wait_with_output().map_err(|err| format!("I could not talk to age ({})", err)));
+    let stdout = SafeVec::new(output.stdout);
+    if !output.status.success() {
+        return Err(format!("age failed ({})", output.status));
+    }
+    Ok(stdout)
+}
+
+/// Encrypts data to age recipients, like `age1...` keys or SSH public keys, armored so
+/// that it can be pasted.
+pub fn encrypt(recipients: &[String], data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut command = age();
+    command.arg("--encrypt").arg("--armor");
+    for recipient in recipients.iter() {
+        command.arg("--recipient").arg(recipient);
+    }
+    run(command, data).map(|encrypted| encrypted.to_vec())
+}
+
+/// Decrypts an age file with an identity file, or with a passphrase age asks for if the
+/// file was encrypted with one.
+pub fn decrypt(identity: Option<&str>, encrypted: &[u8]) -> Result<SafeVec, String> {
+    let mut command = age();
+    command.arg("--decrypt");
+    if let Some(identity) = identity {
+        command.arg("--identity").arg(identity);
+    }
+    run(command, encrypted)
+}
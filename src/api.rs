@@ -0,0 +1,308 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::commands::CommandError;
+use super::commands::audit;
+use super::generate::PasswordSpec;
+use super::otp;
+use super::password::v2::{GenerationPolicy, Password, PasswordStore};
+use super::serde_json::{Map, Value};
+use super::safe_string::SafeString;
+use super::strength;
+use super::timestamp::Timestamp;
+use std::ops::Deref;
+
+/// Why an API call failed.
+pub enum Error {
+    NotFound(String),
+    AlreadyExists(String),
+    InvalidParams(String),
+    Failed(String),
+}
+
+impl Error {
+    pub fn message(&self) -> String {
+        match *self {
+            Error::NotFound(ref name) => format!("there is no app named {}", name),
+            Error::AlreadyExists(ref name) => format!("there is already an app named {}", name),
+            Error::InvalidParams(ref message) => message.clone(),
+            Error::Failed(ref message) => message.clone(),
+        }
+    }
+}
+
+fn summary(password: &Password) -> Value {
+    let mut object = Map::new();
+    object.insert("name".to_string(), Value::String(password.name.clone()));
+    object.insert("username".to_string(), Value::String(password.username.clone()));
+    object.insert("kind".to_string(), Value::String(password.kind().name.to_string()));
+    object.insert("url".to_string(), password.url.clone().map(Value::String).unwrap_or(Value::Null));
+    Value::Object(object)
+}
+
+fn string_param(params: &Value, name: &str) -> Result<String, Error> {
+    params.get(name)
+        .and_then(|value| value.as_str())
+        .map(|value| value.to_string())
+        .ok_or(Error::InvalidParams(format!("\"{}\" must be a string", name)))
+}
+
+/// A string param that may be left out.
+fn optional_string_param(params: &Value, name: &str) -> Result<Option<String>, Error> {
+    match params.get(name) {
+        None | Some(&Value::Null) => Ok(None),
+        Some(&Value::String(ref value)) => Ok(Some(value.clone())),
+        Some(_) => Err(Error::InvalidParams(format!("\"{}\" must be a string", name))),
+    }
+}
+
+/// A number param that may be left out.
+fn optional_number_param(params: &Value, name: &str) -> Result<Option<usize>, Error> {
+    match params.get(name) {
+        None | Some(&Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(|value| Some(value as usize)).ok_or(Error::InvalidParams(format!("\"{}\" must be a number", name))),
+    }
+}
+
+/// Puts the changed app in place of the app named `name`.
+fn replace(store: &mut PasswordStore, name: &str, password: Password) -> Result<(), Error> {
+    store.delete_password(name).and_then(|_| store.add_password(password)).map_err(|err| Error::Failed(format!("{:?}", err)))
+}
+
+/// Refuses passwords below the minimum strength score of the environment or the
+/// configuration file, since there is no `--enforce-strength` here.
+fn check_strength(secret: &str) -> Result<(), Error> {
+    let required_score = try!(strength::default_score().map_err(|err| match err {
+        CommandError::Failed(message) => Error::Failed(message),
+        _ => Error::Failed("I could not read the minimum strength".to_string()),
+    }));
+    match required_score {
+        Some(required) if strength::estimate(secret).score < required => {
+            Err(Error::InvalidParams(format!("the password is too weak, its strength score must be at least {}/4", required)))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// The apps, without their passwords.
+pub fn list(store: &PasswordStore) -> Value {
+    Value::Array(store.get_all_passwords().iter().map(summary).collect())
+}
+
+/// An app, with its password.
+pub fn get(store: &PasswordStore, name: &str) -> Result<Value, Error> {
+    let password = try!(store.get_password(name).ok_or(Error::NotFound(name.to_string())));
+    let mut json = summary(&password);
+    if let Value::Object(ref mut object) = json {
+        object.insert("password".to_string(), Value::String(password.password.deref().to_string()));
+    }
+    Ok(json)
+}
+
+/// Adds a login from an object with "name", "username", "password" and optionally "url".
+pub fn add(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let username = try!(string_param(params, "username"));
+    let secret = SafeString::new(try!(string_param(params, "password")));
+    if store.has_password(&name) {
+        return Err(Error::AlreadyExists(name));
+    }
+    try!(check_strength(secret.deref()));
+
+    let mut password = Password::new(name.clone(), username, secret);
+    password.url = params.get("url").and_then(|url| url.as_str()).map(|url| url.to_string());
+    let json = summary(&password);
+    try!(store.add_password(password).map_err(|_| Error::AlreadyExists(name)));
+    Ok(json)
+}
+
+/// Deletes an app, from an object with "name".
+pub fn delete(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let password = try!(store.delete_password(&name).map_err(|_| Error::NotFound(name)));
+    Ok(summary(&password))
+}
+
+/// Changes the password of an app, from an object with "name" and "password". The
+/// previous password goes to the history.
+pub fn set_password(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let secret = SafeString::new(try!(string_param(params, "password")));
+    let mut password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    try!(check_strength(secret.deref()));
+    password.replace_password(secret);
+    let json = summary(&password);
+    try!(replace(store, &name, password));
+    Ok(json)
+}
+
+/// Changes the username, URL or notes of an app, from an object with "name" and any of
+/// "username", "url" and "notes". An empty URL or empty notes are removed.
+pub fn edit(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let mut password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    if let Some(username) = try!(optional_string_param(params, "username")) {
+        password.username = username;
+    }
+    if let Some(url) = try!(optional_string_param(params, "url")) {
+        password.url = if url.is_empty() { None } else { Some(url) };
+    }
+    if let Some(notes) = try!(optional_string_param(params, "notes")) {
+        password.notes = if notes.is_empty() { None } else { Some(SafeString::new(notes)) };
+    }
+    password.updated_at = Timestamp::now();
+    let json = summary(&password);
+    try!(replace(store, &name, password));
+    Ok(json)
+}
+
+/// Renames an app, from an object with "name" and "new_name".
+pub fn rename(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let new_name = try!(string_param(params, "new_name"));
+    let mut password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    // Changing the case of a name is a rename too.
+    if new_name.to_lowercase() != password.name.to_lowercase() && store.has_password(&new_name) {
+        return Err(Error::AlreadyExists(new_name));
+    }
+    password.name = new_name;
+    password.updated_at = Timestamp::now();
+    let json = summary(&password);
+    try!(replace(store, &name, password));
+    Ok(json)
+}
+
+/// The tags of an app, from an object with "name", after setting them to "tags", a list
+/// of strings, if it is there. An empty list removes them.
+pub fn tags(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let mut password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    if let Some(tags) = params.get("tags") {
+        let invalid = || Error::InvalidParams("\"tags\" must be a list of strings".to_string());
+        let mut list = Vec::new();
+        for tag in try!(tags.as_array().ok_or_else(|| invalid())).iter() {
+            let tag = try!(tag.as_str().ok_or_else(|| invalid())).trim().to_string();
+            if !tag.is_empty() && !list.contains(&tag) {
+                list.push(tag);
+            }
+        }
+        password.tags = if list.is_empty() { None } else { Some(list) };
+        password.updated_at = Timestamp::now();
+        try!(replace(store, &name, password.clone()));
+    }
+    Ok(Value::Array(password.tags.unwrap_or(Vec::new()).into_iter().map(Value::String).collect()))
+}
+
+/// Adds a login with a generated password, from an object with "name", "username" and
+/// optionally "length", "alnum" and "words", like `rooster generate`. Returns the app
+/// with its password.
+pub fn generate(store: &mut PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let username = try!(string_param(params, "username"));
+    if store.has_password(&name) {
+        return Err(Error::AlreadyExists(name));
+    }
+
+    let length = try!(optional_number_param(params, "length"));
+    let words = try!(optional_number_param(params, "words"));
+    let alnum = params.get("alnum").and_then(|alnum| alnum.as_bool()).unwrap_or(false);
+    // The same limits as the options of `rooster generate`.
+    if length.map_or(false, |length| length < 4) {
+        return Err(Error::InvalidParams("\"length\" must be at least 4".to_string()));
+    }
+    if words.map_or(false, |words| words < 4) {
+        return Err(Error::InvalidParams("\"words\" must be at least 4".to_string()));
+    }
+    let policy = GenerationPolicy {
+        alnum: alnum,
+        length: length.unwrap_or(32),
+        words: words,
+        separator: None,
+        wordlist: None,
+        no_ambiguous: false,
+        charset: None,
+        require_each_class: false,
+    };
+    let spec = try!(PasswordSpec::from_policy(&policy).ok_or(Error::Failed("I could not load the word list".to_string())));
+    let secret = try!(spec.generate().map_err(|err| Error::Failed(format!("I could not generate the password ({})", err))));
+
+    let mut password = Password::new(name.clone(), username, SafeString::new(secret));
+    // Remember custom rules, so `rooster regenerate` follows them too.
+    if length.is_some() || words.is_some() || alnum {
+        password.generation_policy = Some(policy);
+    }
+    try!(store.add_password(password).map_err(|_| Error::AlreadyExists(name.clone())));
+    get(store, &name)
+}
+
+/// The weak, reused and similar passwords, like `rooster audit` without --pwned-db: an
+/// object with "weak", a list of apps with their strength "score", and "reused" and
+/// "similar", lists of groups of app names.
+pub fn audit(store: &PasswordStore) -> Value {
+    let passwords = store.get_all_passwords();
+    let findings = audit::findings(passwords);
+    let groups = |clusters: &[Vec<usize>]| -> Value {
+        Value::Array(clusters.iter().map(|cluster| {
+            Value::Array(cluster.iter().map(|&i| Value::String(passwords[i].name.clone())).collect())
+        }).collect())
+    };
+
+    let mut object = Map::new();
+    object.insert("weak".to_string(), Value::Array(findings.weak.iter().map(|&(i, score)| {
+        let mut weak = Map::new();
+        weak.insert("name".to_string(), Value::String(passwords[i].name.clone()));
+        weak.insert("score".to_string(), Value::from(score));
+        Value::Object(weak)
+    }).collect()));
+    object.insert("reused".to_string(), groups(&findings.reused));
+    object.insert("similar".to_string(), groups(&findings.similar));
+    Value::Object(object)
+}
+
+/// One field of an app, from an object with "name" and "field", see `Password::field`.
+pub fn field(store: &PasswordStore, params: &Value) -> Result<Value, Error> {
+    let name = try!(string_param(params, "name"));
+    let field = try!(string_param(params, "field"));
+    let password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    let value = try!(password.field(&field).ok_or(Error::InvalidParams(format!("{} has no field named {}", name, field))));
+    Ok(Value::String(value.deref().to_string()))
+}
+
+/// The current one-time password of an app, from an object with "name", and whether the
+/// store changed: counter-based codes move the counter forward.
+pub fn one_time_password(store: &mut PasswordStore, params: &Value) -> Result<(Value, bool), Error> {
+    let name = try!(string_param(params, "name"));
+    let mut password = try!(store.get_password(&name).ok_or(Error::NotFound(name.clone())));
+    let (code, seconds_left) = {
+        let secret = try!(password.otp.as_mut().ok_or(Error::InvalidParams(format!("{} has no one-time password", name))));
+        let (code, counter) = try!(otp::generate(secret).map_err(Error::Failed));
+        let seconds_left = match secret.counter {
+            Some(_) => {
+                secret.counter = Some(counter + 1);
+                None
+            },
+            None => Some(otp::seconds_left(secret.period)),
+        };
+        (code, seconds_left)
+    };
+    if seconds_left.is_none() {
+        try!(replace(store, &name, password));
+    }
+
+    let mut object = Map::new();
+    object.insert("code".to_string(), Value::String(code));
+    object.insert("seconds_left".to_string(), seconds_left.map(Value::from).unwrap_or(Value::Null));
+    Ok((Value::Object(object), seconds_left.is_none()))
+}
@@ -0,0 +1,113 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::safe_string::SafeString;
+use super::session;
+use std::env;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::process::Stdio;
+
+/// Something to do with the virtual keyboard.
+pub enum Action {
+    /// Types text, which may be secret.
+    Type(SafeString),
+    /// Presses a key by its name, like "Tab" or "Return".
+    Key(&'static str),
+}
+
+/// Keys that sequences may press, by their name in sequences and in xdotool and wtype.
+pub static KEYS: &'static [(&'static str, &'static str)] = &[
+    ("TAB", "Tab"),
+    ("ENTER", "Return"),
+    ("SPACE", "space"),
+    ("BACKSPACE", "BackSpace"),
+    ("ESCAPE", "Escape"),
+];
+
+/// Tools that emulate a keyboard, for the display server in use.
+enum Backend {
+    /// xdotool, for X11.
+    Xdotool,
+    /// wtype, for Wayland compositors with the virtual keyboard protocol.
+    Wtype,
+}
+
+fn backend() -> Backend {
+    match env::var("WAYLAND_DISPLAY") {
+        Ok(ref display) if !display.is_empty() => Backend::Wtype,
+        _ => Backend::Xdotool,
+    }
+}
+
+fn check_status(program: &str, status: ::std::process::ExitStatus) -> IoResult<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(IoError::new(IoErrorKind::Other, format!("{} exited with {}", program, status)))
+    }
+}
+
+/// Types text by piping it to the tool, so that it never shows up in the process list.
+fn type_text(text: &str) -> IoResult<()> {
+    let (program, args): (&str, &[&str]) = match backend() {
+        Backend::Xdotool => ("xdotool", &["type", "--clearmodifiers", "--file", "-"]),
+        Backend::Wtype => ("wtype", &["-"]),
+    };
+    let mut child = try!(
+        session::command(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    );
+    {
+        let stdin = match child.stdin.as_mut() {
+            Some(stdin) => stdin,
+            None => { return Err(IoError::new(IoErrorKind::Other, format!("no stdin for {}", program))); }
+        };
+        try!(stdin.write_all(text.as_bytes()));
+    }
+    drop(child.stdin.take());
+    let status = try!(child.wait());
+    check_status(program, status)
+}
+
+fn press_key(key: &str) -> IoResult<()> {
+    let (program, args) = match backend() {
+        Backend::Xdotool => ("xdotool", vec!["key", "--clearmodifiers", key]),
+        Backend::Wtype => ("wtype", vec!["-k", key]),
+    };
+    let status = try!(
+        session::command(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    );
+    check_status(program, status)
+}
+
+/// Performs the actions in the window that has the focus.
+pub fn perform(actions: &[Action]) -> IoResult<()> {
+    for action in actions.iter() {
+        match *action {
+            Action::Type(ref text) => try!(type_text(text)),
+            Action::Key(key) => try!(press_key(key)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::backup;
+use super::crypto::digest::Digest;
+use super::crypto::sha2::Sha256;
+use super::webdav::WebDav;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, stdout, Read, Write, Seek, SeekFrom, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
+use std::path::PathBuf;
+use std::process;
+
+/// Where the encrypted password file lives. The password store only deals with
+/// bytes, backends deal with getting them from and to their home. Backends can be
+/// sent to another thread, to read the file while the master password is typed.
+pub trait VaultBackend: Send {
+    /// Reads the whole password file. Nothing at all means a new password file.
+    fn read(&mut self) -> IoResult<Vec<u8>>;
+
+    /// Replaces the whole password file.
+    fn write(&mut self, content: &[u8]) -> IoResult<()>;
+
+    /// Whether the password file changed since the last `read` or `write`, for
+    /// instance because a sync tool like Dropbox replaced it.
+    fn has_changed(&mut self) -> IoResult<bool>;
+}
+
+fn hash(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+/// Whether the password file path is a URL rather than a local path.
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// The password file path that means stdin and stdout, for pipelines.
+pub const STDIO_PATH: &'static str = "-";
+
+/// Whether the password file is read from stdin and written to stdout.
+pub fn is_stdio(path: &str) -> bool {
+    path == STDIO_PATH
+}
+
+/// A password file read from stdin, and written to stdout. It never touches the disk.
+pub struct StdioBackend {
+    /// Stdin can only be read once.
+    content: Option<Vec<u8>>,
+}
+
+impl StdioBackend {
+    pub fn new() -> StdioBackend {
+        StdioBackend {
+            content: None,
+        }
+    }
+}
+
+impl VaultBackend for StdioBackend {
+    fn read(&mut self) -> IoResult<Vec<u8>> {
+        if let Some(ref content) = self.content {
+            return Ok(content.clone());
+        }
+        let mut content = Vec::new();
+        try!(stdin().read_to_end(&mut content));
+        self.content = Some(content.clone());
+        Ok(content)
+    }
+
+    fn write(&mut self, content: &[u8]) -> IoResult<()> {
+        let mut stdout = stdout();
+        try!(stdout.write_all(content));
+        try!(stdout.flush());
+        self.content = Some(content.to_vec());
+        Ok(())
+    }
+
+    fn has_changed(&mut self) -> IoResult<bool> {
+        Ok(false)
+    }
+}
+
+/// The password file path of `--ephemeral`, a vault that only lives in memory.
+pub const EPHEMERAL_PATH: &'static str = ":ephemeral:";
+
+/// Whether the vault only lives in memory and is never saved.
+pub fn is_ephemeral(path: &str) -> bool {
+    path == EPHEMERAL_PATH
+}
+
+/// A vault that starts empty and is gone when Rooster exits. It refuses to be saved.
+pub struct EphemeralBackend;
+
+impl VaultBackend for EphemeralBackend {
+    fn read(&mut self) -> IoResult<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn write(&mut self, _content: &[u8]) -> IoResult<()> {
+        Err(IoError::new(IoErrorKind::Other, "an ephemeral vault is never saved"))
+    }
+
+    fn has_changed(&mut self) -> IoResult<bool> {
+        Ok(false)
+    }
+}
+
+/// A password file on the local file system.
+pub struct FileBackend {
+    path: PathBuf,
+    file: File,
+    /// Hash of the content we last read or wrote.
+    hash: Option<String>,
+}
+
+impl FileBackend {
+    pub fn new(path: PathBuf, file: File) -> FileBackend {
+        FileBackend {
+            path: path,
+            file: file,
+            hash: None,
+        }
+    }
+}
+
+impl VaultBackend for FileBackend {
+    fn read(&mut self) -> IoResult<Vec<u8>> {
+        let mut content = Vec::new();
+        try!(self.file.seek(SeekFrom::Start(0)));
+        try!(self.file.read_to_end(&mut content));
+        self.hash = Some(hash(&content));
+        Ok(content)
+    }
+
+    fn write(&mut self, content: &[u8]) -> IoResult<()> {
+        // What the file held goes to a backup first.
+        let mut previous = Vec::new();
+        try!(self.file.seek(SeekFrom::Start(0)).and_then(|_| self.file.read_to_end(&mut previous)));
+        if let Err(err) = backup::keep(&self.path, &previous) {
+            println_warn!("I could not back up the password file to {} ({}), saving it anyway.", backup::dir(&self.path).display(), err);
+        }
+
+        // Reset the file pointer.
+        try!(self.file.seek(SeekFrom::Start(0)).and_then(|_| self.file.set_len(0)));
+        try!(self.file.write_all(content));
+        try!(self.file.sync_all());
+        self.hash = Some(hash(content));
+        Ok(())
+    }
+
+    fn has_changed(&mut self) -> IoResult<bool> {
+        // Sync tools usually replace the file rather than write to it, so we look at
+        // what is at the path now, not at the file we opened.
+        let mut content = Vec::new();
+        match File::open(&self.path).and_then(|mut file| file.read_to_end(&mut content)) {
+            Ok(_) => {},
+            Err(ref err) if err.kind() == IoErrorKind::NotFound => {},
+            Err(err) => return Err(err),
+        }
+        if self.hash.as_ref() == Some(&hash(&content)) {
+            return Ok(false);
+        }
+
+        // From now on, reads and writes go to the new file.
+        self.file = try!(OpenOptions::new().read(true).write(true).create(true).open(&self.path));
+        Ok(true)
+    }
+}
+
+/// A password file on a WebDAV server. Writes only go through if nobody else
+/// changed the file since we read it.
+pub struct WebDavBackend {
+    webdav: WebDav,
+    etag: Option<String>,
+}
+
+impl WebDavBackend {
+    pub fn new(webdav: WebDav) -> WebDavBackend {
+        WebDavBackend {
+            webdav: webdav,
+            etag: None,
+        }
+    }
+
+    /// curl reads and writes files, so the password file goes through a temporary one.
+    fn temp_path(&self) -> PathBuf {
+        env::temp_dir().join(format!("rooster-{}.tmp", process::id()))
+    }
+}
+
+fn other_error(err: String) -> IoError {
+    IoError::new(IoErrorKind::Other, err)
+}
+
+impl VaultBackend for WebDavBackend {
+    fn read(&mut self) -> IoResult<Vec<u8>> {
+        let path = self.temp_path();
+        let response = self.webdav.get(&path, None).map_err(other_error);
+        let mut content = Vec::new();
+        let result = response.and_then(|response| {
+            match response.status {
+                200 => {
+                    self.etag = response.etag;
+                    File::open(&path).and_then(|mut file| file.read_to_end(&mut content)).map(|_| ())
+                },
+                // There is no password file yet, the first write creates it.
+                404 => Ok(()),
+                status => Err(other_error(format!("the server answered with HTTP {}", status))),
+            }
+        });
+        let _ = fs::remove_file(&path);
+        result.map(|_| content)
+    }
+
+    fn write(&mut self, content: &[u8]) -> IoResult<()> {
+        let path = self.temp_path();
+        let response = File::create(&path)
+            .and_then(|mut file| file.write_all(content))
+            .and_then(|_| self.webdav.put(&path, self.etag.as_ref().map(|etag| &etag[..])).map_err(other_error));
+        let _ = fs::remove_file(&path);
+        match try!(response) {
+            ref response if response.status >= 200 && response.status < 300 => {
+                self.etag = response.etag.clone();
+                Ok(())
+            },
+            ref response if response.status == 412 => {
+                Err(other_error("the password file changed on the server in the meantime".to_string()))
+            },
+            response => Err(other_error(format!("the server answered with HTTP {}", response.status))),
+        }
+    }
+
+    fn has_changed(&mut self) -> IoResult<bool> {
+        let etag = match self.etag {
+            Some(ref etag) => etag.clone(),
+            // Without an ETag, the conditional write will tell.
+            None => return Ok(false),
+        };
+        let path = self.temp_path();
+        let response = self.webdav.get(&path, Some(&etag)).map_err(other_error);
+        let _ = fs::remove_file(&path);
+        match try!(response).status {
+            304 => Ok(false),
+            200 | 404 => Ok(true),
+            status => Err(other_error(format!("the server answered with HTTP {}", status))),
+        }
+    }
+}
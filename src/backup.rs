@@ -0,0 +1,127 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rotating backups of password files on disk. Before a password file is replaced, what
+//! it held is copied to a directory next to it, in a file named after the time of the
+//! copy, and only the newest copies are kept. `--as-of` opens one of them.
+
+use super::config;
+use super::timestamp::Timestamp;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{Result as IoResult, Write, ErrorKind as IoErrorKind};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+/// How many backups are kept when the configuration file does not say.
+const KEEP_DEFAULT: usize = 10;
+
+/// Extension of the names of backup directories.
+const DIR_SUFFIX: &'static str = ".backups";
+
+/// The backups sit in a directory next to the password file.
+pub fn dir(password_file: &Path) -> PathBuf {
+    let mut name = password_file.as_os_str().to_os_string();
+    name.push(DIR_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Whether the password file is one of the backups of another one.
+pub fn is_backup(path: &str) -> bool {
+    Path::new(path).parent()
+        .and_then(|parent| parent.file_name())
+        .map_or(false, |name| name.to_string_lossy().ends_with(DIR_SUFFIX))
+}
+
+/// Only the user may read the backups.
+#[cfg(unix)]
+fn dir_builder() -> DirBuilder {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true).mode(0o700);
+    builder
+}
+
+#[cfg(windows)]
+fn dir_builder() -> DirBuilder {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    builder
+}
+
+#[cfg(unix)]
+fn file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true).mode(0o600);
+    options
+}
+
+#[cfg(windows)]
+fn file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// The backups of the password file, oldest first.
+pub fn list(password_file: &Path) -> Vec<(Timestamp, PathBuf)> {
+    let mut backups: Vec<(Timestamp, PathBuf)> = match fs::read_dir(dir(password_file)) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let seconds = match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("rooster") => path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u64>().ok()),
+                    _ => None,
+                };
+                seconds.map(|seconds| (Timestamp::from_secs(seconds), path))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    backups.sort_by(|a, b| a.0.cmp(&b.0));
+    backups
+}
+
+/// Copies what the password file held before it is replaced, then deletes the oldest
+/// backups beyond the number the configuration file says to keep.
+pub fn keep(password_file: &Path, content: &[u8]) -> IoResult<()> {
+    let keep = config::load().ok().and_then(|config| config.backups).unwrap_or(KEEP_DEFAULT);
+    if keep == 0 || content.is_empty() {
+        return Ok(());
+    }
+
+    let dir = dir(password_file);
+    try!(dir_builder().create(&dir));
+    // Of two saves in the same second, the backup of the first has the older content.
+    let path = dir.join(format!("{}.rooster", Timestamp::now().as_secs()));
+    match file_options().open(&path) {
+        Ok(mut file) => {
+            try!(file.write_all(content));
+            try!(file.sync_all());
+        },
+        Err(ref err) if err.kind() == IoErrorKind::AlreadyExists => {},
+        Err(err) => { return Err(err); },
+    }
+
+    let backups = list(password_file);
+    let extra = backups.len().saturating_sub(keep);
+    for &(_, ref path) in backups[..extra].iter() {
+        try!(fs::remove_file(path));
+    }
+    Ok(())
+}
+
+/// The newest backup of the password file from before the time.
+pub fn newest_before(password_file: &Path, before: Timestamp) -> Option<(Timestamp, PathBuf)> {
+    list(password_file).into_iter().filter(|&(at, _)| at < before).last()
+}
@@ -0,0 +1,67 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+const ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Decodes base32 (RFC 4648) as people type it: any case, with spaces and padding.
+pub fn decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+    for c in input.chars() {
+        if c == ' ' || c == '-' || c == '=' {
+            continue;
+        }
+        if c as u32 > 0x7f {
+            return None;
+        }
+        let upper = (c as u8).to_ascii_uppercase();
+        let value = match ALPHABET.iter().position(|&b| b == upper) {
+            Some(value) => value as u64,
+            None => { return None; }
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if output.is_empty() {
+        return None;
+    }
+    Some(output)
+}
+
+/// Encodes bytes as base32 (RFC 4648), with padding.
+pub fn encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity((input.len() + 4) / 5 * 8);
+    for chunk in input.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        // Each 5 bytes make 8 characters, partial chunks make fewer plus padding.
+        let chars = (chunk.len() * 8 + 4) / 5;
+        for i in 0..8 {
+            if i < chars {
+                output.push(ALPHABET[((bits >> (35 - 5 * i)) & 0x1f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
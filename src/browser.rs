@@ -0,0 +1,49 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::session;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::process::Stdio;
+
+/// Program that opens a URL in the default browser, and its arguments before the URL.
+#[cfg(target_os = "macos")]
+static OPEN_PROGRAM: &'static [&'static str] = &["open"];
+
+/// Program that opens a URL in the default browser, and its arguments before the URL.
+#[cfg(all(unix, not(target_os = "macos")))]
+static OPEN_PROGRAM: &'static [&'static str] = &["xdg-open"];
+
+/// Program that opens a URL in the default browser, and its arguments before the URL.
+/// Unlike `cmd /C start`, it does not treat & in the URL as a command separator.
+#[cfg(windows)]
+static OPEN_PROGRAM: &'static [&'static str] = &["rundll32", "url.dll,FileProtocolHandler"];
+
+/// Opens the URL in the default browser.
+pub fn open(url: &str) -> IoResult<()> {
+    let status = try!(
+        session::command(OPEN_PROGRAM[0])
+            .args(&OPEN_PROGRAM[1..])
+            .arg(url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    );
+    if status.success() {
+        Ok(())
+    } else {
+        Err(IoError::new(IoErrorKind::Other, format!("{} exited with {}", OPEN_PROGRAM[0], status)))
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::rustc_serialize::base64::{ToBase64, STANDARD};
+use super::session;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::process::Stdio;
+
+/// Environment variable to choose how to copy: "osc52" to have the terminal do it, which
+/// also works over SSH, or unset to use the clipboard programs.
+pub const CLIPBOARD_ENV_VAR: &'static str = "ROOSTER_CLIPBOARD";
+
+/// Programs that read the clipboard content on stdin, tried in order.
+#[cfg(target_os = "macos")]
+static CLIPBOARD_PROGRAMS: &'static [&'static [&'static str]] = &[
+    &["pbcopy"],
+];
+
+/// Programs that read the clipboard content on stdin, tried in order.
+#[cfg(all(unix, not(target_os = "macos")))]
+static CLIPBOARD_PROGRAMS: &'static [&'static [&'static str]] = &[
+    &["xsel", "--clipboard", "--input"],
+    &["xclip", "-selection", "clipboard"],
+];
+
+/// Programs that read the clipboard content on stdin under Wayland, tried first.
+#[cfg(all(unix, not(target_os = "macos")))]
+static WAYLAND_CLIPBOARD_PROGRAMS: &'static [&'static [&'static str]] = &[
+    &["wl-copy"],
+];
+
+#[cfg(target_os = "macos")]
+fn clipboard_programs() -> Vec<&'static [&'static str]> {
+    CLIPBOARD_PROGRAMS.to_vec()
+}
+
+/// The clipboard programs for the current session, Wayland ones first if it is one.
+/// X11 programs are kept as a fallback, since Wayland sessions usually run XWayland.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn clipboard_programs() -> Vec<&'static [&'static str]> {
+    let mut programs = Vec::new();
+    match env::var("WAYLAND_DISPLAY") {
+        Ok(ref display) if !display.is_empty() => programs.extend(WAYLAND_CLIPBOARD_PROGRAMS.iter().cloned()),
+        _ => {}
+    }
+    programs.extend(CLIPBOARD_PROGRAMS.iter().cloned());
+    programs
+}
+
+/// The terminal, even when stdout is piped.
+#[cfg(unix)]
+static TERMINAL: &'static str = "/dev/tty";
+
+/// The terminal, even when stdout is piped.
+#[cfg(windows)]
+static TERMINAL: &'static str = "CONOUT$";
+
+fn use_osc52() -> bool {
+    match env::var(CLIPBOARD_ENV_VAR) {
+        Ok(mode) => mode == "osc52",
+        Err(_) => false,
+    }
+}
+
+/// The OSC 52 escape sequence that sets the clipboard, wrapped for tmux if needed.
+fn osc52_sequence(text: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", text.as_bytes().to_base64(STANDARD));
+    match env::var("TMUX") {
+        Ok(_) => format!("\x1bPtmux;\x1b{}\x1b\\", sequence),
+        Err(_) => sequence,
+    }
+}
+
+/// Asks the terminal to copy the text, writing to the terminal rather than stdout so
+/// that it works when the output is piped.
+fn copy_osc52(text: &str) -> IoResult<()> {
+    let mut tty = try!(OpenOptions::new().write(true).open(TERMINAL));
+    try!(tty.write_all(osc52_sequence(text).as_bytes()));
+    tty.flush()
+}
+
+#[cfg(unix)]
+fn copy_with(program: &[&str], text: &str) -> IoResult<()> {
+    let mut child = try!(
+        session::command(program[0])
+            .args(&program[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    );
+    {
+        let stdin = match child.stdin.as_mut() {
+            Some(stdin) => stdin,
+            None => { return Err(IoError::new(IoErrorKind::Other, "no stdin for the clipboard program")); }
+        };
+        try!(stdin.write_all(text.as_bytes()));
+    }
+    // Close stdin so the program knows the content is complete.
+    drop(child.stdin.take());
+
+    let status = try!(child.wait());
+    if status.success() {
+        Ok(())
+    } else {
+        Err(IoError::new(IoErrorKind::Other, format!("{} exited with {}", program[0], status)))
+    }
+}
+
+/// Copies text to the clipboard with the first clipboard program that works.
+#[cfg(unix)]
+fn copy_native(text: &str) -> IoResult<()> {
+    let mut last_error = IoError::new(IoErrorKind::NotFound, "no clipboard program found");
+    for program in clipboard_programs().iter() {
+        match copy_with(program, text) {
+            Ok(()) => { return Ok(()); },
+            Err(err) => { last_error = err; }
+        }
+    }
+    Err(last_error)
+}
+
+/// Copies text to the clipboard with the Windows API, as UTF-16 so that any character
+/// gets through, unlike with clip.exe.
+#[cfg(windows)]
+fn copy_native(text: &str) -> IoResult<()> {
+    use super::winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use super::winapi::um::winuser::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData, CF_UNICODETEXT};
+    use std::ptr;
+
+    let mut wide: Vec<u16> = text.encode_utf16().collect();
+    wide.push(0);
+    unsafe {
+        if OpenClipboard(ptr::null_mut()) == 0 {
+            return Err(IoError::last_os_error());
+        }
+        let result = (|| {
+            if EmptyClipboard() == 0 {
+                return Err(IoError::last_os_error());
+            }
+            let memory = GlobalAlloc(GMEM_MOVEABLE, wide.len() * 2);
+            if memory.is_null() {
+                return Err(IoError::last_os_error());
+            }
+            let locked = GlobalLock(memory) as *mut u16;
+            if locked.is_null() {
+                let err = IoError::last_os_error();
+                GlobalFree(memory);
+                return Err(err);
+            }
+            ptr::copy_nonoverlapping(wide.as_ptr(), locked, wide.len());
+            GlobalUnlock(memory);
+            // Once set, the memory belongs to the clipboard and must not be freed.
+            if SetClipboardData(CF_UNICODETEXT, memory as _).is_null() {
+                let err = IoError::last_os_error();
+                GlobalFree(memory);
+                return Err(err);
+            }
+            Ok(())
+        })();
+        CloseClipboard();
+        // Our copy of the text is not needed anymore.
+        for c in wide.iter_mut() {
+            *c = 0;
+        }
+        result
+    }
+}
+
+/// Copies text to the clipboard, or through the terminal in OSC 52 mode.
+pub fn copy(text: &str) -> IoResult<()> {
+    if use_osc52() {
+        return copy_osc52(text);
+    }
+    copy_native(text)
+}
+
+/// Shell command that shows a desktop notification once the clipboard is cleared.
+#[cfg(target_os = "macos")]
+static NOTIFY_COMMAND: &'static str =
+    "osascript -e 'display notification \"Your clipboard was cleared.\" with title \"Rooster\"'";
+
+/// Shell command that shows a desktop notification once the clipboard is cleared.
+#[cfg(all(unix, not(target_os = "macos")))]
+static NOTIFY_COMMAND: &'static str = "notify-send Rooster 'Your clipboard was cleared.'";
+
+/// Empties the clipboard after a delay, from a background shell so that Rooster can
+/// exit right away. The clipboard is cleared even if something else was copied since.
+///
+/// Once done, a desktop notification confirms it. Without a notification daemon, the
+/// confirmation goes to the terminal instead.
+#[cfg(unix)]
+pub fn clear_after(seconds: u64) -> IoResult<()> {
+    let clear = if use_osc52() {
+        format!("printf '%s' '{}' > /dev/tty", osc52_sequence(""))
+    } else {
+        clipboard_programs().iter()
+            .map(|program| format!("{} < /dev/null", program.join(" ")))
+            .collect::<Vec<String>>()
+            .join(" || ")
+    };
+    let notify = format!(
+        "{{ {} ; }} 2> /dev/null || echo 'Rooster: your clipboard was cleared.' >&2",
+        NOTIFY_COMMAND
+    );
+    try!(
+        session::command("sh")
+            .arg("-c")
+            .arg(format!("sleep {}; {{ {} ; }} 2> /dev/null; {}", seconds, clear, notify))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+    );
+    Ok(())
+}
+
+/// Empties the clipboard after a delay, from a background command prompt so that
+/// Rooster can exit right away. The confirmation goes to the terminal.
+///
+/// In OSC 52 mode the clipboard is not cleared, since the background process has no
+/// terminal to write to.
+#[cfg(windows)]
+pub fn clear_after(seconds: u64) -> IoResult<()> {
+    if use_osc52() {
+        return Ok(());
+    }
+    // There is no sleep command, but ping waits about one second between attempts.
+    try!(
+        session::command("cmd")
+            .arg("/C")
+            .arg(format!(
+                "ping -n {} 127.0.0.1 > NUL & echo off | clip & echo Rooster: your clipboard was cleared. 1>&2",
+                seconds + 1
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+    );
+    Ok(())
+}
@@ -12,23 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::ffi;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 // static COLOR_BLACK: &'static str   = "\x1b[30m";
 static COLOR_RED: &'static str     = "\x1b[31m";
 static COLOR_GREEN: &'static str   = "\x1b[32m";
-// static COLOR_YELLOW: &'static str  = "\x1b[33m";
-// static COLOR_BLUE: &'static str    = "\x1b[34m";
+static COLOR_YELLOW: &'static str  = "\x1b[33m";
+static COLOR_BLUE: &'static str    = "\x1b[34m";
 // static COLOR_MAGENTA: &'static str = "\x1b[35m";
-// static COLOR_CYAN: &'static str    = "\x1b[36m";
+static COLOR_CYAN: &'static str    = "\x1b[36m";
 // static COLOR_WHITE: &'static str   = "\x1b[37m";
+static COLOR_RESET: &'static str   = "\x1b[39m";
+
+/// Whether the user wants colors at all. Even then, only terminals get them.
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
 
 pub enum Color {
     // Black,
     Red,
     Green,
-    // Yellow,
-    // Blue,
+    Yellow,
+    Blue,
     // Magenta,
-    // Cyan,
+    Cyan,
     // White,
 }
 
@@ -38,11 +46,41 @@ impl Color {
             // Color::Black   => COLOR_BLACK,
             Color::Red     => COLOR_RED,
             Color::Green   => COLOR_GREEN,
-            // Color::Yellow  => COLOR_YELLOW,
-            // Color::Blue    => COLOR_BLUE,
+            Color::Yellow  => COLOR_YELLOW,
+            Color::Blue    => COLOR_BLUE,
             // Color::Magenta => COLOR_MAGENTA,
-            // Color::Cyan    => COLOR_CYAN,
+            Color::Cyan    => COLOR_CYAN,
             // Color::White   => COLOR_WHITE,
         }
     }
 }
+
+/// Where colored text goes, since only one of them may be a terminal.
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Turns colors off for `--no-color`, "color": false in the configuration file, or
+/// the $NO_COLOR environment variable (https://no-color.org).
+pub fn init(no_color: bool, config_color: Option<bool>) {
+    let disabled = no_color || config_color == Some(false) || env::var_os("NO_COLOR").is_some();
+    COLOR_ENABLED.store(!disabled, Ordering::Relaxed);
+}
+
+pub fn disable() {
+    COLOR_ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Wraps the text in the color, unless colors are off or the stream is not a terminal.
+pub fn paint(color: Color, stream: Stream, text: &str) -> String {
+    let fd = match stream {
+        Stream::Stdout => 1,
+        Stream::Stderr => 2,
+    };
+    if COLOR_ENABLED.load(Ordering::Relaxed) && ffi::is_terminal(fd) {
+        format!("{}{}{}", color.to_color_code(), text, COLOR_RESET)
+    } else {
+        text.to_string()
+    }
+}
@@ -12,60 +12,216 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::super::config;
 use super::super::getopts;
 use super::super::password;
-use super::super::rpassword::read_password;
-use super::super::safe_string::SafeString;
-use std::io::Write;
+use super::super::password::kind::{Kind, Field, KINDS, LOGIN};
+use super::super::safe_string::{SafeString, read_password_safe, read_to_end_safe};
+use super::super::generate::PasswordSpec;
+use super::super::strength;
+use super::super::template;
+use super::CommandError;
+use std::io::{stdin, Write};
 use std::ops::Deref;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("g", "generate", "Generate the password instead of typing it");
+    opts.optopt("k", "kind", "Set the kind of entry, default is login", "login");
+    opts.optopt("t", "template", "Ask for the fields of a template, see `rooster template`", "TEMPLATE");
+    opts.optflag("n", "notes", "Also type notes for this entry");
+    opts.optopt("", "url", "Set the address of the login page, for `rooster open`", "URL");
+    PasswordSpec::add_options(opts);
+    strength::add_options(opts);
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster add -h");
-    println!("    rooster add <app_name> <username>");
+    println!("    rooster add [options] <app_name> <username>");
+    println!("    rooster add --kind <kind> [options] <app_name> [<username>]");
+    println!("    rooster add --template <template> [options] <app_name> [<username>]");
+    println!("");
+    println!("Kinds:");
+    for k in KINDS.iter() {
+        println!("    {:10}{}", k.name, k.description);
+    }
     println!("");
     println!("Example:");
     println!("    rooster add YouTube me@example.com");
+    println!("    rooster add --generate --length 16 YouTube me@example.com");
+    println!("    rooster add --notes YouTube me@example.com");
+    println!("    rooster add --url https://www.youtube.com YouTube me@example.com");
+    println!("    rooster add --kind note 'Bike lock'");
+    println!("    rooster add --template server myhost root");
 }
 
-pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    if matches.free.len() < 3 {
-        println_err!("Woops, seems like the app name or the username is missing here. For help, try:");
-        println_err!("    rooster add -h");
-        return Err(1);
+/// Reads text until the end of the input, for values that span several lines.
+fn read_multiline(app_name: &str, what: &str) -> Result<SafeString, CommandError> {
+    println_stderr!("Type the {} for {}, then press Ctrl+D:", what, app_name);
+    match read_to_end_safe(&mut stdin()) {
+        Ok(text) => Ok(text),
+        Err(err) => {
+            println_err!("\nI couldn't read the {} ({:?}).", what, err);
+            Err(CommandError::Reported)
+        }
     }
+}
 
-    let app_name = matches.free[1].clone();
-    let username = matches.free[2].clone();
+/// Reads the entry's password, which may span several lines depending on the kind.
+fn read_secret(app_name: &str, kind: &Kind) -> Result<SafeString, CommandError> {
+    if kind.secret_multiline {
+        read_multiline(app_name, kind.secret_name)
+    } else {
+        print_stderr!("What {} do you want for {}? ", kind.secret_name, app_name);
+        match read_password_safe() {
+            Ok(password) => Ok(password),
+            Err(err) => {
+                println_err!("\nI couldn't read the app's {} ({:?}).", kind.secret_name, err);
+                Err(CommandError::Reported)
+            }
+        }
+    }
+}
 
-    if store.has_password(app_name.deref()) {
-        println_err!("Woops, there is already an app with that name.");
-        return Err(1);
+fn read_field(app_name: &str, field: &Field) -> Result<SafeString, CommandError> {
+    read_value(app_name, field.name, field.secret)
+}
+
+fn read_value(app_name: &str, name: &str, secret: bool) -> Result<SafeString, CommandError> {
+    print_stderr!("What is the {} for {}? ", name, app_name);
+    let value = if secret {
+        read_password_safe()
+    } else {
+        let mut line = String::new();
+        stdin().read_line(&mut line).map(|_| {
+            // Truncated in place, so that the only copy is wiped with the SafeString.
+            let len = line.trim_right_matches(&['\r', '\n'][..]).len();
+            line.truncate(len);
+            SafeString::new(line)
+        })
+    };
+    match value {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            println_err!("\nI couldn't read the {} ({:?}).", name, err);
+            Err(CommandError::Reported)
+        }
     }
+}
 
-    print_stderr!("What password do you want for {}? ", app_name);
-    match read_password() {
-        Ok(password_as_string) => {
-            let password = password::v2::Password::new(
-                app_name.clone(),
-                username,
-                SafeString::new(password_as_string)
-            );
-            match store.add_password(password) {
-                Ok(_) => {
-                    println_ok!("Alright! Your password for {} has been added.", app_name);
-                },
-                Err(err) => {
-                    println_err!("Woops, I couldn't add the password ({:?}).", err);
-                    return Err(1);
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let template = match matches.opt_str("template") {
+        Some(template_name) => {
+            if matches.opt_present("kind") {
+                return Err(CommandError::Failed("a template already sets the kind, so --kind can't be used with --template".to_string()));
+            }
+            let found = try!(config::load()
+                .and_then(|config| template::find(&config, template_name.deref()))
+                .map_err(|err| CommandError::Failed(format!("I could not load the templates: {}", err))));
+            match found {
+                Some(template) => Some(template),
+                None => {
+                    println_err!("Woops, I don't know the template \"{}\". For a list of templates, try:", template_name);
+                    println_err!("    rooster template");
+                    return Err(CommandError::Reported);
                 }
             }
+        },
+        None => None,
+    };
+
+    let kind = match matches.opt_str("kind") {
+        Some(kind_name) => {
+            match Kind::from_name(kind_name.deref()) {
+                Some(kind) => kind,
+                None => {
+                    println_err!("Woops, I don't know the kind \"{}\". For a list of kinds, try:", kind_name);
+                    println_err!("    rooster add -h");
+                    return Err(CommandError::Reported);
+                }
+            }
+        },
+        None => template.as_ref().map(|template| template.kind).unwrap_or(&LOGIN),
+    };
+
+    // Logins need a username, other kinds of entries may go without one.
+    if matches.free.is_empty() || (matches.free.len() < 2 && kind.name == LOGIN.name) {
+        return Err(CommandError::MissingArgument("app name or the username"));
+    }
+
+    let app_name = matches.free[0].clone();
+    let username = matches.free.get(1).cloned().unwrap_or(String::new());
+
+    if store.has_password(app_name.deref()) {
+        return Err(CommandError::Failed("there is already an app with that name".to_string()));
+    }
+
+    // Kind-specific fields are read first, because multi-line secrets are read until
+    // the end of the input.
+    let mut values = Vec::new();
+    for field in kind.fields.iter() {
+        values.push(try!(read_field(app_name.deref(), field)));
+    }
+    let mut custom_values = Vec::new();
+    if let Some(ref template) = template {
+        for field in template.fields.iter() {
+            let value = try!(read_value(app_name.deref(), field.name.deref(), field.secret));
+            custom_values.push((field.name.clone(), value));
+        }
+    }
+
+    let mut generation_policy = None;
+    let password_as_string = if matches.opt_present("generate") {
+        if !kind.generated {
+            return Err(CommandError::Failed(format!("I can't generate a {} for you, you'll have to type it", kind.secret_name)));
+        }
+        let spec = match PasswordSpec::from_matches(matches) {
+            Some(spec) => spec,
+            None => { return Err(CommandError::Reported); }
+        };
+        if PasswordSpec::has_options(matches) {
+            generation_policy = Some(spec.to_policy());
+        }
+        match spec.generate() {
+            Ok(password_as_string) => SafeString::new(password_as_string),
+            Err(io_err) => {
+                println_stderr!("Woops, I could not generate the password ({:?}).", io_err);
+                return Err(CommandError::Reported);
+            }
+        }
+    } else {
+        let required_score = try!(strength::required_score(matches));
+        let secret = try!(read_secret(app_name.deref(), kind));
+        try!(strength::check(secret.deref(), required_score));
+        secret
+    };
+
+    let notes = if matches.opt_present("notes") {
+        Some(try!(read_multiline(app_name.deref(), "notes")))
+    } else {
+        None
+    };
+
+    let mut password = password::v2::Password::new(
+        app_name.clone(),
+        username,
+        password_as_string
+    );
+    password.payload = kind.build_payload(values);
+    password.notes = notes;
+    password.url = matches.opt_str("url");
+    password.generation_policy = generation_policy;
+    for (name, value) in custom_values.into_iter() {
+        password.set_custom_field(name, value);
+    }
 
-            return Ok(());
+    match store.add_password(password) {
+        Ok(_) => {
+            println_ok!("Alright! Your {} for {} has been added.", kind.secret_name, app_name);
+            Ok(())
         },
         Err(err) => {
-            println_err!("\nI couldn't read the app's password ({:?}).", err);
-            return Err(1);
+            Err(CommandError::Failed(format!("I couldn't add the password ({:?})", err)))
         }
     }
 }
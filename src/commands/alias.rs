@@ -0,0 +1,126 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::CommandError;
+use std::io::Write;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster alias -h");
+    println!("    rooster alias add <app_name> <alias>");
+    println!("    rooster alias rm <alias>");
+    println!("    rooster alias list");
+    println!("");
+    println!("Aliases are short names for apps. Commands like `get` and `delete` accept them");
+    println!("wherever they take an app name, while the app keeps its full name.");
+    println!("");
+    println!("Example:");
+    println!("    rooster alias add YouTube yt");
+    println!("    rooster get yt");
+}
+
+fn save(store: &mut password::v2::PasswordStore, password: password::v2::Password) -> Result<(), CommandError> {
+    let name = password.name.clone();
+    match store.delete_password(&name).and_then(|_| store.add_password(password)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't save the aliases ({:?})", err)))
+        }
+    }
+}
+
+fn add(store: &mut password::v2::PasswordStore, app_name: &str, alias: &str) -> Result<(), CommandError> {
+    if store.has_password(alias) {
+        return Err(CommandError::Failed(format!("\"{}\" is already the name or an alias of an app", alias)));
+    }
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+
+    let name = password.name.clone();
+    let mut aliases = password.aliases.take().unwrap_or(Vec::new());
+    aliases.push(alias.to_string());
+    password.aliases = Some(aliases);
+    try!(save(store, password));
+    println_ok!("Done! You can now use {} for {}.", alias, name);
+    Ok(())
+}
+
+fn remove(store: &mut password::v2::PasswordStore, alias: &str) -> Result<(), CommandError> {
+    let lowercase_alias = alias.to_lowercase();
+    let mut password = match store.get_password(alias) {
+        Some(ref password) if password.name.to_lowercase() == lowercase_alias => {
+            return Err(CommandError::Failed(format!("\"{}\" is the name of an app, not an alias", alias)));
+        },
+        Some(password) => password,
+        None => {
+            println_err!("Woops, there is no alias named \"{}\". For a list of aliases, try:", alias);
+            println_err!("    rooster alias list");
+            return Err(CommandError::Reported);
+        }
+    };
+
+    let name = password.name.clone();
+    password.aliases = password.aliases.take()
+        .map(|aliases| aliases.into_iter().filter(|a| a.to_lowercase() != lowercase_alias).collect::<Vec<String>>())
+        .and_then(|aliases| if aliases.is_empty() { None } else { Some(aliases) });
+    try!(save(store, password));
+    println_ok!("Done! {} is no longer an alias for {}.", alias, name);
+    Ok(())
+}
+
+fn list(store: &password::v2::PasswordStore) {
+    for password in store.get_all_passwords().iter() {
+        if let Some(ref aliases) = password.aliases {
+            for alias in aliases.iter() {
+                println!("{} -> {}", alias, password.name);
+            }
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let action = match matches.free.get(0) {
+        Some(action) => &action[..],
+        None => {
+            return Err(CommandError::MissingArgument("action"));
+        }
+    };
+
+    match (action, matches.free.get(1), matches.free.get(2)) {
+        ("add", Some(app_name), Some(alias)) => add(store, app_name, alias),
+        ("rm", Some(alias), None) => remove(store, alias),
+        ("list", None, None) => {
+            list(store);
+            Ok(())
+        },
+        ("add", _, _) | ("rm", _, _) | ("list", _, _) => {
+            println_err!("Woops, that's not the right number of arguments for {}. For help, try:", action);
+            println_err!("    rooster alias -h");
+            Err(CommandError::Reported)
+        },
+        _ => {
+            Err(CommandError::Failed(format!("I don't know the action \"{}\". Try add, rm or list", action)))
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::timestamp::Timestamp;
+use super::super::password;
+use super::super::password::v2::Attachment;
+use super::super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::super::safe_string::SafeString;
+use super::super::safe_vec::SafeVec;
+use super::CommandError;
+use std::fs::File;
+use std::io::{stdout, Read, Write};
+use std::ops::Deref;
+use std::path::Path;
+
+/// The password file is rewritten on every command, so attachments have to stay small.
+const MAX_ATTACHMENT_SIZE: u64 = 1024 * 1024;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("o", "output", "With get, write the file here instead of printing it", "FILE");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster attach -h");
+    println!("    rooster attach add <app_name> <file>");
+    println!("    rooster attach get [-o <output_file>] <app_name> <file_name>");
+    println!("    rooster attach rm <app_name> <file_name>");
+    println!("    rooster attach list <app_name>");
+    println!("");
+    println!("Attachments are encrypted along with your passwords. They can be up to {} KiB.", MAX_ATTACHMENT_SIZE / 1024);
+    println!("");
+    println!("Example:");
+    println!("    rooster attach add github ~/github-recovery-codes.pdf");
+    println!("    rooster attach get -o codes.pdf github github-recovery-codes.pdf");
+}
+
+fn save(store: &mut password::v2::PasswordStore, app_name: &str, password: password::v2::Password) -> Result<(), CommandError> {
+    match store.delete_password(app_name).and_then(|_| store.add_password(password)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't save the attachment ({:?})", err)))
+        }
+    }
+}
+
+fn read_attachment(path: &str) -> Result<Attachment, CommandError> {
+    let name = match Path::new(path).file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_string(),
+        None => {
+            return Err(CommandError::Failed(format!("\"{}\" is not a file name", path)));
+        }
+    };
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not open {} ({})", path, err)));
+        }
+    };
+    // Read one byte past the limit to know whether the file is too big.
+    let mut bytes = Vec::new();
+    if let Err(err) = Read::by_ref(&mut file).take(MAX_ATTACHMENT_SIZE + 1).read_to_end(&mut bytes) {
+        return Err(CommandError::Failed(format!("I could not read {} ({})", path, err)));
+    }
+    let content = SafeVec::new(bytes);
+    if content.len() as u64 > MAX_ATTACHMENT_SIZE {
+        return Err(CommandError::Failed(format!("{} is too big, attachments can be up to {} KiB", path, MAX_ATTACHMENT_SIZE / 1024)));
+    }
+
+    Ok(Attachment {
+        name: name,
+        content: SafeString::new(content.to_base64(STANDARD)),
+        size: content.len() as u64,
+        added_at: Timestamp::now(),
+    })
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let (action, app_name, file_name) = match (matches.free.get(0), matches.free.get(1)) {
+        (Some(action), Some(app_name)) => (&action[..], &app_name[..], matches.free.get(2)),
+        _ => {
+            return Err(CommandError::MissingArgument("action or the app name"));
+        }
+    };
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+    let mut attachments = password.attachments.take().unwrap_or(Vec::new());
+
+    if action == "list" {
+        for attachment in attachments.iter() {
+            println!("{:40} {:>10} bytes", attachment.name, attachment.size);
+        }
+        return Ok(());
+    }
+
+    let file_name = match file_name {
+        Some(file_name) => file_name,
+        None => {
+            return Err(CommandError::MissingArgument("file name"));
+        }
+    };
+
+    match action {
+        "add" => {
+            let attachment = try!(read_attachment(file_name));
+            if attachments.iter().any(|a| a.name == attachment.name) {
+                return Err(CommandError::Failed(format!("{} already has an attachment named {}. Remove it first", app_name, attachment.name)));
+            }
+            let name = attachment.name.clone();
+            attachments.push(attachment);
+            password.attachments = Some(attachments);
+            try!(save(store, app_name, password));
+            println_ok!("Done! I've attached {} to {}.", name, app_name);
+            Ok(())
+        },
+        "get" => {
+            let attachment = match attachments.iter().find(|a| &a.name == file_name) {
+                Some(attachment) => attachment,
+                None => {
+                    return Err(CommandError::Failed(format!("{} has no attachment named {}", app_name, file_name)));
+                }
+            };
+            let content = match attachment.content.from_base64() {
+                Ok(content) => SafeVec::new(content),
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("the attachment seems corrupted ({})", err)));
+                }
+            };
+            let result = match matches.opt_str("output") {
+                Some(path) => File::create(&path).and_then(|mut file| file.write_all(content.deref())),
+                None => stdout().write_all(content.deref()),
+            };
+            match result {
+                Ok(_) => Ok(()),
+                Err(err) => {
+                    Err(CommandError::Failed(format!("I could not write the attachment ({})", err)))
+                }
+            }
+        },
+        "rm" => {
+            let count = attachments.len();
+            attachments.retain(|a| &a.name != file_name);
+            if attachments.len() == count {
+                return Err(CommandError::Failed(format!("{} has no attachment named {}", app_name, file_name)));
+            }
+            password.attachments = if attachments.is_empty() { None } else { Some(attachments) };
+            try!(save(store, app_name, password));
+            println_ok!("Done! I've removed {} from {}.", file_name, app_name);
+            Ok(())
+        },
+        _ => {
+            Err(CommandError::Failed(format!("I don't know the action \"{}\". Try add, get, rm or list", action)))
+        }
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::pwned::PwnedDatabase;
+use super::super::strength;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Passwords with a lower strength score are reported as weak.
+const WEAK_SCORE: u8 = 3;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "pwned-db", "Also look for passwords leaked in data breaches, in this local copy of the Have I Been Pwned list", "FILE");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster audit -h");
+    println!("    rooster audit");
+    println!("    rooster audit --pwned-db pwned-passwords-sha1-ordered-by-hash-v8.txt");
+    println!("");
+    println!("Looks for passwords that would make it easy to break into your accounts:");
+    println!("- weak passwords, with a strength score below {}/4,", WEAK_SCORE);
+    println!("- passwords used for several apps,");
+    println!("- groups of similar passwords, like Hunter2! and Hunter3!, since knowing one of");
+    println!("  them is enough to guess the others.");
+    println!("");
+    println!("Passwords are similar when they only differ by their numbers, or by a typo or");
+    println!("two. Strong random passwords almost never are, so they don't show up.");
+    println!("");
+    println!("With --pwned-db, passwords are also checked against the list of passwords leaked");
+    println!("in data breaches from https://haveibeenpwned.com/Passwords, in the SHA-1 format");
+    println!("ordered by hash. The file is only read from your disk, so this works without a");
+    println!("network connection and your passwords never leave your machine.");
+    println!("");
+    println!("Example:");
+    println!("    rooster audit");
+}
+
+/// Number of single character insertions, deletions or substitutions to go from a to b.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current.push(*[substitution, previous[j + 1] + 1, current[j] + 1].iter().min().unwrap());
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The password with each run of digits replaced by a single '#', so that passwords
+/// that only differ by a counter or a year look the same.
+fn without_numbers(password: &str) -> String {
+    let mut output = String::new();
+    for c in password.chars() {
+        if !c.is_numeric() {
+            output.push(c);
+        } else if !output.ends_with('#') {
+            output.push('#');
+        }
+    }
+    output
+}
+
+/// Whether two different passwords are close enough that one gives the other away.
+fn similar(a: &str, b: &str) -> bool {
+    let (a_chars, b_chars): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let shortest = ::std::cmp::min(a_chars.len(), b_chars.len());
+    // Below this, most short passwords would be a typo or two away from each other.
+    if shortest < 6 {
+        return false;
+    }
+    let stem = without_numbers(a);
+    if stem == without_numbers(b) && stem.chars().filter(|&c| c != '#').count() >= 4 {
+        return true;
+    }
+    // Random passwords are almost never this close, so this mostly finds variations.
+    let allowed = if shortest >= 12 { 2 } else { 1 };
+    edit_distance(&a_chars, &b_chars) <= allowed
+}
+
+/// Follows the links to the first app of the group.
+fn root(groups: &mut Vec<usize>, i: usize) -> usize {
+    let mut i = i;
+    while groups[i] != i {
+        groups[i] = groups[groups[i]];
+        i = groups[i];
+    }
+    i
+}
+
+/// Groups of apps, by index, that share or nearly share a password. Each group has
+/// at least two apps.
+fn clusters<F: Fn(&str, &str) -> bool>(passwords: &[password::v2::Password], linked: F) -> Vec<Vec<usize>> {
+    let mut groups: Vec<usize> = (0..passwords.len()).collect();
+    for i in 0..passwords.len() {
+        for j in i + 1..passwords.len() {
+            if linked(passwords[i].password.deref(), passwords[j].password.deref()) {
+                let (a, b) = (root(&mut groups, i), root(&mut groups, j));
+                groups[b] = a;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for i in 0..passwords.len() {
+        let r = root(&mut groups, i);
+        match clusters.iter().position(|cluster| root(&mut groups, cluster[0]) == r) {
+            Some(position) => clusters[position].push(i),
+            None => clusters.push(vec![i]),
+        }
+    }
+    clusters.retain(|cluster| cluster.len() > 1);
+    clusters
+}
+
+fn names(passwords: &[password::v2::Password], cluster: &[usize]) -> String {
+    cluster.iter().map(|&i| passwords[i].name.clone()).collect::<Vec<String>>().join(", ")
+}
+
+/// What an audit finds without the list of leaked passwords, by app index.
+pub struct Findings {
+    /// Apps with a weak password, and its strength score.
+    pub weak: Vec<(usize, u8)>,
+    /// Groups of apps with the same password.
+    pub reused: Vec<Vec<usize>>,
+    /// Groups of apps with different but similar passwords.
+    pub similar: Vec<Vec<usize>>,
+}
+
+/// Looks for weak, reused and similar passwords, for this command and `rooster ipc`.
+pub fn findings(passwords: &[password::v2::Password]) -> Findings {
+    Findings {
+        weak: passwords.iter()
+            .map(|p| strength::estimate(p.password.deref()).score)
+            .enumerate()
+            .filter(|&(_, score)| score < WEAK_SCORE)
+            .collect(),
+        reused: clusters(passwords, |a, b| a == b),
+        // Exact copies are already reported as reused, so only look at different passwords.
+        similar: clusters(passwords, |a, b| a != b && similar(a, b)),
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let passwords = store.get_all_passwords();
+    let mut problems = 0;
+
+    let findings = findings(passwords);
+    if !findings.weak.is_empty() {
+        println!("Weak passwords:");
+        for &(i, score) in findings.weak.iter() {
+            println!("    {} ({}/4)", passwords[i].name, score);
+        }
+        problems += findings.weak.len();
+    }
+
+    if !findings.reused.is_empty() {
+        println!("Passwords used for several apps:");
+        for cluster in findings.reused.iter() {
+            println!("    {}", names(passwords, cluster));
+        }
+        problems += findings.reused.len();
+    }
+
+    if !findings.similar.is_empty() {
+        println!("Groups of similar passwords:");
+        for cluster in findings.similar.iter() {
+            println!("    {}", names(passwords, cluster));
+        }
+        problems += findings.similar.len();
+    }
+
+    if let Some(path) = matches.opt_str("pwned-db") {
+        let mut database = match PwnedDatabase::open(Path::new(&path)) {
+            Ok(database) => database,
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not open {} ({})", path, err)));
+            }
+        };
+        let mut pwned = Vec::new();
+        for p in passwords.iter() {
+            match database.count(p.password.deref()) {
+                Ok(0) => {},
+                Ok(count) => pwned.push((p, count)),
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I could not read {} ({})", path, err)));
+                }
+            }
+        }
+        if !pwned.is_empty() {
+            println!("Passwords leaked in data breaches:");
+            for &(p, count) in pwned.iter() {
+                println!("    {} (seen {} times)", p.name, count);
+            }
+            problems += pwned.len();
+        }
+    }
+
+    if problems == 0 {
+        println_ok!("All good! I did not find weak, reused or similar passwords.");
+    } else {
+        println_stderr!("");
+        println_warn!("I found {} problem(s). To change a password, try:", problems);
+        println_warn!("    rooster regenerate <app_name>");
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::autotype::{self, Action, KEYS};
+use super::super::password;
+use super::super::safe_string::SafeString;
+use super::CommandError;
+use super::protect::unprotect;
+use std::io::Write;
+use std::mem;
+use std::ops::Deref;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_SEQUENCE: &'static str = "{username}{TAB}{password}{ENTER}";
+
+/// Seconds to switch to the window to type in, by default.
+const DEFAULT_DELAY: u64 = 3;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "delay", "Seconds to wait before typing, default is 3", "SECONDS");
+    opts.optopt("", "set-sequence", "Save what to type for this app instead of typing it", "SEQUENCE");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster autotype -h");
+    println!("    rooster autotype [--delay <seconds>] <app_name>");
+    println!("    rooster autotype --set-sequence <sequence> <app_name>");
+    println!("");
+    println!("Types the username and the password in the window you switch to, as if you typed");
+    println!("them on your keyboard, so they never go through the clipboard. This needs xdotool");
+    println!("on X11 or wtype on Wayland.");
+    println!("");
+    println!("The sequence is {}. Sequences may use fields like in", DEFAULT_SEQUENCE);
+    println!("`rooster get --format` templates, and these keys:");
+    for &(name, _) in KEYS.iter() {
+        println!("    {{{}}}", name);
+    }
+    println!("");
+    println!("Example:");
+    println!("    rooster autotype youtube");
+    println!("    rooster autotype --set-sequence '{{username}}{{ENTER}}{{password}}{{ENTER}}' google");
+}
+
+/// Turns a sequence into keyboard actions.
+fn parse_sequence(sequence: &str, password: &password::v2::Password) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    let mut text = String::new();
+    let mut chars = sequence.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            },
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => { return Err(format!("the placeholder \"{{{}\" is never closed", name)); }
+                    }
+                }
+                if let Some(&(_, key)) = KEYS.iter().find(|&&(key_name, _)| key_name == name) {
+                    if !text.is_empty() {
+                        actions.push(Action::Type(SafeString::new(mem::replace(&mut text, String::new()))));
+                    }
+                    actions.push(Action::Key(key));
+                    continue;
+                }
+                match password.field(name.deref()) {
+                    Some(value) => text.push_str(value.deref()),
+                    None => { return Err(format!("there is no field or key named \"{}\"", name)); }
+                }
+            },
+            '}' => {
+                return Err("found a \"}\" without a matching \"{\", use \"}}\" for a literal brace".to_string());
+            },
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        actions.push(Action::Type(SafeString::new(text)));
+    }
+    Ok(actions)
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+
+    if let Some(sequence) = matches.opt_str("set-sequence") {
+        if let Err(err) = parse_sequence(sequence.deref(), &password) {
+            return Err(CommandError::Failed(format!("I could not use this sequence: {}", err)));
+        }
+        password.autotype = Some(sequence);
+        return match store.delete_password(app_name).and_then(|_| store.add_password(password)) {
+            Ok(_) => {
+                println_ok!("Done! I've saved the sequence for {}.", app_name);
+                Ok(())
+            },
+            Err(err) => {
+                Err(CommandError::Failed(format!("I couldn't save the sequence ({:?})", err)))
+            }
+        };
+    }
+
+    let delay = match matches.opt_str("delay") {
+        Some(seconds) => {
+            match seconds.parse::<u64>() {
+                Ok(seconds) => seconds,
+                Err(_) => {
+                    return Err(CommandError::Failed(format!("--delay must be a number of seconds, not \"{}\"", seconds)));
+                }
+            }
+        },
+        None => DEFAULT_DELAY,
+    };
+
+    let password = try!(unprotect(&password));
+    let sequence = password.autotype.clone().unwrap_or(DEFAULT_SEQUENCE.to_string());
+    let actions = match parse_sequence(sequence.deref(), &password) {
+        Ok(actions) => actions,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not use the sequence of {}: {}", app_name, err)));
+        }
+    };
+
+    if delay > 0 {
+        println_stderr!("Switch to the window to type in, I'll start typing in {} seconds.", delay);
+        thread::sleep(Duration::from_secs(delay));
+    }
+
+    match autotype::perform(&actions) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            println_err!("Woops, I could not type in the window ({}). Is xdotool or wtype installed?", err);
+            Err(CommandError::Reported)
+        }
+    }
+}
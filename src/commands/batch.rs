@@ -0,0 +1,190 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::generate::PasswordSpec;
+use super::super::password;
+use super::super::safe_string::SafeString;
+use super::super::serde_json::{self, Value};
+use super::super::strength;
+use super::CommandError;
+use super::conflict::{self, Outcome, Strategy};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    PasswordSpec::add_options(opts);
+    conflict::add_options(opts);
+    strength::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster batch -h");
+    println!("    rooster batch [options] <file>");
+    println!("");
+    println!("Applies a list of operations from a JSON file, with a single master password prompt");
+    println!("and a single save. Either every operation succeeds or nothing is saved.");
+    println!("");
+    println!("The file holds an array of objects, each with an \"op\" and a \"name\":");
+    println!("    add     {{name, username, password, url}}");
+    println!("    update  {{name, username, password, url}}, all but the name are optional");
+    println!("    delete  {{name}}");
+    println!("");
+    println!("Adds without a password, and updates with \"generate\": true, get a new password");
+    println!("following the options below. Passwords given in the file are checked against");
+    println!("--enforce-strength, like those typed in `rooster add`.");
+    println!("");
+    conflict::print_help();
+    println!("");
+    println!("Example:");
+    println!("    rooster batch ops.json");
+    println!("    rooster batch --alnum --length 24 ops.json");
+    println!("");
+    println!("    [{{\"op\": \"add\", \"name\": \"db-1\", \"username\": \"admin\"}},");
+    println!("     {{\"op\": \"update\", \"name\": \"db-2\", \"generate\": true}},");
+    println!("     {{\"op\": \"delete\", \"name\": \"db-3\"}}]");
+}
+
+fn string_param(operation: &Value, name: &str) -> Result<Option<String>, String> {
+    match operation.get(name) {
+        None | Some(&Value::Null) => Ok(None),
+        Some(&Value::String(ref value)) => Ok(Some(value.clone())),
+        Some(_) => Err(format!("\"{}\" must be a string", name)),
+    }
+}
+
+fn required_param(operation: &Value, name: &str) -> Result<String, String> {
+    try!(string_param(operation, name)).ok_or(format!("\"{}\" is missing", name))
+}
+
+fn check_strength(secret: &str, required_score: Option<u8>) -> Result<(), String> {
+    strength::check(secret, required_score).map_err(|err| match err {
+        CommandError::Failed(message) => message,
+        _ => "the password is too weak".to_string(),
+    })
+}
+
+fn new_password(spec: &PasswordSpec) -> Result<SafeString, String> {
+    spec.generate()
+        .map(SafeString::new)
+        .map_err(|err| format!("I could not generate a password ({})", err))
+}
+
+/// Applies one operation, and returns the verb to report it with.
+fn apply(operation: &Value, spec: &PasswordSpec, strategy: Option<Strategy>, required_score: Option<u8>, store: &mut password::v2::PasswordStore) -> Result<&'static str, String> {
+    let op = try!(required_param(operation, "op"));
+    let name = try!(required_param(operation, "name"));
+    match op.deref() {
+        "add" => {
+            let username = try!(required_param(operation, "username"));
+            let secret = match try!(string_param(operation, "password")) {
+                Some(secret) => {
+                    try!(check_strength(&secret, required_score));
+                    SafeString::new(secret)
+                },
+                None => try!(new_password(spec)),
+            };
+            let mut password = password::v2::Password::new(name, username, secret);
+            password.url = try!(string_param(operation, "url"));
+            match conflict::add(store, password, strategy) {
+                Ok(Outcome::Added) | Ok(Outcome::Renamed(_)) => Ok("added"),
+                Ok(Outcome::Replaced) | Ok(Outcome::Merged) => Ok("updated"),
+                Ok(Outcome::Kept) => Ok("kept"),
+                Err(CommandError::Failed(err)) => Err(err),
+                Err(_) => Err("I could not add the app".to_string()),
+            }
+        },
+        "update" => {
+            let mut password = try!(store.get_password(&name).ok_or("there is no app with that name".to_string()));
+            if let Some(username) = try!(string_param(operation, "username")) {
+                password.username = username;
+            }
+            if let Some(url) = try!(string_param(operation, "url")) {
+                password.url = Some(url);
+            }
+            let generate = operation.get("generate").and_then(|generate| generate.as_bool()).unwrap_or(false);
+            match try!(string_param(operation, "password")) {
+                Some(_) if generate => {
+                    return Err("\"password\" and \"generate\" can't be used together".to_string());
+                },
+                Some(secret) => {
+                    try!(check_strength(&secret, required_score));
+                    password.replace_password(SafeString::new(secret));
+                },
+                None if generate => password.replace_password(try!(new_password(spec))),
+                None => {},
+            }
+            try!(store.delete_password(&name).and_then(|_| store.add_password(password)).map_err(|err| format!("{:?}", err)));
+            Ok("updated")
+        },
+        "delete" => {
+            try!(store.delete_password(&name).map_err(|_| "there is no app with that name".to_string()));
+            Ok("deleted")
+        },
+        _ => Err(format!("\"{}\" is not an operation, try add, update or delete", op)),
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("file name"));
+    }
+
+    let ref path = matches.free[0];
+
+    let mut content = String::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        return Err(CommandError::Failed(format!("I could not read {} ({})", path, err)));
+    }
+
+    let operations = match serde_json::from_str::<Value>(content.deref()) {
+        Ok(Value::Array(operations)) => operations,
+        Ok(_) => {
+            return Err(CommandError::Failed(format!("{} must hold an array of operations", path)));
+        },
+        Err(err) => {
+            return Err(CommandError::Failed(format!("{} is not valid JSON ({})", path, err)));
+        }
+    };
+
+    let spec = match PasswordSpec::from_matches(matches) {
+        None => { return Err(CommandError::Reported); },
+        Some(spec) => spec,
+    };
+    let strategy = try!(conflict::strategy_from_matches(matches));
+    let required_score = try!(strength::required_score(matches));
+
+    // Returning an error leaves the password file untouched, so a failed operation
+    // discards the ones before it too.
+    let mut counts = (0, 0, 0);
+    for (i, operation) in operations.iter().enumerate() {
+        match apply(operation, &spec, strategy, required_score, store) {
+            Ok("added") => counts.0 += 1,
+            Ok("updated") => counts.1 += 1,
+            Ok("kept") => {},
+            Ok(_) => counts.2 += 1,
+            Err(err) => {
+                let name = operation.get("name").and_then(|name| name.as_str()).unwrap_or("?");
+                println_err!("Woops, operation {} ({}) failed: {}.", i + 1, name, err);
+                println_err!("I did not save any of the operations.");
+                return Err(CommandError::Reported);
+            }
+        }
+    }
+
+    println_ok!("Done! I've added {}, updated {} and deleted {} apps.", counts.0, counts.1, counts.2);
+    Ok(())
+}
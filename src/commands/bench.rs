@@ -0,0 +1,98 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password::provider::{self, KdfParams};
+use super::CommandError;
+use std::time::{Duration, Instant};
+
+/// The scrypt costs measured, as powers of 2, from the one Rooster uses by default.
+const MIN_LOG2_N: u8 = 12;
+const MAX_LOG2_N: u8 = 20;
+
+/// Bigger costs are not measured once one takes longer than this, they would only be
+/// slower.
+const MAX_SECONDS: f64 = 5.0;
+
+/// How long opening the password file may take with the recommended parameters.
+const DEFAULT_TARGET_MS: u64 = 1000;
+
+/// Data encrypted and signed to measure throughput.
+const DATA_LEN: usize = 16 * 1024 * 1024;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "target", "How long deriving the key may take, in milliseconds, default is 1000", "MS");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster bench -h");
+    println!("    rooster bench [options]");
+    println!("");
+    println!("Measures how fast this machine derives keys with scrypt at increasing costs, and");
+    println!("how fast it encrypts and signs data. Then it recommends the scrypt parameters");
+    println!("that take about the target time, to use with `rooster rekey`.");
+    println!("");
+    println!("Please include its output in bug reports about Rooster being slow.");
+    println!("");
+    println!("Example:");
+    println!("    rooster bench");
+    println!("    rooster bench --target 3000");
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000f64
+}
+
+fn time<F: FnOnce()>(f: F) -> f64 {
+    let started = Instant::now();
+    f();
+    seconds(started.elapsed())
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let target_ms = match matches.opt_str("target") {
+        Some(ms) => try!(ms.parse::<u64>().map_err(|_| CommandError::Failed(format!("--target must be a number of milliseconds, not \"{}\"", ms)))),
+        None => DEFAULT_TARGET_MS,
+    };
+    let crypto = provider::provider(provider::RUST_CRYPTO).expect("the default crypto provider is always available");
+
+    println!("scrypt, r 8, p 1:");
+    let mut recommended = MIN_LOG2_N;
+    for log2_n in MIN_LOG2_N..MAX_LOG2_N + 1 {
+        let params = KdfParams { log2_n: log2_n, r: 8, p: 1 };
+        let elapsed = time(|| { crypto.derive_key(params, "rooster bench", &[0u8; 32]); });
+        println!("    log2_n {:>2}: {:>7.0} ms", log2_n, elapsed * 1000f64);
+        if elapsed * 1000f64 <= target_ms as f64 {
+            recommended = log2_n;
+        }
+        if elapsed > MAX_SECONDS {
+            break;
+        }
+    }
+
+    let data = vec![0u8; DATA_LEN];
+    let key = [0u8; 32];
+    let iv = [0u8; 16];
+    let megabytes = (DATA_LEN / 1024 / 1024) as f64;
+    let encryption = time(|| { let _ = crypto.encrypt(&key, &iv, &data); });
+    let signature = time(|| { crypto.mac(&key, &data); });
+    println!("AES-256-CBC:  {:>7.0} MiB/s", megabytes / encryption);
+    println!("HMAC-SHA512:  {:>7.0} MiB/s", megabytes / signature);
+
+    println!("");
+    println!("Recommended for about {} ms: scrypt log2_n {}, r 8, p 1", target_ms, recommended);
+    println!("    rooster rekey --scrypt-log2-n {} --scrypt-r 8 --scrypt-p 1", recommended);
+    Ok(())
+}
@@ -14,11 +14,16 @@
 
 use super::super::getopts;
 use super::super::password;
-use super::super::rpassword::read_password;
-use super::super::safe_string::SafeString;
+use super::super::safe_string::read_password_safe;
+use super::super::strength;
+use super::CommandError;
 use std::io::Write;
 use std::ops::Deref;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    strength::add_options(opts);
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster change-master -h");
@@ -28,33 +33,35 @@ pub fn callback_help() {
     println!("    rooster change-master");
 }
 
-pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let required_score = try!(strength::required_score(matches));
+
     print_stderr!("Type your new master password: ");
-    match read_password() {
+    match read_password_safe() {
         Ok(master_password) => {
-            let master_password = SafeString::new(master_password);
+            try!(strength::check(master_password.deref(), required_score));
 
             print_stderr!("Type your new master password once more: ");
-            let master_password_confirmation = match read_password() {
-                Ok(master_password_confirmation) => {
-                    SafeString::new(master_password_confirmation)
-                }
+            let master_password_confirmation = match read_password_safe() {
+                Ok(master_password_confirmation) => master_password_confirmation,
                 Err(err) => {
                     println_err!("I could not read your new master password ({:?}).", err);
-                    return Err(1);
+                    return Err(CommandError::Reported);
                 }
             };
 
             if master_password != master_password_confirmation {
                 println_err!("The master password confirmation did not match. Aborting.");
-                return Err(1);
+                return Err(CommandError::Reported);
             }
 
-            store.change_master_password(master_password.deref());
+            if let Err(err) = store.change_master_password(master_password.deref()) {
+                return Err(CommandError::Failed(format!("I could not change your master password ({:?})", err)));
+            }
         }
         Err(err) => {
             println_err!("I could not read your new master password ({:?}).", err);
-            return Err(1);
+            return Err(CommandError::Reported);
         }
     }
     println_ok!("Your master password has been changed.");
@@ -0,0 +1,83 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::read_password_safe;
+use super::super::safe_vec::SafeVec;
+use super::super::write_new_password_file;
+use super::CommandError;
+use super::conflict::{self, Outcome};
+use std::fs::File;
+use std::io::{Read, Write};
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("o", "output", "The new password file to write", "FILE");
+    conflict::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster combine -h");
+    println!("    rooster combine [options] <file> <file>... -o <new_file>");
+    println!("");
+    println!("Puts the apps of several password files together in a new password file. You'll");
+    println!("be asked the master password of each file. The new file has the master password");
+    println!("and key slots of the first file, and the files given are never changed.");
+    println!("");
+    conflict::print_help();
+    println!("");
+    println!("Example:");
+    println!("    rooster combine personal.rooster old.rooster -o merged.rooster");
+    println!("    rooster combine a.rooster b.rooster --on-conflict rename -o merged.rooster");
+}
+
+/// Opens a password file, only to read it.
+fn open(path: &str) -> Result<password::v2::PasswordStore, CommandError> {
+    let mut input = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::Failed(format!("I could not read {} ({})", path, err)));
+    }
+    print_stderr!("Type the master password of {}: ", path);
+    let master_password = try!(read_password_safe().map_err(|err| CommandError::Failed(format!("I could not read your master password ({})", err))));
+    password::v2::PasswordStore::from_input(master_password, SafeVec::new(input)).map_err(|err| match err {
+        password::PasswordError::WrongMasterPasswordError => CommandError::WrongMasterPassword,
+        err => CommandError::Failed(format!("I could not open {} ({:?})", path, err)),
+    })
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let output = try!(matches.opt_str("output").ok_or(CommandError::MissingArgument("output file (-o)")));
+    let paths = &matches.free;
+    if paths.len() < 2 {
+        return Err(CommandError::MissingArgument("second file to combine"));
+    }
+    let strategy = try!(conflict::strategy_from_matches(matches));
+
+    let mut combined = try!(open(&paths[0]));
+    let (mut added, mut updated, mut kept) = (0, 0, 0);
+    for path in paths[1..].iter() {
+        let other = try!(open(path));
+        for password in other.get_all_passwords().iter() {
+            match try!(conflict::add(&mut combined, password.clone(), strategy)) {
+                Outcome::Added | Outcome::Renamed(_) => added += 1,
+                Outcome::Replaced | Outcome::Merged => updated += 1,
+                Outcome::Kept => kept += 1,
+            }
+        }
+    }
+    try!(write_new_password_file(&combined, &output).map_err(CommandError::Failed));
+    println_ok!("Done! {} has the apps of {}, plus {} added, {} updated and {} kept as they were.", output, paths[0], added, updated, kept);
+    Ok(())
+}
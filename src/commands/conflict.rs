@@ -0,0 +1,157 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::ffi;
+use super::super::getopts;
+use super::super::password;
+use super::CommandError;
+use std::io::{stdin, Write};
+
+/// What to do with an imported app when there is already one with its name.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Keep the existing app, drop the imported one.
+    Keep,
+    /// Replace the existing app with the imported one.
+    Replace,
+    /// Keep both, the imported one under a new name.
+    Rename,
+    /// Fill the fields the existing app leaves empty from the imported one.
+    Merge,
+}
+
+impl Strategy {
+    fn parse(strategy: &str) -> Option<Strategy> {
+        match strategy {
+            "keep" => Some(Strategy::Keep),
+            "replace" => Some(Strategy::Replace),
+            "rename" => Some(Strategy::Rename),
+            "merge" => Some(Strategy::Merge),
+            _ => None,
+        }
+    }
+}
+
+/// What happened to an imported app.
+pub enum Outcome {
+    Added,
+    Kept,
+    Replaced,
+    /// Added under this name.
+    Renamed(String),
+    Merged,
+}
+
+pub fn add_options(opts: &mut getopts::Options) {
+    opts.optopt("", "on-conflict", "What to do with apps that already exist: keep, replace, rename or merge, instead of asking", "STRATEGY");
+}
+
+pub fn print_help() {
+    println!("When an app already exists, you are asked whether to keep the existing one, replace");
+    println!("it, keep both with the new one renamed, or merge the new one into it, which fills");
+    println!("the fields the existing app leaves empty. --on-conflict picks one for all apps.");
+}
+
+pub fn strategy_from_matches(matches: &getopts::Matches) -> Result<Option<Strategy>, CommandError> {
+    match matches.opt_str("on-conflict") {
+        Some(strategy) => match Strategy::parse(&strategy) {
+            Some(strategy) => Ok(Some(strategy)),
+            None => Err(CommandError::Failed(format!("--on-conflict must be keep, replace, rename or merge, not \"{}\"", strategy))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Asks what to do with the app until it gets an answer.
+fn ask(name: &str) -> Result<Strategy, CommandError> {
+    if !ffi::is_terminal(0) {
+        return Err(CommandError::Failed(format!("you already have an app named {}. Tell me what to do with --on-conflict", name)));
+    }
+    println_stderr!("You already have an app named {}. Keep the existing one, replace it, keep both", name);
+    println_stderr!("with the new one renamed, or merge the new one into it? [k/r/b/m]");
+    loop {
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) => {
+                return Err(CommandError::Failed("I got no answer".to_string()));
+            },
+            Ok(_) => {
+                match line.trim() {
+                    "k" => { return Ok(Strategy::Keep); },
+                    "r" => { return Ok(Strategy::Replace); },
+                    "b" => { return Ok(Strategy::Rename); },
+                    "m" => { return Ok(Strategy::Merge); },
+                    _ => { println_stderr!("I did not get that. Keep, replace, keep both or merge? [k/r/b/m]"); },
+                }
+            },
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+            }
+        }
+    }
+}
+
+/// The first of "name (2)", "name (3)", ... that is free.
+fn free_name(store: &password::v2::PasswordStore, name: &str) -> String {
+    let mut i = 2;
+    loop {
+        let candidate = format!("{} ({})", name, i);
+        if !store.has_password(&candidate) {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+/// Adds an imported app. If there is already one with its name, the strategy says what
+/// to do, or the user does when there is none.
+pub fn add(store: &mut password::v2::PasswordStore, mut password: password::v2::Password, strategy: Option<Strategy>) -> Result<Outcome, CommandError> {
+    let existing = match store.get_password(&password.name) {
+        Some(existing) => existing,
+        None => {
+            try!(store.add_password(password).map_err(|err| CommandError::Failed(format!("I couldn't add the app ({:?})", err))));
+            return Ok(Outcome::Added);
+        }
+    };
+
+    let strategy = match strategy {
+        Some(strategy) => strategy,
+        None => try!(ask(&password.name)),
+    };
+    let name = existing.name.clone();
+    match strategy {
+        Strategy::Keep => Ok(Outcome::Kept),
+        Strategy::Replace => {
+            try!(store.delete_password(&name).and_then(|_| store.add_password(password))
+                .map_err(|err| CommandError::Failed(format!("I couldn't replace {} ({:?})", name, err))));
+            Ok(Outcome::Replaced)
+        },
+        Strategy::Rename => {
+            password.name = free_name(store, &name);
+            let renamed = password.name.clone();
+            try!(store.add_password(password).map_err(|err| CommandError::Failed(format!("I couldn't add {} ({:?})", renamed, err))));
+            Ok(Outcome::Renamed(renamed))
+        },
+        Strategy::Merge => {
+            if existing.protected.is_some() || password.protected.is_some() {
+                return Err(CommandError::Failed(format!("{} is protected with a passphrase, so it can't be merged", name)));
+            }
+            let mut merged = existing;
+            merged.fill_from(&password);
+            try!(store.delete_password(&name).and_then(|_| store.add_password(merged))
+                .map_err(|err| CommandError::Failed(format!("I couldn't merge into {} ({:?})", name, err))));
+            Ok(Outcome::Merged)
+        }
+    }
+}
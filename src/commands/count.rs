@@ -0,0 +1,46 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::CommandError;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "tag", "Only count the apps with this tag", "TAG");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster count -h");
+    println!("    rooster count [options] [<filter>]");
+    println!("");
+    println!("Prints how many apps there are, and nothing else, for shell scripts. With a");
+    println!("filter, only the apps whose name contains it are counted, ignoring case.");
+    println!("");
+    println!("Example:");
+    println!("    rooster count");
+    println!("    rooster count work/");
+    println!("    rooster count --tag bank");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let filter = matches.free.get(0).map(|filter| filter.to_lowercase());
+    let tag = matches.opt_str("tag");
+    let count = store.get_all_passwords().iter()
+        .filter(|p| filter.as_ref().map_or(true, |filter| p.name.to_lowercase().contains(&filter[..])))
+        .filter(|p| tag.as_ref().map_or(true, |tag| p.tags.as_ref().map_or(false, |tags| tags.contains(tag))))
+        .count();
+    println!("{}", count);
+    Ok(())
+}
@@ -0,0 +1,115 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::super::strength;
+use super::CommandError;
+use std::io::{stdin, Write};
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("f", "force", "Do not ask for confirmation");
+    strength::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster decoy -h");
+    println!("    rooster decoy [options]");
+    println!("");
+    println!("Sets up a decoy vault in your password file, with its own master password. Typing");
+    println!("that password instead of your master password opens the decoy vault, where you");
+    println!("can add a few harmless passwords, for when you may be forced to open the file.");
+    println!("");
+    println!("Every password file has room for a second vault, filled with random bytes until a");
+    println!("decoy is set. Each save gives the other vault new random bytes and the scrypt");
+    println!("parameters, key slots and size of the vault that was opened, so that without its");
+    println!("master password nobody can tell whether there is one, even with several copies of");
+    println!("the file.");
+    println!("");
+    println!("The decoy vault follows the key of your real vault. Do not give it a new key, key");
+    println!("slots or scrypt parameters of its own: since it can't tell your real vault from");
+    println!("random bytes, it would draw new random bytes in its place.");
+    println!("");
+    println!("There is only room for one other vault: this replaces the current decoy vault, if");
+    println!("any. Careful, if you run this from the decoy vault, the other vault is your real");
+    println!("one and this deletes all your real passwords.");
+    println!("");
+    println!("Example:");
+    println!("    rooster decoy");
+}
+
+/// Asks a yes or no question on stdin until it gets an answer.
+fn confirm() -> Result<bool, CommandError> {
+    loop {
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) => { return Ok(false); },
+            Ok(_) => {
+                if line.starts_with("y") {
+                    return Ok(true);
+                } else if line.starts_with("n") {
+                    return Ok(false);
+                } else {
+                    println_stderr!("I did not get that. Replace the other vault? [y/n]");
+                }
+            },
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+            }
+        }
+    }
+}
+
+fn read_decoy_password(prompt: &str) -> Result<SafeString, CommandError> {
+    print_stderr!("{}", prompt);
+    match read_password_safe() {
+        Ok(master_password) => Ok(master_password),
+        Err(err) => Err(CommandError::Failed(format!("I could not read the decoy master password ({})", err))),
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let required_score = try!(strength::required_score(matches));
+
+    if !matches.opt_present("force") {
+        println_warn!("This replaces the other vault of this file with an empty decoy vault. If you");
+        println_warn!("opened the file with a decoy master password, the other vault has your real");
+        println_warn!("passwords, and they will be lost.");
+        println_stderr!("Replace the other vault? [y/n]");
+        if !try!(confirm()) {
+            return Err(CommandError::Failed("I did not change anything".to_string()));
+        }
+    }
+
+    let decoy_password = try!(read_decoy_password("Type the decoy master password: "));
+    try!(strength::check(decoy_password.deref(), required_score));
+    let confirmation = try!(read_decoy_password("Type the decoy master password once more: "));
+    if decoy_password != confirmation {
+        return Err(CommandError::Failed("the decoy master password confirmation did not match".to_string()));
+    }
+    if store.is_master_password(decoy_password.deref()) {
+        return Err(CommandError::Failed("the decoy master password must be different from your master password".to_string()));
+    }
+
+    match store.set_decoy(decoy_password) {
+        Ok(()) => {
+            println_ok!("Done! Type the decoy master password to open the decoy vault and add a few passwords to it.");
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I could not set up the decoy vault ({:?})", err))),
+    }
+}
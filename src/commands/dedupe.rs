@@ -0,0 +1,212 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password::v2::{Password, PreviousPassword};
+use super::super::safe_string::SafeString;
+use super::CommandError;
+use std::io::{stdin, Write};
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster dedupe -h");
+    println!("    rooster dedupe");
+    println!("");
+    println!("Finds apps that look like duplicates, because they have the same name and");
+    println!("username once case and punctuation are ignored, or the same password. For");
+    println!("each group, you can merge the apps into one, delete one of them or skip.");
+    println!("");
+    println!("Example:");
+    println!("    rooster dedupe");
+}
+
+/// Lowercase letters and digits only, so "YouTube" and "you-tube" are the same.
+fn normalize(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+fn same_app(a: &Password, b: &Password) -> bool {
+    normalize(a.name.deref()) == normalize(b.name.deref())
+        && normalize(a.username.deref()) == normalize(b.username.deref())
+}
+
+fn same_password(a: &Password, b: &Password) -> bool {
+    a.password.len() > 0 && a.password == b.password
+}
+
+/// Groups entries for which `same` holds, only keeping groups of 2 or more entries.
+fn find_groups<F>(passwords: &[Password], same: F) -> Vec<Vec<String>> where F: Fn(&Password, &Password) -> bool {
+    let mut grouped = vec![false; passwords.len()];
+    let mut groups = Vec::new();
+    for i in 0..passwords.len() {
+        if grouped[i] {
+            continue;
+        }
+        let mut group = vec![passwords[i].name.clone()];
+        for j in (i + 1)..passwords.len() {
+            if !grouped[j] && same(&passwords[i], &passwords[j]) {
+                grouped[j] = true;
+                group.push(passwords[j].name.clone());
+            }
+        }
+        if group.len() > 1 {
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+fn ask(question: &str) -> Result<String, CommandError> {
+    print_stderr!("{} ", question);
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(_) => Ok(line.trim().to_string()),
+        Err(err) => {
+            println_err!("\nI couldn't read your answer ({:?}).", err);
+            Err(CommandError::Reported)
+        }
+    }
+}
+
+/// Asks which entry of the group to use, by its number in the list.
+fn ask_entry(question: &str, count: usize) -> Result<usize, CommandError> {
+    loop {
+        let answer = try!(ask(format!("{} [1-{}]", question, count).as_ref()));
+        match answer.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= count => { return Ok(n - 1); },
+            _ => { println_stderr!("I did not get that. Please type a number between 1 and {}.", count); }
+        }
+    }
+}
+
+/// Moves everything worth keeping from `other` into `keep`: its password goes to the
+/// history if it is different, and its notes are appended.
+fn merge_into(keep: &mut Password, other: &Password) {
+    let mut previous = Vec::new();
+    if let Some(ref history) = other.history {
+        previous.extend(history.iter().cloned());
+    }
+    if other.password != keep.password {
+        previous.push(PreviousPassword {
+            password: other.password.clone(),
+            replaced_at: other.updated_at,
+        });
+    }
+    if !previous.is_empty() {
+        let mut history = keep.history.take().unwrap_or(Vec::new());
+        history.extend(previous.into_iter());
+        history.sort_by(|a, b| a.replaced_at.cmp(&b.replaced_at));
+        keep.history = Some(history);
+    }
+
+    keep.notes = match (keep.notes.take(), other.notes.clone()) {
+        (Some(ref a), Some(ref b)) if a != b => Some(SafeString::new(format!("{}\n{}", a.deref(), b.deref()))),
+        (Some(a), _) => Some(a),
+        (None, b) => b,
+    };
+
+    if keep.username.is_empty() {
+        keep.username = other.username.clone();
+    }
+    if other.created_at < keep.created_at {
+        keep.created_at = other.created_at;
+    }
+}
+
+fn resolve_group(store: &mut password::v2::PasswordStore, names: &[String], reason: &str) -> Result<(), CommandError> {
+    // Entries may have been merged or deleted while resolving a previous group.
+    let entries: Vec<Password> = names.iter().filter_map(|name| store.get_password(name)).collect();
+    if entries.len() < 2 {
+        return Ok(());
+    }
+
+    let newest = entries.iter().map(|p| p.updated_at).max();
+    println_stderr!("");
+    println_stderr!("These apps have {}:", reason);
+    for (i, p) in entries.iter().enumerate() {
+        println_stderr!(
+            "    {}) {:30} {:30} {:8}{}",
+            i + 1,
+            p.name,
+            p.username,
+            p.kind().name,
+            if Some(p.updated_at) == newest { " (most recently updated)" } else { "" }
+        );
+    }
+
+    loop {
+        let answer = try!(ask("What do you want to do? [m]erge, [d]elete one, [s]kip"));
+        if answer.starts_with("m") {
+            let keep_index = try!(ask_entry("Which one do you want to keep?", entries.len()));
+            let mut keep = entries[keep_index].clone();
+            for (i, other) in entries.iter().enumerate() {
+                if i != keep_index {
+                    merge_into(&mut keep, other);
+                    let _ = store.delete_password(other.name.deref());
+                }
+            }
+            let _ = store.delete_password(keep.name.deref());
+            let keep_name = keep.name.clone();
+            match store.add_password(keep) {
+                Ok(_) => {
+                    println_ok!("Done! I've merged these apps into {}.", keep_name);
+                    return Ok(());
+                },
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I couldn't save the merged app ({:?})", err)));
+                }
+            }
+        } else if answer.starts_with("d") {
+            let delete_index = try!(ask_entry("Which one do you want to delete?", entries.len()));
+            match store.delete_password(entries[delete_index].name.deref()) {
+                Ok(_) => {
+                    println_ok!("Done! I've deleted the password for {}.", entries[delete_index].name);
+                    return Ok(());
+                },
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I couldn't delete this app ({:?})", err)));
+                }
+            }
+        } else if answer.starts_with("s") {
+            return Ok(());
+        } else {
+            println_stderr!("I did not get that. Type m to merge, d to delete one or s to skip.");
+        }
+    }
+}
+
+pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let same_app_groups = find_groups(store.get_all_passwords(), same_app);
+    let same_password_groups = find_groups(store.get_all_passwords(), same_password);
+
+    if same_app_groups.is_empty() && same_password_groups.is_empty() {
+        println_ok!("Good news, I did not find any duplicates.");
+        return Ok(());
+    }
+
+    for names in same_app_groups.iter() {
+        try!(resolve_group(store, names, "the same name and username"));
+    }
+    for names in same_password_groups.iter() {
+        try!(resolve_group(store, names, "the same password"));
+    }
+
+    Ok(())
+}
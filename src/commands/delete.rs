@@ -14,35 +14,81 @@
 
 use super::super::getopts;
 use super::super::password;
-use std::io::Write;
+use super::CommandError;
+use std::io::{stdin, Write};
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("f", "force", "Delete without asking for confirmation, for scripts");
+}
 
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster delete -h");
-    println!("    rooster delete <app_name>");
+    println!("    rooster delete [options] <app_name> [<app_name> ...]");
+    println!("");
+    println!("Lists the apps to delete and asks for confirmation first, unless you add --force.");
+    println!("If one of the apps does not exist, nothing is deleted.");
     println!("");
     println!("Example:");
     println!("    rooster delete youtube");
+    println!("    rooster delete --force youtube vimeo");
+}
+
+/// Asks a yes or no question on stdin until it gets an answer.
+fn confirm() -> Result<bool, CommandError> {
+    loop {
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) => { return Ok(false); },
+            Ok(_) => {
+                if line.starts_with("y") {
+                    return Ok(true);
+                } else if line.starts_with("n") {
+                    return Ok(false);
+                } else {
+                    println_stderr!("I did not get that. Delete them? [y/n]");
+                }
+            },
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+            }
+        }
+    }
 }
 
-pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    if matches.free.len() < 2 {
-        println_err!("Woops, seems like the app name is missing here. For help, try:");
-        println_err!("    rooster delete -h");
-        return Err(1);
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
     }
 
-    let ref app_name = matches.free[1];
+    // Check every name first, so a typo does not leave the job half done.
+    let mut app_names: Vec<&String> = Vec::new();
+    for app_name in matches.free.iter() {
+        if !store.has_password(app_name) {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+        if !app_names.contains(&app_name) {
+            app_names.push(app_name);
+        }
+    }
 
-    match store.delete_password(app_name) {
-        Ok(_) => {
-            println_ok!("Done! I've deleted the password for {}.", app_name);
+    if !matches.opt_present("force") {
+        println_stderr!("I'm about to delete the password for:");
+        for app_name in app_names.iter() {
+            println_stderr!("    {}", app_name);
+        }
+        println_stderr!("Delete them? [y/n]");
+        if !try!(confirm()) {
+            println_stderr!("Alright, I did not delete anything.");
             return Ok(());
-        },
-        Err(err) => {
-            println_err!("Woops, I couldn't find a password for this app ({:?}). Make sure you didn't make a typo.", err);
-            println_err!("You can use 'rooster list' to see a list of available passwords.");
-            return Err(1);
         }
     }
+
+    for app_name in app_names.iter() {
+        if let Err(err) = store.delete_password(app_name) {
+            return Err(CommandError::Failed(format!("I couldn't delete the password for {} ({:?})", app_name, err)));
+        }
+        println_ok!("Done! I've deleted the password for {}.", app_name);
+    }
+    Ok(())
 }
@@ -0,0 +1,138 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::backend;
+use super::super::getopts;
+use super::super::journal;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::rand::{Rng, OsRng};
+use super::super::throttle;
+use super::CommandError;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, stdin, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// What is written over the files, in this order: random bytes, then zeros.
+const PASSES: usize = 2;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("f", "force", "Destroy without asking for confirmation, for scripts");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster destroy -h");
+    println!("    rooster destroy [options] [<file>]");
+    println!("");
+    println!("Overwrites a password file with random bytes, then with zeros, and deletes it,");
+    println!("along with the files Rooster keeps next to it: the journal, the count of wrong");
+    println!("master passwords and the sync state. Without a file, it destroys your password");
+    println!("file. You have to type the name of the file to confirm, unless you add --force.");
+    println!("Files that are not Rooster password files are left alone, even with --force.");
+    println!("");
+    println!("This is for retiring a machine. On SSDs and on file systems that copy on write,");
+    println!("old copies of the data may survive on the disk, so encrypt the whole disk too.");
+    println!("");
+    println!("Example:");
+    println!("    rooster destroy");
+    println!("    rooster destroy ~/old.rooster");
+}
+
+/// The file and the files that go along with it, see `journal::path`, `throttle::path`
+/// and `rooster sync`.
+fn files_of(path: &str) -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(path), journal::path(path), throttle::path(path)];
+    for extension in ["sync", "sync-base", "download", "remote"].iter() {
+        files.push(PathBuf::from(format!("{}.{}", path, extension)));
+    }
+    files
+}
+
+fn overwrite(path: &Path) -> io::Result<()> {
+    let len = try!(fs::metadata(path)).len() as usize;
+    let mut file = try!(OpenOptions::new().write(true).open(path));
+    let mut rng = try!(OsRng::new());
+    let mut block = vec![0u8; 4096];
+    for pass in 0..PASSES {
+        try!(file.seek(SeekFrom::Start(0)));
+        let mut left = len;
+        while left > 0 {
+            let size = if left < block.len() { left } else { block.len() };
+            if pass + 1 < PASSES {
+                rng.fill_bytes(&mut block[..size]);
+            } else {
+                for byte in block[..size].iter_mut() {
+                    *byte = 0;
+                }
+            }
+            try!(file.write_all(&block[..size]));
+            left -= size;
+        }
+        try!(file.sync_all());
+    }
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let path = match matches.free.get(0) {
+        Some(path) => path.clone(),
+        None => try!(password_file_path().map_err(|_| CommandError::Failed("I could not determine where your password file is".to_string()))),
+    };
+    if backend::is_remote(&path) {
+        return Err(CommandError::Failed("your password file is on a server, I can only destroy files on this machine".to_string()));
+    }
+    if backend::is_stdio(&path) {
+        return Err(CommandError::Failed("your password file comes from stdin, I can only destroy files on this machine".to_string()));
+    }
+    if backend::is_ephemeral(&path) {
+        return Err(CommandError::Failed("an ephemeral vault is gone when Rooster exits, there is nothing to destroy".to_string()));
+    }
+
+    // A typo in the path must not shred some other file, or the files next to it.
+    let mut input = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::Failed(format!("I could not read {} ({})", path, err)));
+    }
+    if !password::v2::is_password_file(&input) {
+        return Err(CommandError::Failed(format!("{} is not a Rooster password file, I destroyed nothing", path)));
+    }
+
+    let files: Vec<PathBuf> = files_of(&path).into_iter().filter(|file| file.is_file()).collect();
+
+    if !matches.opt_present("force") {
+        println_stderr!("I'm about to overwrite and delete, for good:");
+        for file in files.iter() {
+            println_stderr!("    {}", file.display());
+        }
+        println_stderr!("The passwords in them can't be recovered afterwards. To go on, type the name of");
+        print_stderr!("the first file, {}: ", path);
+        let mut line = String::new();
+        if let Err(err) = stdin().read_line(&mut line) {
+            return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+        }
+        if line.trim_right_matches(&['\r', '\n'][..]) != path {
+            println_info!("That is not the name of the file, I destroyed nothing.");
+            return Ok(());
+        }
+    }
+
+    for file in files.iter() {
+        if let Err(err) = overwrite(file).and_then(|_| fs::remove_file(file)) {
+            return Err(CommandError::Failed(format!("I could not destroy {} ({})", file.display(), err)));
+        }
+        println_ok!("Destroyed {}.", file.display());
+    }
+    Ok(())
+}
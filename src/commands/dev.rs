@@ -0,0 +1,142 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::generate::WORDLIST;
+use super::super::password::kind::KINDS;
+use super::super::password::v2::{Password, PasswordStore};
+use super::super::rand::{Rng, SeedableRng, XorShiftRng};
+use super::super::safe_string::SafeString;
+use super::super::timestamp::Timestamp;
+use super::super::write_new_password_file;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+/// The master password of every fixture, so that tools can open them.
+const FIXTURE_MASTER_PASSWORD: &'static str = "fixture";
+
+const DEFAULT_ENTRIES: usize = 100;
+
+/// When the first app of a fixture was created, then one more hour for each app.
+const FIXTURE_EPOCH: u64 = 1_500_000_000;
+
+const FIXTURE_TAGS: &'static [&'static str] = &["work", "personal", "bank", "shopping", "social"];
+
+const PASSWORD_CHARS: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&*+-=?@^_";
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "entries", "How many apps the fixture has, default is 100", "N");
+    opts.optopt("", "seed", "Another seed gives other apps, default is 1", "N");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster dev -h");
+    println!("    rooster dev generate-fixture [options] <file>");
+    println!("");
+    println!("Tools for working on Rooster and on programs that read Rooster files.");
+    println!("");
+    println!("generate-fixture creates a new password file full of fake apps of every kind,");
+    println!("to measure how fast listing and searching are, or to test other tools without");
+    println!("real secrets. The same seed always gives the same apps. Its master password is");
+    println!("\"{}\".", FIXTURE_MASTER_PASSWORD);
+    println!("");
+    println!("Example:");
+    println!("    rooster dev generate-fixture --entries 1000 fixture.rooster");
+    println!("    rooster --file fixture.rooster list");
+}
+
+fn random_string<R: Rng>(rng: &mut R, chars: &[u8], len: usize) -> String {
+    (0..len).map(|_| chars[rng.gen_range(0, chars.len())] as char).collect()
+}
+
+/// The fake value of a field of a kind, for the app number `i`.
+fn field_value<R: Rng>(rng: &mut R, field: &str, name: &str, i: usize) -> SafeString {
+    SafeString::new(match field {
+        "holder" | "full name" => format!("Fixture User {}", i),
+        "expiry" => format!("{:02}/{:02}", rng.gen_range(1, 13), rng.gen_range(20, 40)),
+        "cvv" => format!("{:03}", rng.gen_range(0, 1000)),
+        "ssid" => format!("{}-wifi", name),
+        "security" => "WPA2".to_string(),
+        "expires" => format!("20{:02}-{:02}-01", rng.gen_range(20, 40), rng.gen_range(1, 13)),
+        "public key" => format!("ssh-ed25519 {} fixture@{}", random_string(rng, b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", 68), name),
+        "birth date" => format!("19{:02}-{:02}-{:02}", rng.gen_range(50, 100), rng.gen_range(1, 13), rng.gen_range(1, 29)),
+        "address" => format!("{} Fixture Street", i),
+        "phone" => format!("+1 555 {:04}", i % 10000),
+        "email" => format!("user{}@example.com", i),
+        _ => random_string(rng, PASSWORD_CHARS, 16),
+    })
+}
+
+/// The apps of a fixture, the same ones for the same seed.
+fn fixture(entries: usize, seed: u32) -> Vec<Password> {
+    // XorShift does not accept a seed of only zeros, hence the other words.
+    let mut rng = XorShiftRng::from_seed([0x193a_6754, 0xa8a7_d469, 0x9783_0e05, seed]);
+    let words: Vec<&str> = WORDLIST.lines().collect();
+    (0..entries).map(|i| {
+        let kind = KINDS[rng.gen_range(0, KINDS.len())];
+        let name = format!("{}-{}", words[rng.gen_range(0, words.len())], i);
+        let mut password = Password::new(
+            name.clone(),
+            format!("user{}@example.com", i),
+            SafeString::new(random_string(&mut rng, PASSWORD_CHARS, 20)),
+        );
+        let values = kind.fields.iter().map(|field| field_value(&mut rng, field.name, &name, i)).collect();
+        password.payload = kind.build_payload(values);
+        password.created_at = Timestamp::from_secs(FIXTURE_EPOCH + i as u64 * 3600);
+        password.updated_at = password.created_at;
+        if kind.name == "login" {
+            password.url = Some(format!("https://{}.example.com/login", name));
+        }
+        if rng.gen_range(0, 2) == 0 {
+            password.tags = Some(vec![FIXTURE_TAGS[rng.gen_range(0, FIXTURE_TAGS.len())].to_string()]);
+        }
+        if rng.gen_range(0, 4) == 0 {
+            password.notes = Some(SafeString::new(format!("Fixture notes for {}.", name)));
+        }
+        password
+    }).collect()
+}
+
+fn generate_fixture(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let path = try!(matches.free.get(1).ok_or(CommandError::MissingArgument("file of the fixture")));
+    let entries = match matches.opt_str("entries") {
+        Some(entries) => try!(entries.parse::<usize>().map_err(|_| CommandError::Failed(format!("--entries must be a number of apps, not \"{}\"", entries)))),
+        None => DEFAULT_ENTRIES,
+    };
+    let seed = match matches.opt_str("seed") {
+        Some(seed) => try!(seed.parse::<u32>().map_err(|_| CommandError::Failed(format!("--seed must be a number, not \"{}\"", seed)))),
+        None => 1,
+    };
+
+    let mut store = try!(PasswordStore::new(SafeString::new(FIXTURE_MASTER_PASSWORD.to_string())).map_err(|err| CommandError::Failed(format!("I could not create the fixture ({})", err))));
+    for password in fixture(entries, seed) {
+        try!(store.add_password(password).map_err(|err| CommandError::Failed(format!("I could not add an app to the fixture ({:?})", err))));
+    }
+
+    // Never overwrite a file, it could be a real password file.
+    try!(write_new_password_file(&store, path).map_err(CommandError::Failed));
+
+    println_ok!("Created {} with {} apps, its master password is \"{}\".", path, entries, FIXTURE_MASTER_PASSWORD);
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    match matches.free.get(0).map(|command| command.deref()) {
+        Some("generate-fixture") => generate_fixture(matches),
+        Some(command) => Err(CommandError::Failed(format!("there is no `rooster dev {}`, see `rooster dev -h`", command))),
+        None => Err(CommandError::MissingArgument("dev command")),
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password::v2::{Password, MERGED_FIELDS};
+use super::super::safe_string::read_password_safe;
+use super::super::safe_vec::SafeVec;
+use super::CommandError;
+use std::fs::File;
+use std::io::{Read, Write};
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster diff -h");
+    println!("    rooster diff <other-file>");
+    println!("");
+    println!("Shows the apps that were added, removed or changed in another password file,");
+    println!("compared to yours. Handy to check what a sync or a restore from a backup did.");
+    println!("");
+    println!("You'll be asked the master password of the other file. Only the names of the");
+    println!("apps and of the changed fields are shown, never the passwords themselves. The");
+    println!("other file is only read, never changed.");
+    println!("");
+    println!("Example:");
+    println!("    rooster diff ~/Dropbox/passwords.rooster");
+}
+
+/// Opens the other password file, only to read it.
+fn open_other(path: &str) -> Result<password::v2::PasswordStore, CommandError> {
+    let mut input = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::Failed(format!("I could not read \"{}\" ({})", path, err)));
+    }
+    print_stderr!("Type the master password of the other file: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            println_err!("I could not read your master password ({})", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    password::v2::PasswordStore::from_input(master_password, SafeVec::new(input)).map_err(|err| CommandError::Failed(format!("I could not open \"{}\" ({:?})", path, err)))
+}
+
+fn find<'a>(passwords: &'a [Password], name: &str) -> Option<&'a Password> {
+    passwords.iter().find(|p| p.name.to_lowercase() == name.to_lowercase())
+}
+
+/// Names of the fields that differ.
+fn changed_fields(a: &Password, b: &Password) -> Vec<String> {
+    MERGED_FIELDS.iter()
+        .filter(|field| a.field_digest(field) != b.field_digest(field))
+        .map(|field| field.replace("_", " "))
+        .collect()
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.len() < 1 {
+        return Err(CommandError::MissingArgument("password file to compare with"));
+    }
+
+    let other = try!(open_other(&matches.free[0]));
+    let ours = store.get_all_passwords();
+    let theirs = other.get_all_passwords();
+
+    let added: Vec<&Password> = theirs.iter().filter(|p| find(ours, &p.name).is_none()).collect();
+    let removed: Vec<&Password> = ours.iter().filter(|p| find(theirs, &p.name).is_none()).collect();
+    let changed: Vec<(&Password, Vec<String>)> = ours.iter()
+        .filter_map(|p| find(theirs, &p.name).map(|q| (p, changed_fields(p, q))))
+        .filter(|&(_, ref fields)| !fields.is_empty())
+        .collect();
+
+    if added.is_empty() && removed.is_empty() && changed.is_empty() {
+        println_ok!("Both files have the same apps.");
+        return Ok(());
+    }
+
+    for p in added.iter() {
+        println!("+ {} (only in the other file)", p.name);
+    }
+    for p in removed.iter() {
+        println!("- {} (only in yours)", p.name);
+    }
+    for &(p, ref fields) in changed.iter() {
+        println!("~ {} ({})", p.name, fields.join(", "));
+    }
+    Ok(())
+}
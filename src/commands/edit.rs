@@ -0,0 +1,664 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::color::{self, Color, Stream};
+use super::super::getopts;
+use super::super::password;
+use super::super::password::v2::CustomField;
+use super::super::rand::{Rng, OsRng};
+use super::super::safe_string::{SafeString, read_to_end_safe};
+use super::super::session;
+use super::super::strength;
+use super::CommandError;
+use super::protect::check_unprotected;
+use std::cmp::max;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, Result as IoResult, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("e", "editor", "Edit the entry in your text editor, from $VISUAL or $EDITOR");
+    strength::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster edit -h");
+    println!("    rooster edit [--editor [--enforce-strength <n>]] <app_name>");
+    println!("");
+    println!("Changes the username, URL and tags of an app. Press Enter to keep the current");
+    println!("value. Tags are separated by commas, like work, video.");
+    println!("");
+    println!("With --editor, the username, URL, aliases, tags, notes and custom fields are");
+    println!("opened in your text editor instead, written like this:");
+    println!("");
+    println!("    username = \"me@example.com\"");
+    println!("    url = \"https://www.youtube.com\"");
+    println!("    aliases = [\"yt\"]");
+    println!("    tags = [\"video\"]");
+    println!("    notes = \"\"\"");
+    println!("    Several lines");
+    println!("    of notes");
+    println!("    \"\"\"");
+    println!("");
+    println!("    [fields]");
+    println!("    \"api key\" = \"...\"");
+    println!("");
+    println!("The changes are saved when you quit the editor. The file is kept in memory when");
+    println!("possible ($XDG_RUNTIME_DIR or /dev/shm), and overwritten before it is deleted.");
+    println!("");
+    println!("Example:");
+    println!("    rooster edit YouTube");
+    println!("    rooster edit --editor YouTube");
+}
+
+/// A file that only the user can read, overwritten with zeros when dropped so that
+/// its content does not stay on the disk.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create() -> IoResult<TempFile> {
+        // Both are usually in memory (tmpfs), unlike /tmp on many systems. Windows has
+        // nothing like them, so the file ends up in the user's temporary directory.
+        let dir = match env::var("XDG_RUNTIME_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) if Path::new("/dev/shm").is_dir() => PathBuf::from("/dev/shm"),
+            Err(_) => {
+                println_warn!("Careful, the entry will be written to {} while you edit it.", env::temp_dir().display());
+                env::temp_dir()
+            }
+        };
+        let mut rng = try!(OsRng::new());
+        let path = dir.join(format!("rooster-{}-{:08x}.toml", process::id(), rng.next_u32()));
+        try!(TempFile::options().open(&path));
+        Ok(TempFile { path: path })
+    }
+
+    /// Only the user may read the file.
+    #[cfg(unix)]
+    fn options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true).mode(0o600);
+        options
+    }
+
+    /// Files in the user's temporary directory are only readable by the user already.
+    #[cfg(windows)]
+    fn options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        options
+    }
+
+    /// Editors that save by renaming a new file over the old one leave a file with the
+    /// permissions of the umask, so it is made private again whenever rooster uses it.
+    fn write(&self, text: &str) -> IoResult<()> {
+        try!(TempFile::restrict(&self.path));
+        OpenOptions::new().write(true).truncate(true).open(&self.path).and_then(|mut file| file.write_all(text.as_bytes()))
+    }
+
+    #[cfg(unix)]
+    fn restrict(path: &Path) -> IoResult<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+    }
+
+    #[cfg(windows)]
+    fn restrict(_path: &Path) -> IoResult<()> {
+        Ok(())
+    }
+
+    fn read(&self) -> IoResult<SafeString> {
+        try!(TempFile::restrict(&self.path));
+        File::open(&self.path).and_then(|mut file| read_to_end_safe(&mut file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            let zeros = vec![0u8; metadata.len() as usize];
+            let _ = OpenOptions::new().write(true).open(&self.path)
+                .and_then(|mut file| file.write_all(&zeros).and_then(|_| file.sync_all()));
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Editor to use when neither $VISUAL nor $EDITOR is set.
+#[cfg(unix)]
+const DEFAULT_EDITOR: &'static str = "vi";
+
+/// Editor to use when neither $VISUAL nor $EDITOR is set.
+#[cfg(windows)]
+const DEFAULT_EDITOR: &'static str = "notepad";
+
+/// The editor may have arguments, like "code --wait", so it is run by the shell.
+#[cfg(unix)]
+fn editor_command(editor: &str, path: &Path) -> Command {
+    let mut command = session::command("sh");
+    command.arg("-c").arg(format!("{} \"$1\"", editor)).arg("sh").arg(path);
+    command
+}
+
+/// The editor may have arguments, like "code --wait". They are split on spaces, since
+/// the command prompt has no equivalent of "$1".
+#[cfg(windows)]
+fn editor_command(editor: &str, path: &Path) -> Command {
+    let mut words = editor.split_whitespace();
+    let mut command = session::command(words.next().unwrap_or(DEFAULT_EDITOR));
+    command.args(words).arg(path);
+    command
+}
+
+/// Opens the file in the user's editor and waits until it is closed.
+fn open_editor(path: &Path) -> Result<(), CommandError> {
+    let editor = env::var("VISUAL").or(env::var("EDITOR")).unwrap_or(DEFAULT_EDITOR.to_string());
+    let status = editor_command(&editor, path).status();
+    match status {
+        Ok(ref status) if status.success() => Ok(()),
+        Ok(status) => Err(CommandError::Failed(format!("{} exited with {}, so I did not change anything", editor, status))),
+        Err(err) => Err(CommandError::Failed(format!("I could not start {} ({})", editor, err))),
+    }
+}
+
+/// Quotes text as a TOML string, on several lines if it has line breaks.
+fn quote(text: &str) -> String {
+    let multiline = text.contains('\n');
+    let mut quoted = String::new();
+    quoted.push_str(if multiline { "\"\"\"\n" } else { "\"" });
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' if multiline => quoted.push('\n'),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push_str(if multiline { "\"\"\"" } else { "\"" });
+    quoted
+}
+
+/// Writes the editable fields of the entry, in the format `Parser` reads.
+fn to_toml(password: &password::v2::Password) -> SafeString {
+    let mut text = String::new();
+    text.push_str(&format!("# Editing {}. Lines starting with # are ignored.\n", password.name));
+    text.push_str("# Save and quit to apply your changes, or quit without saving to cancel.\n\n");
+    text.push_str(&format!("username = {}\n", quote(&password.username)));
+    text.push_str(&format!("url = {}\n", quote(password.url.as_ref().map(|url| &url[..]).unwrap_or(""))));
+    let aliases: Vec<String> = password.aliases.as_ref().map(|aliases| aliases.iter().map(|alias| quote(alias)).collect()).unwrap_or(Vec::new());
+    text.push_str(&format!("aliases = [{}]\n", aliases.join(", ")));
+    let tags: Vec<String> = password.tags.as_ref().map(|tags| tags.iter().map(|tag| quote(tag)).collect()).unwrap_or(Vec::new());
+    text.push_str(&format!("tags = [{}]\n", tags.join(", ")));
+    let mut notes = password.notes.as_ref().map(|notes| notes.to_string()).unwrap_or(String::new());
+    // Always on several lines, so that notes are easy to add.
+    if !notes.ends_with('\n') {
+        notes.push('\n');
+    }
+    text.push_str(&format!("notes = {}\n", quote(&notes)));
+    text.push_str("\n[fields]\n");
+    if let Some(ref fields) = password.custom_fields {
+        for field in fields.iter() {
+            text.push_str(&format!("{} = {}\n", quote(&field.name), quote(field.value.deref())));
+        }
+    }
+    SafeString::new(text)
+}
+
+/// The editable fields, as read back from the file. Empty values mean the field is
+/// not set.
+struct Edited {
+    username: Option<String>,
+    url: Option<String>,
+    aliases: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    notes: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+enum Value {
+    Text(String),
+    List(Vec<String>),
+}
+
+struct ParseError {
+    line: usize,
+    message: String,
+}
+
+/// Reads the small part of TOML that `to_toml` writes: quoted strings, multi-line
+/// strings, lists of strings, comments and the [fields] table.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Parser {
+        Parser { chars: text.chars().collect(), pos: 0, line: 1 }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError { line: self.line, message: message })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') || self.peek() == Some('\t') {
+            self.next();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.next();
+        }
+    }
+
+    /// Skips an optional comment and the line break after a value.
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            while self.peek().is_some() && self.peek() != Some('\n') {
+                self.next();
+            }
+        }
+        if self.starts_with("\r\n") {
+            self.next();
+        }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => self.error(format!("I expected the end of the line, not '{}'", c)),
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        match self.next() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => {
+                let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(::std::char::from_u32) {
+                    Some(c) => Ok(c),
+                    None => self.error(format!("\\u{} is not a valid character", hex)),
+                }
+            },
+            Some(c) => self.error(format!("\\{} is not a valid escape, use \\\\ for a backslash", c)),
+            None => self.error("the text ends with a backslash".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        if self.starts_with("\"\"\"") {
+            self.pos += 3;
+            // Like in TOML, a line break right after the opening quotes is not part of the text.
+            if self.starts_with("\r\n") {
+                self.next();
+            }
+            if self.peek() == Some('\n') {
+                self.next();
+            }
+            let mut text = String::new();
+            loop {
+                if self.starts_with("\"\"\"") {
+                    self.pos += 3;
+                    return Ok(text);
+                }
+                match self.next() {
+                    Some('\\') => text.push(try!(self.escape())),
+                    Some(c) => text.push(c),
+                    None => { return self.error("some text is missing its closing \"\"\"".to_string()); }
+                }
+            }
+        }
+        if self.next() != Some('"') {
+            return self.error("I expected text in quotes, like \"text\"".to_string());
+        }
+        let mut text = String::new();
+        loop {
+            if self.peek() == Some('\n') {
+                return self.error("this text is missing its closing quote. For several lines, use \"\"\"".to_string());
+            }
+            match self.next() {
+                Some('"') => { return Ok(text); },
+                Some('\\') => text.push(try!(self.escape())),
+                Some(c) => text.push(c),
+                None => {
+                    return self.error("this text is missing its closing quote. For several lines, use \"\"\"".to_string());
+                },
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '-') {
+                break;
+            }
+            key.push(c);
+            self.next();
+        }
+        if key.is_empty() {
+            return self.error("I expected a field name".to_string());
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        if self.peek() != Some('[') {
+            return self.string().map(Value::Text);
+        }
+        self.next();
+        let mut list = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::List(list));
+            }
+            list.push(try!(self.string()));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {},
+                Some(']') => { return Ok(Value::List(list)); },
+                _ => { return self.error("the items of a list must be separated by commas, like [\"a\", \"b\"]".to_string()); }
+            }
+        }
+    }
+
+    fn parse(&mut self) -> Result<Edited, ParseError> {
+        let mut edited = Edited { username: None, url: None, aliases: None, tags: None, notes: None, fields: Vec::new() };
+        let mut seen: Vec<String> = Vec::new();
+        let mut in_fields = false;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => { return Ok(edited); },
+                Some('#') => { try!(self.end_of_line()); },
+                Some('[') => {
+                    self.next();
+                    let mut table = String::new();
+                    while self.peek().is_some() && self.peek() != Some(']') && self.peek() != Some('\n') {
+                        table.push(self.next().unwrap());
+                    }
+                    if self.next() != Some(']') || table.trim() != "fields" {
+                        return self.error("the only table is [fields]".to_string());
+                    }
+                    try!(self.end_of_line());
+                    in_fields = true;
+                },
+                Some(_) => {
+                    // Errors about the value as a whole point to the line of its key.
+                    let line = self.line;
+                    let value_error = |message: String| -> Result<Edited, ParseError> { Err(ParseError { line: line, message: message }) };
+                    let key = try!(self.key());
+                    self.skip_spaces();
+                    if self.next() != Some('=') {
+                        return self.error(format!("I expected an = after {}", key));
+                    }
+                    self.skip_spaces();
+                    let value = try!(self.value());
+                    try!(self.end_of_line());
+
+                    let full_key = if in_fields { format!("fields.{}", key) } else { key.clone() };
+                    if seen.contains(&full_key) {
+                        return value_error(format!("{} is there twice", key));
+                    }
+                    seen.push(full_key);
+
+                    let non_empty = |text: String| if text.trim().is_empty() { None } else { Some(text) };
+                    match (in_fields, &key[..], value) {
+                        (true, _, Value::Text(text)) => { edited.fields.push((key.clone(), text)); },
+                        (false, "username", Value::Text(text)) => { edited.username = Some(text); },
+                        (false, "url", Value::Text(text)) => { edited.url = non_empty(text); },
+                        (false, "notes", Value::Text(text)) => { edited.notes = non_empty(text); },
+                        (false, "aliases", Value::List(list)) => {
+                            let aliases: Vec<String> = list.into_iter().filter(|alias| !alias.trim().is_empty()).collect();
+                            edited.aliases = if aliases.is_empty() { None } else { Some(aliases) };
+                        },
+                        (false, "aliases", Value::Text(_)) => {
+                            return value_error("aliases must be a list, like [\"a\", \"b\"]".to_string());
+                        },
+                        (false, "tags", Value::List(list)) => { edited.tags = tags(list); },
+                        (false, "tags", Value::Text(_)) => {
+                            return value_error("tags must be a list, like [\"a\", \"b\"]".to_string());
+                        },
+                        (_, _, Value::List(_)) if in_fields || ["username", "url", "notes"].contains(&&key[..]) => {
+                            return value_error(format!("{} must be text in quotes, not a list", key));
+                        },
+                        _ => {
+                            return value_error(format!("{} can't be edited here, only username, url, aliases, tags, notes and [fields] can", key));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Tags without the blank ones and duplicates, `None` if none are left.
+fn tags<I: IntoIterator<Item = String>>(tags: I) -> Option<Vec<String>> {
+    let mut kept: Vec<String> = Vec::new();
+    for tag in tags.into_iter().map(|tag| tag.trim().to_string()) {
+        if !tag.is_empty() && !kept.contains(&tag) {
+            kept.push(tag);
+        }
+    }
+    if kept.is_empty() { None } else { Some(kept) }
+}
+
+/// Prints which lines differ between two texts, so that the mistake is easier to find.
+/// Only the line numbers are printed, not the lines: notes and custom fields are secrets,
+/// and the terminal may be scrolled back or recorded.
+fn print_diff(before: &str, after: &str) {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    // lengths[i][j] is the length of the longest common part of a[i..] and b[j..].
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { max(lengths[i + 1][j], lengths[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            println_stderr!("{}", color::paint(Color::Green, Stream::Stderr, &format!("+ line {} of your file", j + 1)));
+            j += 1;
+        } else {
+            println_stderr!("{}", color::paint(Color::Red, Stream::Stderr, &format!("- line {} of the original", i + 1)));
+            i += 1;
+        }
+    }
+}
+
+/// Asks a yes or no question on stdin until it gets an answer.
+fn confirm() -> Result<bool, CommandError> {
+    loop {
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) => { return Ok(false); },
+            Ok(_) => {
+                if line.starts_with("y") {
+                    return Ok(true);
+                } else if line.starts_with("n") {
+                    return Ok(false);
+                } else {
+                    println_stderr!("I did not get that. Edit it again? [y/n]");
+                }
+            },
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+            }
+        }
+    }
+}
+
+/// Asks for a new value on the terminal, keeping the current one if nothing is typed.
+fn ask(what: &str, current: &str) -> Result<String, CommandError> {
+    print_stderr!("{} [{}]: ", what, current);
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(_) => {
+            let line = line.trim_right_matches(&['\r', '\n'][..]);
+            Ok(if line.is_empty() { current.to_string() } else { line.to_string() })
+        },
+        Err(err) => Err(CommandError::Failed(format!("I could not read from stdin ({})", err))),
+    }
+}
+
+/// Prints the strength of the custom fields that were added or changed, and fails if one
+/// is below the required score.
+fn check_fields(password: &password::v2::Password, edited: &Edited, required_score: Option<u8>) -> Result<(), CommandError> {
+    for &(ref name, ref value) in edited.fields.iter() {
+        if password.custom_field(name).map_or(true, |field| field.value.deref() != value) {
+            println_info!("{}:", name);
+            try!(strength::check(value, required_score));
+        }
+    }
+    Ok(())
+}
+
+fn edit_in_editor(password: &mut password::v2::Password, required_score: Option<u8>) -> Result<bool, CommandError> {
+    let original = to_toml(password);
+    let file = try!(TempFile::create().map_err(|err| CommandError::Failed(format!("I could not create a file to edit ({})", err))));
+    let mut text = original.clone();
+    loop {
+        try!(file.write(text.deref()).map_err(|err| CommandError::Failed(format!("I could not write the file to edit ({})", err))));
+        try!(open_editor(&file.path));
+        let edited_text = try!(file.read().map_err(|err| CommandError::Failed(format!("I could not read the edited file ({})", err))));
+        if edited_text.deref() == original.deref() {
+            return Ok(false);
+        }
+        let parsed = Parser::new(edited_text.deref()).parse();
+        let weak = match parsed {
+            Ok(ref edited) => check_fields(password, edited, required_score).err(),
+            Err(_) => None,
+        };
+        match parsed {
+            Ok(_) if weak.is_some() => {
+                if let Some(CommandError::Failed(message)) = weak {
+                    println_err!("Woops, {}.", message);
+                }
+                println_stderr!("Edit it again? [y/n]");
+                if !try!(confirm()) {
+                    return Err(CommandError::Failed("I did not change anything".to_string()));
+                }
+                text = edited_text;
+            },
+            Ok(edited) => {
+                password.username = edited.username.unwrap_or(String::new());
+                password.url = edited.url;
+                password.aliases = edited.aliases;
+                password.tags = edited.tags;
+                password.notes = edited.notes.map(SafeString::new);
+                password.custom_fields = if edited.fields.is_empty() {
+                    None
+                } else {
+                    Some(edited.fields.into_iter().map(|(name, value)| CustomField { name: name, value: SafeString::new(value) }).collect())
+                };
+                return Ok(true);
+            },
+            Err(err) => {
+                println_err!("Woops, line {}: {}.", err.line, err.message);
+                println_stderr!("");
+                println_stderr!("Here are the lines you changed:");
+                print_diff(original.deref(), edited_text.deref());
+                println_stderr!("");
+                println_stderr!("Edit it again? [y/n]");
+                if !try!(confirm()) {
+                    return Err(CommandError::Failed("I did not change anything".to_string()));
+                }
+                text = edited_text;
+            }
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let app_name = match matches.free.get(0) {
+        Some(app_name) => app_name.clone(),
+        None => {
+            return Err(CommandError::MissingArgument("app name"));
+        }
+    };
+
+    let mut password = match store.get_password(app_name.deref()) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name));
+        }
+    };
+
+    if matches.opt_present("editor") {
+        try!(check_unprotected(&password));
+        let required_score = try!(strength::required_score(matches));
+        if !try!(edit_in_editor(&mut password, required_score)) {
+            println_info!("Nothing changed.");
+            return Ok(());
+        }
+    } else {
+        password.username = try!(ask("Username", &password.username));
+        let url = try!(ask("URL", password.url.as_ref().map(|url| &url[..]).unwrap_or("")));
+        password.url = if url.is_empty() { None } else { Some(url) };
+        let current_tags = password.tags.as_ref().map(|tags| tags.join(", ")).unwrap_or(String::new());
+        password.tags = tags(try!(ask("Tags", &current_tags)).split(',').map(|tag| tag.to_string()));
+    }
+
+    let name = password.name.clone();
+    match store.delete_password(name.deref()).and_then(|_| store.add_password(password)) {
+        Ok(_) => {
+            println_ok!("Done! I've saved your changes to {}.", name);
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I couldn't save your changes ({:?})", err))),
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::CommandError;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster exists -h");
+    println!("    rooster exists <app_name>");
+    println!("");
+    println!("Prints nothing, but exits with 0 if there is an app with this name or alias, and");
+    println!("with 1 if there is not, for shell scripts. Other errors, like a wrong master");
+    println!("password, have their own exit code, see `rooster -h`.");
+    println!("");
+    println!("Example:");
+    println!("    rooster exists youtube || rooster add youtube me@example.com");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let app_name = match matches.free.get(0) {
+        Some(app_name) => app_name,
+        None => {
+            return Err(CommandError::MissingArgument("app name"));
+        }
+    };
+    match store.get_password(app_name) {
+        Some(_) => Ok(()),
+        None => Err(CommandError::Reported),
+    }
+}
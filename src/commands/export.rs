@@ -12,33 +12,224 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+
+use super::super::age;
 use super::super::getopts;
+use super::super::base32;
 use super::super::password;
-use super::super::safe_string::SafeString;
-use super::super::rustc_serialize::json;
+use super::super::qr;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::super::serde_json;
+use super::super::hooks;
+use super::CommandError;
+use std::fs::File;
 use std::ops::Deref;
-use std::io::Write;
+use std::io::{stdout, Write};
+
+/// Base32 characters per line of a paper backup, in groups of 4.
+const PAPER_LINE_LEN: usize = 48;
+
+/// Paper backups bigger than this have no QR code, it would be too dense to scan.
+const PAPER_QR_MAX_LEN: usize = 1500;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("", "paper", "Print an encrypted backup to keep on paper");
+    opts.optopt("", "entries", "Only export these apps, separated by commas", "APPS");
+    opts.optopt("", "encrypted", "Write a password file encrypted with a new passphrase here", "FILE");
+    opts.optopt("", "format", "Print the passwords as json, the default, or encrypted with age", "FORMAT");
+    opts.optmulti("r", "recipient", "With --format age, who can decrypt: an age1... key or an SSH public key", "RECIPIENT");
+}
 
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster export -h");
-    println!("    rooster export");
+    println!("    rooster export [--entries <apps>]");
+    println!("    rooster export --paper [--entries <apps>]");
+    println!("    rooster export --encrypted <file> [--entries <apps>]");
+    println!("    rooster export --format age -r <recipient> ... [--entries <apps>]");
+    println!("");
+    println!("Without options, prints your passwords as JSON, unencrypted.");
+    println!("");
+    println!("With --format age, prints the same JSON encrypted with age, for each recipient");
+    println!("given with -r. Anyone with age can decrypt it, without Rooster. `rooster import");
+    println!("--format age` reads it back. Set $ROOSTER_AGE to use another program than age.");
+    println!("");
+    println!("With --paper, prints a backup to keep on paper, in case you lose your password");
+    println!("file. It is a Rooster file encrypted with a passphrase of your choice, written");
+    println!("in base32 and as a QR code when small enough, along with instructions to get");
+    println!("your passwords back. You may want to only back up your most important apps.");
+    println!("");
+    println!("With --encrypted, writes a password file encrypted with a passphrase of your");
+    println!("choice, to share some passwords with a teammate. Send the passphrase through");
+    println!("another channel. They can then use the file with:");
+    println!("    ROOSTER_FILE=<file> rooster list");
+    println!("");
+    println!("After exporting, runs ~/.config/rooster/hooks/post-export if it exists, with");
+    println!("ROOSTER_EXPORT set to json, age, paper or encrypted, ROOSTER_EXPORTED to the number of");
+    println!("apps, and ROOSTER_EXPORT_FILE to the file for --encrypted. No passwords are passed.");
     println!("");
     println!("Example:");
     println!("    rooster export");
+    println!("    rooster export --paper --entries email,bank > backup.txt");
+    println!("    rooster export --entries github,aws --encrypted shared.rooster");
+    println!("    rooster export --format age -r age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p > backup.age");
 }
 
-pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    let passwords_ref = store.get_all_passwords();
+/// The passwords to export, all of them unless `--entries` is given.
+fn selected_passwords(matches: &getopts::Matches, store: &password::v2::PasswordStore) -> Result<Vec<password::v2::Password>, CommandError> {
+    match matches.opt_str("entries") {
+        Some(entries) => {
+            let mut passwords = Vec::new();
+            for name in entries.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+                match store.get_password(name) {
+                    Some(password) => passwords.push(password),
+                    None => {
+                        return Err(CommandError::NoSuchApp(name.to_string()));
+                    }
+                }
+            }
+            Ok(passwords)
+        },
+        None => Ok(store.get_all_passwords().to_vec()),
+    }
+}
+
+fn read_new_passphrase(what: &str) -> Result<SafeString, CommandError> {
+    print_stderr!("Type a passphrase for the {}: ", what);
+    let passphrase = match read_password_safe() {
+        Ok(passphrase) => passphrase,
+        Err(err) => {
+            println_err!("\nI could not read the passphrase ({}).", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    print_stderr!("Type the passphrase again: ");
+    let confirmation = match read_password_safe() {
+        Ok(confirmation) => confirmation,
+        Err(err) => {
+            println_err!("\nI could not read the passphrase ({}).", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    if passphrase != confirmation {
+        println_err!("The passphrases did not match. Aborting.");
+        return Err(CommandError::Reported);
+    }
+    Ok(passphrase)
+}
+
+/// Encrypts the passwords in the Rooster file format, with a new passphrase.
+fn encrypt_passwords(passwords: Vec<password::v2::Password>, what: &str) -> Result<Vec<u8>, CommandError> {
+    let passphrase = try!(read_new_passphrase(what));
 
-    let passwords_json = match json::encode(&passwords_ref) {
+    let mut new_store = match password::v2::PasswordStore::new(passphrase) {
+        Ok(store) => store,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not create the {} ({:?})", what, err)));
+        }
+    };
+    for p in passwords.into_iter() {
+        if let Err(err) = new_store.add_password(p) {
+            return Err(CommandError::Failed(format!("I could not create the {} ({:?})", what, err)));
+        }
+    }
+    let mut bytes = Vec::new();
+    if let Err(err) = new_store.write_to(&mut bytes) {
+        return Err(CommandError::Failed(format!("I could not create the {} ({:?})", what, err)));
+    }
+    Ok(bytes)
+}
+
+fn export_encrypted(passwords: Vec<password::v2::Password>, path: &str) -> Result<(), CommandError> {
+    let count = passwords.len();
+    let bytes = try!(encrypt_passwords(passwords, "exported file"));
+    match File::create(path).and_then(|mut file| file.write_all(&bytes)) {
+        Ok(_) => {
+            println_ok!("Done! I've exported {} apps to {}.", count, path);
+            Ok(())
+        },
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not write {} ({})", path, err)))
+        }
+    }
+}
+
+fn export_paper(passwords: Vec<password::v2::Password>) -> Result<(), CommandError> {
+    let count = passwords.len();
+    let bytes = try!(encrypt_passwords(passwords, "paper backup"));
+    let encoded = base32::encode(&bytes);
+
+    println!("ROOSTER PAPER BACKUP");
+    println!("");
+    println!("This is a Rooster password file with {} apps, encrypted with a passphrase.", count);
+    println!("To get your passwords back:");
+    println!("1. Type or scan the lines below into backup.txt, with their line numbers.");
+    println!("2. Turn them back into a Rooster file:");
+    println!("       cut -d' ' -f2- backup.txt | tr -d ' \\n' | base32 -d > backup.rooster");
+    println!("3. Open it with Rooster, using the passphrase as the master password:");
+    println!("       ROOSTER_FILE=backup.rooster rooster list");
+    println!("");
+
+    let chars: Vec<char> = encoded.chars().collect();
+    for (i, line) in chars.chunks(PAPER_LINE_LEN).enumerate() {
+        let groups: Vec<String> = line.chunks(4).map(|group| group.iter().cloned().collect()).collect();
+        println!("{:04} {}", i + 1, groups.join(" "));
+    }
+
+    if encoded.len() <= PAPER_QR_MAX_LEN {
+        match qr::render(encoded.as_bytes()) {
+            Ok(code) => {
+                println!("");
+                println!("The same data as a QR code:");
+                println!("{}", code.deref());
+            },
+            Err(err) => { println_stderr!("I could not add a QR code to the backup ({}), skipping it.", err); }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let passwords = try!(selected_passwords(matches, store));
+    let count = passwords.len().to_string();
+
+    if matches.opt_present("paper") {
+        try!(export_paper(passwords));
+        hooks::run("post-export", &[("ROOSTER_EXPORT", "paper".to_string()), ("ROOSTER_EXPORTED", count)]);
+        return Ok(());
+    }
+    if let Some(path) = matches.opt_str("encrypted") {
+        try!(export_encrypted(passwords, path.deref()));
+        hooks::run("post-export", &[("ROOSTER_EXPORT", "encrypted".to_string()), ("ROOSTER_EXPORTED", count), ("ROOSTER_EXPORT_FILE", path)]);
+        return Ok(());
+    }
+
+    let format = matches.opt_str("format").unwrap_or("json".to_string());
+    if format != "json" && format != "age" {
+        return Err(CommandError::Failed(format!("the format must be json or age, not \"{}\"", format)));
+    }
+    let recipients = matches.opt_strs("recipient");
+    if format == "age" && recipients.is_empty() {
+        return Err(CommandError::MissingArgument("-r option"));
+    }
+
+    let passwords_json = match serde_json::to_string(&passwords) {
         Ok(passwords_json) => passwords_json,
         Err(json_err) => {
             println_stderr!("Woops, I could not encode the passwords into JSON ({:?}).", json_err);
-            return Err(1);
+            return Err(CommandError::Reported);
         }
     };
     let passwords = SafeString::new(passwords_json);
-    println!("{}", passwords.deref());
+    if format == "age" {
+        let encrypted = try!(age::encrypt(&recipients, passwords.as_bytes()).map_err(CommandError::Failed));
+        if let Err(err) = stdout().write_all(&encrypted) {
+            return Err(CommandError::Failed(format!("I could not print the export ({})", err)));
+        }
+    } else {
+        println!("{}", passwords.deref());
+    }
+    hooks::run("post-export", &[("ROOSTER_EXPORT", format), ("ROOSTER_EXPORTED", count)]);
     Ok(())
 }
@@ -0,0 +1,140 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::read_password_safe;
+use super::super::strength;
+use super::CommandError;
+use super::protect::{check_unprotected, unprotect};
+use std::io::Write;
+use std::ops::Deref;
+
+/// Names that `rooster get --field` already uses for something else.
+static RESERVED_NAMES: &'static [&'static str] = &["name", "username", "password", "notes", "kind", "url", "all"];
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    strength::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster field -h");
+    println!("    rooster field set [--enforce-strength <n>] <app_name> <field_name>");
+    println!("    rooster field get <app_name> <field_name>");
+    println!("    rooster field del <app_name> <field_name>");
+    println!("    rooster field list <app_name>");
+    println!("");
+    println!("Custom fields hold anything else you need for an app, like API keys, PINs or");
+    println!("answers to security questions. They can also be used with `rooster get --field`");
+    println!("and in `rooster get --format` templates.");
+    println!("");
+    println!("Example:");
+    println!("    rooster field set github 'api_key'");
+    println!("    rooster field get github 'api_key'");
+}
+
+fn save(store: &mut password::v2::PasswordStore, app_name: &str, password: password::v2::Password) -> Result<(), CommandError> {
+    match store.delete_password(app_name).and_then(|_| store.add_password(password)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't save the custom field ({:?})", err)))
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let (action, app_name) = match (matches.free.get(0), matches.free.get(1)) {
+        (Some(action), Some(app_name)) => (&action[..], &app_name[..]),
+        _ => {
+            return Err(CommandError::MissingArgument("action or the app name"));
+        }
+    };
+
+    let password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+    let mut password = match action {
+        "set" | "del" => {
+            try!(check_unprotected(&password));
+            password
+        },
+        _ => try!(unprotect(&password)),
+    };
+
+    if action == "list" {
+        if let Some(ref fields) = password.custom_fields {
+            for field in fields.iter() {
+                println!("{}", field.name);
+            }
+        }
+        return Ok(());
+    }
+
+    let field_name = match matches.free.get(2) {
+        Some(field_name) => field_name.clone(),
+        None => {
+            return Err(CommandError::MissingArgument("field name"));
+        }
+    };
+
+    match action {
+        "set" => {
+            if RESERVED_NAMES.contains(&field_name.deref()) || password.kind().fields.iter().any(|f| f.name.replace(" ", "_") == field_name) {
+                return Err(CommandError::Failed(format!("\"{}\" is already a field of every {} entry, pick another name", field_name, password.kind().name)));
+            }
+            let required_score = try!(strength::required_score(matches));
+            print_stderr!("What is the {} for {}? ", field_name, app_name);
+            let value = match read_password_safe() {
+                Ok(value) => value,
+                Err(err) => {
+                    println_err!("\nI couldn't read the value ({:?}).", err);
+                    return Err(CommandError::Reported);
+                }
+            };
+            try!(strength::check(value.deref(), required_score));
+            password.set_custom_field(field_name.clone(), value);
+            try!(save(store, app_name, password));
+            println_ok!("Done! I've saved {} for {}.", field_name, app_name);
+            Ok(())
+        },
+        "get" => {
+            match password.custom_field(field_name.deref()) {
+                Some(field) => {
+                    print_stdout!("{}", field.value.deref());
+                    print_stderr!("\n");
+                    Ok(())
+                },
+                None => {
+                    Err(CommandError::Failed(format!("there is no field named \"{}\" for {}", field_name, app_name)))
+                }
+            }
+        },
+        "del" => {
+            if !password.remove_custom_field(field_name.deref()) {
+                return Err(CommandError::Failed(format!("there is no field named \"{}\" for {}", field_name, app_name)));
+            }
+            try!(save(store, app_name, password));
+            println_ok!("Done! I've deleted {} for {}.", field_name, app_name);
+            Ok(())
+        },
+        _ => {
+            Err(CommandError::Failed(format!("I don't know the action \"{}\". Try set, get, del or list", action)))
+        }
+    }
+}
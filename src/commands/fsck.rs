@@ -0,0 +1,136 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::base32;
+use super::super::getopts;
+use super::super::journal;
+use super::super::password;
+use super::super::password::v2::Password;
+use super::super::password_file_path;
+use super::super::rustc_serialize::base64::FromBase64;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster fsck -h");
+    println!("    rooster fsck");
+    println!("");
+    println!("Checks your password file for problems and fixes what can be fixed.");
+    println!("");
+    println!("Before saving, Rooster writes your changes to a journal next to the password file.");
+    println!("If it gets interrupted while saving, the changes are brought back from the journal");
+    println!("the next time you open the password file, with any command. This command does");
+    println!("that too, and then looks for:");
+    println!("- apps with the same name, which are renamed so you can reach all of them,");
+    println!("- attachments that cannot be decoded,");
+    println!("- one-time password secrets that are not valid base32.");
+    println!("");
+    println!("Example:");
+    println!("    rooster fsck");
+}
+
+/// Apps whose name is already used, ignoring case, by an app before them.
+fn duplicate_names(passwords: &[Password]) -> Vec<String> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut duplicates = Vec::new();
+    for p in passwords.iter() {
+        let name = p.name.to_lowercase();
+        if seen.contains(&name) {
+            duplicates.push(p.name.clone());
+        } else {
+            seen.push(name);
+        }
+    }
+    duplicates
+}
+
+/// Gives each app a name of its own, by adding a number to the names already used.
+fn rename_duplicates(store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let passwords = store.get_all_passwords().to_vec();
+    for p in passwords.iter() {
+        let _ = store.delete_password(p.name.deref());
+    }
+    for mut p in passwords.into_iter() {
+        let name = p.name.clone();
+        let mut n = 2;
+        while store.has_password(p.name.deref()) {
+            p.name = format!("{} ({})", name, n);
+            n += 1;
+        }
+        if p.name != name {
+            println_stderr!("Renamed a duplicate of {} to {}.", name, p.name);
+        }
+        if let Err(err) = store.add_password(p) {
+            return Err(CommandError::Failed(format!("I could not rename the duplicates of {} ({:?})", name, err)));
+        }
+    }
+    Ok(())
+}
+
+/// Problems that need a human, one line per problem.
+fn problems(passwords: &[Password]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for p in passwords.iter() {
+        if p.name.trim().is_empty() {
+            problems.push("An app has an empty name.".to_string());
+        }
+        for attachment in p.attachments.iter().flat_map(|attachments| attachments.iter()) {
+            match attachment.content.from_base64() {
+                Ok(ref content) if content.len() as u64 == attachment.size => {},
+                Ok(_) => problems.push(format!("The attachment {} of {} does not have the expected size.", attachment.name, p.name)),
+                Err(_) => problems.push(format!("The attachment {} of {} cannot be decoded.", attachment.name, p.name)),
+            }
+        }
+        if let Some(ref otp) = p.otp {
+            if base32::decode(otp.secret.deref()).is_none() {
+                problems.push(format!("The one-time password secret of {} is not valid base32.", p.name));
+            }
+        }
+    }
+    problems
+}
+
+pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    // The journal is only emptied once the password file is saved, so changes brought
+    // back when opening the password file are still in there.
+    if let Ok(path) = password_file_path() {
+        if let Ok((changes, unreadable)) = journal::read(&journal::path(&path), store) {
+            if !changes.is_empty() || unreadable > 0 {
+                println_stderr!("Recovered {} change(s) from the journal, {} could not be read.", changes.len(), unreadable);
+            }
+        }
+    }
+
+    if !duplicate_names(store.get_all_passwords()).is_empty() {
+        try!(rename_duplicates(store));
+    }
+
+    let problems = problems(store.get_all_passwords());
+    if problems.is_empty() {
+        println_ok!("Your password file looks fine.");
+        return Ok(());
+    }
+    for problem in problems.iter() {
+        println_err!("{}", problem);
+    }
+    // Still a success, so what was fixed gets saved.
+    println_warn!("I can't fix these automatically, you may want to edit or delete these apps.");
+    Ok(())
+}
@@ -15,56 +15,65 @@
 use super::super::getopts;
 use super::super::password;
 use super::super::safe_string::SafeString;
-use super::super::generate::{PasswordSpec, generate_hard_password};
+use super::super::generate::PasswordSpec;
+use super::CommandError;
 use std::io::Write;
 use std::ops::Deref;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    PasswordSpec::add_options(opts);
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster generate -h");
-    println!("    rooster generate <app_name> <username>");
+    println!("    rooster generate [options] <app_name> <username>");
     println!("");
     println!("Example:");
     println!("    rooster generate YouTube me@example.com");
+    println!("    rooster generate --alnum --length 16 YouTube me@example.com");
+    println!("    rooster generate --words 6 --separator ' ' YouTube me@example.com");
+    println!("    rooster generate --no-ambiguous YouTube me@example.com");
+    println!("    rooster generate --charset 'abcdef0123456789' --require-each-class bank me");
 }
 
-pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    if matches.free.len() < 3 {
-        println_err!("Woops, seems like the app name or the username is missing here. For help, try:");
-        println_err!("    rooster generate -h");
-        return Err(1);
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.len() < 2 {
+        return Err(CommandError::MissingArgument("app name or the username"));
     }
 
-    let app_name = matches.free[1].clone();
-    let username = matches.free[2].clone();
+    let app_name = matches.free[0].clone();
+    let username = matches.free[1].clone();
 
     if store.has_password(app_name.deref()) {
-        println_err!("Woops, there is already an app with that name.");
-        return Err(1);
+        return Err(CommandError::Failed("there is already an app with that name".to_string()));
     }
 
-    let password_spec = PasswordSpec::from_matches(matches);
+    let spec = match PasswordSpec::from_matches(matches) {
+        None => { return Err(CommandError::Reported); },
+        Some(spec) => spec,
+    };
 
-    let password_as_string = match password_spec {
-        None => { return Err(1); },
-        Some(spec) => {
-            match generate_hard_password(spec.alnum, spec.len) {
-                Ok(password_as_string) => password_as_string,
-                Err(io_err) => {
-                    println_stderr!("Woops, I could not generate the password ({:?}).", io_err);
-                    return Err(1);
-                }
-            }
+    let password_as_string = match spec.generate() {
+        Ok(password_as_string) => password_as_string,
+        Err(io_err) => {
+            println_stderr!("Woops, I could not generate the password ({:?}).", io_err);
+            return Err(CommandError::Reported);
         }
     };
 
     // Read the master password and try to save the new password.
-    let password = password::v2::Password::new(
+    let mut password = password::v2::Password::new(
         app_name.clone(),
         username,
         SafeString::new(password_as_string)
     );
 
+    // Remember custom rules, so `rooster regenerate` follows them too.
+    if PasswordSpec::has_options(matches) {
+        password.generation_policy = Some(spec.to_policy());
+    }
+
     match store.add_password(password) {
         Ok(_) => {
             println_ok!("Alright! Your password for {} has been added.", app_name);
@@ -72,7 +81,7 @@ pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::Passw
         },
         Err(err) => {
             println_err!("\nI couldn't add this password ({:?}).", err);
-            return Err(1);
+            return Err(CommandError::Reported);
         }
     }
 }
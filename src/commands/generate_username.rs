@@ -0,0 +1,126 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::generate::PasswordSpec;
+use super::super::password;
+use super::super::rand::{Rng, OsRng};
+use super::super::safe_string::SafeString;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "style", "How to make the username: handle or email-plus, default is handle", "STYLE");
+    opts.optopt("", "base", "With --style email-plus, the email address to add the app name to", "EMAIL");
+    PasswordSpec::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster generate-username -h");
+    println!("    rooster generate-username [options] <app_name>");
+    println!("");
+    println!("Makes a username just for this app and a password for it, and saves both. Using a");
+    println!("different username on each site makes it harder to link your accounts together.");
+    println!("");
+    println!("Styles:");
+    println!("    handle      two random words and a number, like amberfalcon27");
+    println!("    email-plus  your address with the app name after a +, like me+youtube@example.com,");
+    println!("                which most email providers deliver to me@example.com");
+    println!("");
+    println!("The password options are the same as for `rooster generate`.");
+    println!("");
+    println!("Example:");
+    println!("    rooster generate-username YouTube");
+    println!("    rooster generate-username --style email-plus --base me@example.com YouTube");
+}
+
+/// Two random words from the passphrase word list and two digits.
+fn handle(words: &[String]) -> Result<String, CommandError> {
+    let mut rng = try!(OsRng::new().map_err(|err| CommandError::Failed(format!("I could not generate the username ({})", err))));
+    let first = &words[rng.gen_range(0, words.len())];
+    let second = &words[rng.gen_range(0, words.len())];
+    Ok(format!("{}{}{:02}", first.to_lowercase(), second.to_lowercase(), rng.gen_range(0, 100)))
+}
+
+/// The base address with the app name, lowercase and without spaces or symbols, as a
+/// "+" suffix to the local part.
+fn email_plus(base: &str, app_name: &str) -> Result<String, CommandError> {
+    let at = try!(base.rfind('@').ok_or(CommandError::Failed(format!("\"{}\" is not an email address", base))));
+    let (local, domain) = base.split_at(at);
+    let tag: String = app_name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    if tag.is_empty() {
+        return Err(CommandError::Failed(format!("\"{}\" has no letters or digits to put in the address", app_name)));
+    }
+    // An existing tag is replaced, so that me+old@example.com gives me+app@example.com.
+    let local = local.split('+').next().unwrap_or(local);
+    Ok(format!("{}+{}{}", local, tag, domain))
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let app_name = matches.free[0].clone();
+    if store.has_password(app_name.deref()) {
+        return Err(CommandError::Failed("there is already an app with that name".to_string()));
+    }
+
+    let spec = match PasswordSpec::from_matches(matches) {
+        None => { return Err(CommandError::Reported); },
+        Some(spec) => spec,
+    };
+
+    let username = match matches.opt_str("style").as_ref().map(|style| &style[..]).unwrap_or("handle") {
+        "handle" => try!(handle(&spec.wordlist)),
+        "email-plus" => {
+            match matches.opt_str("base") {
+                Some(base) => try!(email_plus(&base, &app_name)),
+                None => {
+                    return Err(CommandError::Failed("--style email-plus needs your email address in --base".to_string()));
+                }
+            }
+        },
+        style => {
+            return Err(CommandError::Failed(format!("I don't know the style \"{}\". Try handle or email-plus", style)));
+        }
+    };
+
+    let password_as_string = match spec.generate() {
+        Ok(password_as_string) => password_as_string,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not generate the password ({})", err)));
+        }
+    };
+
+    let mut password = password::v2::Password::new(app_name.clone(), username.clone(), SafeString::new(password_as_string));
+    if PasswordSpec::has_options(matches) {
+        password.generation_policy = Some(spec.to_policy());
+    }
+
+    match store.add_password(password) {
+        Ok(_) => {
+            print_stdout!("{}", username);
+            print_stderr!("\n");
+            println_ok!("Alright! Your username and password for {} have been added.", app_name);
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I couldn't add this password ({:?})", err))),
+    }
+}
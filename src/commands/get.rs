@@ -14,40 +14,220 @@
 
 use super::super::getopts;
 use super::super::password;
-use std::io::Write;
+use super::super::password::kind::mask;
+use super::super::output::is_quiet;
+use super::super::qr;
+use super::super::safe_string::SafeString;
+use super::super::usage;
+use super::CommandError;
+use super::protect::unprotect;
+use std::io::{stdin, Write};
 use std::ops::Deref;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("f", "field", "Choose what to print: username, password, notes, all or a kind-specific field, default is password", "password");
+    opts.optflag("u", "username", "Print the username instead of the password, same as --field username");
+    opts.optopt("", "format", "Print the fields in a template, like '{username}:{password}'", "TEMPLATE");
+    opts.optflag("", "qr", "Show the output as a QR code, to scan it with a phone");
+    opts.optflag("", "reveal", "Show the field on screen after a keypress and wipe it after another, or with --field all, show sensitive fields in full");
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster get -h");
-    println!("    rooster get <app_name>");
+    println!("    rooster get [options] <app_name>");
     println!("");
     println!("Example:");
     println!("    rooster get youtube");
     println!("    rooster get youtube | pbcopy   # for Mac users");
     println!("    rooster get youtube | xsel -ib # for Linux users");
+    println!("    rooster get --field username youtube");
+    println!("    rooster get --field all youtube");
+    println!("    rooster get --format '{{username}}:{{password}}' youtube");
+    println!("    rooster get --qr wifi-home");
+    println!("    rooster get --reveal youtube");
+    println!("");
+    println!("Templates may use {{name}}, {{username}}, {{password}}, {{notes}}, {{kind}}, {{url}},");
+    println!("kind-specific fields like {{ssid}} and custom fields. Use {{{{ and }}}} for literal");
+    println!("braces.");
+    println!("");
+    println!("With --field all, card and ID numbers only show their last 4 characters and");
+    println!("secret fields like a CVV or custom fields are hidden, unless you add --reveal.");
+    println!("");
+    println!("Otherwise, --reveal waits for you to press Enter before it shows the field, and");
+    println!("wipes it from the terminal when you press Enter again. It never goes to stdout,");
+    println!("so it stays out of pipes and out of sight until you are ready.");
+}
+
+/// Waits for Enter on stdin.
+fn wait_for_enter() -> Result<(), CommandError> {
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not read from stdin ({})", err)))
+        }
+    }
+}
+
+/// Shows the value on the terminal between two presses of Enter, then clears its line.
+fn reveal(field: &str, value: &SafeString) -> Result<(), CommandError> {
+    print_stderr!("Press Enter to show the {}, then Enter again to hide it.", field);
+    try!(wait_for_enter());
+    // Enter moved the cursor down a line, so go back up and overwrite the prompt.
+    print_stderr!("\x1b[1A\r\x1b[2K{}", value.deref());
+    let result = wait_for_enter();
+    print_stderr!("\x1b[1A\r\x1b[2K");
+    result
+}
+
+/// Replaces `{field}` placeholders in the template with the entry's fields.
+fn format_entry(template: &str, password: &password::v2::Password) -> Result<SafeString, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            },
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => { return Err(format!("the placeholder \"{{{}\" is never closed", name)); }
+                    }
+                }
+                match password.field(name.deref()) {
+                    Some(value) => output.push_str(value.deref()),
+                    None => { return Err(format!("there is no field named \"{}\"", name)); }
+                }
+            },
+            '}' => {
+                return Err("found a \"}\" without a matching \"{\", use \"}}\" for a literal brace".to_string());
+            },
+            c => output.push(c),
+        }
+    }
+    Ok(SafeString::new(output))
 }
 
-pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    if matches.free.len() < 2 {
-        println_err!("Woops, seems like the app name is missing here. For help, try:");
-        println_err!("    rooster get -h");
-        return Err(1);
+/// Prints every field of the entry, one per line, hiding sensitive ones unless `reveal`.
+fn print_all_fields(password: &password::v2::Password, reveal: bool) {
+    let kind = password.kind();
+    println!("app: {}", password.name);
+    println!("kind: {}", kind.name);
+    println!("username: {}", password.username);
+    if kind.masked && !reveal {
+        println!("{}: {}", kind.secret_name, mask(password.password.deref()));
+    } else {
+        println!("{}: {}", kind.secret_name, password.password.deref());
     }
+    if let Some(ref payload) = password.payload {
+        for (field, value) in kind.fields.iter().zip(payload.values().iter()) {
+            if field.secret && !reveal {
+                println!("{}: {}", field.name, "********");
+            } else {
+                println!("{}: {}", field.name, value.deref());
+            }
+        }
+    }
+    println!("notes: {}", password.notes.as_ref().map(|n| n.deref()).unwrap_or(""));
+    if let Some(ref url) = password.url {
+        println!("url: {}", url);
+    }
+    if let Some(ref fields) = password.custom_fields {
+        for field in fields.iter() {
+            if reveal {
+                println!("{}: {}", field.name, field.value.deref());
+            } else {
+                println!("{}: {}", field.name, "********");
+            }
+        }
+    }
+}
 
-    let ref app_name = matches.free[1];
+/// Records the read when usage tracking is enabled, for `rooster stats`.
+fn record_use(store: &mut password::v2::PasswordStore, app_name: &str) -> Result<(), CommandError> {
+    if !usage::tracking_enabled() {
+        return Ok(());
+    }
+    match store.record_use(app_name) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't record that you used this password ({:?})", err)))
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let field = if matches.opt_present("username") {
+        "username".to_string()
+    } else {
+        matches.opt_str("field").unwrap_or("password".to_string())
+    };
 
     match store.get_password(app_name) {
-        Some(ref password) => {
-            print_stdout!("{}", password.password.deref());
-            print_stderr!("\n");
-            return Ok(());
+        Some(password) => {
+            // The username and URL are not protected, so they don't need the passphrase.
+            let password = if matches.opt_present("format") || !["name", "username", "url", "kind"].contains(&field.deref()) {
+                try!(unprotect(&password))
+            } else {
+                password
+            };
+            let password = &password;
+            let output = if let Some(template) = matches.opt_str("format") {
+                match format_entry(template.deref(), password) {
+                    Ok(output) => output,
+                    Err(err) => {
+                        return Err(CommandError::Failed(format!("I could not use this template: {}", err)));
+                    }
+                }
+            } else if field == "all" {
+                // With every field, --reveal unmasks the sensitive ones instead.
+                print_all_fields(password, matches.opt_present("reveal"));
+                return record_use(store, app_name);
+            } else {
+                match password.field(field.deref()) {
+                    Some(value) => value,
+                    None => {
+                        return Err(CommandError::Failed(format!("I don't know the field \"{}\". Try username, password, notes or all", field)));
+                    }
+                }
+            };
+
+            if matches.opt_present("reveal") {
+                try!(reveal(if matches.opt_present("format") { "entry" } else { field.deref() }, &output));
+            } else if matches.opt_present("qr") {
+                match qr::render(output.as_bytes()) {
+                    Ok(code) => { print_stdout!("{}", code.deref()); },
+                    Err(err) => {
+                        return Err(CommandError::Failed(format!("I could not make a QR code out of this ({})", err)));
+                    }
+                }
+            } else {
+                print_stdout!("{}", output.deref());
+                // Keeps the shell prompt on its own line, without a newline in the output.
+                if !is_quiet() {
+                    print_stderr!("\n");
+                }
+            }
+            record_use(store, app_name)
         },
         None => {
-            println_err!("I couldn't find a password for this app. Make sure you");
-            println_err!("didn't make a typo. For a list of passwords, try:");
-            println_err!("    rooster list");
-            return Err(1);
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
         }
     }
 }
@@ -0,0 +1,122 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::color::{self, Color, Stream};
+use super::super::getopts;
+use super::super::password;
+use super::super::regex::{Regex, RegexBuilder};
+use super::CommandError;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("i", "ignore-case", "Match upper and lower case letters alike");
+    opts.optflag("", "passwords", "Also search the passwords and secret fields, and show them when they match");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster grep -h");
+    println!("    rooster grep [options] <pattern>");
+    println!("");
+    println!("Searches the names, aliases, usernames, URLs, notes and custom fields of every app");
+    println!("for a regular expression, and prints the matching lines with the match");
+    println!("highlighted. Passwords are only searched with --passwords.");
+    println!("");
+    println!("Example:");
+    println!("    rooster grep @example.com");
+    println!("    rooster grep -i 'vpn|ssh'");
+}
+
+/// The fields to search, as (label, value) pairs.
+fn searched_fields(password: &password::v2::Password, with_secrets: bool) -> Vec<(String, String)> {
+    let mut fields = vec![
+        ("name".to_string(), password.name.clone()),
+        ("username".to_string(), password.username.clone()),
+    ];
+    if let Some(ref aliases) = password.aliases {
+        fields.extend(aliases.iter().map(|alias| ("alias".to_string(), alias.clone())));
+    }
+    if let Some(ref url) = password.url {
+        fields.push(("url".to_string(), url.clone()));
+    }
+    if let Some(ref notes) = password.notes {
+        fields.push(("notes".to_string(), notes.deref().to_string()));
+    }
+    let kind = password.kind();
+    if let Some(ref payload) = password.payload {
+        for (field, value) in kind.fields.iter().zip(payload.values().iter()) {
+            if with_secrets || !field.secret {
+                fields.push((field.name.to_string(), value.deref().to_string()));
+            }
+        }
+    }
+    if let Some(ref custom_fields) = password.custom_fields {
+        for field in custom_fields.iter() {
+            fields.push((field.name.clone(), field.value.deref().to_string()));
+        }
+    }
+    if with_secrets {
+        fields.push((kind.secret_name.to_string(), password.password.deref().to_string()));
+    }
+    fields
+}
+
+/// The line with every match of the pattern highlighted.
+fn highlight(pattern: &Regex, line: &str) -> String {
+    let mut output = String::new();
+    let mut last = 0;
+    for found in pattern.find_iter(line) {
+        output.push_str(&line[last..found.start()]);
+        output.push_str(&color::paint(Color::Red, Stream::Stdout, found.as_str()));
+        last = found.end();
+    }
+    output.push_str(&line[last..]);
+    output
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("pattern"));
+    }
+
+    let pattern = match RegexBuilder::new(&matches.free[0]).case_insensitive(matches.opt_present("ignore-case")).build() {
+        Ok(pattern) => pattern,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("this is not a valid pattern ({})", err)));
+        }
+    };
+
+    let with_secrets = matches.opt_present("passwords");
+    let mut found = 0;
+    for password in store.get_all_passwords().iter() {
+        let mut lines = Vec::new();
+        for (label, value) in searched_fields(password, with_secrets).into_iter() {
+            for line in value.lines().filter(|line| pattern.is_match(line)) {
+                lines.push(format!("    {}: {}", label, highlight(&pattern, line)));
+            }
+        }
+        if !lines.is_empty() {
+            println!("{}", color::paint(Color::Cyan, Stream::Stdout, &password.name));
+            for line in lines.iter() {
+                println!("{}", line);
+            }
+            found += 1;
+        }
+    }
+
+    if found == 0 {
+        return Err(CommandError::Failed(format!("nothing matches \"{}\"", matches.free[0])));
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::age;
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::SafeString;
+use super::super::safe_vec::SafeVec;
+use super::super::serde_json;
+use super::CommandError;
+use super::conflict::{self, Outcome};
+use std::fs::File;
+use std::io::{stdin, Read, Write};
+use std::ops::Deref;
+use std::str;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "format", "The format of the file: json, the default, or age", "FORMAT");
+    opts.optopt("i", "identity", "With --format age, the identity file to decrypt with", "FILE");
+    conflict::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster import -h");
+    println!("    rooster import [options] [<file>]");
+    println!("");
+    println!("Adds the apps of a file from `rooster export`, read from the input if there is no");
+    println!("file. With --format age, the file is decrypted with age first, using the identity");
+    println!("file given with -i, or a passphrase that age asks for.");
+    println!("");
+    conflict::print_help();
+    println!("");
+    println!("Example:");
+    println!("    rooster import backup.json");
+    println!("    rooster import --format age -i ~/.age/key.txt backup.age");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let format = matches.opt_str("format").unwrap_or("json".to_string());
+    if format != "json" && format != "age" {
+        return Err(CommandError::Failed(format!("the format must be json or age, not \"{}\"", format)));
+    }
+    let strategy = try!(conflict::strategy_from_matches(matches));
+
+    let mut input = Vec::new();
+    let result = match matches.free.get(0) {
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_end(&mut input)),
+        None => stdin().read_to_end(&mut input),
+    };
+    if let Err(err) = result {
+        return Err(CommandError::Failed(format!("I could not read the file to import ({})", err)));
+    }
+    let mut input = SafeVec::new(input);
+    if format == "age" {
+        let identity = matches.opt_str("identity");
+        input = try!(age::decrypt(identity.as_ref().map(|identity| identity.deref()), &input).map_err(CommandError::Failed));
+    }
+
+    let json = match str::from_utf8(&input) {
+        Ok(json) => SafeString::new(json.to_string()),
+        Err(_) => {
+            return Err(CommandError::Failed("the file to import is not UTF-8, is it encrypted? Try --format age".to_string()));
+        }
+    };
+    let passwords = match serde_json::from_str::<Vec<password::v2::Password>>(json.deref()) {
+        Ok(passwords) => passwords,
+        Err(_) => {
+            return Err(CommandError::Failed("the file to import is not an export of Rooster".to_string()));
+        }
+    };
+
+    let (mut added, mut updated, mut kept) = (0, 0, 0);
+    for password in passwords.into_iter() {
+        match try!(conflict::add(store, password, strategy)) {
+            Outcome::Added | Outcome::Renamed(_) => added += 1,
+            Outcome::Replaced | Outcome::Merged => updated += 1,
+            Outcome::Kept => kept += 1,
+        }
+    }
+    println_ok!("Done! I've added {} apps, updated {} and kept {} as they were.", added, updated, kept);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::CommandError;
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster info -h");
+    println!("    rooster info <app_name>");
+    println!("");
+    println!("Shows everything about an app except its secrets: username, address, password");
+    println!("length, history, what else is stored, and when it was created, updated and last");
+    println!("used, in your local time. Handy to check an app without showing its password.");
+    println!("");
+    println!("Example:");
+    println!("    rooster info youtube");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+
+    println!("Name:           {}", password.name);
+    println!("Kind:           {}", password.kind().name);
+    if let Some(ref aliases) = password.aliases {
+        println!("Aliases:        {}", aliases.join(", "));
+    }
+    if let Some(ref tags) = password.tags {
+        println!("Tags:           {}", tags.join(", "));
+    }
+    println!("Username:       {}", password.username);
+    if let Some(ref url) = password.url {
+        println!("URL:            {}", url);
+    }
+    println!("Password:       {} characters", password.password.chars().count());
+    println!("History:        {} previous passwords", password.history.as_ref().map(|h| h.len()).unwrap_or(0));
+    if password.notes.is_some() {
+        println!("Notes:          yes");
+    }
+    if let Some(ref fields) = password.custom_fields {
+        let names: Vec<&str> = fields.iter().map(|field| field.name.deref()).collect();
+        println!("Custom fields:  {}", names.join(", "));
+    }
+    if let Some(ref attachments) = password.attachments {
+        let names: Vec<&str> = attachments.iter().map(|attachment| attachment.name.deref()).collect();
+        println!("Attachments:    {}", names.join(", "));
+    }
+    if let Some(ref otp) = password.otp {
+        println!("One-time code:  {}", if otp.counter.is_some() { "HOTP" } else { "TOTP" });
+    }
+    if let Some(ref codes) = password.recovery_codes {
+        println!("Recovery codes: {} left", codes.len());
+    }
+    println!("Created:        {}", password.created_at.to_local_string());
+    println!("Updated:        {}", password.updated_at.to_local_string());
+    match password.last_used_at {
+        Some(last_used_at) => println!("Last used:      {} ({} uses)", last_used_at.to_local_string(), password.use_count.unwrap_or(0)),
+        None => println!("Last used:      never, or usage tracking was off"),
+    }
+    Ok(())
+}
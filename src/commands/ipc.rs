@@ -0,0 +1,194 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::api;
+use super::super::getopts;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::save_password_file;
+use super::super::serde_json::{self, Map, Value};
+use super::sync;
+use super::CommandError;
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, Read, Write};
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster ipc -h");
+    println!("    rooster ipc");
+    println!("");
+    println!("Speaks JSON-RPC 2.0 on stdin and stdout, one message per line, for editor plugins");
+    println!("and graphical interfaces that keep Rooster running in the background. The master");
+    println!("password is the first line on stdin, and is only asked once.");
+    println!("");
+    println!("Methods, with their params:");
+    println!("    list                              the apps, without their passwords");
+    println!("    get           {{name}}              an app, with its password");
+    println!("    field         {{name, field}}       one field of an app");
+    println!("    otp           {{name}}              the current one-time password");
+    println!("    add           {{name, username, password, url}}");
+    println!("    generate      {{name, username, length, alnum, words}}");
+    println!("                                      adds an app with a generated password");
+    println!("    set_password  {{name, password}}");
+    println!("    edit          {{name, username, url, notes}}");
+    println!("    rename        {{name, new_name}}");
+    println!("    tags          {{name, tags}}        the tags of an app, set to tags if given");
+    println!("    delete        {{name}}");
+    println!("    audit                             weak, reused and similar passwords");
+    println!("    sync                              like `rooster sync`, then reloads the file");
+    println!("");
+    println!("Params other than name are optional for generate, edit and tags. Changes are");
+    println!("saved right away. Rooster stops when stdin is closed.");
+    println!("");
+    println!("Example:");
+    println!("    {{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"get\", \"params\": {{\"name\": \"YouTube\"}}}}");
+}
+
+/// Error codes from the JSON-RPC 2.0 specification, and ours from -32000.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const NOT_FOUND: i64 = -32000;
+const ALREADY_EXISTS: i64 = -32001;
+const FAILED: i64 = -32002;
+
+fn response(id: Value, result: Result<Value, (i64, String)>) -> Value {
+    let mut object = Map::new();
+    object.insert("jsonrpc".to_string(), Value::String("2.0".to_string()));
+    object.insert("id".to_string(), id);
+    match result {
+        Ok(result) => {
+            object.insert("result".to_string(), result);
+        },
+        Err((code, message)) => {
+            let mut error = Map::new();
+            error.insert("code".to_string(), Value::from(code));
+            error.insert("message".to_string(), Value::String(message));
+            object.insert("error".to_string(), Value::Object(error));
+        }
+    }
+    Value::Object(object)
+}
+
+fn api_error(err: api::Error) -> (i64, String) {
+    let code = match err {
+        api::Error::NotFound(_) => NOT_FOUND,
+        api::Error::AlreadyExists(_) => ALREADY_EXISTS,
+        api::Error::InvalidParams(_) => INVALID_PARAMS,
+        api::Error::Failed(_) => FAILED,
+    };
+    (code, err.message())
+}
+
+fn command_error(err: CommandError) -> (i64, String) {
+    match err {
+        CommandError::MissingArgument(argument) => (INVALID_PARAMS, format!("the {} is missing", argument)),
+        CommandError::NoSuchApp(name) => (NOT_FOUND, format!("there is no app named {}", name)),
+        CommandError::WrongMasterPassword => (FAILED, "the password file does not open with your master password".to_string()),
+        CommandError::PasswordFile(message) | CommandError::Failed(message) => (FAILED, message),
+        CommandError::Reported | CommandError::Exit(_) => (FAILED, "it failed, see stderr".to_string()),
+    }
+}
+
+/// Syncs the password file like `rooster sync`, opening the copies with the key of the
+/// store instead of asking for the master password, and reloads the store if the sync
+/// changed the file. Returns what the sync did.
+fn sync(store: &mut password::v2::PasswordStore) -> Result<Value, (i64, String)> {
+    let path = try!(password_file_path().map_err(|_| (FAILED, "I could not determine where your password file is".to_string())));
+    let action = {
+        let store = &*store;
+        try!(sync::run(&path, &mut |content: &[u8]| store.reopen(content).map_err(sync::open_error)).map_err(command_error))
+    };
+    if action == "downloaded" || action == "merged" {
+        let mut content = Vec::new();
+        try!(File::open(&path).and_then(|mut file| file.read_to_end(&mut content)).map_err(|err| {
+            (FAILED, format!("the sync went fine, but I could not read the new password file ({})", err))
+        }));
+        *store = try!(store.reopen(&content).map_err(|err| {
+            (FAILED, format!("the sync went fine, but I could not open the new password file ({:?})", err))
+        }));
+    }
+    Ok(Value::String(action.to_string()))
+}
+
+fn call(method: &str, params: &Value, store: &mut password::v2::PasswordStore) -> Result<Value, (i64, String)> {
+    let (result, changed) = match method {
+        "list" => (Ok(api::list(store)), false),
+        "get" => {
+            let name = try!(params.get("name").and_then(|name| name.as_str()).ok_or((INVALID_PARAMS, "\"name\" must be a string".to_string())));
+            (api::get(store, name), false)
+        },
+        "field" => (api::field(store, params), false),
+        // Only counter-based codes change the store.
+        "otp" => {
+            let (result, changed) = try!(api::one_time_password(store, params).map_err(api_error));
+            (Ok(result), changed)
+        },
+        "add" => (api::add(store, params), true),
+        "generate" => (api::generate(store, params), true),
+        "set_password" => (api::set_password(store, params), true),
+        "edit" => (api::edit(store, params), true),
+        "rename" => (api::rename(store, params), true),
+        "tags" => (api::tags(store, params), params.get("tags").is_some()),
+        "delete" => (api::delete(store, params), true),
+        "audit" => (Ok(api::audit(store)), false),
+        "sync" => return sync(store),
+        _ => return Err((METHOD_NOT_FOUND, format!("there is no method named {}", method))),
+    };
+    let result = try!(result.map_err(api_error));
+    if changed {
+        try!(save_password_file(store).map_err(|err| (FAILED, format!("the change was not saved: {}", err))));
+    }
+    Ok(result)
+}
+
+fn handle(line: &str, store: &mut password::v2::PasswordStore) -> Value {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request) => request,
+        Err(_) => return response(Value::Null, Err((PARSE_ERROR, "the message is not valid JSON".to_string()))),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = match request.get("method").and_then(|method| method.as_str()) {
+        Some(method) => method.to_string(),
+        None => return response(id, Err((INVALID_REQUEST, "\"method\" must be a string".to_string()))),
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Object(Map::new()));
+    response(id, call(&method, &params, store))
+}
+
+pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let input = stdin();
+    for line in input.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read from stdin ({})", err)));
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&line, store);
+        let mut output = stdout();
+        if writeln!(output, "{}", response).and_then(|_| output.flush()).is_err() {
+            return Err(CommandError::Reported);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::fido2;
+use super::super::getopts;
+use super::super::password;
+use super::super::rand::{Rng, OsRng};
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::super::safe_vec::SafeVec;
+use super::CommandError;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::ops::Deref;
+
+/// Length of the key files Rooster creates.
+const KEYFILE_LEN: usize = 64;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster key -h");
+    println!("    rooster key list");
+    println!("    rooster key add gpg <key_id>");
+    println!("    rooster key add keyfile <file>");
+    println!("    rooster key add recovery-key");
+    println!("    rooster key add yubikey");
+    println!("    rooster key add fido2");
+    println!("    rooster key add tpm [<name>]");
+    println!("    rooster key add second-factor");
+    println!("    rooster key remove <number>");
+    println!("    rooster key remove-second-factor");
+    println!("    rooster key enroll-fido2");
+    println!("");
+    println!("Key slots are other ways to open your password file than the master password.");
+    println!("The first one gives the password file a random key, which the master password");
+    println!("and each key slot wrap, so adding or removing one does not re-encrypt anything.");
+    println!("");
+    println!("A key file is created with random bytes if it does not exist. Each kind of key");
+    println!("slot has a global option to open the password file with it:");
+    println!("    --gpg               your GPG key, which may be on a smartcard, and its PIN");
+    println!("    --keyfile <file>    a file, on a USB stick for example, also $ROOSTER_KEYFILE");
+    println!("    --recovery-key      a random key Rooster printed once, to keep on paper");
+    println!("    --yubikey           a YubiKey set up for HMAC-SHA1 challenge-response");
+    println!("    --fido2             a FIDO2 authenticator with the hmac-secret extension");
+    println!("    --tpm               the TPM2 of this machine, with a PIN");
+    println!("");
+    println!("The master password keeps working on its own, unless you add the YubiKey as a");
+    println!("second factor: then the master password only opens the password file along with");
+    println!("the YubiKey. Other key slots still open it on their own, so remove those you don't");
+    println!("want, like a recovery key.");
+    println!("");
+    println!("`rooster key enroll-fido2` is `rooster key add fido2`. Enroll each of your FIDO2");
+    println!("authenticators, plugged in one at a time, so that any of them opens the password");
+    println!("file. It needs libfido2, and asks for the PIN of the authenticator if it has one.");
+    println!("");
+    println!("A TPM key slot only opens on the machine it was added on, with its PIN, so add");
+    println!("one on each machine you trust, named after it. The TPM locks itself for a while");
+    println!("after too many wrong PINs, so a short one is enough. It needs tpm2-tools.");
+    println!("");
+    println!("Set $ROOSTER_GPG to use another GnuPG program than gpg, $ROOSTER_YUBIKEY_SLOT to");
+    println!("use slot 1 of the YubiKey, and $ROOSTER_FIDO2_DEVICE to pick a FIDO2 authenticator");
+    println!("when several are plugged in.");
+    println!("");
+    println!("Example:");
+    println!("    rooster key add gpg 0x1234ABCD");
+    println!("    rooster key add keyfile /media/usb/rooster.key");
+    println!("    rooster key add second-factor");
+    println!("    rooster --keyfile /media/usb/rooster.key list");
+    println!("    rooster key list");
+    println!("    rooster key remove 2");
+}
+
+fn kind_name(kind: u8) -> &'static str {
+    match kind {
+        password::v2::KEY_SLOT_MASTER_PASSWORD => "master password",
+        password::v2::KEY_SLOT_GPG => "gpg",
+        password::v2::KEY_SLOT_KEYFILE => "key file",
+        password::v2::KEY_SLOT_RECOVERY_KEY => "recovery key",
+        password::v2::KEY_SLOT_YUBIKEY => "yubikey",
+        password::v2::KEY_SLOT_MEMBER => "member",
+        password::v2::KEY_SLOT_MASTER_PASSWORD_YUBIKEY => "master password and yubikey",
+        password::v2::KEY_SLOT_FIDO2 => "fido2",
+        password::v2::KEY_SLOT_TPM => "tpm",
+        _ => "unknown",
+    }
+}
+
+fn list(store: &password::v2::PasswordStore) {
+    if store.key_slots().is_empty() {
+        println_info!("Your password file has no key slots, only your master password opens it.");
+        return;
+    }
+    for (i, key_slot) in store.key_slots().iter().enumerate() {
+        if key_slot.label == kind_name(key_slot.kind) || key_slot.kind == password::v2::KEY_SLOT_RECOVERY_KEY || key_slot.kind == password::v2::KEY_SLOT_YUBIKEY {
+            println!("{:3}  {}", i + 1, kind_name(key_slot.kind));
+        } else {
+            println!("{:3}  {:16} {}", i + 1, kind_name(key_slot.kind), key_slot.label);
+        }
+    }
+}
+
+/// The master password, typed again, since key slots wrap the key with it.
+fn read_master_password(store: &password::v2::PasswordStore) -> Result<SafeString, CommandError> {
+    print_stderr!("Type your master password once more: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read your master password ({})", err)));
+        }
+    };
+    if !store.is_master_password(master_password.deref()) {
+        return Err(CommandError::WrongMasterPassword);
+    }
+    Ok(master_password)
+}
+
+/// Adds a key slot for the FIDO2 authenticator plugged in.
+fn add_fido2(store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let device = try!(fido2::device().map_err(CommandError::Failed));
+    let master_password = try!(read_master_password(store));
+    try!(store.add_fido2_key_slot(master_password.deref(), &device).map_err(CommandError::Failed));
+    println_ok!("Done! Your {} can now open your password file, with `rooster --fido2`.", device.name);
+    Ok(())
+}
+
+/// The PIN of a new TPM key slot, typed twice.
+fn read_new_pin() -> Result<SafeString, CommandError> {
+    print_stderr!("Choose a PIN for the TPM: ");
+    let pin = try!(read_password_safe().map_err(|err| CommandError::Failed(format!("I could not read the PIN ({})", err))));
+    if pin.is_empty() {
+        return Err(CommandError::Failed("the PIN cannot be empty".to_string()));
+    }
+    print_stderr!("Type the PIN again: ");
+    let confirmation = try!(read_password_safe().map_err(|err| CommandError::Failed(format!("I could not read the PIN ({})", err))));
+    if pin != confirmation {
+        return Err(CommandError::Failed("the PINs did not match".to_string()));
+    }
+    Ok(pin)
+}
+
+/// Only the user may read the key files Rooster creates.
+#[cfg(unix)]
+fn keyfile_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true).mode(0o400);
+    options
+}
+
+#[cfg(windows)]
+fn keyfile_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// Reads a key file, or creates it with random bytes if there is none yet.
+fn read_or_create_keyfile(path: &str) -> Result<SafeVec, String> {
+    let mut content = Vec::new();
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut content)) {
+        Ok(_) if content.is_empty() => Err(format!("{} is empty, it would not protect anything", path)),
+        Ok(_) => Ok(SafeVec::new(content)),
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => {
+            let mut content = SafeVec::new(vec![0u8; KEYFILE_LEN]);
+            try!(OsRng::new().map_err(|err| format!("I could not make a key file ({})", err))).fill_bytes(&mut content);
+            try!(keyfile_options().open(path).and_then(|mut file| file.write_all(&content)).map_err(|err| format!("I could not create {} ({})", path, err)));
+            println_info!("I created the key file {}. Keep a copy: without it, this key slot no longer opens anything.", path);
+            Ok(content)
+        },
+        Err(err) => Err(format!("I could not read {} ({})", path, err)),
+    }
+}
+
+fn add(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let argument = matches.free.get(2);
+    match matches.free.get(1).map(|kind| kind.deref()) {
+        Some("gpg") => {
+            let recipient = try!(argument.ok_or(CommandError::MissingArgument("GPG key ID")));
+            let master_password = try!(read_master_password(store));
+            try!(store.add_gpg_key_slot(master_password.deref(), recipient.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! Your GPG key {} can now open your password file, with `rooster --gpg`.", recipient);
+        },
+        Some("keyfile") => {
+            let path = try!(argument.ok_or(CommandError::MissingArgument("key file")));
+            let content = try!(read_or_create_keyfile(path.deref()).map_err(CommandError::Failed));
+            let master_password = try!(read_master_password(store));
+            try!(store.add_keyfile_key_slot(master_password.deref(), path.deref(), content.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! {} can now open your password file, with `rooster --keyfile {}`.", path, path);
+        },
+        Some("recovery-key") => {
+            let master_password = try!(read_master_password(store));
+            let recovery_key = try!(store.add_recovery_key_slot(master_password.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! This recovery key can now open your password file, with `rooster --recovery-key`:");
+            println!("{}", recovery_key.deref());
+            println_info!("Write it down and keep it somewhere safe. I will not show it again.");
+        },
+        Some("yubikey") => {
+            let master_password = try!(read_master_password(store));
+            try!(store.add_yubikey_key_slot(master_password.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! Your YubiKey can now open your password file, with `rooster --yubikey`.");
+        },
+        Some("fido2") => {
+            try!(add_fido2(store));
+        },
+        Some("tpm") => {
+            let label = argument.map(|name| name.deref()).unwrap_or("tpm");
+            let master_password = try!(read_master_password(store));
+            let pin = try!(read_new_pin());
+            try!(store.add_tpm_key_slot(master_password.deref(), label, &pin).map_err(CommandError::Failed));
+            println_ok!("Done! The TPM of this machine can now open your password file, with `rooster --tpm` and your PIN.");
+        },
+        Some("second-factor") => {
+            let master_password = try!(read_master_password(store));
+            try!(store.require_yubikey(master_password.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! Your master password now only opens your password file along with your YubiKey.");
+        },
+        Some(kind) => {
+            return Err(CommandError::Failed(format!("there is no \"{}\" key slot, try gpg, keyfile, recovery-key, yubikey, fido2, tpm or second-factor", kind)));
+        },
+        None => {
+            return Err(CommandError::MissingArgument("kind of key slot"));
+        },
+    }
+    Ok(())
+}
+
+fn remove(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let number = match matches.free.get(1) {
+        Some(number) => number,
+        None => {
+            return Err(CommandError::MissingArgument("key slot number"));
+        }
+    };
+    let index = match number.parse::<usize>() {
+        Ok(number) if number > 0 => number - 1,
+        _ => {
+            return Err(CommandError::Failed(format!("the key slot number must be one from `rooster key list`, not \"{}\"", number)));
+        }
+    };
+    let removed = try!(store.remove_key_slot(index).map_err(CommandError::Failed));
+    println_ok!("Done! The {} key slot {} no longer opens your password file.", kind_name(removed.kind), removed.label);
+    println_info!("Copies of the password file made before still open with it. Run `rooster rekey` if that matters.");
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    match matches.free.get(0).map(|action| action.deref()) {
+        Some("list") | None => {
+            list(store);
+            Ok(())
+        },
+        Some("add") => add(matches, store),
+        Some("remove") => remove(matches, store),
+        Some("enroll-fido2") => add_fido2(store),
+        Some("remove-second-factor") => {
+            let master_password = try!(read_master_password(store));
+            try!(store.stop_requiring_yubikey(master_password.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! Your master password opens your password file on its own again.");
+            Ok(())
+        },
+        Some(action) => Err(CommandError::Failed(format!("there is no `rooster key {}`, try list, add, remove, remove-second-factor or enroll-fido2", action))),
+    }
+}
@@ -13,33 +13,238 @@
 // limitations under the License.
 
 use super::super::getopts;
+use super::super::color::{self, Color, Stream};
+use super::super::config;
 use super::super::password;
+use super::super::password_file_path;
+use super::super::safe_string::read_password_safe;
+use super::super::safe_vec::SafeVec;
+use super::super::timestamp::{self, Timestamp};
+use super::CommandError;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
 use std::iter::repeat;
 use std::iter::FromIterator;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("s", "sort", "Sort the apps by name or last-used, default is the order they were added in", "KEY");
+    opts.optflag("", "all-vaults", "Also list the apps of the other vaults in the configuration file");
+    opts.optopt("", "updated-since", "Only list the apps updated in this period, like 30d", "DURATION");
+    opts.optflag("t", "tree", "Show the apps as a tree of folders, from names like work/aws/prod");
+    opts.optopt("g", "group-by", "Show the apps in sections by tag, folder or type", "KEY");
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster list -h");
-    println!("    rooster list");
+    println!("    rooster list [options] [<folder>]");
+    println!("    rooster list --sort last-used");
+    println!("    rooster list --all-vaults");
+    println!("    rooster list --updated-since 30d");
+    println!("    rooster list --tree work/");
+    println!("    rooster list --group-by tag");
+    println!("");
+    println!("Slashes in app names make folders, like work/aws/prod. Give a folder to only list");
+    println!("the apps inside it, and --tree to show the folders as a tree.");
+    println!("");
+    println!("--group-by shows one table for each tag, folder or type of app (login, card...).");
+    println!("An app with several tags is in the table of each of them. Tags are set with");
+    println!("`rooster edit`.");
+    println!("");
+    println!("Sorting by last-used puts the most recently used apps first. It needs usage");
+    println!("tracking, see `rooster stats -h`.");
+    println!("");
+    println!("With --all-vaults, you'll be asked the master password of each other vault. The");
+    println!("other vaults are only read, never changed.");
+    println!("");
+    println!("Durations for --updated-since are a number followed by h (hours), d (days),");
+    println!("w (weeks) or y (years).");
     println!("");
     println!("Example:");
     println!("    rooster list");
 }
 
-pub fn callback_exec(_matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    // We'll now print the password in a table.
+/// Prints the apps sorted by name and indented under their folders.
+fn print_tree(mut paths: Vec<(String, &str)>) {
+    paths.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    let mut previous: Vec<&str> = Vec::new();
+    for &(ref path, username) in paths.iter() {
+        let parts: Vec<&str> = path.split('/').collect();
+        let (leaf, folders) = parts.split_last().unwrap();
+        // Only print the folders that the previous app was not in already.
+        let common = folders.iter().zip(previous.iter()).take_while(|&(a, b)| a == b).count();
+        for (depth, folder) in folders.iter().enumerate().skip(common) {
+            println!("{}{}", String::from_iter(repeat("    ").take(depth)), color::paint(Color::Blue, Stream::Stdout, &format!("{}/", folder)));
+        }
+        println!("{}{} ({})", String::from_iter(repeat("    ").take(folders.len())), color::paint(Color::Cyan, Stream::Stdout, leaf), username);
+        previous = folders.to_vec();
+    }
+}
+
+/// The sections an app is listed in with `--group-by`, an empty name for apps without
+/// a tag or folder.
+fn groups_of(password: &password::v2::Password, key: &str) -> Vec<String> {
+    match key {
+        "tag" => password.tags.clone().unwrap_or(vec![String::new()]),
+        "folder" => vec![password.name.rsplitn(2, '/').nth(1).unwrap_or("").to_string()],
+        _ => vec![password.kind().name.to_string()],
+    }
+}
+
+/// Prints the apps in a table, with their vault if there are several.
+fn print_table(passwords: &[(&str, &password::v2::Password)], all_vaults: bool) {
     // The table is delimited by borders.
-    let horizontal_border = String::from_iter(repeat('-').take(73));
+    let horizontal_border = String::from_iter(repeat('-').take(if all_vaults { 99 } else { 84 }));
 
     println!("{}", horizontal_border);
-    println!("| {:2} | {:30} | {:30} |", "id", "app", "username");
+    if all_vaults {
+        println!("| {:2} | {:12} | {:30} | {:30} | {:8} |", "id", "vault", "app", "username", "kind");
+    } else {
+        println!("| {:2} | {:30} | {:30} | {:8} |", "id", "app", "username", "kind");
+    }
     println!("{}", horizontal_border);
     let mut i = 0;
-    for p in store.get_all_passwords().iter() {
-        println!("| {:2?} | {:30} | {:30} |", i, p.name, p.username);
+    for &(vault, p) in passwords.iter() {
+        if all_vaults {
+            let name = color::paint(Color::Cyan, Stream::Stdout, &format!("{:30}", p.name));
+            println!("| {:2?} | {:12} | {} | {:30} | {:8} |", i, vault, name, p.username, p.kind().name);
+        } else {
+            let name = color::paint(Color::Cyan, Stream::Stdout, &format!("{:30}", p.name));
+            println!("| {:2?} | {} | {:30} | {:8} |", i, name, p.username, p.kind().name);
+        }
         i += 1;
     }
     println!("{}", horizontal_border);
+}
+
+/// Opens another vault, only to read it.
+fn open_vault(name: &str, path: &str) -> Result<password::v2::PasswordStore, CommandError> {
+    let mut input = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::Failed(format!("I could not read the password file of the {} vault ({})", name, err)));
+    }
+    print_stderr!("Type the master password of the {} vault: ", name);
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            println_err!("I could not read your master password ({})", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    password::v2::PasswordStore::from_input(master_password, SafeVec::new(input)).map_err(|err| CommandError::Failed(format!("I could not open the {} vault ({:?})", name, err)))
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let all_vaults = matches.opt_present("all-vaults");
+
+    // Other vaults are opened first, so that the table can borrow from them.
+    let mut current_vault = "default".to_string();
+    let mut other_vaults = Vec::new();
+    if all_vaults {
+        let config = match config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not load the configuration file: {}", err)));
+            }
+        };
+        let current_path = password_file_path().unwrap_or(String::new());
+        for (name, path) in config.vaults().into_iter() {
+            if path == current_path {
+                current_vault = name;
+            } else {
+                let other = try!(open_vault(&name, &path));
+                other_vaults.push((name, other));
+            }
+        }
+    }
+
+    let mut passwords: Vec<(&str, &password::v2::Password)> = store.get_all_passwords().iter()
+        .map(|p| (&current_vault[..], p))
+        .collect();
+    for &(ref name, ref other) in other_vaults.iter() {
+        passwords.extend(other.get_all_passwords().iter().map(|p| (&name[..], p)));
+    }
+
+    if let Some(duration) = matches.opt_str("updated-since") {
+        let seconds = match timestamp::parse_duration(&duration) {
+            Some(seconds) => seconds,
+            None => {
+                return Err(CommandError::Failed(format!("\"{}\" is not a duration like 30d", duration)));
+            }
+        };
+        let since = Timestamp::from_secs(Timestamp::now().as_secs().saturating_sub(seconds));
+        passwords.retain(|&(_, p)| p.updated_at >= since);
+    }
+
+    if let Some(folder) = matches.free.get(0) {
+        let prefix = format!("{}/", folder.trim_right_matches('/').to_lowercase());
+        passwords.retain(|&(_, p)| p.name.to_lowercase().starts_with(&prefix));
+    }
+
+    let group_by = matches.opt_str("group-by");
+    match group_by {
+        Some(ref key) if key == "tag" || key == "folder" || key == "type" => {},
+        Some(ref key) => {
+            return Err(CommandError::Failed(format!("I can't group by \"{}\". Try tag, folder or type", key)));
+        },
+        None => {},
+    }
+
+    if matches.opt_present("tree") {
+        if group_by.is_some() {
+            return Err(CommandError::Failed("a tree already groups the apps by folder, use either --tree or --group-by".to_string()));
+        }
+        print_tree(passwords.iter().map(|&(vault, p)| {
+            let path = if all_vaults { format!("{}/{}", vault, p.name) } else { p.name.clone() };
+            (path, &p.username[..])
+        }).collect());
+        return Ok(());
+    }
+
+    match matches.opt_str("sort") {
+        None => {},
+        Some(ref key) if key == "name" => {
+            passwords.sort_by(|a, b| a.1.name.to_lowercase().cmp(&b.1.name.to_lowercase()));
+        },
+        Some(ref key) if key == "last-used" => {
+            // Most recent first, apps that were never used last.
+            passwords.sort_by(|a, b| b.1.last_used_at.cmp(&a.1.last_used_at));
+        },
+        Some(key) => {
+            return Err(CommandError::Failed(format!("I can't sort by \"{}\". Try name or last-used", key)));
+        }
+    }
+
+    // We'll now print the password in a table, or one table for each group.
+    let key = match group_by {
+        Some(key) => key,
+        None => {
+            print_table(&passwords, all_vaults);
+            return Ok(());
+        }
+    };
+    let mut groups: BTreeMap<String, Vec<(&str, &password::v2::Password)>> = BTreeMap::new();
+    for &(vault, p) in passwords.iter() {
+        for group in groups_of(p, &key) {
+            groups.entry(group).or_insert(Vec::new()).push((vault, p));
+        }
+    }
+    let mut first = true;
+    for (group, passwords) in groups.iter() {
+        if !first {
+            println!("");
+        }
+        first = false;
+        let title = match (&group[..], &key[..]) {
+            ("", "tag") => "No tag".to_string(),
+            ("", _) => "No folder".to_string(),
+            (group, "folder") => format!("{}/", group),
+            (group, _) => group.to_string(),
+        };
+        println!("{} ({})", color::paint(Color::Blue, Stream::Stdout, &title), passwords.len());
+        print_table(passwords, all_vaults);
+    }
 
     Ok(())
 }
@@ -0,0 +1,160 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::config;
+use super::super::getopts;
+use super::super::session;
+use super::CommandError;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::process::Stdio;
+
+/// The systemd user unit that runs `rooster lock --watch`, see `install_service`.
+const SERVICE_NAME: &'static str = "rooster-lock.service";
+
+/// What `gdbus monitor` prints for the signals of logind that mean nobody is at the
+/// computer: going to sleep, and the session being locked, by `loginctl lock-session` or
+/// by a lock screen that sets the locked hint.
+static LOCK_SIGNALS: &'static [&'static str] = &[
+    ".Manager.PrepareForSleep (true,)",
+    ".Session.Lock ()",
+    "'LockedHint': <true>",
+];
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("", "watch", "Keep running, and end every session when the screen locks or the computer sleeps");
+    opts.optflag("", "install-service", "Run `rooster lock --watch` from a systemd user service, from now on and at every login");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster lock -h");
+    println!("    eval $(rooster lock)");
+    println!("    rooster lock --watch");
+    println!("    rooster lock --install-service");
+    println!("");
+    println!("Ends every session started with `rooster unlock` or `rooster session start`, in");
+    println!("every shell, so that the next command asks for the master password again. Run it");
+    println!("before you step away from your computer. Without eval, the sessions end all the");
+    println!("same, only $ROOSTER_SESSION stays set in this shell.");
+    println!("");
+    println!("See whether this shell has a session with `rooster session status`.");
+    println!("");
+    println!("With --watch, Rooster keeps running and ends every session when logind says the");
+    println!("computer is going to sleep or the session is locked. It needs Linux with systemd,");
+    println!("and gdbus, which comes with GLib.");
+    println!("");
+    println!("With --install-service, it runs in a systemd user service instead, {}, which", SERVICE_NAME);
+    println!("starts at login. Sessions need no service to work, so commands never wait for it.");
+    println!("Remove it with `systemctl --user disable --now {}`.", SERVICE_NAME);
+    println!("");
+    println!("Example:");
+    println!("    eval $(rooster lock)");
+    println!("    rooster lock --watch &");
+}
+
+/// Follows the signals of logind until gdbus stops, see `LOCK_SIGNALS`.
+fn watch() -> Result<(), CommandError> {
+    let mut child = try!(session::command("gdbus")
+        .args(&["monitor", "--system", "--dest", "org.freedesktop.login1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| CommandError::Failed(format!("I could not run gdbus to watch logind ({})", err))));
+    let stdout = match child.stdout.take() {
+        Some(stdout) => stdout,
+        None => {
+            return Err(CommandError::Failed("I could not read what gdbus prints".to_string()));
+        }
+    };
+
+    println_ok!("I'll end every session when the screen locks or the computer sleeps.");
+    for line in BufReader::new(stdout).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => { break; }
+        };
+        if LOCK_SIGNALS.iter().any(|signal| line.contains(signal)) {
+            match session::end_all() {
+                Ok(0) => {},
+                Ok(count) => { println_info!("I've ended {} session(s).", count); },
+                Err(err) => { println_err!("Woops, I could not end every session ({}).", err); },
+            }
+        }
+    }
+    match child.wait() {
+        Ok(status) => Err(CommandError::Failed(format!("gdbus stopped ({}), so sessions no longer end on their own", status))),
+        Err(err) => Err(CommandError::Failed(format!("I lost track of gdbus ({})", err))),
+    }
+}
+
+/// Runs `systemctl --user` with these arguments.
+fn systemctl(args: &[&str]) -> Result<(), CommandError> {
+    match session::command("systemctl").arg("--user").args(args).status() {
+        Ok(ref status) if status.success() => Ok(()),
+        Ok(status) => Err(CommandError::Failed(format!("systemctl --user {} failed ({})", args.join(" "), status))),
+        Err(err) => Err(CommandError::Failed(format!("I could not run systemctl ({})", err))),
+    }
+}
+
+/// Writes the unit of `SERVICE_NAME`, then enables and starts it.
+fn install_service() -> Result<(), CommandError> {
+    let program = try!(env::current_exe().map_err(|err| CommandError::Failed(format!("I could not find where Rooster is installed ({})", err))));
+    let dir = try!(config::systemd_user_dir().ok_or(CommandError::Failed("there are no systemd user units on this system".to_string())));
+    let path = dir.join(SERVICE_NAME);
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str("Description=End Rooster sessions when the screen locks or the computer sleeps\n");
+    unit.push_str("\n");
+    unit.push_str("[Service]\n");
+    unit.push_str(&format!("ExecStart=\"{}\" lock --watch\n", program.display()));
+    unit.push_str("Restart=on-failure\n");
+    unit.push_str("\n");
+    unit.push_str("[Install]\n");
+    unit.push_str("WantedBy=default.target\n");
+    let written = fs::create_dir_all(&dir).and_then(|_| File::create(&path)).and_then(|mut file| file.write_all(unit.as_bytes()));
+    if let Err(err) = written {
+        return Err(CommandError::Failed(format!("I could not write {} ({})", path.display(), err)));
+    }
+
+    try!(systemctl(&["daemon-reload"]));
+    try!(systemctl(&["enable", "--now", SERVICE_NAME]));
+    println_ok!("Done! {} runs now and at every login.", SERVICE_NAME);
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    if matches.opt_present("install-service") {
+        return install_service();
+    }
+    if matches.opt_present("watch") {
+        return watch();
+    }
+
+    let ended = session::end_all();
+    println!("unset {}", session::SESSION_ENV_VAR);
+    match ended {
+        Ok(0) => {
+            println_info!("There was no session to end.");
+            Ok(())
+        },
+        Ok(count) => {
+            println_ok!("Done! I've ended {} session(s).", count);
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I could not end every session ({})", err))),
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::domain;
+use super::super::password;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster match-url -h");
+    println!("    rooster match-url <url>");
+    println!("");
+    println!("Prints the apps that may hold the password for a website, one per line, with");
+    println!("the app name and the username separated by a tab. Apps whose URL is on the same");
+    println!("domain come first, then apps whose name looks like the domain.");
+    println!("");
+    println!("Example:");
+    println!("    rooster match-url https://accounts.google.com/signin");
+}
+
+/// Lowercase letters and digits only, to compare app names with domains.
+fn normalize(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+/// How well the entry matches the domain: 2 for its URL, 1 for its name, 0 otherwise.
+fn score(password: &password::v2::Password, registrable: &str) -> u8 {
+    if let Some(ref url) = password.url {
+        if let Some(host) = domain::host(url.deref()) {
+            if domain::registrable_domain(host.deref()) == registrable {
+                return 2;
+            }
+        }
+    }
+
+    let name = normalize(password.name.deref());
+    let site = normalize(domain::site_name(registrable));
+    if name == normalize(registrable) || (site.len() >= 3 && name.contains(site.deref())) {
+        return 1;
+    }
+    0
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let url = match matches.free.get(0) {
+        Some(url) => url,
+        None => {
+            return Err(CommandError::MissingArgument("URL"));
+        }
+    };
+
+    let registrable = match domain::host(url.deref()) {
+        Some(host) => domain::registrable_domain(host.deref()),
+        None => {
+            return Err(CommandError::Failed(format!("I could not find the domain in \"{}\"", url)));
+        }
+    };
+
+    let mut candidates: Vec<(u8, &password::v2::Password)> = store.get_all_passwords().iter()
+        .map(|p| (score(p, registrable.deref()), p))
+        .filter(|&(score, _)| score > 0)
+        .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0));
+
+    if candidates.is_empty() {
+        println_err!("I couldn't find any app for {}.", registrable);
+        return Err(CommandError::Reported);
+    }
+
+    for &(_, p) in candidates.iter() {
+        println!("{}\t{}", p.name, p.username);
+    }
+
+    Ok(())
+}
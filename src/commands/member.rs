@@ -0,0 +1,102 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster member -h");
+    println!("    rooster member list");
+    println!("    rooster member add <gpg_key_id>");
+    println!("    rooster member remove <gpg_key_id>");
+    println!("");
+    println!("Turns a password file into a team vault, that each member opens with their own");
+    println!("GPG key instead of the master password:");
+    println!("    rooster --gpg list");
+    println!("");
+    println!("Members get a key slot each, see `rooster key`. Adding one only needs their public");
+    println!("key. Removing one gives the vault a new key, so that a copy of the key they kept");
+    println!("opens nothing new. Both need the master password, which the team administrator");
+    println!("keeps.");
+    println!("");
+    println!("Example:");
+    println!("    rooster member add alice@example.com");
+    println!("    rooster member remove bob@example.com");
+}
+
+/// The master password, typed again, since member key slots are added and removed with it.
+fn check_master_password(store: &password::v2::PasswordStore) -> Result<SafeString, CommandError> {
+    print_stderr!("Type the master password of the vault: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read the master password ({})", err)));
+        }
+    };
+    if !store.is_master_password(master_password.deref()) {
+        return Err(CommandError::WrongMasterPassword);
+    }
+    Ok(master_password)
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let action = matches.free.get(0).map(|action| action.deref());
+    if action == Some("list") || action.is_none() {
+        let members = store.members();
+        if members.is_empty() {
+            println_info!("This vault has no members yet. Add some with `rooster member add`.");
+        }
+        for member in members.iter() {
+            println!("{}", member);
+        }
+        return Ok(());
+    }
+
+    let recipient = match matches.free.get(1) {
+        Some(recipient) => recipient,
+        None => {
+            return Err(CommandError::MissingArgument("GPG key ID of the member"));
+        }
+    };
+    match action {
+        Some("add") => {
+            let master_password = try!(check_master_password(store));
+            try!(store.add_member(master_password.deref(), recipient.deref()).map_err(CommandError::Failed));
+            println_ok!("Done! {} can now open the vault, with `rooster --gpg`.", recipient);
+        },
+        Some("remove") => {
+            let master_password = try!(check_master_password(store));
+            let removed = try!(store.remove_member(master_password.deref(), recipient.deref()).map_err(CommandError::Failed));
+            for key_slot in removed.iter() {
+                println_warn!("I removed the key slot \"{}\", it cannot wrap the new key without its secret. Add it again with `rooster key add`.", key_slot.label);
+            }
+            println_ok!("Done! {} can no longer open the vault, which now has a new key.", recipient);
+            println_info!("They may still know the passwords they could read. Change the important ones.");
+        },
+        Some(action) => {
+            return Err(CommandError::Failed(format!("there is no `rooster member {}`, try list, add or remove", action)));
+        },
+        None => unreachable!(),
+    }
+    Ok(())
+}
@@ -20,3 +20,67 @@ pub mod list;
 pub mod regenerate;
 pub mod export;
 pub mod change_master_password;
+pub mod report;
+pub mod run;
+pub mod dedupe;
+pub mod stats;
+pub mod recovery;
+pub mod otp;
+pub mod field;
+pub mod attach;
+pub mod open;
+pub mod match_url;
+pub mod autotype;
+pub mod recovery_kit;
+pub mod share;
+pub mod receive;
+pub mod sync;
+pub mod fsck;
+pub mod verify;
+pub mod diff;
+pub mod serve;
+pub mod ipc;
+pub mod info;
+pub mod batch;
+pub mod alias;
+pub mod grep;
+pub mod audit;
+pub mod generate_username;
+pub mod template;
+pub mod edit;
+pub mod session;
+pub mod lock;
+pub mod unlock;
+pub mod decoy;
+pub mod protect;
+pub mod destroy;
+pub mod rekey;
+pub mod bench;
+pub mod conflict;
+pub mod key;
+pub mod import;
+pub mod member;
+pub mod dev;
+pub mod exists;
+pub mod count;
+pub mod mv;
+pub mod split;
+pub mod combine;
+
+/// Why a command failed. `main` turns it into a message and an exit code.
+pub enum CommandError {
+    /// A required argument was not given, like "app name".
+    MissingArgument(&'static str),
+    /// There is no app with this name or alias.
+    NoSuchApp(String),
+    WrongMasterPassword,
+    /// The password file could not be opened, read, decrypted or saved, and why.
+    PasswordFile(String),
+    /// Anything else, and why, as in "I could not read foo.json (not found)".
+    Failed(String),
+    /// The command already printed why it failed.
+    Reported,
+    /// Rooster should exit with this code, like the one of a command it ran, which
+    /// already printed why it failed.
+    Exit(i32),
+}
@@ -0,0 +1,108 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::config;
+use super::super::get_backend;
+use super::super::getopts;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::save_password_file;
+use super::super::safe_string::read_password_safe;
+use super::super::safe_vec::SafeVec;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "to", "The vault to move the app to, from the configuration file", "VAULT");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster mv -h");
+    println!("    rooster mv <app_name> --to <vault>");
+    println!("");
+    println!("Moves an app from this vault to another vault of the configuration file, with");
+    println!("its history, notes, fields and attachments. You'll be asked the master password");
+    println!("of both vaults.");
+    println!("");
+    println!("The app is saved in the other vault before it is removed from this one, and put");
+    println!("back if that fails, so it is never lost along the way.");
+    println!("");
+    println!("Example:");
+    println!("    rooster mv github --to work");
+    println!("    rooster --vault work mv github --to default");
+}
+
+/// Saves the other vault, reading it first so that the WebDAV backend knows which
+/// version of the file it replaces.
+fn save_vault(store: &password::v2::PasswordStore, path: &str) -> Result<(), String> {
+    let mut backend = try!(get_backend(path).map_err(|err| err.to_string()));
+    try!(backend.read().map_err(|err| err.to_string()));
+    store.sync(&mut *backend).map_err(|err| format!("{:?}", err))
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let app_name = try!(matches.free.get(0).ok_or(CommandError::MissingArgument("app name")));
+    let vault = try!(matches.opt_str("to").ok_or(CommandError::MissingArgument("vault to move the app to")));
+
+    let password = try!(store.get_password(app_name).ok_or(CommandError::NoSuchApp(app_name.clone())));
+
+    let config = try!(config::load().map_err(|err| CommandError::Failed(format!("I could not load the configuration file: {}", err))));
+    let path = try!(config.vault_path(&vault).ok_or(CommandError::Failed(format!("there is no vault named \"{}\" in the configuration file", vault))));
+    if password_file_path().ok() == Some(path.clone()) {
+        return Err(CommandError::Failed(format!("{} is already in the {} vault", password.name, vault)));
+    }
+
+    // Open the other vault.
+    let mut backend = try!(get_backend(&path).map_err(|err| CommandError::Failed(format!("I could not open the password file of the {} vault ({})", vault, err))));
+    let input = try!(backend.read().map_err(|err| CommandError::Failed(format!("I could not read the password file of the {} vault ({})", vault, err))));
+    print_stderr!("Type the master password of the {} vault: ", vault);
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read your master password ({})", err)));
+        }
+    };
+    // An empty file is a new vault, like for any other command.
+    let mut other = if input.is_empty() {
+        try!(password::v2::PasswordStore::new(master_password).map_err(|err| CommandError::Failed(format!("I could not create the {} vault ({})", vault, err))))
+    } else {
+        try!(password::v2::PasswordStore::from_input(master_password, SafeVec::new(input)).map_err(|err| match err {
+            password::PasswordError::WrongMasterPasswordError => CommandError::WrongMasterPassword,
+            err => CommandError::Failed(format!("I could not open the {} vault ({:?})", vault, err)),
+        }))
+    };
+
+    // Add the app there first, so that it is never in neither vault.
+    let name = password.name.clone();
+    if other.has_password(name.deref()) {
+        return Err(CommandError::Failed(format!("there already is an app named {} in the {} vault", name, vault)));
+    }
+    try!(other.add_password(password).map_err(|err| CommandError::Failed(format!("I could not add {} to the {} vault ({:?})", name, vault, err))));
+    try!(save_vault(&other, &path).map_err(|err| CommandError::Failed(format!("I could not save the {} vault ({}), {} was not moved", vault, err, name))));
+
+    // Then remove it here. If this vault can't be saved, take it out of the other one.
+    let removed = store.delete_password(name.deref()).map_err(|err| format!("{:?}", err)).and_then(|_| save_password_file(store));
+    if let Err(err) = removed {
+        let rolled_back = other.delete_password(name.deref()).map_err(|err| format!("{:?}", err)).and_then(|_| save_vault(&other, &path));
+        if let Err(rollback_err) = rolled_back {
+            println_warn!("I could not take {} out of the {} vault again ({}), it is in both vaults now.", name, vault, rollback_err);
+        }
+        return Err(CommandError::Failed(format!("I could not remove {} from this vault ({}), so I did not move it", name, err)));
+    }
+
+    println_ok!("Done! {} is in the {} vault now.", name, vault);
+    Ok(())
+}
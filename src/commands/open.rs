@@ -0,0 +1,119 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::browser;
+use super::super::clipboard;
+use super::super::password;
+use super::CommandError;
+use super::protect::unprotect;
+use std::io::Write;
+use std::ops::Deref;
+
+/// Seconds before the password is removed from the clipboard, by default.
+const DEFAULT_CLEAR_AFTER: u64 = 30;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "set-url", "Save this URL for the app before opening it", "URL");
+    opts.optopt("", "clear-after", "Seconds before the clipboard is cleared, default is 30, 0 to never clear it", "SECONDS");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster open -h");
+    println!("    rooster open [options] <app_name>");
+    println!("");
+    println!("Opens the app's website in your browser and copies the password to your");
+    println!("clipboard, so you can paste it in the login form. The clipboard is cleared");
+    println!("after a few seconds.");
+    println!("");
+    println!("Over SSH, set ${}=osc52 to copy through your terminal, if it supports", clipboard::CLIPBOARD_ENV_VAR);
+    println!("OSC 52 escape sequences.");
+    println!("");
+    println!("Example:");
+    println!("    rooster open youtube");
+    println!("    rooster open --set-url https://www.youtube.com youtube");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let clear_after = match matches.opt_str("clear-after") {
+        Some(seconds) => {
+            match seconds.parse::<u64>() {
+                Ok(seconds) => seconds,
+                Err(_) => {
+                    return Err(CommandError::Failed(format!("--clear-after must be a number of seconds, not \"{}\"", seconds)));
+                }
+            }
+        },
+        None => DEFAULT_CLEAR_AFTER,
+    };
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+
+    if let Some(url) = matches.opt_str("set-url") {
+        password.url = Some(url);
+        match store.delete_password(app_name).and_then(|_| store.add_password(password.clone())) {
+            Ok(_) => {},
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I couldn't save the URL ({:?})", err)));
+            }
+        }
+    }
+
+    let url = match password.url {
+        Some(ref url) => url.clone(),
+        None => {
+            println_err!("Woops, I don't know the website of {}. To set it, try:", app_name);
+            println_err!("    rooster open --set-url <url> {}", app_name);
+            return Err(CommandError::Reported);
+        }
+    };
+
+    let password = try!(unprotect(&password));
+    match clipboard::copy(password.password.deref()) {
+        Ok(()) => {
+            println_ok!("I've copied the password for {} to your clipboard.", app_name);
+        },
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not copy the password to your clipboard ({})", err)));
+        }
+    }
+
+    if clear_after > 0 {
+        if let Err(err) = clipboard::clear_after(clear_after) {
+            println_err!("Woops, I could not clear your clipboard later ({}), do not forget to.", err);
+        } else {
+            println_stderr!("It will be cleared in {} seconds.", clear_after);
+        }
+    }
+
+    match browser::open(url.deref()) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not open {} in your browser ({})", url, err)))
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::base32;
+use super::super::otp;
+use super::super::password;
+use super::super::password::v2::OtpSecret;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::CommandError;
+use super::protect::check_unprotected;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("s", "set", "Save the secret the site gives you when setting up two-factor authentication");
+    opts.optopt("", "uri", "With --set, read the secret from an otpauth:// URI instead of asking for it", "URI");
+    opts.optopt("", "qr-image", "With --set, read the secret from a QR code image, needs zbarimg", "FILE");
+    opts.optflag("", "remove", "Remove the secret");
+    opts.optflag("", "hotp", "Use counter-based codes (HOTP) instead of time-based codes (TOTP)");
+    opts.optopt("", "counter", "The counter of the next HOTP code, default is 0", "N");
+    opts.optopt("", "digits", "Number of digits in a code, default is 6", "N");
+    opts.optopt("", "period", "Seconds each TOTP code is valid for, default is 30", "SECONDS");
+    opts.optopt("", "algorithm", "SHA1, SHA256 or SHA512, default is SHA1", "ALGORITHM");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster otp -h");
+    println!("    rooster otp <app_name>");
+    println!("    rooster otp --set <app_name>");
+    println!("    rooster otp --remove <app_name>");
+    println!("");
+    println!("Prints the current one-time password for two-factor authentication. Sites use");
+    println!("time-based codes (TOTP) unless they say otherwise. For counter-based codes (HOTP),");
+    println!("the counter goes up each time you get a code, and is saved right away so that a");
+    println!("code is never given twice.");
+    println!("");
+    println!("Sites usually show a QR code to set up two-factor authentication. Rather than");
+    println!("typing the secret, you can save the QR code as an image and use --qr-image, or");
+    println!("pass the otpauth:// URI it contains with --uri. The URI also sets the digits,");
+    println!("period and algorithm.");
+    println!("");
+    println!("Example:");
+    println!("    rooster otp --set github");
+    println!("    rooster otp --set --qr-image github-qr.png github");
+    println!("    rooster otp github");
+}
+
+fn parse_number<T: ::std::str::FromStr>(matches: &getopts::Matches, name: &str, default: T) -> Result<T, CommandError> {
+    match matches.opt_str(name) {
+        Some(value) => {
+            value.parse::<T>().map_err(|_| CommandError::Failed(format!("--{} must be a number, not \"{}\"", name, value)))
+        },
+        None => Ok(default),
+    }
+}
+
+fn read_secret(matches: &getopts::Matches, app_name: &str) -> Result<OtpSecret, CommandError> {
+    let uri = match (matches.opt_str("uri"), matches.opt_str("qr-image")) {
+        (Some(uri), _) => Some(SafeString::new(uri)),
+        (None, Some(path)) => {
+            match otp::read_qr_image(path.deref()) {
+                Ok(uri) => Some(uri),
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I could not read the QR code ({})", err)));
+                }
+            }
+        },
+        (None, None) => None,
+    };
+    if let Some(uri) = uri {
+        return otp::parse_uri(uri.deref()).map_err(|err| CommandError::Failed(format!("I could not use this otpauth:// URI: {}", err)));
+    }
+
+    let algorithm = matches.opt_str("algorithm").unwrap_or(otp::DEFAULT_ALGORITHM.to_string()).to_uppercase();
+    if !otp::ALGORITHMS.contains(&algorithm.deref()) {
+        return Err(CommandError::Failed(format!("I don't know the algorithm \"{}\". Try SHA1, SHA256 or SHA512", algorithm)));
+    }
+    let digits = try!(parse_number(matches, "digits", otp::DEFAULT_DIGITS));
+    if digits < 6 || digits > 8 {
+        return Err(CommandError::Failed("codes must have between 6 and 8 digits".to_string()));
+    }
+    let period = try!(parse_number(matches, "period", otp::DEFAULT_PERIOD));
+    if period == 0 {
+        return Err(CommandError::Failed("the period must be at least 1 second".to_string()));
+    }
+    let counter = if matches.opt_present("hotp") {
+        Some(try!(parse_number(matches, "counter", 0)))
+    } else {
+        None
+    };
+
+    print_stderr!("What is the two-factor secret for {}? ", app_name);
+    let secret = match read_password_safe() {
+        Ok(secret) => secret,
+        Err(err) => {
+            println_err!("\nI couldn't read the secret ({:?}).", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    if base32::decode(secret.deref()).is_none() {
+        return Err(CommandError::Failed("this secret does not look right. It should only have letters and digits 2 to 7".to_string()));
+    }
+
+    Ok(OtpSecret {
+        secret: secret,
+        algorithm: algorithm,
+        digits: digits,
+        period: period,
+        counter: counter,
+    })
+}
+
+fn save(store: &mut password::v2::PasswordStore, app_name: &str, password: password::v2::Password) -> Result<(), CommandError> {
+    match store.delete_password(app_name).and_then(|_| store.add_password(password)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't save the two-factor secret ({:?})", err)))
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+    try!(check_unprotected(&password));
+
+    if matches.opt_present("set") {
+        password.otp = Some(try!(read_secret(matches, app_name)));
+        try!(save(store, app_name, password));
+        println_ok!("Done! Try `rooster otp {}` to get your first code.", app_name);
+        return Ok(());
+    }
+
+    if matches.opt_present("remove") {
+        password.otp = None;
+        try!(save(store, app_name, password));
+        println_ok!("Done! I've removed the two-factor secret for {}.", app_name);
+        return Ok(());
+    }
+
+    let (code, counter) = match password.otp {
+        Some(ref secret) => {
+            match otp::generate(secret) {
+                Ok(result) => result,
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I could not make a code for {} ({})", app_name, err)));
+                }
+            }
+        },
+        None => {
+            println_err!("Woops, there is no two-factor secret for {}. To add one, try:", app_name);
+            println_err!("    rooster otp --set {}", app_name);
+            return Err(CommandError::Reported);
+        }
+    };
+
+    let period = password.otp.as_ref().map(|secret| secret.period).unwrap_or(otp::DEFAULT_PERIOD);
+    let is_hotp = password.otp.as_ref().map(|secret| secret.counter.is_some()).unwrap_or(false);
+    if is_hotp {
+        // The counter is saved along with the password file as soon as this returns.
+        if let Some(ref mut secret) = password.otp {
+            secret.counter = Some(counter + 1);
+        }
+        try!(save(store, app_name, password));
+    }
+
+    print_stdout!("{}", code);
+    print_stderr!("\n");
+    if !is_hotp {
+        println_stderr!("This code is valid for {} more seconds.", otp::seconds_left(period));
+    }
+
+    Ok(())
+}
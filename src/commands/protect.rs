@@ -0,0 +1,127 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::protect;
+use super::super::safe_string::read_password_safe;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("r", "remove", "Remove the passphrase, so that the master password is enough again");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster protect -h");
+    println!("    rooster protect [--remove] <app_name>");
+    println!("");
+    println!("Protects the secrets of an app with a passphrase of its own, on top of the master");
+    println!("password, for the few that matter most like your bank. `rooster get` asks for the");
+    println!("passphrase when it needs the password, notes, custom fields, recovery codes or");
+    println!("one-time password secret. The name, username and URL stay readable.");
+    println!("");
+    println!("To change a protected password or its fields, remove the passphrase first.");
+    println!("");
+    println!("Example:");
+    println!("    rooster protect mybank");
+    println!("    rooster protect --remove mybank");
+}
+
+/// Asks for the passphrase of a protected app and decrypts its secrets.
+pub fn unprotect(password: &password::v2::Password) -> Result<password::v2::Password, CommandError> {
+    if password.protected.is_none() {
+        return Ok(password.clone());
+    }
+    print_stderr!("Type the passphrase for {}: ", password.name);
+    let passphrase = match read_password_safe() {
+        Ok(passphrase) => passphrase,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read the passphrase ({})", err)));
+        }
+    };
+    protect::unprotect(password, passphrase.deref()).map_err(|err| CommandError::Failed(format!("I could not open {}: {}", password.name, err)))
+}
+
+/// Refuses to change the secrets of a protected app, since they are not in the entry.
+pub fn check_unprotected(password: &password::v2::Password) -> Result<(), CommandError> {
+    if password.protected.is_some() {
+        return Err(CommandError::Failed(format!("{} is protected with a passphrase. Remove it first with `rooster protect --remove`", password.name)));
+    }
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let app_name = match matches.free.get(0) {
+        Some(app_name) => app_name.clone(),
+        None => {
+            return Err(CommandError::MissingArgument("app name"));
+        }
+    };
+    let password = match store.get_password(app_name.deref()) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name));
+        }
+    };
+
+    let changed = if matches.opt_present("remove") {
+        if password.protected.is_none() {
+            return Err(CommandError::Failed(format!("{} is not protected", password.name)));
+        }
+        try!(unprotect(&password))
+    } else {
+        if password.protected.is_some() {
+            return Err(CommandError::Failed(format!("{} is already protected", password.name)));
+        }
+        print_stderr!("Type the passphrase for {}: ", password.name);
+        let passphrase = match read_password_safe() {
+            Ok(passphrase) => passphrase,
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read the passphrase ({})", err)));
+            }
+        };
+        print_stderr!("Type the passphrase once more: ");
+        let confirmation = match read_password_safe() {
+            Ok(confirmation) => confirmation,
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read the passphrase ({})", err)));
+            }
+        };
+        if passphrase != confirmation {
+            return Err(CommandError::Failed("the passphrase confirmation did not match".to_string()));
+        }
+        if passphrase.is_empty() {
+            return Err(CommandError::Failed("the passphrase can't be empty".to_string()));
+        }
+        let mut protected = password.clone();
+        try!(protect::protect(&mut protected, passphrase.deref()).map_err(|err| CommandError::Failed(format!("I could not protect {}: {}", password.name, err))));
+        protected
+    };
+
+    let name = password.name.clone();
+    match store.delete_password(name.deref()).and_then(|_| store.add_password(changed)) {
+        Ok(_) => {
+            if matches.opt_present("remove") {
+                println_ok!("Done! {} is only protected by your master password again.", name);
+            } else {
+                println_ok!("Done! {} is now protected by its passphrase.", name);
+            }
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I couldn't save {} ({:?})", name, err))),
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::serde_json;
+use super::super::safe_string::SafeString;
+use super::super::share;
+use super::CommandError;
+use super::conflict::{self, Outcome};
+use std::fs::File;
+use std::io::{stdin, Read, Write};
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optflag("", "public-key", "Print your public key, creating your key pair if needed");
+    opts.optopt("", "as", "Save the app under this name instead", "APP");
+    conflict::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster receive -h");
+    println!("    rooster receive --public-key");
+    println!("    rooster receive [--as <app_name>] [<share_file>]");
+    println!("");
+    println!("Adds an app that someone shared with you using `rooster share`. The share is");
+    println!("read from the file, or from the input if there is no file.");
+    println!("");
+    println!("People need your public key to share with you. Your private key is kept in your");
+    println!("password file.");
+    println!("");
+    conflict::print_help();
+    println!("");
+    println!("Example:");
+    println!("    rooster receive --public-key > me.pub");
+    println!("    rooster receive aws.share");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.opt_present("public-key") {
+        if store.sharing_key().is_none() {
+            match share::generate_private_key() {
+                Ok(key) => store.set_sharing_key(key),
+                Err(err) => {
+                    return Err(CommandError::Failed(format!("I could not create your key pair ({})", err)));
+                }
+            }
+        }
+        return match share::public_key(store.sharing_key().unwrap().deref()) {
+            Ok(public_key) => {
+                println!("{}", public_key);
+                Ok(())
+            },
+            Err(err) => {
+                Err(CommandError::Failed(format!("your private key seems corrupted: {}", err)))
+            }
+        };
+    }
+
+    let strategy = try!(conflict::strategy_from_matches(matches));
+
+    let private_key = match store.sharing_key() {
+        Some(key) => key.clone(),
+        None => {
+            println_err!("Woops, you don't have a key pair yet, so nobody could share with you. Try:");
+            println_err!("    rooster receive --public-key");
+            return Err(CommandError::Reported);
+        }
+    };
+
+    let mut armored = String::new();
+    let result = match matches.free.get(0) {
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_string(&mut armored)),
+        None => stdin().read_to_string(&mut armored),
+    };
+    if let Err(err) = result {
+        return Err(CommandError::Failed(format!("I could not read the share ({})", err)));
+    }
+
+    let data = match share::decrypt(armored.deref(), private_key.deref()) {
+        Ok(data) => SafeString::new(String::from_utf8_lossy(&data).into_owned()),
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not open the share: {}", err)));
+        }
+    };
+    let mut password = match serde_json::from_str::<password::v2::Password>(data.deref()) {
+        Ok(password) => password,
+        Err(_) => {
+            return Err(CommandError::Failed("the share does not hold an app".to_string()));
+        }
+    };
+    if let Some(name) = matches.opt_str("as") {
+        password.name = name;
+    }
+
+    let name = password.name.clone();
+    match try!(conflict::add(store, password, strategy)) {
+        Outcome::Added => println_ok!("Done! I've added {} to your passwords.", name),
+        Outcome::Kept => println_ok!("I've kept your {} as it was.", name),
+        Outcome::Replaced => println_ok!("Done! I've replaced {} with the one you received.", name),
+        Outcome::Renamed(renamed) => println_ok!("Done! I've added {} to your passwords as {}.", name, renamed),
+        Outcome::Merged => println_ok!("Done! I've merged what you received into {}.", name),
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::{SafeString, read_to_end_safe};
+use super::CommandError;
+use super::protect::check_unprotected;
+use std::io::{stdin, Write};
+use std::ops::Deref;
+
+/// Below this many codes left, we remind the user to get new ones.
+const LOW_CODES_WARNING: usize = 2;
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster recovery -h");
+    println!("    rooster recovery add <app_name>");
+    println!("    rooster recovery use <app_name>");
+    println!("    rooster recovery count <app_name>");
+    println!("");
+    println!("Sites often give you a list of one-time codes to log in when you lose your phone.");
+    println!("`add` saves them, separated by spaces or new lines, replacing the codes you had.");
+    println!("`use` prints the next code and removes it, so the same code is never used twice.");
+    println!("`count` shows how many codes are left.");
+    println!("");
+    println!("Example:");
+    println!("    rooster recovery add github");
+    println!("    rooster recovery use github");
+}
+
+fn read_codes(app_name: &str) -> Result<Vec<SafeString>, CommandError> {
+    println_stderr!("Type the recovery codes for {}, then press Ctrl+D:", app_name);
+    match read_to_end_safe(&mut stdin()) {
+        Ok(text) => {
+            Ok(text.split_whitespace().map(|code| SafeString::new(code.to_string())).collect())
+        },
+        Err(err) => {
+            println_err!("\nI couldn't read the recovery codes ({:?}).", err);
+            Err(CommandError::Reported)
+        }
+    }
+}
+
+fn save(store: &mut password::v2::PasswordStore, app_name: &str, password: password::v2::Password) -> Result<(), CommandError> {
+    match store.delete_password(app_name).and_then(|_| store.add_password(password)) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(CommandError::Failed(format!("I couldn't save the recovery codes ({:?})", err)))
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let (action, app_name) = match (matches.free.get(0), matches.free.get(1)) {
+        (Some(action), Some(app_name)) => (&action[..], &app_name[..]),
+        _ => {
+            return Err(CommandError::MissingArgument("action or the app name"));
+        }
+    };
+
+    let mut password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+    try!(check_unprotected(&password));
+
+    match action {
+        "add" => {
+            let codes = try!(read_codes(app_name));
+            if codes.is_empty() {
+                return Err(CommandError::Failed("I did not find any recovery code in what you typed".to_string()));
+            }
+            let count = codes.len();
+            password.recovery_codes = Some(codes);
+            try!(save(store, app_name, password));
+            println_ok!("Done! I've saved {} recovery codes for {}.", count, app_name);
+            Ok(())
+        },
+        "use" => {
+            let next_code = password.recovery_codes.as_mut().and_then(|codes| {
+                if codes.is_empty() { None } else { Some(codes.remove(0)) }
+            });
+            let code = match next_code {
+                Some(code) => code,
+                None => {
+                    return Err(CommandError::Failed(format!("there are no recovery codes left for {}", password.name)));
+                }
+            };
+            let left = password.recovery_codes.as_ref().map(|codes| codes.len()).unwrap_or(0);
+            let name = password.name.clone();
+            try!(save(store, app_name, password));
+
+            print_stdout!("{}", code.deref());
+            print_stderr!("\n");
+            if left <= LOW_CODES_WARNING {
+                println_warn!("Only {} recovery codes left for {}, you may want to get new ones.", left, name);
+            }
+            Ok(())
+        },
+        "count" => {
+            let left = password.recovery_codes.as_ref().map(|codes| codes.len()).unwrap_or(0);
+            println!("{} recovery codes left for {}.", left, password.name);
+            Ok(())
+        },
+        _ => {
+            Err(CommandError::Failed(format!("I don't know the action \"{}\". Try add, use or count", action)))
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::super::safe_vec::SafeVec;
+use super::super::shamir::{self, Share};
+use super::super::throttle;
+use super::CommandError;
+use std::fs::File;
+use std::io::{stdin, BufRead, Read, Write};
+use std::ops::Deref;
+use std::str;
+
+const DEFAULT_SHARES: u8 = 5;
+const DEFAULT_THRESHOLD: u8 = 3;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("n", "shares", "How many shares to create, default is 5", "N");
+    opts.optopt("k", "threshold", "How many shares are needed to recover, default is 3", "K");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster recovery-kit -h");
+    println!("    rooster recovery-kit create [-n <shares>] [-k <threshold>]");
+    println!("    rooster recovery-kit restore");
+    println!("");
+    println!("If you forget your master password, your passwords are lost. A recovery kit");
+    println!("splits your master password in shares, to print and give to people you trust.");
+    println!("Any <threshold> of them give your master password back, but fewer shares do not");
+    println!("reveal anything about it.");
+    println!("");
+    println!("`restore` reads shares, one per line, until you press Ctrl+D, then prints your");
+    println!("master password. If you change your master password, create a new kit.");
+    println!("");
+    println!("Example:");
+    println!("    rooster recovery-kit create -n 5 -k 3");
+    println!("    rooster recovery-kit restore");
+}
+
+/// Checks the master password by opening the password file with it.
+fn check_master_password(master_password: &SafeString) -> Result<(), CommandError> {
+    let path = match password_file_path() {
+        Ok(path) => path,
+        Err(_) => {
+            return Err(CommandError::Failed("I could not determine where your password file is".to_string()));
+        }
+    };
+    let mut input = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::Failed(format!("I could not read the password file \"{}\" ({})", path, err)));
+    }
+    let throttle_path = throttle::path(&path);
+    throttle::wait(&throttle_path);
+    match password::v2::PasswordStore::from_input(master_password.clone(), SafeVec::new(input)) {
+        Ok(_) => {
+            let _ = throttle::succeeded(&throttle_path);
+            Ok(())
+        },
+        Err(err) => {
+            if let password::PasswordError::WrongMasterPasswordError = err {
+                let _ = throttle::failed(&throttle_path);
+            }
+            Err(CommandError::Failed("this master password does not open your password file".to_string()))
+        }
+    }
+}
+
+fn parse_count(matches: &getopts::Matches, name: &str, default: u8) -> Result<u8, CommandError> {
+    match matches.opt_str(name) {
+        Some(value) => {
+            value.parse::<u8>().map_err(|_| CommandError::Failed(format!("--{} must be a number from 1 to 255, not \"{}\"", name, value)))
+        },
+        None => Ok(default),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Shares are written as "rooster-<threshold>-<x>-<hex>".
+fn parse_share(line: &str) -> Option<(u8, Share)> {
+    let parts: Vec<&str> = line.trim().split('-').collect();
+    if parts.len() != 4 || parts[0] != "rooster" || parts[3].len() % 2 != 0 {
+        return None;
+    }
+    let threshold = match parts[1].parse::<u8>() { Ok(t) => t, Err(_) => { return None; } };
+    let x = match parts[2].parse::<u8>() { Ok(x) if x > 0 => x, _ => { return None; } };
+    let mut y = Vec::with_capacity(parts[3].len() / 2);
+    // Pairs of bytes rather than of characters, so that pasted text that is not ASCII
+    // is refused instead of cut in the middle of a character.
+    for pair in parts[3].as_bytes().chunks(2) {
+        match str::from_utf8(pair).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => y.push(byte),
+            None => { return None; }
+        }
+    }
+    Some((threshold, Share { x: x, y: SafeVec::new(y) }))
+}
+
+fn create(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let count = try!(parse_count(matches, "shares", DEFAULT_SHARES));
+    let threshold = try!(parse_count(matches, "threshold", DEFAULT_THRESHOLD));
+    if threshold < 2 || threshold > count {
+        return Err(CommandError::Failed("the threshold must be at least 2 and at most the number of shares".to_string()));
+    }
+
+    print_stderr!("Type your master password: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            println_err!("I could not read your master password ({})", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    try!(check_master_password(&master_password));
+
+    let shares = match shamir::split(master_password.as_bytes(), count, threshold) {
+        Ok(shares) => shares,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not create the shares ({})", err)));
+        }
+    };
+
+    println!("Rooster recovery kit: any {} of these {} shares give the master password back.", threshold, count);
+    println!("Give each share to a different person. To recover, run `rooster recovery-kit");
+    println!("restore` and type the shares.");
+    println!("");
+    for share in shares.iter() {
+        println!("Share {}: rooster-{}-{}-{}", share.x, threshold, share.x, to_hex(share.y.deref()));
+    }
+    Ok(())
+}
+
+fn restore() -> Result<(), CommandError> {
+    println_stderr!("Type the shares, one per line, then press Ctrl+D:");
+    let mut shares: Vec<Share> = Vec::new();
+    let mut threshold = 0;
+    let stdin = stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => SafeString::new(line),
+            Err(err) => {
+                return Err(CommandError::Failed(format!("I could not read the shares ({})", err)));
+            }
+        };
+        // Shares may be pasted with their "Share N:" prefix.
+        let text = line.rsplit(' ').next().unwrap_or("");
+        if text.is_empty() {
+            continue;
+        }
+        match parse_share(text) {
+            Some((t, share)) => {
+                threshold = t;
+                if !shares.iter().any(|s| s.x == share.x) {
+                    shares.push(share);
+                }
+            },
+            None => { println_err!("Woops, this does not look like a share, I'll skip it."); }
+        }
+    }
+
+    if shares.is_empty() {
+        return Err(CommandError::Failed("I did not get any share".to_string()));
+    }
+    if shares.len() < threshold as usize {
+        return Err(CommandError::Failed(format!("I need {} different shares, but only got {}", threshold, shares.len())));
+    }
+
+    let secret = shamir::combine(&shares[..threshold as usize]);
+    // Straight from the shares into a `SafeString`, without another copy left behind.
+    let master_password = match str::from_utf8(secret.deref()) {
+        Ok(master_password) => SafeString::new(master_password.to_string()),
+        Err(_) => {
+            println_err!("Woops, these shares do not give a valid master password back. Are they from the same kit?");
+            return Err(CommandError::Reported);
+        }
+    };
+    try!(check_master_password(&master_password));
+
+    println_ok!("Found it! Here is your master password:");
+    print_stdout!("{}", master_password.deref());
+    print_stderr!("\n");
+    Ok(())
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    match matches.free.get(0).map(|action| &action[..]) {
+        Some("create") => create(matches),
+        Some("restore") => restore(),
+        _ => {
+            println_err!("Woops, I need to know whether to create or restore a kit. For help, try:");
+            println_err!("    rooster recovery-kit -h");
+            Err(CommandError::Reported)
+        }
+    }
+}
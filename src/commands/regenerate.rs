@@ -14,64 +14,108 @@
 
 use super::super::getopts;
 use super::super::safe_string::SafeString;
-use super::super::ffi;
+use super::super::clipboard;
 use super::super::password;
-use super::super::generate::{PasswordSpec, generate_hard_password};
+use super::super::generate::PasswordSpec;
+use super::super::strength;
+use super::CommandError;
+use super::protect::check_unprotected;
 use std::io::Write;
 use std::ops::Deref;
 
+pub fn callback_options(opts: &mut getopts::Options) {
+    PasswordSpec::add_options(opts);
+    strength::add_options(opts);
+}
+
 pub fn callback_help() {
     println!("Usage:");
     println!("    rooster regenerate -h");
-    println!("    rooster regenerate <app_name>");
+    println!("    rooster regenerate [options] <app_name>");
     println!("");
     println!("Example:");
     println!("    rooster regenerate youtube");
+    println!("    rooster regenerate --alnum --length 16 youtube");
+    println!("    rooster regenerate --length 8 --enforce-strength 3 router");
+    println!("");
+    println!("The previous password is kept in the app's history and the new one is");
+    println!("copied to your clipboard. Options given here are saved as the app's policy");
+    println!("and reused next time.");
 }
 
-pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), i32> {
-    if matches.free.len() < 2 {
-        println_err!("Woops, seems like the app name is missing here. For help, try:");
-        println_err!("    rooster regenerate -h");
-        return Err(1);
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
     }
 
-    let app_name = matches.free[1].clone();
+    let app_name = matches.free[0].clone();
+    let required_score = try!(strength::required_score(matches));
 
-    let password_spec = PasswordSpec::from_matches(matches);
+    let policy = match store.get_password(app_name.deref()) {
+        Some(ref previous) if previous.protected.is_some() => {
+            return check_unprotected(previous);
+        },
+        Some(ref previous) if !previous.kind().generated => {
+            return Err(CommandError::Failed(format!("I can't generate a {} for you, you'll have to type it", previous.kind().secret_name)));
+        },
+        Some(previous) => previous.generation_policy.clone(),
+        None => None,
+    };
 
-    let password_as_string = match password_spec {
-        None => { return Err(1); },
-        Some(spec) => {
-            match generate_hard_password(spec.alnum, spec.len) {
-                Ok(password_as_string) => password_as_string,
-                Err(io_err) => {
-                    println_stderr!("Woops, I could not generate the password ({:?}).", io_err);
-                    return Err(1);
-                }
-            }
+    // Options given on the command line replace the app's generation policy. Without
+    // options, the policy saved for the app applies, if any.
+    let password_spec = match policy {
+        Some(ref policy) if !PasswordSpec::has_options(matches) => PasswordSpec::from_policy(policy),
+        _ => PasswordSpec::from_matches(matches),
+    };
+
+    let spec = match password_spec {
+        None => { return Err(CommandError::Reported); },
+        Some(spec) => spec,
+    };
+
+    let password_as_string = match spec.generate() {
+        Ok(password_as_string) => password_as_string,
+        Err(io_err) => {
+            println_stderr!("Woops, I could not generate the password ({:?}).", io_err);
+            return Err(CommandError::Reported);
         }
     };
+    // The options or the app's policy may ask for a short password.
+    try!(strength::check(&password_as_string, required_score));
 
-    match store.delete_password(app_name.deref()) {
+    let new_password = match store.delete_password(app_name.deref()) {
         Ok(mut previous) => {
-            previous.password = SafeString::new(password_as_string);
-            previous.updated_at = ffi::time();
+            previous.replace_password(SafeString::new(password_as_string));
+            if PasswordSpec::has_options(matches) {
+                previous.generation_policy = Some(spec.to_policy());
+            }
+            let new_password = previous.password.clone();
 
             match store.add_password(previous) {
                 Ok(_) => {
                     println_ok!("Done ! The password for {} has been regenerated.", app_name);
-                    return Ok(());
                 },
                 Err(err) => {
-                    println_err!("Woops, I couldn't save the new password ({:?}).", err);
-                    return Err(1);
+                    return Err(CommandError::Failed(format!("I couldn't save the new password ({:?})", err)));
                 }
             }
+            new_password
+        },
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I couldn't get that password ({:?})", err)));
+        }
+    };
+
+    // The password has been regenerated either way, so failing to copy it is not an error.
+    match clipboard::copy(new_password.deref()) {
+        Ok(()) => {
+            println_ok!("The new password is in your clipboard.");
         },
         Err(err) => {
-            println_err!("Woops, I couldn't get that password ({:?}).", err);
-            return Err(1);
+            println_err!("I could not copy the new password to your clipboard ({}). To see it, try:", err);
+            println_err!("    rooster get {}", app_name);
         }
     }
+    Ok(())
 }
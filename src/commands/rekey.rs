@@ -0,0 +1,100 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password::provider::{self, KdfParams};
+use super::super::safe_string::read_password_safe;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+use std::str::FromStr;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "scrypt-log2-n", "A new scrypt cost, as a power of 2 (12 by default)", "LOG2_N");
+    opts.optopt("", "scrypt-r", "A new scrypt block size (8 by default)", "R");
+    opts.optopt("", "scrypt-p", "A new scrypt parallelism (1 by default)", "P");
+    opts.optopt("", "cipher", "A new cipher, aes or xchacha20", "CIPHER");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster rekey -h");
+    println!("    rooster rekey [options]");
+    println!("");
+    println!("Encrypts your password file with a new key, derived from your master password");
+    println!("with a new random salt, and with new scrypt parameters if you give any. Do it if");
+    println!("someone may have copied your password file: the copy is then encrypted with a");
+    println!("key that no longer protects anything new.");
+    println!("");
+    println!("With key slots, see `rooster key`, the password file gets a new random key, which");
+    println!("the master password and GPG key slots wrap again. Other key slots are removed,");
+    println!("since their secret is needed to wrap the new key: add them again afterwards.");
+    println!("");
+    println!("Higher scrypt parameters make your master password harder to guess, and opening");
+    println!("the file slower. The parameters not given stay what they are.");
+    println!("");
+    println!("The cipher is AES-256-CBC or XChaCha20, both signed with HMAC-SHA512. It only");
+    println!("changes with --cipher.");
+    println!("");
+    println!("Example:");
+    println!("    rooster rekey");
+    println!("    rooster rekey --scrypt-log2-n 16");
+    println!("    rooster rekey --cipher xchacha20");
+}
+
+fn parse_option<T: FromStr>(matches: &getopts::Matches, name: &str, current: T) -> Result<T, CommandError> {
+    match matches.opt_str(name) {
+        Some(value) => value.parse::<T>().map_err(|_| CommandError::Failed(format!("--{} must be a number, not \"{}\"", name, value))),
+        None => Ok(current),
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let current = store.kdf_params();
+    let kdf_params = KdfParams {
+        log2_n: try!(parse_option(matches, "scrypt-log2-n", current.log2_n)),
+        r: try!(parse_option(matches, "scrypt-r", current.r)),
+        p: try!(parse_option(matches, "scrypt-p", current.p)),
+    };
+    try!(password::v2::check_kdf_params(kdf_params).map_err(CommandError::Failed));
+    let cipher = match matches.opt_str("cipher") {
+        Some(name) => try!(provider::by_name(&name).ok_or(CommandError::Failed(format!("--cipher must be aes or xchacha20, not \"{}\"", name)))),
+        None => store.provider(),
+    };
+
+    // The key is derived from the master password, which the store does not keep.
+    print_stderr!("Type your master password once more: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read your master password ({})", err)));
+        }
+    };
+    if !store.is_master_password(master_password.deref()) {
+        return Err(CommandError::WrongMasterPassword);
+    }
+
+    let removed = match store.rekey(master_password.deref(), kdf_params, cipher) {
+        Ok(removed) => removed,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not make a new key ({})", err)));
+        }
+    };
+    for key_slot in removed.iter() {
+        println_warn!("I removed the key slot \"{}\", it cannot wrap the new key without its secret. Add it again with `rooster key add`.", key_slot.label);
+    }
+    println_ok!("Done! Your password file now has a new key, with scrypt log2_n {}, r {} and p {}, and the {} cipher.", kdf_params.log2_n, kdf_params.r, kdf_params.p, provider::name(cipher));
+    Ok(())
+}
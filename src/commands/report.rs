@@ -0,0 +1,81 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::crash;
+use super::CommandError;
+use std::fs::{self, File};
+use std::io::{Read, Write, ErrorKind as IoErrorKind};
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster report -h");
+    println!("    rooster report show");
+    println!("    rooster report delete");
+    println!("");
+    println!("Example:");
+    println!("    rooster report show");
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let path = match crash::crash_report_path() {
+        Some(path) => path,
+        None => {
+            return Err(CommandError::Failed("I could not determine where crash reports are stored".to_string()));
+        }
+    };
+
+    match matches.free.get(0).map(|action| &action[..]) {
+        Some("show") => {
+            let mut report = String::new();
+            match File::open(&path).and_then(|mut file| file.read_to_string(&mut report)) {
+                Ok(_) => {
+                    print!("{}", report);
+                    Ok(())
+                },
+                Err(ref err) if err.kind() == IoErrorKind::NotFound => {
+                    println_ok!("Good news, there is no crash report.");
+                    Ok(())
+                },
+                Err(err) => {
+                    Err(CommandError::Failed(format!("I could not read the crash report at {} ({})", path.display(), err)))
+                }
+            }
+        },
+        Some("delete") => {
+            match fs::remove_file(&path) {
+                Ok(_) => {
+                    println_ok!("Done! I've deleted the crash report.");
+                    Ok(())
+                },
+                Err(ref err) if err.kind() == IoErrorKind::NotFound => {
+                    println_ok!("Good news, there is no crash report.");
+                    Ok(())
+                },
+                Err(err) => {
+                    Err(CommandError::Failed(format!("I could not delete the crash report at {} ({})", path.display(), err)))
+                }
+            }
+        },
+        _ => {
+            println_err!("Woops, I need to know what to do with the crash report. For help, try:");
+            println_err!("    rooster report -h");
+            Err(CommandError::Reported)
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::ffi;
+use super::super::getopts;
+use super::super::output;
+use super::super::password;
+use super::super::safe_vec::SafeVec;
+use super::super::session;
+use super::CommandError;
+use super::protect::unprotect;
+use std::ops::Deref;
+use std::process::Command;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optmulti("e", "env", "Set an environment variable to a field of an app, the password by default", "VAR=APP[#FIELD]");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster run -h");
+    println!("    rooster run [options] -- <command> [<args> ...]");
+    println!("");
+    println!("The variables are only set for the command, not in your shell.");
+    println!("");
+    println!("Example:");
+    println!("    rooster run --env DB_PASS=postgres/main -- psql -h localhost");
+    println!("    rooster run -e DB_USER=postgres#username -e DB_PASS=postgres -- ./deploy.sh");
+}
+
+/// "VAR=value\0", allocated once so that no partial copy is left behind.
+fn env_entry(var: &str, value: &str) -> SafeVec {
+    let mut entry = Vec::with_capacity(var.len() + value.len() + 2);
+    entry.extend_from_slice(var.as_bytes());
+    entry.push(b'=');
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(0);
+    SafeVec::new(entry)
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("command to run"));
+    }
+
+    let mut vars = Vec::new();
+    for env in matches.opt_strs("env").iter() {
+        let (var, target) = match env.find('=') {
+            Some(i) if i > 0 => (&env[..i], &env[i + 1..]),
+            _ => {
+                return Err(CommandError::Failed(format!("\"{}\" should look like VAR=APP or VAR=APP#FIELD", env)));
+            }
+        };
+        let (app_name, field) = match target.rfind('#') {
+            Some(i) => (&target[..i], &target[i + 1..]),
+            None => (target, "password"),
+        };
+
+        let password = try!(store.get_password(app_name).ok_or(CommandError::NoSuchApp(app_name.to_string())));
+        let password = match field {
+            "name" | "username" | "url" | "kind" => password,
+            _ => try!(unprotect(&password)),
+        };
+        match password.field(field) {
+            Some(ref value) if value.contains('\0') => {
+                return Err(CommandError::Failed(format!("the field \"{}\" of \"{}\" has a NUL byte, which an environment variable can't hold", field, app_name)));
+            },
+            Some(value) => { vars.push(env_entry(var, value.deref())); },
+            None => {
+                return Err(CommandError::Failed(format!("\"{}\" has no field named \"{}\"", app_name, field)));
+            }
+        }
+    }
+
+    let mut command = Command::new(&matches.free[0]);
+    command.args(&matches.free[1..]);
+    // Not `session::command`: the session is taken out along with the values going in.
+    try!(ffi::set_child_env(&mut command, session::SESSION_ENV_VAR, vars).map_err(|err| CommandError::Failed(format!("I could not set the environment of the command ({})", err))));
+
+    let status = command.status();
+
+    // On Unix, the values are only in the child's environment and in buffers that the
+    // command owns. Dropping it wipes them before anything else happens. On Windows,
+    // the command keeps copies that are freed without being wiped.
+    drop(command);
+
+    match status {
+        Ok(status) => {
+            if status.success() {
+                Ok(())
+            } else {
+                // Forward the exit code so scripts can tell what happened to the command.
+                Err(CommandError::Exit(status.code().unwrap_or(output::EXIT_FAILURE)))
+            }
+        },
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not run \"{}\" ({})", matches.free[0], err)))
+        }
+    }
+}
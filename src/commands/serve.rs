@@ -0,0 +1,272 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::api;
+use super::super::getopts;
+use super::super::otp::percent_decode;
+use super::super::password;
+use super::super::rand::{Rng, OsRng};
+use super::super::rustc_serialize::hex::ToHex;
+use super::super::save_password_file;
+use super::super::serde_json::{self, Map, Value};
+use super::CommandError;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Set this to always use the same token, instead of a new one each time.
+pub const TOKEN_ENV_VAR: &'static str = "ROOSTER_API_TOKEN";
+
+const DEFAULT_LISTEN: &'static str = "127.0.0.1:7879";
+
+/// Requests only ever carry one app, this is plenty.
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// The request line and each header are read before the token is checked, so they
+/// are bounded too.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "listen", "Address to listen on, default is 127.0.0.1:7879", "ADDRESS");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster serve -h");
+    println!("    rooster serve [--listen <address>]");
+    println!("");
+    println!("Unlocks the password file once and serves it over HTTP, so that scripts can get");
+    println!("passwords without running rooster each time. Every request needs the header:");
+    println!("    Authorization: Bearer <token>");
+    println!("");
+    println!("The token is printed when the server starts, or taken from ${}.", TOKEN_ENV_VAR);
+    println!("");
+    println!("Endpoints:");
+    println!("    GET  /apps          the apps, without their passwords");
+    println!("    GET  /apps/<name>   an app, with its password");
+    println!("    POST /apps          add a login, from {{\"name\", \"username\", \"password\", \"url\"}}");
+    println!("");
+    println!("Anyone who has the token gets your passwords, so only listen on 127.0.0.1");
+    println!("unless you really know what you are doing. Stop the server with Ctrl+C.");
+    println!("");
+    println!("Example:");
+    println!("    rooster serve");
+    println!("    curl -H \"Authorization: Bearer $TOKEN\" http://127.0.0.1:7879/apps/YouTube");
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads a line of at most MAX_LINE bytes, with its line break.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<usize, String> {
+    let read = try!(reader.by_ref().take(MAX_LINE as u64).read_line(line).map_err(|err| err.to_string()));
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err("a line of the request is too long".to_string());
+    }
+    Ok(read)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    try!(read_line(&mut reader, &mut line));
+    let mut parts = line.split_whitespace();
+    let method = try!(parts.next().ok_or("empty request".to_string())).to_string();
+    let path = try!(parts.next().ok_or("no path in the request".to_string())).to_string();
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let mut header = String::new();
+        let read = try!(read_line(&mut reader, &mut header));
+        if read == 0 || header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err("the request has too many headers".to_string());
+        }
+        let mut name_value = header.splitn(2, ':');
+        if let (Some(name), Some(value)) = (name_value.next(), name_value.next()) {
+            match &name.trim().to_lowercase()[..] {
+                "authorization" => { authorization = Some(value.trim().to_string()); },
+                "content-length" => {
+                    content_length = try!(value.trim().parse::<usize>().map_err(|_| "invalid Content-Length".to_string()));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        return Err("the request body is too large".to_string());
+    }
+    let mut body = vec![0u8; content_length];
+    try!(reader.read_exact(&mut body).map_err(|err| err.to_string()));
+
+    Ok(Request {
+        method: method,
+        path: path,
+        authorization: authorization,
+        body: body,
+    })
+}
+
+fn error(message: &str) -> Value {
+    let mut object = Map::new();
+    object.insert("error".to_string(), Value::String(message.to_string()));
+    Value::Object(object)
+}
+
+fn from_result(result: Result<Value, api::Error>, status: u32) -> (u32, Value) {
+    match result {
+        Ok(json) => (status, json),
+        Err(err) => {
+            let status = match err {
+                api::Error::NotFound(_) => 404,
+                api::Error::AlreadyExists(_) => 409,
+                api::Error::InvalidParams(_) => 400,
+                api::Error::Failed(_) => 500,
+            };
+            (status, error(&err.message()))
+        }
+    }
+}
+
+/// Compares in constant time, so the token can't be guessed one character at a time.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn handle(request: &Request, token: &str, store: &mut password::v2::PasswordStore) -> (u32, Value) {
+    let authorized = request.authorization.as_ref()
+        .map(|value| same_token(value, &format!("Bearer {}", token)))
+        .unwrap_or(false);
+    if !authorized {
+        return (401, error("missing or wrong token"));
+    }
+
+    let path = request.path.split('?').next().unwrap_or("");
+    match (&request.method[..], path) {
+        ("GET", "/apps") => (200, api::list(store)),
+        ("POST", "/apps") => {
+            let params = match serde_json::from_str::<Value>(&String::from_utf8_lossy(&request.body)) {
+                Ok(params) => params,
+                Err(_) => return (400, error("the body is not valid JSON")),
+            };
+            let added = api::add(store, &params);
+            if added.is_ok() {
+                if let Err(err) = save_password_file(store) {
+                    return (500, error(&format!("the app was added but not saved: {}", err)));
+                }
+            }
+            from_result(added, 201)
+        },
+        ("GET", _) if path.starts_with("/apps/") => {
+            match percent_decode(&path["/apps/".len()..]) {
+                Ok(name) => from_result(api::get(store, &name), 200),
+                Err(err) => (400, error(&err)),
+            }
+        },
+        _ => (404, error("no such endpoint")),
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: u32, body: &Value) -> Result<(), String> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).map_err(|err| err.to_string())
+}
+
+fn new_token() -> Result<String, CommandError> {
+    let mut bytes = [0u8; 32];
+    match OsRng::new() {
+        Ok(mut rng) => {
+            rng.fill_bytes(&mut bytes);
+            Ok(bytes.to_hex())
+        },
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not generate a token ({})", err)))
+        }
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let listen = matches.opt_str("listen").unwrap_or(DEFAULT_LISTEN.to_string());
+    let address = match listen.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(_) => {
+            return Err(CommandError::Failed(format!("\"{}\" is not an address like 127.0.0.1:7879", listen)));
+        }
+    };
+    let (token, generated) = match env::var(TOKEN_ENV_VAR) {
+        Ok(ref token) if !token.is_empty() => (token.clone(), false),
+        _ => (try!(new_token()), true),
+    };
+
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not listen on {} ({})", address, err)));
+        }
+    };
+    if !address.ip().is_loopback() {
+        println_warn!("Careful, {} can be reached from other machines.", address);
+    }
+    println_ok!("Listening on http://{}, stop with Ctrl+C.", address);
+    if generated {
+        println_stderr!("Requests need the header:");
+        println_stderr!("    Authorization: Bearer {}", token);
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        // A client that stops talking must not block the others.
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+        let (status, body) = match read_request(&stream) {
+            Ok(request) => {
+                let (status, body) = handle(&request, &token, store);
+                // The path has the name of the app, which stays out of the log.
+                println_stderr!("{} {}", request.method, status);
+                (status, body)
+            },
+            Err(err) => (400, error(&err)),
+        };
+        let _ = respond(&stream, status, &body);
+    }
+    Ok(())
+}
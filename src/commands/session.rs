@@ -0,0 +1,152 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::safe_string::read_password_safe;
+use super::super::safe_vec::SafeVec;
+use super::super::session;
+use super::super::throttle;
+use super::super::timestamp;
+use super::CommandError;
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// How long a session lasts without --timeout.
+const DEFAULT_TIMEOUT: &'static str = "1h";
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("t", "timeout", "End the session after this long, like 15m or 8h, default is 1h", "DURATION");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster session -h");
+    println!("    eval $(rooster session start [--timeout <duration>])");
+    println!("    eval $(rooster session end)");
+    println!("    rooster session status");
+    println!("");
+    println!("`rooster unlock` and `rooster lock` are shortcuts for these, and `rooster lock` ends");
+    println!("the sessions of every shell at once.");
+    println!("");
+    println!("A session lets you type your master password once for the commands you run in this");
+    println!("shell, until it times out or you end it. No background program is involved: the");
+    println!("key of your vault, not your master password, is kept encrypted in $ROOSTER_SESSION.");
+    println!("Rooster does not pass that variable on to the programs it runs, like `rooster run`,");
+    println!("hooks, plugins or your editor. The key to decrypt it is in a file only you can read,");
+    println!("which is deleted when the session ends or times out, so the variable is useless");
+    println!("afterwards. On macOS, that key is in your login keychain instead, and on Windows,");
+    println!("it is encrypted for your account with DPAPI.");
+    println!("");
+    println!("A session is for one password file. After `rooster change-master-password` or");
+    println!("`rooster rekey`, start a new session.");
+    println!("");
+    println!("Example:");
+    println!("    eval $(rooster session start --timeout 15m)");
+    println!("    rooster get youtube");
+    println!("    eval $(rooster session end)");
+}
+
+/// Asks for the master password and returns the key of the vault it opens.
+fn read_vault_key(path: &str) -> Result<SafeVec, CommandError> {
+    print_stderr!("{}", tr!("master-password-prompt"));
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not read your master password ({})", err)));
+        }
+    };
+    let mut input = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut input)) {
+        return Err(CommandError::PasswordFile(format!("I could not read the password file \"{}\" ({})", path, err)));
+    }
+    // Sessions count wrong master passwords too, or they would be a way around the delay.
+    let throttle_path = throttle::path(path);
+    throttle::wait(&throttle_path);
+    match password::v2::PasswordStore::from_input(master_password, SafeVec::new(input)) {
+        Ok(store) => {
+            let _ = throttle::succeeded(&throttle_path);
+            Ok(store.key().clone())
+        },
+        Err(password::PasswordError::WrongMasterPasswordError) => {
+            let _ = throttle::failed(&throttle_path);
+            Err(CommandError::WrongMasterPassword)
+        },
+        Err(err) => Err(CommandError::PasswordFile(format!("I could not open the password file ({:?})", err))),
+    }
+}
+
+/// Starts a session, for `rooster session start` and `rooster unlock`.
+pub fn start(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let timeout = matches.opt_str("timeout").unwrap_or(DEFAULT_TIMEOUT.to_string());
+    let seconds = match timestamp::parse_duration(&timeout) {
+        Some(seconds) if seconds > 0 => seconds,
+        _ => {
+            return Err(CommandError::Failed(format!("\"{}\" is not a duration like 15m or 8h", timeout)));
+        }
+    };
+    let path = match password_file_path() {
+        Ok(path) => path,
+        Err(_) => {
+            return Err(CommandError::Failed("I could not determine where your password file is".to_string()));
+        }
+    };
+
+    let vault_key = try!(read_vault_key(&path));
+    match session::start(&path, &vault_key, seconds) {
+        Ok(value) => {
+            println!("export {}='{}'", session::SESSION_ENV_VAR, value);
+            println_ok!("Alright! Your session lasts {}.", timeout);
+            Ok(())
+        },
+        Err(err) => Err(CommandError::Failed(format!("I could not start the session ({})", err))),
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    match matches.free.get(0).map(|action| &action[..]) {
+        Some("start") => start(matches),
+        Some("end") => {
+            // Unset the variable even if the key is already gone, for instance after a timeout.
+            let ended = session::end();
+            println!("unset {}", session::SESSION_ENV_VAR);
+            match ended {
+                Ok(()) => {
+                    println_ok!("Done! Your session has ended.");
+                    Ok(())
+                },
+                Err(err) => Err(CommandError::Failed(format!("I could not end the session ({})", err))),
+            }
+        },
+        Some("status") => {
+            let open = session::count();
+            match session::expires_at() {
+                Some(expires_at) => {
+                    println!("Your session ends at {}.", expires_at.to_local_string());
+                    if open > 1 {
+                        println!("{} other sessions are open in other shells.", open - 1);
+                    }
+                    Ok(())
+                },
+                None if open > 0 => Err(CommandError::Failed(format!("there is no session in this shell, but {} in other shells. End them all with `rooster lock`", open))),
+                None => Err(CommandError::Failed("there is no session in this shell".to_string())),
+            }
+        },
+        Some(action) => {
+            Err(CommandError::Failed(format!("I don't know the action \"{}\". Try start, end or status", action)))
+        },
+        None => Err(CommandError::MissingArgument("action")),
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::serde_json;
+use super::super::safe_string::SafeString;
+use super::super::share;
+use super::CommandError;
+use super::protect::unprotect;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("t", "to", "The public key file of the person to share with", "FILE");
+    opts.optopt("o", "output", "Write the share to this file instead of printing it", "FILE");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster share -h");
+    println!("    rooster share --to <public_key_file> [--output <file>] <app_name>");
+    println!("");
+    println!("Encrypts an app for someone else, with their public key. Only they can read the");
+    println!("share, with `rooster receive`. To get their public key, they can run:");
+    println!("    rooster receive --public-key");
+    println!("");
+    println!("Example:");
+    println!("    rooster share --to alice.pub --output aws.share aws");
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    if matches.free.is_empty() {
+        return Err(CommandError::MissingArgument("app name"));
+    }
+
+    let ref app_name = matches.free[0];
+
+    let password = match store.get_password(app_name) {
+        Some(password) => password,
+        None => {
+            return Err(CommandError::NoSuchApp(app_name.to_string()));
+        }
+    };
+    let mut password = try!(unprotect(&password));
+    // The history and usage are ours, not theirs.
+    password.history = None;
+    password.last_used_at = None;
+    password.use_count = None;
+
+    let key_path = match matches.opt_str("to") {
+        Some(key_path) => key_path,
+        None => {
+            println_err!("Woops, I need to know who to share with. For help, try:");
+            println_err!("    rooster share -h");
+            return Err(CommandError::Reported);
+        }
+    };
+    let mut public_key = String::new();
+    if let Err(err) = File::open(&key_path).and_then(|mut file| file.read_to_string(&mut public_key)) {
+        return Err(CommandError::Failed(format!("I could not read the public key {} ({})", key_path, err)));
+    }
+
+    let encoded = match serde_json::to_string(&password) {
+        Ok(encoded) => SafeString::new(encoded),
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not encode the app ({:?})", err)));
+        }
+    };
+    let armored = match share::encrypt(encoded.as_bytes(), public_key.deref()) {
+        Ok(armored) => armored,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not encrypt the app with {}: {}", key_path, err)));
+        }
+    };
+
+    match matches.opt_str("output") {
+        Some(path) => {
+            match File::create(&path).and_then(|mut file| file.write_all(armored.as_bytes())) {
+                Ok(_) => {
+                    println_ok!("Done! The share is in {}, only the owner of {} can read it.", path, key_path);
+                    Ok(())
+                },
+                Err(err) => {
+                    Err(CommandError::Failed(format!("I could not write {} ({})", path, err)))
+                }
+            }
+        },
+        None => {
+            print!("{}", armored);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::super::password;
+use super::super::safe_string::{SafeString, read_password_safe};
+use super::super::strength;
+use super::super::write_new_password_file;
+use super::CommandError;
+use std::io::Write;
+use std::ops::Deref;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "tag", "Move the apps with this tag", "TAG");
+    opts.optopt("", "folder", "Move the apps in this folder, like work/", "FOLDER");
+    opts.optflag("", "keep", "Copy the apps instead, keeping them in this vault too");
+    strength::add_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster split -h");
+    println!("    rooster split [options] <new_file>");
+    println!("");
+    println!("Moves the apps with a tag, or in a folder, to a new password file with its own");
+    println!("master password. With both --tag and --folder, only the apps that have the tag");
+    println!("and are in the folder are moved. The new file is written before the apps are");
+    println!("removed from this one, and an existing file is never overwritten.");
+    println!("");
+    println!("To put the new file together with another one, see `rooster combine -h`.");
+    println!("");
+    println!("Example:");
+    println!("    rooster split --tag personal personal.rooster");
+    println!("    rooster split --folder work/ --keep work.rooster");
+}
+
+fn read_new_master_password(matches: &getopts::Matches) -> Result<SafeString, CommandError> {
+    let required_score = try!(strength::required_score(matches));
+    print_stderr!("Type the master password of the new file: ");
+    let master_password = try!(read_password_safe().map_err(|err| CommandError::Failed(format!("I could not read the master password ({})", err))));
+    try!(strength::check(master_password.deref(), required_score));
+    print_stderr!("Type it once more: ");
+    let confirmation = try!(read_password_safe().map_err(|err| CommandError::Failed(format!("I could not read the master password ({})", err))));
+    if master_password != confirmation {
+        return Err(CommandError::Failed("the master password confirmation did not match".to_string()));
+    }
+    Ok(master_password)
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let path = try!(matches.free.get(0).ok_or(CommandError::MissingArgument("new file")));
+    let tag = matches.opt_str("tag");
+    let folder = matches.opt_str("folder").map(|folder| format!("{}/", folder.trim_right_matches('/').to_lowercase()));
+    if tag.is_none() && folder.is_none() {
+        return Err(CommandError::Failed("tell me which apps to move with --tag or --folder".to_string()));
+    }
+
+    let passwords: Vec<password::v2::Password> = store.get_all_passwords().iter()
+        .filter(|p| tag.as_ref().map_or(true, |tag| p.tags.as_ref().map_or(false, |tags| tags.contains(tag))))
+        .filter(|p| folder.as_ref().map_or(true, |folder| p.name.to_lowercase().starts_with(&folder[..])))
+        .cloned()
+        .collect();
+    if passwords.is_empty() {
+        return Err(CommandError::Failed("no app matches, so there is nothing to split".to_string()));
+    }
+
+    let master_password = try!(read_new_master_password(matches));
+    let mut other = try!(password::v2::PasswordStore::new(master_password).map_err(|err| CommandError::Failed(format!("I could not create the new password file ({})", err))));
+    for password in passwords.iter() {
+        try!(other.add_password(password.clone()).map_err(|err| CommandError::Failed(format!("I could not add {} to the new password file ({:?})", password.name, err))));
+    }
+    try!(write_new_password_file(&other, path).map_err(CommandError::Failed));
+
+    if matches.opt_present("keep") {
+        println_ok!("Done! I've copied {} apps to {}.", passwords.len(), path);
+        return Ok(());
+    }
+    for password in passwords.iter() {
+        try!(store.delete_password(&password.name).map_err(|err| CommandError::Failed(format!("I could not remove {} from this vault ({:?}), it is in both files now", password.name, err))));
+    }
+    println_ok!("Done! I've moved {} apps to {}.", passwords.len(), path);
+    Ok(())
+}
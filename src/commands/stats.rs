@@ -0,0 +1,152 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::password;
+use super::super::password_file_path;
+use super::super::strength;
+use super::super::usage;
+use super::CommandError;
+use std::fs;
+use std::io::Write;
+use std::ops::Deref;
+
+/// Apps not used for this many days are considered stale by default.
+const DEFAULT_STALE_DAYS: u64 = 180;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    opts.optopt("", "stale-days", "Show apps not used for this many days, default is 180", "DAYS");
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster stats -h");
+    println!("    rooster stats");
+    println!("");
+    println!("Shows an overview of your passwords: how many there are, how strong they are on");
+    println!("average and which one was not changed for the longest time.");
+    println!("");
+    println!("It also shows the apps you have not used in a while, so you can close the accounts");
+    println!("you don't need anymore. Rooster only knows when you use a password if you set the");
+    println!("${} environment variable to \"true\", since it then needs to", usage::TRACK_USAGE_ENV_VAR);
+    println!("save the password file each time you run `rooster get`.");
+    println!("");
+    println!("Example:");
+    println!("    rooster stats --stale-days 365");
+}
+
+fn print_overview(passwords: &[password::v2::Password]) -> Result<(), CommandError> {
+    println!("Apps: {}", passwords.len());
+
+    if !passwords.is_empty() {
+        let count = passwords.len() as f64;
+        let total_length = passwords.iter().map(|p| p.password.chars().count()).fold(0, |a, b| a + b);
+        let total_bits = passwords.iter()
+            .map(|p| strength::estimate(p.password.deref()).guesses_log10 * 10f64.log2())
+            .fold(0f64, |a, b| a + b);
+        println!("Average password length: {:.1} characters", total_length as f64 / count);
+        println!("Average password strength: {:.0} bits", total_bits / count);
+
+        let oldest = passwords.iter().min_by_key(|p| p.updated_at).unwrap();
+        println!(
+            "Oldest password: {} ({}), not changed for {} days",
+            oldest.name,
+            oldest.username,
+            oldest.updated_at.days_ago()
+        );
+    }
+
+    println!("Apps with notes: {}", passwords.iter().filter(|p| p.notes.is_some()).count());
+    println!("Apps with one-time passwords: {}", passwords.iter().filter(|p| p.otp.is_some()).count());
+
+    let path = match password_file_path() {
+        Ok(path) => path,
+        Err(_) => {
+            return Err(CommandError::Failed("I could not determine where your password file is".to_string()));
+        }
+    };
+    match fs::metadata(&path) {
+        Ok(metadata) => println!("Password file: {} ({} bytes)", path, metadata.len()),
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I couldn't read the size of {} ({:?})", path, err)));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_metadata(metadata: &password::v2::Metadata) {
+    match metadata.created_at {
+        Some(created_at) => println!("Password file created: {}", created_at.to_local_string()),
+        None => println!("Password file created: before Rooster kept track"),
+    }
+    match metadata.rekeyed_at {
+        Some(rekeyed_at) => println!("Last new key: {} ({} days ago)", rekeyed_at.to_local_string(), rekeyed_at.days_ago()),
+        None => println!("Last new key: never, see `rooster rekey`"),
+    }
+    for change in metadata.kdf_history.iter() {
+        println!("Scrypt parameters since {}: log2_n {}, r {}, p {}", change.at.to_local_string(), change.log2_n, change.r, change.p);
+    }
+}
+
+pub fn callback_exec(matches: &getopts::Matches, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let stale_days = match matches.opt_str("stale-days") {
+        Some(days) => {
+            match days.parse::<u64>() {
+                Ok(days) => days,
+                Err(_) => {
+                    return Err(CommandError::Failed(format!("the number of days must be a number, not \"{}\"", days)));
+                }
+            }
+        },
+        None => DEFAULT_STALE_DAYS,
+    };
+
+    try!(print_overview(store.get_all_passwords()));
+    if let Some(metadata) = store.metadata() {
+        print_metadata(metadata);
+    }
+
+    if !usage::tracking_enabled() {
+        println_stderr!("Usage tracking is disabled, so apps you did use may show up here. To enable it,");
+        println_stderr!("set the ${} environment variable to \"true\".", usage::TRACK_USAGE_ENV_VAR);
+        println_stderr!("");
+    }
+
+    // Apps that were never used since tracking started count from their creation.
+    let mut stale: Vec<(&password::v2::Password, u64)> = store.get_all_passwords().iter()
+        .map(|p| (p, p.last_used_at.unwrap_or(p.created_at).days_ago()))
+        .filter(|&(_, days)| days >= stale_days)
+        .collect();
+    stale.sort_by(|a, b| b.1.cmp(&a.1));
+
+    if stale.is_empty() {
+        println!("");
+        println_ok!("All your apps were used in the last {} days.", stale_days);
+        return Ok(());
+    }
+
+    println!("");
+    println!("These apps were not used in the last {} days:", stale_days);
+    for &(p, days) in stale.iter() {
+        let last_used = match p.last_used_at {
+            Some(_) => format!("last used {} days ago", days),
+            None => "never used".to_string(),
+        };
+        println!("    {:30} {:30} {} ({} uses)", p.name, p.username, last_used, p.use_count.unwrap_or(0));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,407 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::getopts;
+use super::super::config;
+use super::super::crypto::digest::Digest;
+use super::super::crypto::sha2::Sha256;
+use super::super::password::PasswordError;
+use super::super::password::v2::{digest, Password, PasswordStore, Unlock};
+use super::super::password_file_path;
+use super::super::read_unlock;
+use super::super::safe_vec::SafeVec;
+use super::super::serde_json;
+use super::super::webdav::WebDav;
+use super::super::backend;
+use super::super::hooks;
+use super::CommandError;
+use std::fs::{self, File};
+use std::io::{Read, Write, ErrorKind as IoErrorKind};
+use std::path::{Path, PathBuf};
+
+/// What the password file and the remote file were at the last sync.
+#[derive(Serialize, Deserialize)]
+struct SyncState {
+    /// ETag of the remote file, `None` if the server did not give one.
+    etag: Option<String>,
+    /// SHA-256 of the password file.
+    hash: String,
+}
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster sync -h");
+    println!("    rooster sync");
+    println!("");
+    println!("Keeps your password file in sync with a copy on a WebDAV server, like Nextcloud.");
+    println!("Set the URL in the configuration file, see `rooster -h`:");
+    println!("    \"webdav\": {{ \"url\": \"https://...\", \"username\": \"me\" }}");
+    println!("");
+    println!("If only one side changed since the last sync, the other side is updated. If both");
+    println!("changed, you're asked for your master password, and the remote file is merged into");
+    println!("yours app by app and field by field, then uploaded. An app deleted on one side and");
+    println!("changed on the other is kept. If the remote file does not open with your master");
+    println!("password, nothing is overwritten and it is saved next to yours, so you can pick");
+    println!("which one to keep.");
+    println!("");
+    println!("Otherwise the password file stays encrypted the whole time, so there is no master");
+    println!("password to type.");
+    println!("");
+    println!("After a successful sync, runs ~/.config/rooster/hooks/post-sync if it exists, with");
+    println!("ROOSTER_SYNC set to uploaded, downloaded, merged or unchanged, and ROOSTER_FILE to");
+    println!("your password file. ROOSTER_SYNC_ADDED, ROOSTER_SYNC_REMOVED and ROOSTER_SYNC_CHANGED");
+    println!("count the apps the sync added, removed and changed in your password file, or on the");
+    println!("server for an upload. Counting needs your master password, so it is asked for when");
+    println!("there is a hook and something changed. The hook could commit the file to git, or");
+    println!("notify you.");
+    println!("");
+    println!("Example:");
+    println!("    rooster sync");
+}
+
+fn with_extension(path: &str, extension: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path, extension))
+}
+
+/// The content of the file, `None` if it does not exist.
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let mut content = Vec::new();
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut content)) {
+        Ok(_) => Ok(Some(content)),
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("could not read {} ({})", path.display(), err)),
+    }
+}
+
+/// SHA-256 of a file's content.
+fn hash(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+fn load_state(path: &Path) -> Option<SyncState> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        Ok(_) => serde_json::from_str(&content).ok(),
+        Err(_) => None,
+    }
+}
+
+fn save_state(path: &Path, etag: Option<String>, hash: String) -> Result<(), CommandError> {
+    let state = SyncState {
+        etag: etag,
+        hash: hash,
+    };
+    let encoded = serde_json::to_string(&state).unwrap_or(String::new());
+    File::create(path).and_then(|mut file| file.write_all(encoded.as_bytes())).map_err(|err| CommandError::Failed(format!("I could not save the sync state to {} ({})", path.display(), err)))
+}
+
+/// Asks how to open the password file the first time it is needed, and only then.
+fn unlock_once<'a>(unlock: &'a mut Option<Unlock>, path: &str) -> Result<&'a Unlock, CommandError> {
+    if unlock.is_none() {
+        *unlock = Some(try!(read_unlock(path).map_err(CommandError::Failed)));
+    }
+    Ok(unlock.as_ref().unwrap())
+}
+
+/// Opens a copy of the password file, asking how to the first time, see `run`.
+fn open_copy(unlock: &mut Option<Unlock>, path: &str, content: &[u8]) -> Result<PasswordStore, CommandError> {
+    let unlock = try!(unlock_once(unlock, path));
+    PasswordStore::open(unlock, SafeVec::new(content.to_vec())).map_err(open_error)
+}
+
+/// What to report when a copy of the password file does not open.
+pub fn open_error(err: PasswordError) -> CommandError {
+    match err {
+        PasswordError::WrongMasterPasswordError => CommandError::WrongMasterPassword,
+        err => CommandError::PasswordFile(format!("I could not open your password file ({:?})", err)),
+    }
+}
+
+/// Merges the remote file into the password file, starting from the copy of the last
+/// sync. Returns the merged file, or `None` if the remote file does not open the way
+/// the password file does.
+fn merge(open: &mut FnMut(&[u8]) -> Result<PasswordStore, CommandError>, local: &[u8], remote: &[u8], base_path: &Path) -> Result<Option<Vec<u8>>, CommandError> {
+    let mut ours = try!(open(local));
+    let theirs = match open(remote) {
+        Ok(store) => store,
+        Err(_) => return Ok(None),
+    };
+    // Without the copy of the last sync, say before the first one, apps deleted on one
+    // side come back from the other.
+    let base = read_file(base_path).ok()
+        .and_then(|content| content)
+        .and_then(|content| open(&content).ok());
+    let base_passwords = base.as_ref().map(|base| base.get_all_passwords().to_vec()).unwrap_or(Vec::new());
+    for name in ours.merge(&base_passwords, &theirs).iter() {
+        println_warn!("{}", tr!("merge-tie", name));
+    }
+
+    let mut merged = Vec::new();
+    try!(ours.write_to(&mut merged).map_err(|err| CommandError::PasswordFile(format!("I could not write the merged password file ({:?})", err))));
+    Ok(Some(merged))
+}
+
+fn upload(webdav: &WebDav, path: &Path, state_path: &Path, if_match: Option<&str>, hash: String) -> Result<&'static str, CommandError> {
+    match webdav.put(path, if_match) {
+        Ok(ref response) if response.status >= 200 && response.status < 300 => {
+            try!(save_state(state_path, response.etag.clone(), hash));
+            println_ok!("Done! I've uploaded your password file.");
+            Ok("uploaded")
+        },
+        Ok(ref response) if response.status == 412 => {
+            Err(CommandError::Failed("the remote file changed while I was uploading. Run `rooster sync` again".to_string()))
+        },
+        Ok(response) => {
+            Err(CommandError::Failed(format!("the server refused the upload (HTTP {})", response.status)))
+        },
+        Err(err) => {
+            Err(CommandError::Failed(format!("I could not upload your password file: {}", err)))
+        }
+    }
+}
+
+/// What a sync did.
+struct Outcome {
+    /// "uploaded", "downloaded", "merged" or "unchanged".
+    action: &'static str,
+    /// What the sync changed, before and after: the password file, or for an upload,
+    /// the remote file as of the last sync and the password file. Empty for a file
+    /// that was not there.
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+impl Outcome {
+    fn new(action: &'static str, before: Option<Vec<u8>>, after: Vec<u8>) -> Outcome {
+        Outcome {
+            action: action,
+            before: before.unwrap_or(Vec::new()),
+            after: after,
+        }
+    }
+
+    fn unchanged() -> Outcome {
+        println_ok!("Everything is up to date.");
+        Outcome::new("unchanged", None, Vec::new())
+    }
+}
+
+/// Syncs the password file. It is only opened, with `open`, if both sides changed.
+fn sync(path_string: &str, open: &mut FnMut(&[u8]) -> Result<PasswordStore, CommandError>) -> Result<Outcome, CommandError> {
+    let webdav = match config::load() {
+        Ok(config::Config { webdav: Some(webdav), .. }) => WebDav::new(webdav.url, webdav.username),
+        Ok(_) => {
+            println_err!("Woops, there is no WebDAV server in your configuration file. For help, try:");
+            println_err!("    rooster sync -h");
+            return Err(CommandError::Reported);
+        },
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I could not load the configuration file: {}", err)));
+        }
+    };
+
+    if backend::is_remote(path_string) {
+        return Err(CommandError::Failed("your password file is already on a server, there is nothing to sync".to_string()));
+    }
+    if backend::is_stdio(path_string) {
+        return Err(CommandError::Failed("your password file comes from stdin, there is nothing to sync".to_string()));
+    }
+    if backend::is_ephemeral(path_string) {
+        return Err(CommandError::Failed("an ephemeral vault is never written to disk, there is nothing to sync".to_string()));
+    }
+    let path = Path::new(path_string);
+    let state_path = with_extension(path_string, "sync");
+    let download_path = with_extension(path_string, "download");
+    let remote_path = with_extension(path_string, "remote");
+    let base_path = with_extension(path_string, "sync-base");
+
+    let state = load_state(&state_path);
+    let local = match read_file(path) {
+        Ok(local) => local,
+        Err(err) => {
+            return Err(CommandError::Failed(format!("I {}", err)));
+        }
+    };
+    let local_hash = local.as_ref().map(|local| hash(local));
+    let local_changed = match (&state, &local_hash) {
+        (&Some(ref state), &Some(ref hash)) => &state.hash != hash,
+        (_, &None) => false,
+        (&None, &Some(_)) => true,
+    };
+    let last_etag = state.as_ref().and_then(|state| state.etag.clone());
+    // What the server had at the last sync, see `callback_exec`.
+    let base = read_file(&base_path).ok().and_then(|base| base);
+
+    let response = match webdav.get(&download_path, last_etag.as_ref().map(|etag| &etag[..])) {
+        Ok(response) => response,
+        Err(err) => {
+            let _ = fs::remove_file(&download_path);
+            return Err(CommandError::Failed(format!("I could not download the remote file: {}", err)));
+        }
+    };
+
+    match response.status {
+        304 => {
+            let _ = fs::remove_file(&download_path);
+            if local_changed {
+                if let (Some(local), Some(hash)) = (local, local_hash) {
+                    try!(upload(&webdav, path, &state_path, last_etag.as_ref().map(|etag| &etag[..]), hash));
+                    return Ok(Outcome::new("uploaded", base, local));
+                }
+            }
+            Ok(Outcome::unchanged())
+        },
+        404 => {
+            let _ = fs::remove_file(&download_path);
+            match (local, local_hash) {
+                (Some(local), Some(hash)) => {
+                    try!(upload(&webdav, path, &state_path, None, hash));
+                    Ok(Outcome::new("uploaded", None, local))
+                },
+                _ => {
+                    Err(CommandError::Failed("there is no password file here nor on the server".to_string()))
+                }
+            }
+        },
+        200 => {
+            let remote = match read_file(&download_path) {
+                Ok(Some(remote)) => remote,
+                _ => {
+                    return Err(CommandError::Failed("I could not read the downloaded file".to_string()));
+                }
+            };
+            let remote_hash = hash(&remote);
+
+            if local_hash.as_ref() == Some(&remote_hash) {
+                let _ = fs::remove_file(&download_path);
+                try!(save_state(&state_path, response.etag, remote_hash));
+                return Ok(Outcome::unchanged());
+            }
+
+            if !local_changed {
+                if let Err(err) = fs::rename(&download_path, path) {
+                    return Err(CommandError::Failed(format!("I could not replace your password file ({})", err)));
+                }
+                try!(save_state(&state_path, response.etag, remote_hash));
+                println_ok!("Done! I've downloaded the password file from the server.");
+                return Ok(Outcome::new("downloaded", local, remote));
+            }
+
+            // Both sides changed, so local is there.
+            let local = local.unwrap_or(Vec::new());
+            println_info!("Your password file and the remote file both changed since the last sync, I'll merge them.");
+            let merged = try!(merge(open, &local, &remote, &base_path));
+            if let Some(merged) = merged {
+                // The merged file replaces the password file in one go, like a download.
+                if let Err(err) = File::create(&download_path).and_then(|mut file| file.write_all(&merged)).and_then(|_| fs::rename(&download_path, path)) {
+                    return Err(CommandError::Failed(format!("I could not replace your password file ({})", err)));
+                }
+                try!(upload(&webdav, path, &state_path, response.etag.as_ref().map(|etag| &etag[..]), hash(&merged)));
+                return Ok(Outcome::new("merged", Some(local), merged));
+            }
+
+            if let Err(err) = fs::rename(&download_path, &remote_path) {
+                return Err(CommandError::Failed(format!("I could not save the remote file ({})", err)));
+            }
+            println_err!("Woops, your password file and the remote file both changed since the last sync,");
+            println_err!("and the remote file does not open with your master password.");
+            println_err!("I've saved the remote file to {}.", remote_path.display());
+            println_err!("Once you've picked which file to keep and put it in place of yours, delete");
+            println_err!("{} and run `rooster sync` again.", state_path.display());
+            Err(CommandError::Reported)
+        },
+        status => {
+            let _ = fs::remove_file(&download_path);
+            Err(CommandError::Failed(format!("the server refused the download (HTTP {})", status)))
+        }
+    }
+}
+
+/// How many apps were added, removed and changed from one content of the password file
+/// to another.
+fn count_changes(open: &mut FnMut(&[u8]) -> Result<PasswordStore, CommandError>, before: &[u8], after: &[u8]) -> Result<(usize, usize, usize), CommandError> {
+    fn passwords(open: &mut FnMut(&[u8]) -> Result<PasswordStore, CommandError>, content: &[u8]) -> Result<Vec<Password>, CommandError> {
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        open(content).map(|store| store.get_all_passwords().to_vec())
+    }
+
+    fn find<'a>(passwords: &'a [Password], name: &str) -> Option<&'a Password> {
+        passwords.iter().find(|p| p.name.to_lowercase() == name.to_lowercase())
+    }
+
+    let before = try!(passwords(open, before));
+    let after = try!(passwords(open, after));
+    let added = after.iter().filter(|p| find(&before, &p.name).is_none()).count();
+    let removed = before.iter().filter(|p| find(&after, &p.name).is_none()).count();
+    let changed = after.iter()
+        .filter(|p| find(&before, &p.name).map_or(false, |q| digest(*p) != digest(&q)))
+        .count();
+    Ok((added, removed, changed))
+}
+
+/// Syncs the password file at `path`, keeps a copy for the next merge and runs the
+/// post-sync hook. The password file is opened with `open`, only when both sides
+/// changed or there is a hook to tell what changed. Returns what the sync did, see
+/// `Outcome`.
+pub fn run(path: &str, open: &mut FnMut(&[u8]) -> Result<PasswordStore, CommandError>) -> Result<&'static str, CommandError> {
+    let outcome = try!(sync(path, open));
+    // The next merge starts from here.
+    if let Err(err) = fs::copy(path, with_extension(path, "sync-base")) {
+        println_warn!("I could not keep a copy of the password file for the next sync ({}).", err);
+    }
+    if !hooks::exists("post-sync") {
+        return Ok(outcome.action);
+    }
+
+    let counts = if outcome.action == "unchanged" {
+        (0, 0, 0)
+    } else {
+        try!(count_changes(open, &outcome.before, &outcome.after).map_err(|err| match err {
+            CommandError::PasswordFile(message) => {
+                CommandError::Failed(format!("the sync went fine, but I could not count the changes for the post-sync hook ({})", message))
+            },
+            err => err,
+        }))
+    };
+    let (added, removed, changed) = counts;
+    hooks::run("post-sync", &[
+        ("ROOSTER_SYNC", outcome.action.to_string()),
+        ("ROOSTER_FILE", path.to_string()),
+        ("ROOSTER_SYNC_ADDED", added.to_string()),
+        ("ROOSTER_SYNC_REMOVED", removed.to_string()),
+        ("ROOSTER_SYNC_CHANGED", changed.to_string()),
+    ]);
+    Ok(outcome.action)
+}
+
+pub fn callback_exec(_matches: &getopts::Matches) -> Result<(), CommandError> {
+    let path = match password_file_path() {
+        Ok(path) => path,
+        Err(_) => {
+            return Err(CommandError::Failed("I could not determine where your password file is".to_string()));
+        }
+    };
+    // The master password is asked for at most once, and only if it is needed.
+    let mut unlock = None;
+    try!(run(&path, &mut |content: &[u8]| open_copy(&mut unlock, &path, content)));
+    Ok(())
+}
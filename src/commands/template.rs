@@ -0,0 +1,72 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::config;
+use super::super::getopts;
+use super::super::template;
+use super::CommandError;
+use std::ops::Deref;
+
+pub fn callback_options(_opts: &mut getopts::Options) {}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster template -h");
+    println!("    rooster template [<template>]");
+    println!("");
+    println!("Lists the templates for `rooster add --template`, and the fields each of them asks");
+    println!("for. Fields marked with * are typed without echo. The fields are saved as custom");
+    println!("fields, see `rooster field`.");
+    println!("");
+    println!("You can add templates, or change the built-in ones, in the configuration file:");
+    println!("");
+    println!("    \"templates\": {{");
+    println!("        \"router\": {{");
+    println!("            \"kind\": \"login\",");
+    println!("            \"fields\": [{{ \"name\": \"address\" }}, {{ \"name\": \"admin PIN\", \"secret\": true }}]");
+    println!("        }}");
+    println!("    }}");
+    println!("");
+    println!("Example:");
+    println!("    rooster template");
+    println!("    rooster template server");
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    let templates = try!(config::load()
+        .and_then(|config| template::all(&config))
+        .map_err(|err| CommandError::Failed(format!("I could not load the templates: {}", err))));
+
+    let shown: Vec<&template::Template> = match matches.free.get(0) {
+        Some(name) => templates.iter().filter(|t| t.name == name.deref()).collect(),
+        None => templates.iter().collect(),
+    };
+    if shown.is_empty() {
+        return Err(CommandError::Failed(format!("I don't know the template \"{}\"", matches.free[0])));
+    }
+
+    for t in shown.iter() {
+        let fields: Vec<String> = t.kind.fields.iter()
+            .map(|field| (field.name, field.secret))
+            .chain(t.fields.iter().map(|field| (field.name.deref(), field.secret)))
+            .map(|(name, secret)| if secret { format!("*{}", name) } else { name.to_string() })
+            .collect();
+        if fields.is_empty() {
+            println!("{:10}{}", t.name, t.kind.name);
+        } else {
+            println!("{:10}{}: {}", t.name, t.kind.name, fields.join(", "));
+        }
+    }
+    Ok(())
+}
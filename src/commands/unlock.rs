@@ -0,0 +1,38 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::getopts;
+use super::CommandError;
+use super::session;
+
+pub fn callback_options(opts: &mut getopts::Options) {
+    session::callback_options(opts);
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster unlock -h");
+    println!("    eval $(rooster unlock [--timeout <duration>])");
+    println!("");
+    println!("Asks for the master password once, for the commands of this shell, until the");
+    println!("timeout or `rooster lock`. It is the same as `rooster session start`, see");
+    println!("`rooster session -h`.");
+    println!("");
+    println!("Example:");
+    println!("    eval $(rooster unlock --timeout 10m)");
+}
+
+pub fn callback_exec(matches: &getopts::Matches) -> Result<(), CommandError> {
+    session::start(matches)
+}
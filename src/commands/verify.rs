@@ -0,0 +1,170 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::backend::{self, VaultBackend};
+use super::super::config;
+use super::super::getopts;
+use super::super::password::{self, PasswordError};
+use super::super::password::v2::{PasswordStore, VERSION, HEADER_LEN};
+use super::super::password_file_path;
+use super::super::safe_string::{self, read_password_safe};
+use super::super::safe_vec::SafeVec;
+use super::super::throttle;
+use super::super::webdav::WebDav;
+use super::CommandError;
+use std::fs::File;
+use std::io::{Read, Write};
+
+pub fn callback_options(_opts: &mut getopts::Options) {
+}
+
+pub fn callback_help() {
+    println!("Usage:");
+    println!("    rooster verify -h");
+    println!("    rooster verify");
+    println!("");
+    println!("Checks that your password file can be read, one layer at a time: the header,");
+    println!("the signature, the decryption and the JSON inside. If something is wrong, you");
+    println!("know exactly where. The password file is never modified.");
+    println!("");
+    println!("Example:");
+    println!("    rooster verify");
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    if backend::is_remote(path) {
+        let username = config::load().ok().and_then(|config| config.webdav).and_then(|webdav| webdav.username);
+        let mut backend = backend::WebDavBackend::new(WebDav::new(path.to_string(), username));
+        return backend.read().map_err(|err| err.to_string());
+    }
+    if backend::is_stdio(path) {
+        return backend::StdioBackend::new().read().map_err(|err| err.to_string());
+    }
+    let mut content = Vec::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_end(&mut content)).map_err(|err| err.to_string()));
+    Ok(content)
+}
+
+pub fn callback_exec(_matches: &getopts::Matches) -> Result<(), CommandError> {
+    let path = try!(password_file_path().map_err(|_| CommandError::Failed("I could not determine where your password file is".to_string())));
+    if backend::is_ephemeral(&path) {
+        return Err(CommandError::Failed("an ephemeral vault only lives in memory, there is no password file to verify".to_string()));
+    }
+    let input = match read_file(&path) {
+        Ok(input) => input,
+        Err(err) => {
+            println_err!("File:       could not be read ({})", err);
+            return Err(CommandError::Reported);
+        }
+    };
+    println_ok!("File:       {} bytes", input.len());
+
+    if input.len() < HEADER_LEN {
+        println_err!("Header:     too short, {} bytes instead of at least {}", input.len(), HEADER_LEN);
+        return Err(CommandError::Reported);
+    }
+    let version = password::v2::version_of(&input).unwrap_or(0);
+    if version < 2 || version > VERSION {
+        println_err!("Version:    {}, expected 2 to {}", version, VERSION);
+        println_err!("            Version 1 files have no header, any other command offers to upgrade them.");
+        return Err(CommandError::Reported);
+    }
+    println_ok!("Version:    {}", version);
+
+    if backend::is_stdio(&path) {
+        safe_string::prompt_from_terminal();
+    }
+    print_stderr!("Type your master password: ");
+    let master_password = match read_password_safe() {
+        Ok(master_password) => master_password,
+        Err(err) => {
+            println_err!("I could not read your master password ({})", err);
+            return Err(CommandError::Reported);
+        }
+    };
+
+    let throttle_path = if backend::is_remote(&path) || backend::is_stdio(&path) { None } else { Some(throttle::path(&path)) };
+    if let Some(ref throttle_path) = throttle_path {
+        throttle::wait(throttle_path);
+    }
+
+    // Each layer is only checked once the ones before it are fine, so the first
+    // error tells which layer is broken.
+    match PasswordStore::from_input(master_password, SafeVec::new(input)) {
+        Ok(store) => {
+            if let Some(ref throttle_path) = throttle_path {
+                let _ = throttle::succeeded(throttle_path);
+            }
+            println_ok!("Password:   right");
+            println_ok!("Signature:  valid");
+            println_ok!("Decryption: ok");
+            println_ok!("JSON:       ok, {} apps", store.get_all_passwords().len());
+            Ok(())
+        },
+        Err(PasswordError::WrongMasterPasswordError) => {
+            if let Some(ref throttle_path) = throttle_path {
+                let _ = throttle::failed(throttle_path);
+            }
+            println_err!("Password:   wrong");
+            Err(CommandError::Reported)
+        },
+        // Version 2 files have no password check, so a wrong master password looks
+        // like a damaged file.
+        Err(PasswordError::CorruptionError) if version == 2 => {
+            println_err!("Signature:  invalid, the master password is wrong or the file is damaged");
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::CorruptionError) => {
+            println_ok!("Password:   right");
+            println_err!("Signature:  invalid, the file is damaged");
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::DecryptionError) => {
+            println_ok!("Password:   right");
+            println_ok!("Signature:  valid");
+            println_err!("Decryption: failed");
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::Utf8Error(offset)) => {
+            println_ok!("Password:   right");
+            println_ok!("Signature:  valid");
+            println_ok!("Decryption: ok");
+            println_err!("UTF-8:      invalid from byte {}", offset);
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::JsonError(offset)) => {
+            println_ok!("Password:   right");
+            println_ok!("Signature:  valid");
+            println_ok!("Decryption: ok");
+            println_err!("JSON:       invalid at byte {}, this version of Rooster may be too old for this file", offset);
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::InvalidJsonError) => {
+            println_ok!("Password:   right");
+            println_ok!("Signature:  valid");
+            println_ok!("Decryption: ok");
+            println_err!("JSON:       invalid, this version of Rooster may be too old for this file");
+            Err(CommandError::Reported)
+        },
+        Err(PasswordError::HeaderError(message)) => {
+            println_err!("Header:     invalid, {}", message);
+            Err(CommandError::Reported)
+        },
+        Err(err) => {
+            println_err!("Header:     could not be read ({:?})", err);
+            Err(CommandError::Reported)
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::serde_json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{ErrorKind as IoErrorKind, Read};
+use std::path::PathBuf;
+
+/// Environment variable holding the path of the configuration file.
+pub const CONFIG_ENV_VAR: &'static str = "ROOSTER_CONFIG";
+
+/// Name of the configuration file, in the directory from `data_dir`.
+const CONFIG_FILE_DEFAULT: &'static str = ".rooster.json";
+
+/// Settings from the configuration file. Every setting is optional, and a missing
+/// file is the same as an empty one.
+///
+/// ```
+/// {
+///     "vaults": {
+///         "personal": "/home/me/personal.rooster",
+///         "work": "/home/me/work.rooster"
+///     },
+///     "default_vault": "personal",
+///     "webdav": {
+///         "url": "https://cloud.example.com/remote.php/dav/files/me/passwords.rooster",
+///         "username": "me"
+///     },
+///     "color": false,
+///     "enforce_strength": 3,
+///     "backups": 10,
+///     "templates": {
+///         "router": {
+///             "kind": "login",
+///             "fields": [{ "name": "address" }, { "name": "admin PIN", "secret": true }]
+///         }
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Password files by name, for `--vault`.
+    pub vaults: Option<BTreeMap<String, String>>,
+    /// The vault to use when neither `--vault` nor $ROOSTER_FILE is set.
+    pub default_vault: Option<String>,
+    /// Where `rooster sync` keeps a copy of the password file.
+    pub webdav: Option<WebDavConfig>,
+    /// Set to false to never color the output, like `--no-color`.
+    pub color: Option<bool>,
+    /// Templates for `rooster add --template`, by name.
+    pub templates: Option<BTreeMap<String, TemplateConfig>>,
+    /// The minimum strength score when there is no `--enforce-strength`, see `strength`.
+    pub enforce_strength: Option<u8>,
+    /// How many backups of each password file on disk to keep, 10 by default and 0 for
+    /// none, see `backup`.
+    pub backups: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct WebDavConfig {
+    /// URL of the password file on the server.
+    pub url: String,
+    /// Without a username, credentials are taken from ~/.netrc.
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TemplateConfig {
+    /// The kind of entry, default is login.
+    pub kind: Option<String>,
+    pub fields: Vec<TemplateFieldConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct TemplateFieldConfig {
+    pub name: String,
+    pub secret: Option<bool>,
+}
+
+impl Config {
+    pub fn vault_path(&self, name: &str) -> Option<String> {
+        self.vaults.as_ref().and_then(|vaults| vaults.get(name).cloned())
+    }
+
+    /// Names and paths of the configured vaults, sorted by name.
+    pub fn vaults(&self) -> Vec<(String, String)> {
+        match self.vaults {
+            Some(ref vaults) => vaults.iter().map(|(name, path)| (name.clone(), path.clone())).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Where Rooster keeps its files by default: the home directory.
+#[cfg(unix)]
+pub fn data_dir() -> Option<PathBuf> {
+    env::home_dir()
+}
+
+/// Where Rooster keeps its files by default: a Rooster directory in AppData, like other
+/// Windows programs, created if needed.
+#[cfg(windows)]
+pub fn data_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|app_data| PathBuf::from(app_data).join("Rooster")).map(|dir| {
+        let _ = ::std::fs::create_dir_all(&dir);
+        dir
+    })
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config`.
+#[cfg(unix)]
+fn config_home() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::home_dir().map(|home| home.join(".config")),
+    }
+}
+
+/// Where hook scripts live: `$XDG_CONFIG_HOME/rooster/hooks`, or `~/.config/rooster/hooks`.
+#[cfg(unix)]
+pub fn hooks_dir() -> Option<PathBuf> {
+    config_home().map(|dir| dir.join("rooster").join("hooks"))
+}
+
+/// Where systemd looks for the units of the user.
+#[cfg(unix)]
+pub fn systemd_user_dir() -> Option<PathBuf> {
+    config_home().map(|dir| dir.join("systemd").join("user"))
+}
+
+/// There is no systemd on Windows.
+#[cfg(windows)]
+pub fn systemd_user_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Where hook scripts live: a hooks directory in the Rooster directory.
+#[cfg(windows)]
+pub fn hooks_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("hooks"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    match env::var(CONFIG_ENV_VAR) {
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => data_dir().map(|dir| dir.join(CONFIG_FILE_DEFAULT)),
+    }
+}
+
+pub fn load() -> Result<Config, String> {
+    let path = match config_path() {
+        Some(path) => path,
+        None => { return Ok(Config::default()); }
+    };
+    let mut content = String::new();
+    match File::open(&path).and_then(|mut file| file.read_to_string(&mut content)) {
+        Ok(_) => {},
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => { return Ok(Config::default()); },
+        Err(err) => { return Err(format!("could not read {} ({})", path.display(), err)); }
+    }
+    serde_json::from_str::<Config>(&content).map_err(|err| format!("{} is not valid ({})", path.display(), err))
+}
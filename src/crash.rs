@@ -0,0 +1,123 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::config;
+use std::backtrace::Backtrace;
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Name of the crash report file, in the directory from `config::data_dir`.
+const CRASH_REPORT_FILE: &'static str = ".rooster-crash-report";
+
+/// Version of the password file format that was opened, 0 if none was opened yet.
+static VAULT_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_vault_version(version: u32) {
+    VAULT_VERSION.store(version as usize, Ordering::SeqCst);
+}
+
+pub fn crash_report_path() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join(CRASH_REPORT_FILE))
+}
+
+/// Path components kept from the source files in the backtrace, like
+/// "src/commands/add.rs", so that it does not show where the user keeps things.
+const BACKTRACE_PATH_COMPONENTS: usize = 3;
+
+/// The backtrace with its file paths cut down, see `BACKTRACE_PATH_COMPONENTS`. Frames
+/// hold function names, which come from the code, never the values of their arguments.
+/// Anything else that is not a frame or a location is dropped.
+fn redact_backtrace(backtrace: &str) -> String {
+    let mut redacted = String::new();
+    for line in backtrace.lines() {
+        let trimmed = line.trim_left();
+        let indent = &line[..line.len() - trimmed.len()];
+        if trimmed.starts_with("at ") {
+            let path = &trimmed[3..];
+            let components: Vec<&str> = path.split(|c: char| c == '/' || c == '\\').collect();
+            let kept = components[components.len().saturating_sub(BACKTRACE_PATH_COMPONENTS)..].join("/");
+            redacted.push_str(&format!("{}at {}\n", indent, kept));
+        } else if trimmed.chars().next().map_or(false, |c| c.is_digit(10)) {
+            redacted.push_str(&format!("{}\n", line));
+        }
+    }
+    redacted
+}
+
+/// Builds the crash report.
+///
+/// The report must never contain anything from the password file, so the panic
+/// message is left out: it may have been built from app names or passwords. The
+/// panic location and the redacted backtrace are enough to find the failing code.
+fn format_report(command_name: &str, location: Option<String>) -> String {
+    let vault_version = match VAULT_VERSION.load(Ordering::SeqCst) {
+        0 => "unknown".to_string(),
+        version => version.to_string(),
+    };
+
+    let mut report = String::new();
+    report.push_str("Rooster crash report\n");
+    report.push_str("\n");
+    report.push_str(&format!("rooster version: {}\n", env!("CARGO_PKG_VERSION")));
+    report.push_str(&format!("platform:        {} {}\n", env::consts::OS, env::consts::ARCH));
+    report.push_str(&format!("command:         {}\n", command_name));
+    report.push_str(&format!("file format:     {}\n", vault_version));
+    report.push_str(&format!("panic location:  {}\n", location.unwrap_or("unknown".to_string())));
+    report.push_str("\n");
+    report.push_str("backtrace:\n");
+    report.push_str(&redact_backtrace(&Backtrace::force_capture().to_string()));
+    report
+}
+
+fn write_report(report: &str) -> Option<PathBuf> {
+    let path = match crash_report_path() {
+        Some(path) => path,
+        None => { return None; }
+    };
+    match File::create(&path).and_then(|mut file| file.write_all(report.as_bytes())) {
+        Ok(_) => Some(path),
+        Err(_) => None,
+    }
+}
+
+/// Writes a crash report to a local file when Rooster panics. Nothing is ever sent
+/// anywhere, the user decides whether to attach the report to a bug report.
+///
+/// It replaces the default hook, which would print the panic message, see
+/// `format_report`.
+pub fn install_panic_hook(command_name: &'static str) {
+    panic::set_hook(Box::new(move |info| {
+        let location = info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        let report = format_report(command_name, location);
+
+        // We're already panicking, so errors writing to stderr are ignored here.
+        let mut stderr = ::std::io::stderr();
+        match write_report(report.as_ref()) {
+            Some(path) => {
+                let _ = writeln!(stderr, "Woops, Rooster crashed. I wrote a crash report to {}.", path.display());
+                let _ = writeln!(stderr, "It contains no passwords and no app names. You can review it with:");
+                let _ = writeln!(stderr, "    rooster report show");
+                let _ = writeln!(stderr, "If you want, attach it to a bug report at https://github.com/conradkleinespel/rooster/issues/new");
+            },
+            None => {
+                let _ = writeln!(stderr, "Woops, Rooster crashed and I could not write a crash report.");
+            }
+        }
+    }));
+}
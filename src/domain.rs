@@ -0,0 +1,79 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+/// Public suffixes with two labels, for which the registrable domain has three labels.
+///
+/// This is a small subset of the Public Suffix List, enough for the sites people
+/// usually have accounts on.
+static TWO_LABEL_SUFFIXES: &'static [&'static str] = &[
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "me.uk", "ltd.uk", "plc.uk",
+    "com.au", "net.au", "org.au", "edu.au", "gov.au",
+    "co.nz", "org.nz", "net.nz",
+    "co.jp", "ne.jp", "or.jp", "ac.jp",
+    "co.kr", "or.kr",
+    "com.br", "net.br", "org.br",
+    "com.cn", "net.cn", "org.cn",
+    "com.mx", "com.ar", "com.tr", "com.tw", "com.hk", "com.sg", "com.my",
+    "co.in", "net.in", "org.in",
+    "co.za", "org.za",
+    "co.il", "org.il",
+    "com.ua", "com.pl", "com.ru",
+    "github.io", "gitlab.io", "herokuapp.com", "blogspot.com", "appspot.com",
+];
+
+/// Extracts the lowercase host of a URL, or of a bare host like "www.example.com".
+pub fn host(url: &str) -> Option<String> {
+    let url = url.trim();
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url,
+    };
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+    // Drop the credentials and the port.
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let host = if host_port.starts_with('[') {
+        // IPv6 addresses have colons, keep everything up to the closing bracket.
+        match host_port.find(']') {
+            Some(i) => &host_port[..i + 1],
+            None => host_port,
+        }
+    } else {
+        host_port.split(':').next().unwrap_or("")
+    };
+    let host = host.trim_right_matches('.').to_lowercase();
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// The part of the host that someone registered, e.g. "google.com" for
+/// "accounts.google.com" and "bbc.co.uk" for "www.bbc.co.uk".
+pub fn registrable_domain(host: &str) -> String {
+    let labels: Vec<&str> = host.split('.').collect();
+    // IP addresses and single labels like "localhost" are used as is.
+    if labels.len() <= 2 || host.starts_with('[') || labels.iter().all(|l| l.chars().all(|c| c.is_digit(10))) {
+        return host.to_string();
+    }
+    let last_two = labels[labels.len() - 2..].join(".");
+    let count = if TWO_LABEL_SUFFIXES.contains(&&last_two[..]) { 3 } else { 2 };
+    labels[labels.len() - count..].join(".")
+}
+
+/// The registrable domain without its public suffix, e.g. "google" for "google.com".
+pub fn site_name(domain: &str) -> &str {
+    domain.split('.').next().unwrap_or(domain)
+}
@@ -12,24 +12,177 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::ptr;
+use std::mem;
+use std::io::{Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
+#[cfg(unix)]
+use std::ffi::CString;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::Command;
 use super::libc;
+use super::safe_vec::SafeVec;
 
+/// Timestamps of version 1 password files.
 #[allow(non_camel_case_types)]
 pub type time_t = libc::c_uint;
 
-mod internal {
-    extern "C" {
-        pub fn time(t: *mut super::time_t) -> super::time_t;
+/// Broken down local time: year, month (1-12), day, hour, minute.
+#[cfg(unix)]
+pub fn localtime(seconds: u64) -> Option<(i32, u32, u32, u32, u32)> {
+    let time = seconds as libc::time_t;
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    if result.is_null() {
+        return None;
     }
+    Some((tm.tm_year + 1900, tm.tm_mon as u32 + 1, tm.tm_mday as u32, tm.tm_hour as u32, tm.tm_min as u32))
 }
 
-pub fn time() -> time_t {
-    let retrieved_time = unsafe { internal::time(ptr::null_mut()) };
+/// Broken down local time: year, month (1-12), day, hour, minute.
+#[cfg(windows)]
+pub fn localtime(seconds: u64) -> Option<(i32, u32, u32, u32, u32)> {
+    let time = seconds as libc::time_t;
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    // Unlike localtime_r, this returns an error number, 0 on success.
+    if unsafe { libc::localtime_s(&mut tm, &time) } != 0 {
+        return None;
+    }
+    Some((tm.tm_year + 1900, tm.tm_mon as u32 + 1, tm.tm_mday as u32, tm.tm_hour as u32, tm.tm_min as u32))
+}
+
+/// The start of a day in the local time zone, in seconds since the Unix epoch.
+pub fn mktime(year: i32, month: u32, day: u32) -> Option<u64> {
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month as libc::c_int - 1;
+    tm.tm_mday = day as libc::c_int;
+    // Whether daylight saving time is in effect that day is for mktime to find out.
+    tm.tm_isdst = -1;
+    let time = unsafe { libc::mktime(&mut tm) };
+    if time < 0 {
+        return None;
+    }
+    Some(time as u64)
+}
+
+/// Whether the file descriptor is a terminal, as opposed to a pipe or a file.
+pub fn is_terminal(fd: i32) -> bool {
+    unsafe { libc::isatty(fd as libc::c_int) != 0 }
+}
+
+/// Runs the function with the echo of the terminal turned off, except for the final
+/// newline, then puts the terminal back the way it was.
+#[cfg(unix)]
+pub fn without_echo<T, F: FnOnce() -> T>(fd: i32, f: F) -> IoResult<T> {
+    let mut term: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd as libc::c_int, &mut term) } != 0 {
+        return Err(IoError::last_os_error());
+    }
+    let original = term;
+    term.c_lflag &= !libc::ECHO;
+    term.c_lflag |= libc::ECHONL;
+    if unsafe { libc::tcsetattr(fd as libc::c_int, libc::TCSANOW, &term) } != 0 {
+        return Err(IoError::last_os_error());
+    }
+    let result = f();
+    unsafe { libc::tcsetattr(fd as libc::c_int, libc::TCSANOW, &original) };
+    Ok(result)
+}
+
+/// Reads a single byte, or nothing at the end of the input. Unlike `std::io::stdin()`,
+/// there is no buffer in between that would keep a copy of what was read.
+#[cfg(unix)]
+pub fn read_byte(fd: i32) -> IoResult<Option<u8>> {
+    let mut byte = 0u8;
+    loop {
+        let read = unsafe { libc::read(fd as libc::c_int, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if read == 1 {
+            return Ok(Some(byte));
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+        let err = IoError::last_os_error();
+        if err.kind() != IoErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Sets variables in the environment of the child only, between fork and exec, and takes
+/// `removed` out of it. Each variable is a "VAR=value\0" buffer that putenv uses in place,
+/// so the values are not copied into the command, and the buffers are wiped when the
+/// command is dropped.
+#[cfg(unix)]
+pub fn set_child_env(command: &mut Command, removed: &str, vars: Vec<SafeVec>) -> IoResult<()> {
+    let removed = try!(CString::new(removed).map_err(|_| IoError::new(IoErrorKind::InvalidInput, "the variable name has a NUL byte")));
+    let mut vars = vars;
+    command.before_exec(move || {
+        // Rooster runs no other thread by then, so nothing holds the allocator's lock
+        // if putenv needs it.
+        unsafe {
+            libc::unsetenv(removed.as_ptr());
+            for var in vars.iter_mut() {
+                if libc::putenv(var.as_mut_ptr() as *mut libc::c_char) != 0 {
+                    return Err(IoError::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    });
+    Ok(())
+}
 
-    if retrieved_time == (!0 as u32) {
-        panic!("Could not get time from system");
+/// Without fork, the values go through the command, which keeps copies of them until it
+/// is dropped.
+#[cfg(windows)]
+pub fn set_child_env(command: &mut Command, removed: &str, vars: Vec<SafeVec>) -> IoResult<()> {
+    command.env_remove(removed);
+    for var in vars.iter() {
+        let entry = try!(::std::str::from_utf8(&var[..var.len() - 1]).map_err(|_| IoError::new(IoErrorKind::InvalidInput, "the variable is not valid UTF-8")));
+        let mut parts = entry.splitn(2, '=');
+        command.env(parts.next().unwrap_or(""), parts.next().unwrap_or(""));
     }
+    Ok(())
+}
+
+/// Encrypts the data with DPAPI, for the current user on this computer only.
+#[cfg(windows)]
+pub fn protect_data(data: &[u8]) -> IoResult<SafeVec> {
+    dpapi(data, true)
+}
+
+/// Decrypts what `protect_data` encrypted.
+#[cfg(windows)]
+pub fn unprotect_data(data: &[u8]) -> IoResult<SafeVec> {
+    dpapi(data, false)
+}
 
-    retrieved_time
+#[cfg(windows)]
+fn dpapi(data: &[u8], protect: bool) -> IoResult<SafeVec> {
+    use super::winapi::um::dpapi::{CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN};
+    use super::winapi::um::winbase::LocalFree;
+    use super::winapi::um::wincrypt::DATA_BLOB;
+    use std::ptr;
+    use std::slice;
+
+    let mut input = DATA_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+    let mut output = DATA_BLOB { cbData: 0, pbData: ptr::null_mut() };
+    let done = unsafe {
+        if protect {
+            CryptProtectData(&mut input, ptr::null(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+        } else {
+            CryptUnprotectData(&mut input, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+        }
+    };
+    if done == 0 {
+        return Err(IoError::last_os_error());
+    }
+    unsafe {
+        let result = SafeVec::new(slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec());
+        // The decrypted key is wiped before Windows gets its memory back.
+        ptr::write_bytes(output.pbData, 0, output.cbData as usize);
+        LocalFree(output.pbData as *mut _);
+        Ok(result)
+    }
 }
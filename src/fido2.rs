@@ -0,0 +1,135 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The hmac-secret extension of a FIDO2 authenticator, through the `fido2-token`,
+//! `fido2-cred` and `fido2-assert` programs of libfido2. The authenticator makes a
+//! credential for Rooster once, then for each salt it is given gives back a secret that
+//! only it can compute. It waits for a touch, and asks for its PIN itself if it has one.
+
+use super::rand::{Rng, OsRng};
+use super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use super::session;
+use std::env;
+use std::io::Write;
+use std::ops::Deref;
+use std::process::Stdio;
+
+/// Environment variable with the authenticator to use, like /dev/hidraw3, the first one
+/// `fido2-token -L` lists by default.
+pub const DEVICE_ENV_VAR: &'static str = "ROOSTER_FIDO2_DEVICE";
+
+/// Length of the salts the hmac-secret is computed for.
+pub const SALT_LEN: usize = 32;
+
+/// The relying party of the credentials Rooster makes.
+const RELYING_PARTY: &'static str = "rooster";
+
+/// A FIDO2 authenticator plugged in.
+pub struct Device {
+    pub path: String,
+    /// What `fido2-token -L` says it is, like "Yubico YubiKey OTP+FIDO+CCID".
+    pub name: String,
+}
+
+/// The authenticator in $ROOSTER_FIDO2_DEVICE, or else the first one plugged in.
+pub fn device() -> Result<Device, String> {
+    if let Ok(path) = env::var(DEVICE_ENV_VAR) {
+        return Ok(Device {
+            path: path.clone(),
+            name: path,
+        });
+    }
+    let output = try!(session::command("fido2-token")
+        .arg("-L")
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("I could not run fido2-token ({}), is libfido2 installed?", err)));
+    if !output.status.success() {
+        return Err(format!("fido2-token failed ({})", output.status));
+    }
+    // Each line is like "/dev/hidraw3: vendor=0x1050, product=0x0407 (Yubico YubiKey)".
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(colon) = line.find(": ") {
+            let path = line[..colon].to_string();
+            let name = match (line.rfind('('), line.rfind(')')) {
+                (Some(start), Some(end)) if start < end => line[start + 1..end].to_string(),
+                _ => path.clone(),
+            };
+            return Ok(Device {
+                path: path,
+                name: name,
+            });
+        }
+    }
+    Err("no FIDO2 authenticator is plugged in".to_string())
+}
+
+fn random_base64(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0u8; len];
+    try!(OsRng::new().map_err(|err| format!("I could not make random bytes ({})", err))).fill_bytes(&mut bytes);
+    Ok(bytes.to_base64(STANDARD))
+}
+
+/// Runs a libfido2 program on the authenticator with `lines` on stdin, and returns the
+/// line of its output at `index`, decoded from base64. What it prints on stderr, like the
+/// prompt for the PIN, is shown as is.
+fn run(program: &str, option: &str, device: &Device, lines: &[&str], index: usize) -> Result<SafeVec, String> {
+    println_stderr!("Touch your FIDO2 authenticator if it blinks.");
+    let mut child = try!(session::command(program)
+        .arg(option)
+        .arg("-h")
+        .arg(&device.path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("I could not run {} ({}), is libfido2 installed?", program, err)));
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = SafeString::new(format!("{}\n", lines.join("\n")));
+        try!(stdin.write_all(input.as_bytes()).map_err(|err| format!("I could not talk to {} ({})", program, err)));
+    }
+    let output = try!(child.wait_with_output().map_err(|err| format!("I could not talk to {} ({})", program, err)));
+    let stdout = SafeString::new(String::from_utf8_lossy(&output.stdout).into_owned());
+    if !output.status.success() {
+        return Err(format!("{} failed ({}), is your authenticator plugged in?", program, output.status));
+    }
+    match stdout.lines().nth(index).map(|line| line.trim().from_base64()) {
+        Some(Ok(decoded)) => Ok(SafeVec::new(decoded)),
+        _ => Err(format!("{} gave an invalid answer", program)),
+    }
+}
+
+/// Makes a new credential with the hmac-secret extension, and returns its ID.
+pub fn make_credential(device: &Device) -> Result<Vec<u8>, String> {
+    let client_data_hash = try!(random_base64(32));
+    let user_id = try!(random_base64(32));
+    // fido2-cred prints the client data hash, the relying party, the format, the
+    // authenticator data, then the credential ID.
+    let lines = [client_data_hash.deref(), RELYING_PARTY, "rooster", user_id.deref()];
+    run("fido2-cred", "-M", device, &lines, 4).map(|credential_id| credential_id.to_vec())
+}
+
+/// The hmac-secret of the credential for the salt.
+pub fn hmac_secret(device: &Device, credential_id: &[u8], salt: &[u8]) -> Result<SafeVec, String> {
+    let client_data_hash = try!(random_base64(32));
+    let credential_id = credential_id.to_base64(STANDARD);
+    let salt = salt.to_base64(STANDARD);
+    // fido2-assert prints the client data hash, the relying party, the authenticator
+    // data, the signature, then the hmac-secret.
+    let lines = [client_data_hash.deref(), RELYING_PARTY, credential_id.deref(), salt.deref()];
+    run("fido2-assert", "-G", device, &lines, 4)
+}
@@ -13,62 +13,150 @@
 // limitations under the License.
 
 use super::getopts;
+use super::password::v2::GenerationPolicy;
 use super::rand::{Rng, OsRng};
-use std::io::{Write, Result as IoResult};
+use std::fs::File;
+use std::io::{Read, Write, Result as IoResult};
 
-fn generate_password(alnum: bool, len: usize) -> IoResult<String> {
+/// Characters that are easily mistaken for one another, left out with `--no-ambiguous`.
+const AMBIGUOUS: &'static str = "0O1lI|";
+
+/// The characters generated passwords are made of: printable ASCII, or only letters and
+/// digits with `alnum`, unless a custom set is given.
+fn alphabet(alnum: bool, charset: Option<&str>, no_ambiguous: bool) -> Vec<char> {
+    let mut chars: Vec<char> = match charset {
+        Some(charset) => charset.chars().collect(),
+        None => (33u8..127).map(|b| b as char).filter(|c| !alnum || c.is_alphanumeric()).collect(),
+    };
+    if no_ambiguous {
+        chars.retain(|&c| !AMBIGUOUS.contains(c));
+    }
+    // Duplicates would make some characters more likely than others.
+    chars.sort();
+    chars.dedup();
+    chars
+}
+
+fn generate_password(alphabet: &[char], len: usize) -> IoResult<String> {
     let mut password_as_string = String::new();
     let mut rng = try!(OsRng::new());
     for _ in 0 .. len {
-        if alnum {
-            match rng.gen_range(0, 3) {
-                // Numbers 0-9
-                0 => { password_as_string.push(rng.gen_range(48, 58) as u8 as char) },
-                // Uppercase A-Z
-                1 => { password_as_string.push(rng.gen_range(65, 91) as u8 as char) },
-                // Lowercase a-z
-                2 => { password_as_string.push(rng.gen_range(97, 123) as u8 as char) },
-                _ => { unreachable!() }
-            }
-        } else {
-            password_as_string.push(rng.gen_range(33, 127) as u8 as char);
-        }
+        password_as_string.push(alphabet[rng.gen_range(0, alphabet.len())]);
     }
     Ok(password_as_string)
 }
 
-/// Returns true if the password contains at least one digit, one uppercase letter and one
-/// lowercase letter.
-fn password_is_hard(password: &str, alnum: bool) -> bool {
-    let is_punctuation = |c| -> bool {
-        "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~".find(c).is_some()
-    };
-
-    if password.find(char::is_numeric).is_some()
-    && password.find(char::is_lowercase).is_some()
-    && password.find(char::is_uppercase).is_some()
-    && (alnum || password.find(is_punctuation).is_some()) {
-        true
-    } else {
-        false
-    }
+/// Returns true if the password contains at least one digit, one uppercase letter, one
+/// lowercase letter and one symbol, for each of these the alphabet has.
+fn password_is_hard(password: &str, alphabet: &[char]) -> bool {
+    let classes: [fn(char) -> bool; 4] = [
+        |c| c.is_numeric(),
+        |c| c.is_lowercase(),
+        |c| c.is_uppercase(),
+        |c| !c.is_alphanumeric(),
+    ];
+    classes.iter().all(|&is_in_class| {
+        !alphabet.iter().any(|&c| is_in_class(c)) || password.chars().any(is_in_class)
+    })
 }
 
-pub fn generate_hard_password(alnum: bool, len: usize) -> IoResult<String> {
+pub fn generate_hard_password(alphabet: &[char], len: usize, require_each_class: bool) -> IoResult<String> {
     loop {
-        let password = try!(generate_password(alnum, len));
-        if password_is_hard(password.as_ref(), alnum) {
+        let password = try!(generate_password(alphabet, len));
+        if !require_each_class || password_is_hard(password.as_ref(), alphabet) {
             return Ok(password);
         }
     }
 }
 
+/// Built-in word list for passphrases: 2549 common English words of 3 to 9 letters.
+pub static WORDLIST: &'static str = include_str!("wordlist.txt");
+
+/// Picks `count` words at random and joins them with `separator`.
+pub fn generate_passphrase(words: &[String], count: usize, separator: &str) -> IoResult<String> {
+    let mut rng = try!(OsRng::new());
+    let mut passphrase = String::new();
+    for i in 0 .. count {
+        if i > 0 {
+            passphrase.push_str(separator);
+        }
+        passphrase.push_str(&words[rng.gen_range(0, words.len())]);
+    }
+    Ok(passphrase)
+}
+
+/// Reads one word per line. Lines may start with dice rolls, as in the EFF word lists,
+/// in which case only the last column is kept.
+fn parse_wordlist(content: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for line in content.lines() {
+        if let Some(word) = line.split_whitespace().last() {
+            words.push(word.to_string());
+        }
+    }
+    // Duplicates would make some words more likely than others.
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Loads the word list at `path`, or the built-in word list.
+fn load_wordlist(path: Option<&str>) -> Option<Vec<String>> {
+    let wordlist = match path {
+        Some(path) => {
+            let mut content = String::new();
+            match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+                Ok(_) => parse_wordlist(content.as_ref()),
+                Err(err) => {
+                    println_err!("Woops! I could not read the word list at \"{}\" ({}).", path, err);
+                    return None;
+                }
+            }
+        },
+        None => parse_wordlist(WORDLIST),
+    };
+    if wordlist.len() < 2 {
+        println_err!("Woops! The word list must have at least 2 different words.");
+        return None;
+    }
+    Some(wordlist)
+}
+
 pub struct PasswordSpec {
     pub alnum: bool,
-    pub len: usize
+    pub len: usize,
+    /// When set, a passphrase of this many words is generated instead of a password.
+    pub words: Option<usize>,
+    pub separator: String,
+    pub wordlist: Vec<String>,
+    pub wordlist_path: Option<String>,
+    /// Leave out characters that look alike, see `AMBIGUOUS`.
+    pub no_ambiguous: bool,
+    /// Characters to pick from instead of the default ones.
+    pub charset: Option<String>,
+    /// Whether every kind of character in the alphabet must be used at least once.
+    pub require_each_class: bool,
 }
 
 impl PasswordSpec {
+    /// Registers the options that customize generated passwords, for commands that
+    /// generate passwords.
+    pub fn add_options(opts: &mut getopts::Options) {
+        opts.optflag("a", "alnum", "Only use alpha numeric (a-z, A-Z, 0-9) in generated passwords");
+        opts.optopt("l", "length", "Set a custom length for the generated password, default is 32", "32");
+        opts.optopt("w", "words", "Generate a passphrase of this many words instead of a password", "6");
+        opts.optopt("", "separator", "Set the separator between passphrase words, default is -", "-");
+        opts.optopt("", "wordlist", "Pick passphrase words from this file, one word per line", "FILE");
+        opts.optflag("", "no-ambiguous", "Leave out characters that look alike: 0, O, 1, l, I and |");
+        opts.optopt("", "charset", "Only use these characters in generated passwords", "CHARS");
+        opts.optflag("", "require-each-class", "With --charset, use at least one digit, lowercase, uppercase and symbol it has");
+    }
+
+    /// Whether any of the options from `add_options` was given.
+    pub fn has_options(matches: &getopts::Matches) -> bool {
+        ["alnum", "length", "words", "separator", "wordlist", "no-ambiguous", "charset", "require-each-class"].iter().any(|name| matches.opt_present(name))
+    }
+
     pub fn from_matches(matches: &getopts::Matches) -> Option<PasswordSpec> {
         let alnum = matches.opt_present("alnum");
         let mut password_len = 32;
@@ -93,9 +181,93 @@ impl PasswordSpec {
                 }
             }
         }
+
+        let words = match matches.opt_str("words") {
+            Some(words) => {
+                match words.parse::<usize>() {
+                    // Below 4 words, passphrases are too easy to guess, whatever the word list.
+                    Ok(parsed_words) if parsed_words >= 4 => Some(parsed_words),
+                    Ok(_) => {
+                        println_err!("Woops! A passphrase must have at least 4 words. This");
+                        println_err!("allows us to make sure your passphrase is secure.");
+                        return None;
+                    },
+                    Err(_) => {
+                        println_err!("Woops! The words option must be a valid number, for instance 6.");
+                        return None;
+                    }
+                }
+            },
+            None => None,
+        };
+
+        let wordlist_path = matches.opt_str("wordlist");
+        let wordlist = match load_wordlist(wordlist_path.as_ref().map(|path| &path[..])) {
+            Some(wordlist) => wordlist,
+            None => { return None; }
+        };
+
+        let no_ambiguous = matches.opt_present("no-ambiguous");
+        let charset = matches.opt_str("charset");
+        if alphabet(alnum, charset.as_ref().map(|charset| &charset[..]), no_ambiguous).len() < 2 {
+            println_err!("Woops! Passwords need at least 2 different characters to pick from.");
+            return None;
+        }
+
         Some(PasswordSpec {
             alnum: alnum,
-            len: password_len
+            len: password_len,
+            words: words,
+            separator: matches.opt_str("separator").unwrap_or("-".to_string()),
+            wordlist: wordlist,
+            wordlist_path: wordlist_path,
+            no_ambiguous: no_ambiguous,
+            // Custom sets are often meant to leave some kinds of characters out.
+            require_each_class: charset.is_none() || matches.opt_present("require-each-class"),
+            charset: charset,
+        })
+    }
+
+    pub fn from_policy(policy: &GenerationPolicy) -> Option<PasswordSpec> {
+        let wordlist = match load_wordlist(policy.wordlist.as_ref().map(|path| &path[..])) {
+            Some(wordlist) => wordlist,
+            None => { return None; }
+        };
+
+        Some(PasswordSpec {
+            alnum: policy.alnum,
+            len: policy.length,
+            words: policy.words,
+            separator: policy.separator.clone().unwrap_or("-".to_string()),
+            wordlist: wordlist,
+            wordlist_path: policy.wordlist.clone(),
+            no_ambiguous: policy.no_ambiguous,
+            charset: policy.charset.clone(),
+            require_each_class: policy.charset.is_none() || policy.require_each_class,
         })
     }
+
+    pub fn to_policy(&self) -> GenerationPolicy {
+        GenerationPolicy {
+            alnum: self.alnum,
+            length: self.len,
+            words: self.words,
+            separator: Some(self.separator.clone()),
+            wordlist: self.wordlist_path.clone(),
+            no_ambiguous: self.no_ambiguous,
+            charset: self.charset.clone(),
+            require_each_class: self.require_each_class,
+        }
+    }
+
+    /// Generates a password or a passphrase, depending on the options.
+    pub fn generate(&self) -> IoResult<String> {
+        match self.words {
+            Some(count) => generate_passphrase(&self.wordlist, count, &self.separator),
+            None => {
+                let alphabet = alphabet(self.alnum, self.charset.as_ref().map(|charset| &charset[..]), self.no_ambiguous);
+                generate_hard_password(&alphabet, self.len, self.require_each_class)
+            },
+        }
+    }
 }
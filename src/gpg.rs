@@ -0,0 +1,65 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wraps and unwraps vault keys with GnuPG, so that a GPG key, maybe on a smartcard, can
+//! open a vault. The `gpg` program does the work: its agent asks for the PIN of the card
+//! or the passphrase of the key, the same way it does for everything else.
+
+use super::safe_vec::SafeVec;
+use super::session;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Environment variable with the GnuPG program to use, `gpg` by default.
+pub const GPG_ENV_VAR: &'static str = "ROOSTER_GPG";
+
+fn gpg() -> Command {
+    let program = env::var(GPG_ENV_VAR).unwrap_or_else(|_| "gpg".to_string());
+    let mut command = session::command(program);
+    command.arg("--batch").arg("--quiet").arg("--no-tty");
+    command
+}
+
+/// Runs gpg with `input` on stdin and returns what it printed on stdout. What it prints on
+/// stderr is shown as is, since it says best what went wrong.
+fn run(mut command: Command, input: &[u8]) -> Result<SafeVec, String> {
+    let mut child = try!(command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit()).spawn().map_err(|err| {
+        format!("I could not run gpg ({}), is GnuPG installed?", err)
+    }));
+    if let Some(mut stdin) = child.stdin.take() {
+        try!(stdin.write_all(input).map_err(|err| format!("I could not talk to gpg ({})", err)));
+    }
+    let output = try!(child.wait_with_output().map_err(|err| format!("I could not talk to gpg ({})", err)));
+    let stdout = SafeVec::new(output.stdout);
+    if !output.status.success() {
+        return Err(format!("gpg failed ({})", output.status));
+    }
+    Ok(stdout)
+}
+
+/// Encrypts a key to a GPG public key. Only the public key is needed, so there is no PIN
+/// to type.
+pub fn encrypt(recipient: &str, key: &[u8]) -> Result<Vec<u8>, String> {
+    let mut command = gpg();
+    command.arg("--encrypt").arg("--recipient").arg(recipient).arg("--output").arg("-");
+    run(command, key).map(|encrypted| encrypted.to_vec())
+}
+
+/// Decrypts a key encrypted by `encrypt`, with whichever secret key gpg has for it.
+pub fn decrypt(encrypted: &[u8]) -> Result<SafeVec, String> {
+    let mut command = gpg();
+    command.arg("--decrypt").arg("--output").arg("-");
+    run(command, encrypted)
+}
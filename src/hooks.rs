@@ -0,0 +1,65 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks are programs the user puts in the hooks directory, named after the event they
+//! follow, like `post-sync`. They learn what happened through environment variables:
+//! counts, directions and file names, never passwords.
+
+use super::config;
+use super::session;
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+
+/// The hook for `event`, if there is one.
+fn path(event: &str) -> Option<PathBuf> {
+    config::hooks_dir().map(|dir| dir.join(event)).and_then(|path| if path.is_file() { Some(path) } else { None })
+}
+
+/// Whether there is a hook for `event`, for commands that only work out what to tell the
+/// hook when it is there.
+pub fn exists(event: &str) -> bool {
+    path(event).is_some()
+}
+
+/// Runs the hook for `event` if there is one, and waits for it. A failing hook is reported
+/// but does not fail the command, which already did its job.
+pub fn run(event: &str, vars: &[(&str, String)]) {
+    let path = match path(event) {
+        Some(path) => path,
+        None => { return; }
+    };
+
+    let mut command = session::command(&path);
+    command.env("ROOSTER_HOOK", event);
+    for &(name, ref value) in vars.iter() {
+        command.env(name, value);
+    }
+    // Only the hook's stderr is shown, so that it never mixes with what Rooster prints on
+    // stdout, like an export.
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::inherit());
+
+    match command.status() {
+        Ok(status) if status.success() => {},
+        Ok(status) => {
+            println_warn!("The {} hook failed ({}).", event, status);
+        },
+        Err(ref err) if err.kind() == IoErrorKind::PermissionDenied => {
+            println_warn!("I could not run the {} hook, {} is not executable.", event, path.display());
+        },
+        Err(err) => {
+            println_warn!("I could not run the {} hook ({}).", event, err);
+        },
+    }
+}
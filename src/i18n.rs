@@ -0,0 +1,99 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Language {
+    English,
+    French,
+}
+
+/// Key, English, French. To translate a message, add it here and print it with
+/// `tr!("key", args...)`, where each `{}` is replaced by the next argument. An empty
+/// translation falls back to English.
+static MESSAGES: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("master-password-prompt",
+     "Type your master password: ",
+     "Tapez votre mot de passe maître : "),
+    ("wrong-master-password",
+     "Woops, that's not the right master password.",
+     "Oups, ce n'est pas le bon mot de passe maître."),
+    ("missing-argument",
+     "Woops, seems like the {} is missing here. For help, try:",
+     "Oups, il manque un argument ({}). Pour de l'aide, essayez :"),
+    ("no-such-app",
+     "I couldn't find a password for {}. Make sure you",
+     "Je n'ai pas trouvé de mot de passe pour {}. Vérifiez que"),
+    ("no-such-app-hint",
+     "didn't make a typo. For a list of passwords, try:",
+     "vous n'avez pas fait de faute de frappe. Pour la liste des mots de passe, essayez :"),
+    ("failed",
+     "Woops, {}.",
+     "Oups, {}."),
+    ("unknown-command",
+     "Woops, the command `{}` does not exist. Try the --help option for more info.",
+     "Oups, la commande `{}` n'existe pas. Essayez l'option --help pour en savoir plus."),
+    ("for-help",
+     "For help, try:",
+     "Pour de l'aide, essayez :"),
+    ("merged-external-changes",
+     "The password file changed since I opened it, maybe a sync tool updated it. I've merged your changes into it.",
+     "Le fichier de mots de passe a changé depuis que je l'ai ouvert, peut-être à cause d'un outil de synchronisation. J'y ai fusionné vos modifications."),
+    ("merge-tie",
+     "The same field of \"{}\" was changed on both sides, only one change was kept. Please check it.",
+     "Le même champ de « {} » a changé des deux côtés, une seule modification a été gardée. Vérifiez-le."),
+    ("journal-replayed",
+     "Rooster was interrupted while saving last time. I've brought back these changes:",
+     "Rooster a été interrompu pendant le dernier enregistrement. J'ai récupéré ces modifications :"),
+];
+
+/// The language of the messages, from $LC_ALL, $LC_MESSAGES or $LANG, like the other
+/// command line tools.
+pub fn language() -> Language {
+    for name in ["LC_ALL", "LC_MESSAGES", "LANG"].iter() {
+        match env::var(name) {
+            Ok(ref value) if value.is_empty() => continue,
+            Ok(ref value) if value.starts_with("fr") => return Language::French,
+            Ok(_) => return Language::English,
+            Err(_) => continue,
+        }
+    }
+    Language::English
+}
+
+/// The message in the current language. Panics on unknown keys, which are a bug.
+pub fn message(key: &str) -> &'static str {
+    let &(_, english, french) = MESSAGES.iter()
+        .find(|&&(k, _, _)| k == key)
+        .unwrap_or_else(|| panic!("no message named {}", key));
+    match language() {
+        Language::French if !french.is_empty() => french,
+        _ => english,
+    }
+}
+
+/// The message in the current language, with each `{}` replaced by the next argument.
+pub fn format(key: &str, args: &[&Display]) -> String {
+    let mut parts = message(key).split("{}");
+    let mut output = parts.next().unwrap_or("").to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            output.push_str(&arg.to_string());
+        }
+        output.push_str(part);
+    }
+    output
+}
@@ -0,0 +1,125 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::password::v2::{digest, Password, PasswordStore};
+use super::timestamp::Timestamp;
+use super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::serde_json;
+use super::safe_string::SafeString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write, ErrorKind as IoErrorKind};
+use std::ops::Deref;
+use std::path::PathBuf;
+
+/// A change to one app, as written to the journal before the password file is
+/// saved. If Rooster dies while saving, the journal has what was lost.
+#[derive(Serialize, Deserialize)]
+pub struct Change {
+    pub name: String,
+    /// The app after the change, `None` if it was deleted.
+    pub password: Option<Password>,
+    pub at: Timestamp,
+}
+
+/// The journal sits next to the password file.
+pub fn path(password_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.journal", password_file))
+}
+
+/// The changes that turn `base` into what is in the store now.
+pub fn changes(base: &[Password], store: &PasswordStore) -> Vec<Change> {
+    let now = Timestamp::now();
+    let mut names: Vec<String> = Vec::new();
+    for p in base.iter().chain(store.get_all_passwords().iter()) {
+        if !names.iter().any(|name| name.to_lowercase() == p.name.to_lowercase()) {
+            names.push(p.name.clone());
+        }
+    }
+
+    names.into_iter()
+        .filter_map(|name| {
+            let before = base.iter().find(|p| p.name.to_lowercase() == name.to_lowercase());
+            let after = store.get_password(&name);
+            // Passwords have no PartialEq, the digest of their JSON will do.
+            if digest(&before) == digest(&after.as_ref()) {
+                return None;
+            }
+            Some(Change {
+                name: name,
+                password: after,
+                at: now,
+            })
+        })
+        .collect()
+}
+
+/// Adds the changes at the end of the journal, one encrypted change per line.
+pub fn append(path: &PathBuf, store: &PasswordStore, changes: &[Change]) -> Result<(), String> {
+    let mut lines = String::new();
+    for change in changes.iter() {
+        let encoded = SafeString::new(try!(serde_json::to_string(change).map_err(|err| err.to_string())));
+        let sealed = try!(store.seal(encoded.as_bytes()).map_err(|err| format!("{:?}", err)));
+        lines.push_str(&sealed.to_base64(STANDARD));
+        lines.push('\n');
+    }
+
+    let mut file = try!(OpenOptions::new().append(true).create(true).open(path).map_err(|err| err.to_string()));
+    try!(file.write_all(lines.as_bytes()).map_err(|err| err.to_string()));
+    file.sync_all().map_err(|err| err.to_string())
+}
+
+/// The changes in the journal, and how many lines could not be read.
+pub fn read(path: &PathBuf, store: &PasswordStore) -> Result<(Vec<Change>, usize), String> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        Ok(_) => {},
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(err) => return Err(err.to_string()),
+    }
+
+    let mut changes = Vec::new();
+    let mut unreadable = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let change = line.trim().from_base64().ok()
+            .and_then(|sealed| store.unseal(&sealed).ok())
+            .and_then(|data| String::from_utf8(data.deref().to_vec()).ok().map(SafeString::new))
+            .and_then(|encoded| serde_json::from_str::<Change>(encoded.deref()).ok());
+        match change {
+            Some(change) => changes.push(change),
+            // A line cut short by a crash, or written with another master password.
+            None => unreadable += 1,
+        }
+    }
+    Ok((changes, unreadable))
+}
+
+/// Applies the changes to the store, in order.
+pub fn replay(store: &mut PasswordStore, changes: Vec<Change>) {
+    for change in changes.into_iter() {
+        let _ = store.delete_password(&change.name);
+        if let Some(password) = change.password {
+            let _ = store.add_password(password);
+        }
+    }
+}
+
+/// Empties the journal, once the password file is safely saved.
+pub fn clear(path: &PathBuf) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generic passwords in the login keychain of macOS, through the `security` program.
+//! The secrets are hex encoded, and never on its command line, where other users could
+//! see them with ps.
+
+use super::rustc_serialize::hex::{FromHex, ToHex};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use super::session;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::process::Stdio;
+
+/// Stores the secret for the account, replacing what was there. `security -i` reads the
+/// command from stdin, so that the secret is not in its arguments.
+pub fn store(service: &str, account: &str, secret: &[u8]) -> IoResult<()> {
+    let mut child = try!(session::command("security")
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn());
+    let line = SafeString::new(format!("add-generic-password -U -s {} -a {} -w {}\n", service, account, secret.to_hex()));
+    if let Some(mut stdin) = child.stdin.take() {
+        try!(stdin.write_all(line.as_bytes()));
+    }
+    try!(child.wait());
+
+    // `security -i` succeeds even when its command fails.
+    match find(service, account) {
+        Ok(ref found) if &found[..] == secret => Ok(()),
+        _ => Err(IoError::new(IoErrorKind::Other, "the keychain did not take the secret")),
+    }
+}
+
+/// The secret of the account. It fails while the keychain is locked.
+pub fn find(service: &str, account: &str) -> IoResult<SafeVec> {
+    let output = try!(session::command("security")
+        .args(&["find-generic-password", "-s", service, "-a", account, "-w"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output());
+    let stdout = SafeVec::new(output.stdout);
+    if !output.status.success() {
+        return Err(IoError::new(IoErrorKind::NotFound, "the keychain has no such secret"));
+    }
+    let hex = SafeString::new(String::from_utf8_lossy(&stdout).trim().to_string());
+    hex.from_hex().map(SafeVec::new).map_err(|_| IoError::new(IoErrorKind::InvalidData, "the secret in the keychain is not hex"))
+}
+
+/// Deletes the secret of the account, if there is one.
+pub fn delete(service: &str, account: &str) -> IoResult<()> {
+    try!(session::command("security")
+        .args(&["delete-generic-password", "-s", service, "-a", account])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status());
+    Ok(())
+}
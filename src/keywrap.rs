@@ -0,0 +1,176 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Seals secrets with the TPM2 of the machine, through the programs of tpm2-tools. A
+//! sealed secret is a pair of blobs that only this TPM loads, and it only gives the secret
+//! back for the PIN it was sealed with. Wrong PINs count towards the dictionary attack
+//! lockout of the TPM, so a short PIN is enough.
+//!
+//! The blobs are under a primary key that `tpm2_createprimary` makes again from the same
+//! template every time, so nothing is kept in the TPM itself.
+
+use super::rand::{Rng, OsRng};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use super::session;
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+/// The blobs of a sealed secret, which `unseal` needs back.
+pub struct Sealed {
+    pub public: Vec<u8>,
+    pub private: Vec<u8>,
+}
+
+/// A directory for the files tpm2-tools reads and writes, in memory when possible, which
+/// is wiped and deleted when dropped.
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    fn create() -> Result<WorkDir, String> {
+        let dir = match env::var("XDG_RUNTIME_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) if Path::new("/dev/shm").is_dir() => PathBuf::from("/dev/shm"),
+            Err(_) => env::temp_dir(),
+        };
+        let mut rng = try!(OsRng::new().map_err(|err| format!("I could not name a directory for the TPM ({})", err)));
+        let path = dir.join(format!("rooster-tpm-{}-{:08x}", process::id(), rng.next_u32()));
+        try!(WorkDir::builder().create(&path).map_err(|err| format!("I could not create {} ({})", path.display(), err)));
+        Ok(WorkDir { path: path })
+    }
+
+    /// Only the user may read the directory.
+    #[cfg(unix)]
+    fn builder() -> DirBuilder {
+        let mut builder = DirBuilder::new();
+        builder.mode(0o700);
+        builder
+    }
+
+    #[cfg(windows)]
+    fn builder() -> DirBuilder {
+        DirBuilder::new()
+    }
+
+    #[cfg(unix)]
+    fn options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true).mode(0o600);
+        options
+    }
+
+    #[cfg(windows)]
+    fn options() -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        options
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    fn write(&self, name: &str, content: &[u8]) -> Result<(), String> {
+        WorkDir::options().open(self.file(name)).and_then(|mut file| file.write_all(content)).map_err(|err| {
+            format!("I could not write the {} for the TPM ({})", name, err)
+        })
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let mut content = Vec::new();
+        try!(File::open(self.file(name)).and_then(|mut file| file.read_to_end(&mut content)).map_err(|err| {
+            format!("tpm2-tools did not write the {} ({})", name, err)
+        }));
+        Ok(content)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Ok(entries) = fs::read_dir(&self.path) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let len = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                let _ = OpenOptions::new().write(true).open(entry.path()).and_then(|mut file| file.write_all(&vec![0u8; len as usize]));
+            }
+        }
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Runs a program of tpm2-tools with `input` on stdin, and returns what it printed on
+/// stdout. What it prints on stderr is shown as is, since it says best what went wrong.
+fn run(command: &mut Command, program: &str, input: &[u8]) -> Result<SafeVec, String> {
+    let mut child = try!(command.arg("-Q").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit()).spawn().map_err(|err| {
+        format!("I could not run {} ({}), are tpm2-tools installed?", program, err)
+    }));
+    if let Some(mut stdin) = child.stdin.take() {
+        try!(stdin.write_all(input).map_err(|err| format!("I could not talk to {} ({})", program, err)));
+    }
+    let output = try!(child.wait_with_output().map_err(|err| format!("I could not talk to {} ({})", program, err)));
+    let stdout = SafeVec::new(output.stdout);
+    if !output.status.success() {
+        return Err(format!("{} failed ({})", program, output.status));
+    }
+    Ok(stdout)
+}
+
+/// Makes the primary key the secrets are sealed under, in `primary.ctx`.
+fn create_primary(dir: &WorkDir) -> Result<(), String> {
+    let mut command = session::command("tpm2_createprimary");
+    command.arg("-C").arg("o").arg("-G").arg("ecc").arg("-c").arg(dir.file("primary.ctx"));
+    run(&mut command, "tpm2_createprimary", &[]).map(|_| ())
+}
+
+/// Seals a secret with the TPM, for the PIN.
+pub fn seal(secret: &[u8], pin: &SafeString) -> Result<Sealed, String> {
+    let dir = try!(WorkDir::create());
+    try!(create_primary(&dir));
+    // The secret goes on stdin, so the PIN has to go in a file.
+    try!(dir.write("pin", pin.as_bytes()));
+    let mut command = session::command("tpm2_create");
+    command.arg("-C").arg(dir.file("primary.ctx"))
+        .arg("-i").arg("-")
+        .arg("-u").arg(dir.file("seal.pub"))
+        .arg("-r").arg(dir.file("seal.priv"))
+        .arg("-p").arg(format!("file:{}", dir.file("pin").display()));
+    try!(run(&mut command, "tpm2_create", secret));
+    Ok(Sealed {
+        public: try!(dir.read("seal.pub")),
+        private: try!(dir.read("seal.priv")),
+    })
+}
+
+/// The secret `seal` sealed, if the PIN is right.
+pub fn unseal(sealed: &Sealed, pin: &SafeString) -> Result<SafeVec, String> {
+    let dir = try!(WorkDir::create());
+    try!(create_primary(&dir));
+    try!(dir.write("seal.pub", &sealed.public));
+    try!(dir.write("seal.priv", &sealed.private));
+    let mut command = session::command("tpm2_load");
+    command.arg("-C").arg(dir.file("primary.ctx"))
+        .arg("-u").arg(dir.file("seal.pub"))
+        .arg("-r").arg(dir.file("seal.priv"))
+        .arg("-c").arg(dir.file("seal.ctx"));
+    try!(run(&mut command, "tpm2_load", &[]));
+    let mut command = session::command("tpm2_unseal");
+    command.arg("-c").arg(dir.file("seal.ctx")).arg("-p").arg("file:-");
+    run(&mut command, "tpm2_unseal", pin.as_bytes())
+}
@@ -12,17 +12,47 @@ macro_rules! println_stderr(
     )
 );
 
+/// A message from the catalog in `i18n`, in the user's language.
+#[macro_export]
+macro_rules! tr(
+    ($key:expr) => (
+        ::i18n::format($key, &[])
+    );
+    ($key:expr, $($arg:expr),+) => (
+        ::i18n::format($key, &[$(&$arg),+])
+    )
+);
+
 #[macro_export]
 macro_rules! println_err(
     ($($args:tt)*) => (
-        println_stderr!("{}", format!("{}{}\x1b[39m", ::color::Color::Red.to_color_code(), format!($($args)*)))
+        println_stderr!("{}", ::color::paint(::color::Color::Red, ::color::Stream::Stderr, &format!($($args)*)))
     )
 );
 
 #[macro_export]
 macro_rules! println_ok(
     ($($args:tt)*) => (
-        println_stderr!("{}", format!("{}{}\x1b[39m", ::color::Color::Green.to_color_code(), format!($($args)*)))
+        if !::output::is_quiet() {
+            println_stderr!("{}", ::color::paint(::color::Color::Green, ::color::Stream::Stderr, &format!($($args)*)))
+        }
+    )
+);
+
+/// For messages that only decorate the output, like progress, hidden with `--quiet`.
+#[macro_export]
+macro_rules! println_info(
+    ($($args:tt)*) => (
+        if !::output::is_quiet() {
+            println_stderr!($($args)*)
+        }
+    )
+);
+
+#[macro_export]
+macro_rules! println_warn(
+    ($($args:tt)*) => (
+        println_stderr!("{}", ::color::paint(::color::Color::Yellow, ::color::Stream::Stderr, &format!($($args)*)))
     )
 );
 
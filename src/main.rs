@@ -15,10 +15,19 @@
 extern crate libc;
 extern crate getopts;
 extern crate rustc_serialize;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate crypto;
 extern crate rpassword;
 extern crate rand;
 extern crate byteorder;
+extern crate qrcode;
+extern crate regex;
+extern crate flate2;
+#[cfg(windows)]
+extern crate winapi;
 
 use std::fs::File;
 use std::env;
@@ -28,14 +37,19 @@ use std::io::Result as IoResult;
 use std::io::Error as IoError;
 use std::io::ErrorKind as IoErrorKind;
 use std::io::stdin;
-use std::io::Write;
 use std::io::Read;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use getopts::Options;
-use rpassword::read_password;
+use safe_string::read_password_safe;
 use safe_string::SafeString;
 use safe_vec::SafeVec;
+use backend::VaultBackend;
+use commands::CommandError;
 use std::ops::Deref;
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use password::v2::Unlock;
 
 mod macros;
 mod aes;
@@ -43,29 +57,450 @@ mod commands;
 mod ffi;
 mod password;
 mod color;
+mod output;
+mod i18n;
 mod safe_string;
 mod safe_vec;
 mod generate;
+mod crash;
+mod qr;
+mod clipboard;
+mod browser;
+mod domain;
+mod autotype;
+mod shamir;
+mod config;
+mod share;
+mod webdav;
+mod backend;
+mod backup;
+mod journal;
+mod api;
+mod strength;
+mod pwned;
+mod template;
+mod session;
+mod throttle;
+mod protect;
+mod progress;
+mod hooks;
+mod plugin;
+mod gpg;
+mod age;
+mod yubikey;
+mod fido2;
+mod keywrap;
+#[cfg(target_os = "macos")]
+mod keychain;
+mod usage;
+mod base32;
+mod otp;
+mod timestamp;
 
 const ROOSTER_ANALYTICS_OPT_OUT_ENV_VAR: &'static str = "ROOSTER_ANALYTICS_OPT_OUT";
 const ROOSTER_FILE_ENV_VAR: &'static str              = "ROOSTER_FILE";
 const ROOSTER_FILE_DEFAULT: &'static str              = ".passwords.rooster";
 
+enum CommandExec {
+    /// Commands that read or modify the password file.
+    WithStore(fn(&getopts::Matches, &mut password::v2::PasswordStore) -> Result<(), CommandError>),
+    /// Commands that never need the password store, or read the password file themselves.
+    Standalone(fn(&getopts::Matches) -> Result<(), CommandError>),
+}
+
 struct Command {
     name: &'static str,
-    callback_exec: fn(&getopts::Matches, &mut password::v2::PasswordStore) -> Result<(), i32>,
+    description: &'static str,
+    callback_options: fn(&mut getopts::Options),
+    callback_exec: CommandExec,
     callback_help: fn(),
 }
 
+/// What opens the password file, see `read_unlock`. A key file is given in
+/// $ROOSTER_KEYFILE instead.
+static UNLOCK_WITH: AtomicUsize = AtomicUsize::new(UNLOCK_WITH_MASTER_PASSWORD);
+const UNLOCK_WITH_MASTER_PASSWORD: usize = 0;
+const UNLOCK_WITH_GPG: usize = 1;
+const UNLOCK_WITH_RECOVERY_KEY: usize = 2;
+const UNLOCK_WITH_YUBIKEY: usize = 3;
+const UNLOCK_WITH_FIDO2: usize = 4;
+const UNLOCK_WITH_TPM: usize = 5;
+
+/// Environment variable with a key file that opens the password file, see `rooster key`.
+const ROOSTER_KEYFILE_ENV_VAR: &'static str = "ROOSTER_KEYFILE";
+
 static COMMANDS: &'static [Command] = &[
-    Command { name: "get", callback_exec: commands::get::callback_exec, callback_help: commands::get::callback_help },
-    Command { name: "add", callback_exec: commands::add::callback_exec, callback_help: commands::add::callback_help },
-    Command { name: "delete", callback_exec: commands::delete::callback_exec, callback_help: commands::delete::callback_help },
-    Command { name: "generate", callback_exec: commands::generate::callback_exec, callback_help: commands::generate::callback_help },
-    Command { name: "regenerate", callback_exec: commands::regenerate::callback_exec, callback_help: commands::regenerate::callback_help },
-    Command { name: "list", callback_exec: commands::list::callback_exec, callback_help: commands::list::callback_help },
-    Command { name: "export", callback_exec: commands::export::callback_exec, callback_help: commands::export::callback_help },
-    Command { name: "change-master-password", callback_exec: commands::change_master_password::callback_exec, callback_help: commands::change_master_password::callback_help },
+    Command {
+        name: "add",
+        description: "Add a new password",
+        callback_options: commands::add::callback_options,
+        callback_exec: CommandExec::WithStore(commands::add::callback_exec),
+        callback_help: commands::add::callback_help,
+    },
+    Command {
+        name: "delete",
+        description: "Delete a password",
+        callback_options: commands::delete::callback_options,
+        callback_exec: CommandExec::WithStore(commands::delete::callback_exec),
+        callback_help: commands::delete::callback_help,
+    },
+    Command {
+        name: "mv",
+        description: "Move an app to another vault",
+        callback_options: commands::mv::callback_options,
+        callback_exec: CommandExec::WithStore(commands::mv::callback_exec),
+        callback_help: commands::mv::callback_help,
+    },
+    Command {
+        name: "generate",
+        description: "Generate a password",
+        callback_options: commands::generate::callback_options,
+        callback_exec: CommandExec::WithStore(commands::generate::callback_exec),
+        callback_help: commands::generate::callback_help,
+    },
+    Command {
+        name: "protect",
+        description: "Protect an app with a passphrase of its own",
+        callback_options: commands::protect::callback_options,
+        callback_exec: CommandExec::WithStore(commands::protect::callback_exec),
+        callback_help: commands::protect::callback_help,
+    },
+    Command {
+        name: "decoy",
+        description: "Set up a decoy vault with its own master password",
+        callback_options: commands::decoy::callback_options,
+        callback_exec: CommandExec::WithStore(commands::decoy::callback_exec),
+        callback_help: commands::decoy::callback_help,
+    },
+    Command {
+        name: "session",
+        description: "Type the master password once for the commands of this shell",
+        callback_options: commands::session::callback_options,
+        callback_exec: CommandExec::Standalone(commands::session::callback_exec),
+        callback_help: commands::session::callback_help,
+    },
+    Command {
+        name: "unlock",
+        description: "Start a session, like `rooster session start`",
+        callback_options: commands::unlock::callback_options,
+        callback_exec: CommandExec::Standalone(commands::unlock::callback_exec),
+        callback_help: commands::unlock::callback_help,
+    },
+    Command {
+        name: "lock",
+        description: "End the sessions of every shell",
+        callback_options: commands::lock::callback_options,
+        callback_exec: CommandExec::Standalone(commands::lock::callback_exec),
+        callback_help: commands::lock::callback_help,
+    },
+    Command {
+        name: "template",
+        description: "List the templates for adding entries",
+        callback_options: commands::template::callback_options,
+        callback_exec: CommandExec::Standalone(commands::template::callback_exec),
+        callback_help: commands::template::callback_help,
+    },
+    Command {
+        name: "generate-username",
+        description: "Make up a username and a password for an app",
+        callback_options: commands::generate_username::callback_options,
+        callback_exec: CommandExec::WithStore(commands::generate_username::callback_exec),
+        callback_help: commands::generate_username::callback_help,
+    },
+    Command {
+        name: "regenerate",
+        description: "Re-generate a previously existing password",
+        callback_options: commands::regenerate::callback_options,
+        callback_exec: CommandExec::WithStore(commands::regenerate::callback_exec),
+        callback_help: commands::regenerate::callback_help,
+    },
+    Command {
+        name: "get",
+        description: "Retrieve a password",
+        callback_options: commands::get::callback_options,
+        callback_exec: CommandExec::WithStore(commands::get::callback_exec),
+        callback_help: commands::get::callback_help,
+    },
+    Command {
+        name: "grep",
+        description: "Search the apps, except their passwords, for a pattern",
+        callback_options: commands::grep::callback_options,
+        callback_exec: CommandExec::WithStore(commands::grep::callback_exec),
+        callback_help: commands::grep::callback_help,
+    },
+    Command {
+        name: "exists",
+        description: "Exit with 0 if there is an app with this name, 1 if not, for scripts",
+        callback_options: commands::exists::callback_options,
+        callback_exec: CommandExec::WithStore(commands::exists::callback_exec),
+        callback_help: commands::exists::callback_help,
+    },
+    Command {
+        name: "count",
+        description: "Print how many apps there are, for scripts",
+        callback_options: commands::count::callback_options,
+        callback_exec: CommandExec::WithStore(commands::count::callback_exec),
+        callback_help: commands::count::callback_help,
+    },
+    Command {
+        name: "info",
+        description: "Show details about an app, without its password",
+        callback_options: commands::info::callback_options,
+        callback_exec: CommandExec::WithStore(commands::info::callback_exec),
+        callback_help: commands::info::callback_help,
+    },
+    Command {
+        name: "open",
+        description: "Open an app's website and copy its password",
+        callback_options: commands::open::callback_options,
+        callback_exec: CommandExec::WithStore(commands::open::callback_exec),
+        callback_help: commands::open::callback_help,
+    },
+    Command {
+        name: "autotype",
+        description: "Type an app's username and password in another window",
+        callback_options: commands::autotype::callback_options,
+        callback_exec: CommandExec::WithStore(commands::autotype::callback_exec),
+        callback_help: commands::autotype::callback_help,
+    },
+    Command {
+        name: "match-url",
+        description: "Find the apps for a website",
+        callback_options: commands::match_url::callback_options,
+        callback_exec: CommandExec::WithStore(commands::match_url::callback_exec),
+        callback_help: commands::match_url::callback_help,
+    },
+    Command {
+        name: "list",
+        description: "List all apps and usernames",
+        callback_options: commands::list::callback_options,
+        callback_exec: CommandExec::WithStore(commands::list::callback_exec),
+        callback_help: commands::list::callback_help,
+    },
+    Command {
+        name: "sync",
+        description: "Sync your password file with a WebDAV server",
+        callback_options: commands::sync::callback_options,
+        callback_exec: CommandExec::Standalone(commands::sync::callback_exec),
+        callback_help: commands::sync::callback_help,
+    },
+    Command {
+        name: "share",
+        description: "Encrypt an app for someone else",
+        callback_options: commands::share::callback_options,
+        callback_exec: CommandExec::WithStore(commands::share::callback_exec),
+        callback_help: commands::share::callback_help,
+    },
+    Command {
+        name: "receive",
+        description: "Add an app someone shared with you",
+        callback_options: commands::receive::callback_options,
+        callback_exec: CommandExec::WithStore(commands::receive::callback_exec),
+        callback_help: commands::receive::callback_help,
+    },
+    Command {
+        name: "export",
+        description: "List all passwords in unencrypted JSON",
+        callback_options: commands::export::callback_options,
+        callback_exec: CommandExec::WithStore(commands::export::callback_exec),
+        callback_help: commands::export::callback_help,
+    },
+    Command {
+        name: "import",
+        description: "Add the apps of an export, in JSON or encrypted with age",
+        callback_options: commands::import::callback_options,
+        callback_exec: CommandExec::WithStore(commands::import::callback_exec),
+        callback_help: commands::import::callback_help,
+    },
+    Command {
+        name: "change-master-password",
+        description: "Change your master password",
+        callback_options: commands::change_master_password::callback_options,
+        callback_exec: CommandExec::WithStore(commands::change_master_password::callback_exec),
+        callback_help: commands::change_master_password::callback_help,
+    },
+    Command {
+        name: "rekey",
+        description: "Encrypt the password file with a new key, after it may have leaked",
+        callback_options: commands::rekey::callback_options,
+        callback_exec: CommandExec::WithStore(commands::rekey::callback_exec),
+        callback_help: commands::rekey::callback_help,
+    },
+    Command {
+        name: "key",
+        description: "List, add or remove key slots, like a GPG key that opens the password file",
+        callback_options: commands::key::callback_options,
+        callback_exec: CommandExec::WithStore(commands::key::callback_exec),
+        callback_help: commands::key::callback_help,
+    },
+    Command {
+        name: "member",
+        description: "Add or remove members of a team vault, who open it with their GPG key",
+        callback_options: commands::member::callback_options,
+        callback_exec: CommandExec::WithStore(commands::member::callback_exec),
+        callback_help: commands::member::callback_help,
+    },
+    Command {
+        name: "bench",
+        description: "Measure how fast this machine derives keys and encrypts",
+        callback_options: commands::bench::callback_options,
+        callback_exec: CommandExec::Standalone(commands::bench::callback_exec),
+        callback_help: commands::bench::callback_help,
+    },
+    Command {
+        name: "dev",
+        description: "Tools for development, like generating a password file of fake apps",
+        callback_options: commands::dev::callback_options,
+        callback_exec: CommandExec::Standalone(commands::dev::callback_exec),
+        callback_help: commands::dev::callback_help,
+    },
+    Command {
+        name: "edit",
+        description: "Change the username, URL, notes and fields of an app",
+        callback_options: commands::edit::callback_options,
+        callback_exec: CommandExec::WithStore(commands::edit::callback_exec),
+        callback_help: commands::edit::callback_help,
+    },
+    Command {
+        name: "field",
+        description: "Set, get or delete custom fields",
+        callback_options: commands::field::callback_options,
+        callback_exec: CommandExec::WithStore(commands::field::callback_exec),
+        callback_help: commands::field::callback_help,
+    },
+    Command {
+        name: "attach",
+        description: "Attach small files to an app",
+        callback_options: commands::attach::callback_options,
+        callback_exec: CommandExec::WithStore(commands::attach::callback_exec),
+        callback_help: commands::attach::callback_help,
+    },
+    Command {
+        name: "otp",
+        description: "Get a one-time password for two-factor authentication",
+        callback_options: commands::otp::callback_options,
+        callback_exec: CommandExec::WithStore(commands::otp::callback_exec),
+        callback_help: commands::otp::callback_help,
+    },
+    Command {
+        name: "recovery",
+        description: "Store and use one-time recovery codes",
+        callback_options: commands::recovery::callback_options,
+        callback_exec: CommandExec::WithStore(commands::recovery::callback_exec),
+        callback_help: commands::recovery::callback_help,
+    },
+    Command {
+        name: "split",
+        description: "Move the apps with a tag or in a folder to a new password file",
+        callback_options: commands::split::callback_options,
+        callback_exec: CommandExec::WithStore(commands::split::callback_exec),
+        callback_help: commands::split::callback_help,
+    },
+    Command {
+        name: "combine",
+        description: "Put the apps of several password files together in a new one",
+        callback_options: commands::combine::callback_options,
+        callback_exec: CommandExec::Standalone(commands::combine::callback_exec),
+        callback_help: commands::combine::callback_help,
+    },
+    Command {
+        name: "dedupe",
+        description: "Find and merge duplicate apps",
+        callback_options: commands::dedupe::callback_options,
+        callback_exec: CommandExec::WithStore(commands::dedupe::callback_exec),
+        callback_help: commands::dedupe::callback_help,
+    },
+    Command {
+        name: "run",
+        description: "Run a command with passwords in its environment",
+        callback_options: commands::run::callback_options,
+        callback_exec: CommandExec::WithStore(commands::run::callback_exec),
+        callback_help: commands::run::callback_help,
+    },
+    Command {
+        name: "fsck",
+        description: "Check the password file for problems and fix them",
+        callback_options: commands::fsck::callback_options,
+        callback_exec: CommandExec::WithStore(commands::fsck::callback_exec),
+        callback_help: commands::fsck::callback_help,
+    },
+    Command {
+        name: "verify",
+        description: "Check that the password file can be read, without changing it",
+        callback_options: commands::verify::callback_options,
+        callback_exec: CommandExec::Standalone(commands::verify::callback_exec),
+        callback_help: commands::verify::callback_help,
+    },
+    Command {
+        name: "destroy",
+        description: "Overwrite and delete a password file you no longer need",
+        callback_options: commands::destroy::callback_options,
+        callback_exec: CommandExec::Standalone(commands::destroy::callback_exec),
+        callback_help: commands::destroy::callback_help,
+    },
+    Command {
+        name: "batch",
+        description: "Add, update and delete many passwords from a JSON file",
+        callback_options: commands::batch::callback_options,
+        callback_exec: CommandExec::WithStore(commands::batch::callback_exec),
+        callback_help: commands::batch::callback_help,
+    },
+    Command {
+        name: "alias",
+        description: "Give an app short names to look it up with",
+        callback_options: commands::alias::callback_options,
+        callback_exec: CommandExec::WithStore(commands::alias::callback_exec),
+        callback_help: commands::alias::callback_help,
+    },
+    Command {
+        name: "diff",
+        description: "Show the differences with another password file",
+        callback_options: commands::diff::callback_options,
+        callback_exec: CommandExec::WithStore(commands::diff::callback_exec),
+        callback_help: commands::diff::callback_help,
+    },
+    Command {
+        name: "serve",
+        description: "Serve the passwords over a local HTTP API",
+        callback_options: commands::serve::callback_options,
+        callback_exec: CommandExec::WithStore(commands::serve::callback_exec),
+        callback_help: commands::serve::callback_help,
+    },
+    Command {
+        name: "ipc",
+        description: "Speak JSON-RPC on stdin and stdout, for plugins",
+        callback_options: commands::ipc::callback_options,
+        callback_exec: CommandExec::WithStore(commands::ipc::callback_exec),
+        callback_help: commands::ipc::callback_help,
+    },
+    Command {
+        name: "audit",
+        description: "Find weak, reused and similar passwords",
+        callback_options: commands::audit::callback_options,
+        callback_exec: CommandExec::WithStore(commands::audit::callback_exec),
+        callback_help: commands::audit::callback_help,
+    },
+    Command {
+        name: "stats",
+        description: "Show an overview of your passwords",
+        callback_options: commands::stats::callback_options,
+        callback_exec: CommandExec::WithStore(commands::stats::callback_exec),
+        callback_help: commands::stats::callback_help,
+    },
+    Command {
+        name: "recovery-kit",
+        description: "Split your master password in shares for emergencies",
+        callback_options: commands::recovery_kit::callback_options,
+        callback_exec: CommandExec::Standalone(commands::recovery_kit::callback_exec),
+        callback_help: commands::recovery_kit::callback_help,
+    },
+    Command {
+        name: "report",
+        description: "Review the crash report written when Rooster last crashed",
+        callback_options: commands::report::callback_options,
+        callback_exec: CommandExec::Standalone(commands::report::callback_exec),
+        callback_help: commands::report::callback_help,
+    },
 ];
 
 fn command_from_name(name: &str) -> Option<&'static Command> {
@@ -116,81 +551,407 @@ fn get_password_file(filename: &str) -> IoResult<File> {
     }
 }
 
-fn execute_command_from_filename(matches: &getopts::Matches, command: &Command, filename: &str) -> Result<(), i32> {
-    match get_password_file(filename) {
-        Ok(ref mut file) => {
-            if matches.opt_present("help") {
-                (command.callback_help)();
-                return Ok(());
+/// Password files with a URL for a path live on a WebDAV server, the others on disk.
+fn get_backend(filename: &str) -> IoResult<Box<VaultBackend>> {
+    if backend::is_stdio(filename) {
+        return Ok(Box::new(backend::StdioBackend::new()));
+    }
+    if backend::is_ephemeral(filename) {
+        return Ok(Box::new(backend::EphemeralBackend));
+    }
+    if backend::is_remote(filename) {
+        let username = config::load().ok().and_then(|config| config.webdav).and_then(|webdav| webdav.username);
+        let webdav = webdav::WebDav::new(filename.to_string(), username);
+        return Ok(Box::new(backend::WebDavBackend::new(webdav)));
+    }
+    let file = try!(get_password_file(filename));
+    Ok(Box::new(backend::FileBackend::new(PathBuf::from(filename), file)))
+}
+
+/// Saves the store right away, for commands that keep running after a change.
+fn save_password_file(store: &password::v2::PasswordStore) -> Result<(), String> {
+    let path = try!(password_file_path().map_err(|_| "I could not find the password file".to_string()));
+    if backend::is_stdio(&path) {
+        return Err("the password file is read from stdin, so I can only save it when the command ends".to_string());
+    }
+    // The store in memory is all there is of an ephemeral vault.
+    if backend::is_ephemeral(&path) {
+        return Ok(());
+    }
+    let mut backend = try!(get_backend(&path).map_err(|err| err.to_string()));
+    // Reading first tells the WebDAV backend which version of the file we replace.
+    try!(backend.read().map_err(|err| err.to_string()));
+    store.sync(&mut *backend).map_err(|err| format!("{:?}", err))
+}
+
+/// Writes the store to a new password file, never over an existing file.
+fn write_new_password_file(store: &password::v2::PasswordStore, path: &str) -> Result<(), String> {
+    let file = try!(std::fs::OpenOptions::new().read(true).write(true).create_new(true).open(path).map_err(|err| format!("I could not create {} ({})", path, err)));
+    let mut backend = backend::FileBackend::new(PathBuf::from(path), file);
+    store.sync(&mut backend).map_err(|err| format!("I could not write {} ({:?})", path, err))
+}
+
+/// If the password file changed behind our back since we read it, brings those
+/// changes into the store instead of overwriting them.
+fn merge_external_changes(backend: &mut VaultBackend, unlock: &Unlock, base: &[password::v2::Password], store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    match backend.has_changed() {
+        Ok(false) => return Ok(()),
+        Ok(true) => {},
+        Err(err) => {
+            return Err(CommandError::PasswordFile(format!("I could not check whether the password file changed ({})", err)));
+        }
+    }
+
+    let input = match backend.read() {
+        Ok(input) => input,
+        Err(err) => {
+            return Err(CommandError::PasswordFile(format!("I could not read the password file again ({}), your changes were not saved", err)));
+        }
+    };
+    if input.len() == 0 {
+        return Ok(());
+    }
+    let theirs = match password::v2::PasswordStore::open(unlock, SafeVec::new(input)) {
+        Ok(theirs) => theirs,
+        Err(_) => {
+            return Err(CommandError::PasswordFile("I could not open the new password file the way you opened it, your changes were not saved".to_string()));
+        }
+    };
+
+    // Commands that save as they go, like `rooster ipc`, changed it themselves.
+    if journal::changes(theirs.get_all_passwords(), store).is_empty() {
+        return Ok(());
+    }
+
+    println_info!("{}", tr!("merged-external-changes"));
+    for name in store.merge(base, &theirs).iter() {
+        println_warn!("{}", tr!("merge-tie", name));
+    }
+    Ok(())
+}
+
+/// Brings back the changes of a save that did not finish.
+fn replay_journal(journal_path: &PathBuf, store: &mut password::v2::PasswordStore) -> Result<(), CommandError> {
+    let (changes, unreadable) = match journal::read(journal_path, store) {
+        Ok(read) => read,
+        Err(err) => {
+            return Err(CommandError::PasswordFile(format!("I could not read the journal at {} ({})", journal_path.display(), err)));
+        }
+    };
+    if changes.is_empty() && unreadable == 0 {
+        return Ok(());
+    }
+
+    println_stderr!("{}", tr!("journal-replayed"));
+    for change in changes.iter() {
+        println_stderr!("- {} ({})", change.name, if change.password.is_some() { "updated" } else { "deleted" });
+    }
+    if unreadable > 0 {
+        println_err!("{} change(s) in the journal could not be read and were skipped.", unreadable);
+    }
+    journal::replay(store, changes);
+    Ok(())
+}
+
+/// What opens the password file: the master password, unless another key slot was asked
+/// for with a global option.
+fn read_unlock(filename: &str) -> Result<Unlock, String> {
+    // Nobody will ever open an ephemeral vault again, so it gets a random master password.
+    if backend::is_ephemeral(filename) {
+        let alphabet: Vec<char> = (33u8..127).map(|b| b as char).collect();
+        return generate::generate_hard_password(&alphabet, 32, false)
+            .map(|master_password| Unlock::MasterPassword(SafeString::new(master_password)))
+            .map_err(|err| format!("I could not make up a master password for the ephemeral vault ({})", err));
+    }
+    if let Ok(path) = env::var(ROOSTER_KEYFILE_ENV_VAR) {
+        let mut content = Vec::new();
+        return match File::open(&path).and_then(|mut file| file.read_to_end(&mut content)) {
+            Ok(_) => Ok(Unlock::Keyfile(SafeVec::new(content))),
+            Err(err) => Err(format!("I could not read the key file {} ({})", path, err)),
+        };
+    }
+    match UNLOCK_WITH.load(Ordering::Relaxed) {
+        UNLOCK_WITH_GPG => Ok(Unlock::Gpg),
+        UNLOCK_WITH_YUBIKEY => Ok(Unlock::YubiKey),
+        UNLOCK_WITH_FIDO2 => Ok(Unlock::Fido2),
+        UNLOCK_WITH_TPM => {
+            print_stderr!("Type your TPM PIN: ");
+            read_password_safe().map(Unlock::Tpm).map_err(|err| format!("I could not read your TPM PIN ({})", err))
+        },
+        UNLOCK_WITH_RECOVERY_KEY => {
+            print_stderr!("Type your recovery key: ");
+            read_password_safe().map(Unlock::RecoveryKey).map_err(|err| format!("I could not read your recovery key ({})", err))
+        },
+        _ => {
+            if let Some(key) = session::key(filename) {
+                return Ok(Unlock::Key(key));
+            }
+            if !output::is_quiet() {
+                print_stderr!("{}", tr!("master-password-prompt"));
+            }
+            read_password_safe().map(Unlock::MasterPassword).map_err(|err| format!("I could not read your master password ({})", err))
+        },
+    }
+}
+
+/// The global options that open the password file with a key slot, see `read_unlock`.
+fn add_unlock_options(opts: &mut Options) {
+    opts.optflag("", "gpg", "Open the password file with your GPG key instead of the master password");
+    opts.optopt("", "keyfile", "Open the password file with this key file instead of the master password", "FILE");
+    opts.optflag("", "recovery-key", "Open the password file with a recovery key instead of the master password");
+    opts.optflag("", "yubikey", "Open the password file with your YubiKey instead of the master password");
+    opts.optflag("", "fido2", "Open the password file with your FIDO2 authenticator instead of the master password");
+    opts.optflag("", "tpm", "Open the password file with the TPM of this machine and a PIN instead of the master password");
+}
+
+/// The options that may come before or after the command name.
+fn add_global_options(opts: &mut Options) {
+    opts.optflag("h", "help", "Display a help message");
+    opts.optopt("", "vault", "Use the password file of this vault, from the configuration file", "NAME");
+    opts.optopt("", "file", "Use this password file, or - to read it from stdin and write it to stdout", "FILE");
+    opts.optflag("", "ephemeral", "Use an empty vault that only lives in memory and is never saved");
+    opts.optopt("", "as-of", "Open the newest backup of the password file from before this day, read-only", "YYYY-MM-DD");
+    opts.optflag("", "no-color", "Do not color the output, it is only colored in terminals anyway");
+    opts.optflag("q", "quiet", "Only print errors, warnings and what was asked for, no prompts");
+    opts.optflag("", "no-delay", "Do not wait after wrong master passwords");
+    add_unlock_options(opts);
+}
+
+fn set_unlock_options(matches: &getopts::Matches) {
+    if matches.opt_present("gpg") {
+        UNLOCK_WITH.store(UNLOCK_WITH_GPG, Ordering::Relaxed);
+    }
+    if matches.opt_present("recovery-key") {
+        UNLOCK_WITH.store(UNLOCK_WITH_RECOVERY_KEY, Ordering::Relaxed);
+    }
+    if matches.opt_present("yubikey") {
+        UNLOCK_WITH.store(UNLOCK_WITH_YUBIKEY, Ordering::Relaxed);
+    }
+    if matches.opt_present("fido2") {
+        UNLOCK_WITH.store(UNLOCK_WITH_FIDO2, Ordering::Relaxed);
+    }
+    if matches.opt_present("tpm") {
+        UNLOCK_WITH.store(UNLOCK_WITH_TPM, Ordering::Relaxed);
+    }
+    // Like --vault, the key file is passed along in the environment.
+    if let Some(path) = matches.opt_str("keyfile") {
+        env::set_var(ROOSTER_KEYFILE_ENV_VAR, path);
+    }
+}
+
+fn execute_command_from_filename(matches: &getopts::Matches, callback_exec: fn(&getopts::Matches, &mut password::v2::PasswordStore) -> Result<(), CommandError>, filename: &str) -> Result<(), CommandError> {
+    match get_backend(filename) {
+        Ok(backend) => {
+            // A file on disk is read while the master password is typed, so that scrypt
+            // starts right when Enter is pressed. A file on a server is read afterwards,
+            // since curl may ask for the password of the server.
+            let reading = if backend::is_remote(filename) {
+                Err(backend)
             } else {
-                print_stderr!("Type your master password: ");
-                match read_password() {
-                    Ok(master_password) => {
-                        let master_password = SafeString::new(master_password);
-                        let mut input: Vec<u8> = Vec::new();
-                        try!(file.read_to_end(&mut input).map_err(|_| 1));
-
-                        // If the password file is empty (ie new), we'll make a new, empty store.
-                        let mut store = if input.len() == 0 {
-                            try!(password::v2::PasswordStore::new(master_password.clone()).map_err(|_| 1))
-                        } else {
-                            // Try to open the file as is.
-                            match password::v2::PasswordStore::from_input(master_password.clone(), SafeVec::new(input.clone())) {
-                                Ok(store) => store,
-                                Err(_) => {
-                                    // If we can't open the file, we may need to upgrade its format first.
-                                    match password::upgrade(master_password.clone(), SafeVec::new(input.clone())) {
-                                        Ok(store) => store,
-                                        Err(_) => {
-                                            // If we can't upgrade its format either, we show a helpful
-                                            // error message.
-                                            println_err!("I could not upgrade the Rooster file. This could be because:");
-                                            println_err!("- you explicitly told Rooster not to open the file,");
-                                            println_err!("- your version of Rooster is outdated,");
-                                            println_err!("- your Rooster file is corrupted,");
-                                            println_err!("- your master password is wrong.");
-                                            println_err!("Try upgrading to the latest version of Rooster.");
-                                            return Err(1);
-                                        }
+                let mut local = backend;
+                Ok(thread::spawn(move || {
+                    let input = local.read();
+                    (local, input)
+                }))
+            };
+
+            // Stdin holds the password file, so prompts go to the terminal.
+            if backend::is_stdio(filename) {
+                safe_string::prompt_from_terminal();
+            }
+            match read_unlock(filename) {
+                Ok(unlock) => {
+                    let (mut backend, input) = match reading {
+                        Ok(reading) => try!(reading.join().map_err(|_| CommandError::PasswordFile("I could not read the password file".to_string()))),
+                        Err(mut backend) => {
+                            let input = backend.read();
+                            (backend, input)
+                        },
+                    };
+                    let input = match input {
+                        Ok(input) => input,
+                        Err(err) => {
+                            return Err(CommandError::PasswordFile(format!("I could not read the password file ({})", err)));
+                        }
+                    };
+
+                    // Only files on disk have files next to them, and backups are left as
+                    // they are.
+                    let local = !backend::is_remote(filename) && !backend::is_stdio(filename) && !backend::is_ephemeral(filename) && !backup::is_backup(filename);
+                    let throttle_path = if local { Some(throttle::path(filename)) } else { None };
+
+                    // If the password file is empty (ie new), we'll make a new, empty store.
+                    let mut store = if input.len() == 0 {
+                        let master_password = match unlock {
+                            Unlock::MasterPassword(ref master_password) => master_password.clone(),
+                            _ => {
+                                return Err(CommandError::PasswordFile("there is no password file yet, so no key slot to open it with".to_string()));
+                            },
+                        };
+                        try!(password::v2::PasswordStore::new(master_password).map_err(|_| CommandError::PasswordFile("I could not create a new password file".to_string())))
+                    } else {
+                        if let Some(ref throttle_path) = throttle_path {
+                            throttle::wait(throttle_path);
+                        }
+
+                        // Try to open the file as is.
+                        match password::v2::PasswordStore::open(&unlock, SafeVec::new(input.clone())) {
+                            Ok(store) => store,
+                            Err(password::PasswordError::WrongMasterPasswordError) if match unlock { Unlock::Key(_) => true, _ => false } => {
+                                return Err(CommandError::PasswordFile("the key of your session no longer opens the password file. End it with `eval $(rooster session end)` and start a new one".to_string()));
+                            },
+                            Err(password::PasswordError::WrongMasterPasswordError) if match unlock { Unlock::MasterPassword(_) => false, _ => true } => {
+                                return Err(CommandError::PasswordFile("no key slot of the password file opened with it. See them with `rooster key list`".to_string()));
+                            },
+                            Err(password::PasswordError::WrongMasterPasswordError) => {
+                                if let Some(ref throttle_path) = throttle_path {
+                                    if let Err(err) = throttle::failed(throttle_path) {
+                                        println_warn!("I could not count this wrong master password in {} ({}).", throttle_path.display(), err);
+                                    }
+                                }
+                                return Err(CommandError::WrongMasterPassword);
+                            },
+                            // Files from version 3 have a password check, so the master password is right.
+                            Err(password::PasswordError::CorruptionError) if password::v2::version_of(&input).map_or(false, |version| version >= 3 && version <= password::v2::VERSION) => {
+                                return Err(CommandError::PasswordFile("your master password is right, but the password file is damaged. Try `rooster verify`".to_string()));
+                            },
+                            Err(password::PasswordError::HeaderError(message)) => {
+                                return Err(CommandError::PasswordFile(format!("the header of the password file is invalid: {}. Try `rooster verify`", message)));
+                            },
+                            Err(password::PasswordError::Utf8Error(offset)) => {
+                                return Err(CommandError::PasswordFile(format!("your master password is right, but the password file is not valid UTF-8 from byte {}. Try `rooster verify`", offset)));
+                            },
+                            Err(password::PasswordError::JsonError(offset)) => {
+                                return Err(CommandError::PasswordFile(format!("your master password is right, but the password file has invalid JSON at byte {}. Your version of Rooster may be too old for this file", offset)));
+                            },
+                            Err(_) => {
+                                // If we can't open the file, we may need to upgrade its format first.
+                                let upgraded = match unlock {
+                                    Unlock::MasterPassword(ref master_password) => password::upgrade(master_password.clone(), SafeVec::new(input.clone())),
+                                    _ => Err(password::PasswordError::WrongVersionError),
+                                };
+                                match upgraded {
+                                    Ok(store) => store,
+                                    Err(_) => {
+                                        // If we can't upgrade its format either, we show a helpful
+                                        // error message.
+                                        println_err!("I could not upgrade the Rooster file. This could be because:");
+                                        println_err!("- you explicitly told Rooster not to open the file,");
+                                        println_err!("- your version of Rooster is outdated,");
+                                        println_err!("- your Rooster file is corrupted,");
+                                        println_err!("- your master password is wrong.");
+                                        return Err(CommandError::PasswordFile("try upgrading to the latest version of Rooster".to_string()));
                                     }
                                 }
                             }
-                        };
+                        }
+                    };
 
-                        // Execute the command and save the new password list
-                        try!((command.callback_exec)(matches, &mut store));
+                    // The version of the file as it was read, before any upgrade. Files of
+                    // version 1 have no version number.
+                    let file_version = match password::v2::version_of(&input) {
+                        _ if input.len() == 0 => password::v2::VERSION,
+                        Some(version) if version >= 2 && version <= password::v2::VERSION => version,
+                        _ => 1,
+                    };
+                    crash::set_vault_version(file_version);
 
-                        match store.sync(file) {
-                            Ok(()) => { Ok(()) },
-                            Err(err) => {
-                                println_err!("I could not save the password file ({:?}).", err);
-                                return Err(1);
+                    if let Some(ref throttle_path) = throttle_path {
+                        if let Err(err) = throttle::succeeded(throttle_path) {
+                            println_warn!("I could not reset the count of wrong master passwords in {} ({}).", throttle_path.display(), err);
+                        }
+                    }
+
+                    for reason in store.rekey_reasons() {
+                        println_warn!("Your password file should get a new key, {}. Try `rooster rekey`.", reason);
+                    }
+
+                    let journal_path = if local { Some(journal::path(filename)) } else { None };
+                    if let Some(ref journal_path) = journal_path {
+                        try!(replay_journal(journal_path, &mut store));
+                    }
+
+                    // Execute the command and save the new password list
+                    let base = store.get_all_passwords().to_vec();
+                    let fingerprint = store.fingerprint();
+                    try!(callback_exec(matches, &mut store));
+                    store.tick(&base);
+                    try!(merge_external_changes(&mut *backend, &unlock, &base, &mut store));
+
+                    // On stdout, the password file would get in the way of what the
+                    // command printed, so it is only written when it changed.
+                    if backend::is_stdio(filename) && store.fingerprint() == fingerprint && input.len() > 0 {
+                        return Ok(());
+                    }
+                    if backend::is_ephemeral(filename) {
+                        return Ok(());
+                    }
+                    if backup::is_backup(filename) {
+                        if store.fingerprint() != fingerprint {
+                            return Err(CommandError::PasswordFile("--as-of opens a backup read-only, so I did not save the changes".to_string()));
+                        }
+                        return Ok(());
+                    }
+
+                    // Log the changes first, so they survive a crash while saving.
+                    if let Some(ref journal_path) = journal_path {
+                        let changes = journal::changes(&base, &store);
+                        if !changes.is_empty() {
+                            if let Err(err) = journal::append(journal_path, &store, &changes) {
+                                return Err(CommandError::PasswordFile(format!("I could not write to the journal at {} ({}), so I did not save the password file", journal_path.display(), err)));
                             }
                         }
-                    },
-                    Err(err) => {
-                        println_err!("I could not read your master password ({})", err);
-                        return Err(1);
                     }
+
+                    match store.sync(&mut *backend) {
+                        Ok(()) => {
+                            if let Some(ref journal_path) = journal_path {
+                                if let Err(err) = journal::clear(journal_path) {
+                                    println_warn!("I saved the password file but could not delete the journal at {} ({}).", journal_path.display(), err);
+                                }
+                            }
+                            Ok(())
+                        },
+                        Err(err) => {
+                            return Err(CommandError::PasswordFile(format!("I could not save the password file ({:?})", err)));
+                        }
+                    }
+                },
+                Err(err) => {
+                    return Err(CommandError::Failed(err));
                 }
             }
         },
         Err(err) => {
-            println_err!("I could not open the password file \"{}\" :( ({})", filename, err);
-            return Err(1);
+            return Err(CommandError::PasswordFile(format!("I could not open the password file \"{}\" ({})", filename, err)));
         }
     }
 }
 
-fn get_password_file_path(rooster_file: Result<String, VarError>, home_dir: Option<PathBuf>) -> Result<String, i32> {
+/// The newest backup of the password file from before the day, for `--as-of`.
+fn backup_as_of(password_file: &str, day: &str) -> Result<String, String> {
+    if backend::is_remote(password_file) || backend::is_stdio(password_file) || backend::is_ephemeral(password_file) {
+        return Err("only password files on disk have backups".to_string());
+    }
+    let before = try!(timestamp::parse_date(day).ok_or(format!("\"{}\" is not a day like 2024-03-01", day)));
+    match backup::newest_before(Path::new(password_file), before) {
+        Some((at, path)) => {
+            println_info!("Opening the backup of {} from {}, read-only.", password_file, at.to_local_string());
+            path.into_os_string().into_string().map_err(|_| "the path of the backup is not valid UTF-8".to_string())
+        },
+        None => Err(format!("there is no backup of {} from before {}", password_file, day)),
+    }
+}
+
+fn get_password_file_path(rooster_file: Result<String, VarError>, data_dir: Option<PathBuf>) -> Result<String, i32> {
     match rooster_file {
         Ok(filename) => {
             Ok(filename)
         },
         Err(VarError::NotPresent) => {
-            let mut filename = match home_dir {
-                Some(home) => {
-                    try!(home.as_os_str().to_os_string().into_string().map_err(|_| 1))
+            let mut filename = match data_dir {
+                Some(dir) => {
+                    try!(dir.as_os_str().to_os_string().into_string().map_err(|_| 1))
                 }
                 None => {
                     return Err(1);
@@ -206,51 +967,158 @@ fn get_password_file_path(rooster_file: Result<String, VarError>, home_dir: Opti
     }
 }
 
-fn usage(password_file: &str) {
+/// Where the password file is, from the environment.
+fn password_file_path() -> Result<String, i32> {
+    if let Err(VarError::NotPresent) = env::var(ROOSTER_FILE_ENV_VAR) {
+        if let Ok(config) = config::load() {
+            if let Some(path) = config.default_vault.as_ref().and_then(|name| config.vault_path(name)) {
+                return Ok(path);
+            }
+        }
+    }
+    get_password_file_path(env::var(ROOSTER_FILE_ENV_VAR), config::data_dir())
+}
+
+/// Uses the password file of a vault from the configuration file, for this command and
+/// the programs it runs. Setting $ROOSTER_FILE is how we pass it along.
+fn select_vault(name: &str) -> Result<(), i32> {
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            println_err!("Woops, I could not load the configuration file: {}.", err);
+            return Err(1);
+        }
+    };
+    match config.vault_path(name) {
+        Some(path) => {
+            env::set_var(ROOSTER_FILE_ENV_VAR, path);
+            Ok(())
+        },
+        None => {
+            println_err!("Woops, there is no vault named \"{}\" in the configuration file.", name);
+            Err(1)
+        }
+    }
+}
+
+fn usage(opts: &Options, password_file: &str) {
     println!("Welcome to Rooster, the simple password manager for geeks :-)");
     println!("");
     println!("The current password file is: {}", password_file);
-    println!("You may override this path in the $ROOSTER_FILE environment variable.");
+    println!("You may override this path in the $ROOSTER_FILE environment variable, or pick");
+    println!("one of the vaults of your configuration file with --vault. An https:// URL works");
+    println!("too, for a password file on a WebDAV server. With --file -, the password file is");
+    println!("read from stdin, and written to stdout if the command changes it, as in:");
+    println!("    gpg -d vault.gpg | rooster --file - add github | gpg -e -r me > new.gpg");
+    println!("With --ephemeral, the vault starts empty and is never saved, for demos and tests.");
+    println!("Each save of a password file on disk keeps what it held in <file>.backups, the");
+    println!("last 10 saves or \"backups\" in the configuration file. --as-of opens the newest");
+    println!("backup from before a day, read-only, as in:");
+    println!("    rooster --as-of 2024-03-01 get github");
     println!("");
     println!("Usage:");
     println!("    rooster -h");
     println!("    rooster [options] <command> [<args> ...]");
     println!("    rooster <command> -h");
-    println!("");
-    println!("Options:");
-    println!("    -h, --help        Display a help message");
-    println!("    -a, --alnum       Only use alpha numeric (a-z, A-Z, 0-9) in generated passwords");
-    println!("    -l, --length      Set a custom length for the generated password, default is 32");
+    print!("{}", opts.usage(""));
     println!("");
     println!("Commands:");
-    println!("    add                        Add a new password");
-    println!("    delete                     Delete a password");
-    println!("    generate                   Generate a password");
-    println!("    regenerate                 Re-generate a previously existing password");
-    println!("    get                        Retrieve a password");
-    println!("    list                       List all apps and usernames");
-    println!("    export                     List all passwords in unencrypted JSON");
-    println!("    change-master-password     Change your master password");
+    for c in COMMANDS.iter() {
+        println!("    {:27}{}", c.name, c.description);
+    }
+    println!("");
+    println!("Other commands run the program rooster-<command> from your PATH, if there is one.");
+    println!("Plugins get the password file in $ROOSTER_FILE, and can use `$ROOSTER_BIN ipc`.");
+    println!("");
+    println!("Exit codes:");
+    println!("    0    success");
+    println!("    {}    any other failure", output::EXIT_FAILURE);
+    println!("    {}    unknown command or option, or a missing argument", output::EXIT_USAGE);
+    println!("    {}    wrong master password", output::EXIT_WRONG_MASTER_PASSWORD);
+    println!("    {}    the password file could not be opened, read or saved", output::EXIT_PASSWORD_FILE);
+    println!("    {}    there is no app with this name", output::EXIT_NO_SUCH_APP);
+}
+
+/// Explains why the command failed, and returns the exit code for it.
+fn report_error(command: &Command, err: &CommandError) -> i32 {
+    match *err {
+        CommandError::MissingArgument(what) => {
+            println_err!("{}", tr!("missing-argument", what));
+            println_err!("    rooster {} -h", command.name);
+            output::EXIT_USAGE
+        },
+        CommandError::NoSuchApp(ref name) => {
+            println_err!("{}", tr!("no-such-app", name));
+            println_err!("{}", tr!("no-such-app-hint"));
+            println_err!("    rooster list");
+            output::EXIT_NO_SUCH_APP
+        },
+        CommandError::WrongMasterPassword => {
+            println_err!("{}", tr!("wrong-master-password"));
+            output::EXIT_WRONG_MASTER_PASSWORD
+        },
+        CommandError::PasswordFile(ref reason) => {
+            println_err!("{}", tr!("failed", reason));
+            output::EXIT_PASSWORD_FILE
+        },
+        CommandError::Failed(ref reason) => {
+            println_err!("{}", tr!("failed", reason));
+            output::EXIT_FAILURE
+        },
+        CommandError::Reported => output::EXIT_FAILURE,
+        CommandError::Exit(code) => code,
+    }
+}
+
+fn command_usage(command: &Command, opts: &Options) {
+    (command.callback_help)();
+    print!("{}", opts.usage(""));
 }
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    // Global options come before the command name. Everything after the command name
+    // is left untouched here and parsed with the command's own options further down.
     let mut opts = Options::new();
-    opts.optflag("h", "help", "Display a help message");
-    opts.optflag("a", "alnum", "Only use alpha numeric (a-z, A-Z, 0-9) in generated passwords");
-    opts.optopt("l", "length", "Set a custom length for the generated password", "32");
+    opts.parsing_style(getopts::ParsingStyle::StopAtFirstFree);
+    add_global_options(&mut opts);
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m },
         Err(err) => {
             println_err!("{}", err);
-             std::process::exit(1);
+            std::process::exit(output::EXIT_USAGE);
         }
     };
+    output::set_quiet(matches.opt_present("quiet"));
+    if matches.opt_present("no-delay") {
+        throttle::disable();
+    }
+    set_unlock_options(&matches);
+
+    match config::load() {
+        Ok(config) => color::init(matches.opt_present("no-color"), config.color),
+        Err(err) => {
+            println_err!("Woops, I could not load the configuration file: {}.", err);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(vault) = matches.opt_str("vault") {
+        if let Err(code) = select_vault(vault.deref()) {
+            std::process::exit(code);
+        }
+    }
+    if let Some(path) = matches.opt_str("file") {
+        env::set_var(ROOSTER_FILE_ENV_VAR, path);
+    }
+    if matches.opt_present("ephemeral") {
+        env::set_var(ROOSTER_FILE_ENV_VAR, backend::EPHEMERAL_PATH);
+    }
 
     // Fetch the Rooster file path now, so we can display it in help messages.
-    let password_file_path = match get_password_file_path(env::var(ROOSTER_FILE_ENV_VAR), env::home_dir()) {
+    let password_file_path = match password_file_path() {
         Ok(path) => path,
         Err(_) => {
             println_err!("Woops, I could not determine where your password file is.");
@@ -274,8 +1142,8 @@ fn main() {
     };
 
     // Global help was requested.
-    if matches.opt_present("h") && matches.free.is_empty() {
-        usage(password_file_path.deref());
+    if matches.opt_present("help") && matches.free.is_empty() {
+        usage(&opts, password_file_path.deref());
         std::process::exit(0);
     }
 
@@ -283,24 +1151,109 @@ fn main() {
     let command_name = match matches.free.get(0) {
         Some(command_name) => command_name,
         None => {
-            usage(password_file_path.deref());
-            std::process::exit(1);
+            usage(&opts, password_file_path.deref());
+            std::process::exit(output::EXIT_USAGE);
+        }
+    };
+
+    let command = match command_from_name(command_name.as_ref()) {
+        Some(command) => command,
+        None => {
+            if let Some(plugin) = plugin::find(command_name.as_ref()) {
+                std::process::exit(plugin::run(&plugin, &matches.free[1..], password_file_path.deref()));
+            }
+            println_err!("{}", tr!("unknown-command", command_name));
+            std::process::exit(output::EXIT_USAGE);
+        }
+    };
+
+    let mut command_opts = Options::new();
+    add_global_options(&mut command_opts);
+    (command.callback_options)(&mut command_opts);
+
+    let command_matches = match command_opts.parse(&matches.free[1..]) {
+        Ok(m) => { m },
+        Err(err) => {
+            println_err!("{}", err);
+            println_err!("{}", tr!("for-help"));
+            println_err!("    rooster {} -h", command.name);
+            std::process::exit(output::EXIT_USAGE);
         }
     };
 
-    match command_from_name(command_name.as_ref()) {
-        Some(command) => {
-            match execute_command_from_filename(&matches, command, password_file_path.deref()) {
-                Err(i) => std::process::exit(i),
-                _ => std::process::exit(0)
+    if command_matches.opt_present("no-color") {
+        color::disable();
+    }
+    if command_matches.opt_present("quiet") {
+        output::set_quiet(true);
+    }
+    if command_matches.opt_present("no-delay") {
+        throttle::disable();
+    }
+    set_unlock_options(&command_matches);
+
+    // Command help was requested, either as `rooster -h <command>` or `rooster <command> -h`.
+    if matches.opt_present("help") || command_matches.opt_present("help") {
+        command_usage(command, &command_opts);
+        std::process::exit(0);
+    }
+
+    // The vault may also be chosen after the command name, as in `rooster list --vault work`.
+    let password_file_path = match command_matches.opt_str("vault") {
+        Some(vault) => {
+            if let Err(code) = select_vault(vault.deref()) {
+                std::process::exit(code);
+            }
+            match get_password_file_path(env::var(ROOSTER_FILE_ENV_VAR), config::data_dir()) {
+                Ok(path) => path,
+                Err(code) => std::process::exit(code),
+            }
+        },
+        None => password_file_path,
+    };
+    let password_file_path = match command_matches.opt_str("file") {
+        Some(path) => {
+            env::set_var(ROOSTER_FILE_ENV_VAR, &path);
+            path
+        },
+        None => password_file_path,
+    };
+    let password_file_path = if command_matches.opt_present("ephemeral") {
+        env::set_var(ROOSTER_FILE_ENV_VAR, backend::EPHEMERAL_PATH);
+        backend::EPHEMERAL_PATH.to_string()
+    } else {
+        password_file_path
+    };
+
+    let password_file_path = match command_matches.opt_str("as-of").or(matches.opt_str("as-of")) {
+        Some(day) => {
+            match backup_as_of(password_file_path.deref(), day.deref()) {
+                Ok(path) => {
+                    env::set_var(ROOSTER_FILE_ENV_VAR, &path);
+                    path
+                },
+                Err(err) => {
+                    println_err!("Woops, {}.", err);
+                    std::process::exit(1);
+                }
             }
         },
-        None => {
-            println_err!(
-                "Woops, the command `{}` does not exist. Try the --help option for more info.",
-                command_name
-            );
-            std::process::exit(1);
+        None => password_file_path,
+    };
+
+    crash::install_panic_hook(command.name);
+
+    let result = match command.callback_exec {
+        CommandExec::WithStore(callback_exec) => {
+            execute_command_from_filename(&command_matches, callback_exec, password_file_path.deref())
+        },
+        CommandExec::Standalone(callback_exec) => {
+            callback_exec(&command_matches)
         }
+    };
+
+    match result {
+        Err(err) => std::process::exit(report_error(command, &err)),
+        _ => std::process::exit(0)
     }
 }
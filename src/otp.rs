@@ -0,0 +1,173 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::crypto::digest::Digest;
+use super::crypto::hmac::Hmac;
+use super::crypto::mac::Mac;
+use super::crypto::sha1::Sha1;
+use super::crypto::sha2::{Sha256, Sha512};
+use super::base32;
+use super::session;
+use super::timestamp::Timestamp;
+use super::password::v2::OtpSecret;
+use super::safe_string::SafeString;
+use std::ops::Deref;
+
+pub const DEFAULT_ALGORITHM: &'static str = "SHA1";
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+pub static ALGORITHMS: &'static [&'static str] = &["SHA1", "SHA256", "SHA512"];
+
+fn hmac<D: Digest>(digest: D, key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut hmac = Hmac::new(digest, key);
+    hmac.input(message);
+    hmac.result().code().to_vec()
+}
+
+/// Computes the HOTP code (RFC 4226) for the counter.
+pub fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: &str) -> Result<String, String> {
+    let mut message = [0u8; 8];
+    for i in 0..8 {
+        message[i] = (counter >> (56 - 8 * i)) as u8;
+    }
+
+    let hash = match algorithm {
+        "SHA1" => hmac(Sha1::new(), key, &message),
+        "SHA256" => hmac(Sha256::new(), key, &message),
+        "SHA512" => hmac(Sha512::new(), key, &message),
+        _ => { return Err(format!("unknown algorithm \"{}\"", algorithm)); }
+    };
+
+    // Dynamic truncation: the last 4 bits choose where to read 31 bits from.
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+    let code = binary as u64 % 10u64.pow(digits);
+
+    Ok(format!("{:0width$}", code, width = digits as usize))
+}
+
+/// Computes the current code for the secret, along with the counter it used.
+pub fn generate(otp: &OtpSecret) -> Result<(String, u64), String> {
+    let key = try!(base32::decode(&otp.secret).ok_or("the secret is not valid base32".to_string()));
+    let counter = match otp.counter {
+        Some(counter) => counter,
+        None => Timestamp::now().as_secs() / otp.period,
+    };
+    let code = try!(hotp(&key, counter, otp.digits, &otp.algorithm));
+    Ok((code, counter))
+}
+
+/// Seconds before the current TOTP code expires.
+pub fn seconds_left(period: u64) -> u64 {
+    period - Timestamp::now().as_secs() % period
+}
+
+/// Decodes %XX escapes in URI components.
+pub fn percent_decode(input: &str) -> Result<String, String> {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = String::from_utf8_lossy(&bytes[i + 1..i + 3]);
+            match u8::from_str_radix(&hex, 16) {
+                Ok(byte) => output.push(byte),
+                Err(_) => { return Err(format!("invalid escape \"%{}\"", hex)); }
+            }
+            i += 3;
+        } else {
+            output.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+            i += 1;
+        }
+    }
+    String::from_utf8(output).map_err(|_| "the URI is not valid UTF-8".to_string())
+}
+
+/// Parses the `otpauth://` URI that sites put in their two-factor QR codes, as described
+/// in https://github.com/google/google-authenticator/wiki/Key-Uri-Format.
+pub fn parse_uri(uri: &str) -> Result<OtpSecret, String> {
+    let uri = uri.trim();
+    if !uri.starts_with("otpauth://") {
+        return Err("the URI does not start with otpauth://".to_string());
+    }
+    let rest = &uri["otpauth://".len()..];
+    let (path, query) = match rest.find('?') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => { return Err("the URI has no parameters".to_string()); }
+    };
+    let is_hotp = match path.split('/').next() {
+        Some("totp") => false,
+        Some("hotp") => true,
+        _ => { return Err("the URI is neither for TOTP nor HOTP".to_string()); }
+    };
+
+    let mut secret = None;
+    let mut algorithm = DEFAULT_ALGORITHM.to_string();
+    let mut digits = DEFAULT_DIGITS;
+    let mut period = DEFAULT_PERIOD;
+    let mut counter = 0;
+    for parameter in query.split('&') {
+        let mut parts = parameter.splitn(2, '=');
+        let key = parts.next().unwrap_or("");
+        let value = try!(percent_decode(parts.next().unwrap_or("")));
+        match key {
+            "secret" => { secret = Some(SafeString::new(value)); },
+            "algorithm" => { algorithm = value.to_uppercase(); },
+            "digits" => { digits = try!(value.parse().map_err(|_| format!("invalid digits \"{}\"", value))); },
+            "period" => { period = try!(value.parse().map_err(|_| format!("invalid period \"{}\"", value))); },
+            "counter" => { counter = try!(value.parse().map_err(|_| format!("invalid counter \"{}\"", value))); },
+            // The issuer and unknown parameters only matter to other apps.
+            _ => {}
+        }
+    }
+
+    let secret = try!(secret.ok_or("the URI has no secret".to_string()));
+    if base32::decode(secret.deref()).is_none() {
+        return Err("the secret is not valid base32".to_string());
+    }
+    if !ALGORITHMS.contains(&algorithm.deref()) {
+        return Err(format!("unknown algorithm \"{}\"", algorithm));
+    }
+    if period == 0 {
+        return Err("the period is 0".to_string());
+    }
+
+    Ok(OtpSecret {
+        secret: secret,
+        algorithm: algorithm,
+        digits: digits,
+        period: period,
+        counter: if is_hotp { Some(counter) } else { None },
+    })
+}
+
+/// Reads the `otpauth://` URI in a QR code image, with zbarimg from the ZBar project.
+pub fn read_qr_image(path: &str) -> Result<SafeString, String> {
+    let output = try!(
+        session::command("zbarimg")
+            .args(&["--quiet", "--raw", path])
+            .output()
+            .map_err(|err| format!("could not run zbarimg, is it installed? ({})", err))
+    );
+    if !output.status.success() {
+        return Err(format!("zbarimg found no QR code in {}", path));
+    }
+    let text = SafeString::new(try!(String::from_utf8(output.stdout).map_err(|_| "the QR code is not text".to_string())));
+    Ok(SafeString::new(text.trim().to_string()))
+}
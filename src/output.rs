@@ -0,0 +1,36 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit codes, which scripts may rely on. Other failures exit with `EXIT_FAILURE`.
+pub const EXIT_FAILURE: i32 = 1;
+/// Unknown command or option.
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_WRONG_MASTER_PASSWORD: i32 = 3;
+/// The password file could not be opened, read, decrypted or saved.
+pub const EXIT_PASSWORD_FILE: i32 = 4;
+pub const EXIT_NO_SUCH_APP: i32 = 5;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// With `--quiet`, only errors, warnings and the requested output are printed, no
+/// prompts or confirmations.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -0,0 +1,391 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::safe_string::SafeString;
+use super::super::serde::{Serialize, Serializer, Deserialize, Deserializer};
+use super::super::serde::de::Error as DeError;
+use super::super::serde::ser::{Error as SerError, SerializeMap};
+use super::super::serde_json::{self, Value};
+use std::ops::Deref;
+
+/// Kind-specific data of a card entry. The card number is the entry's password.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CardDetails {
+    pub holder: String,
+    pub expiry: String,
+    pub cvv: SafeString,
+}
+
+/// Kind-specific data of a wifi entry. The wifi passphrase is the entry's password.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WifiDetails {
+    pub ssid: String,
+    pub security: String,
+}
+
+/// Kind-specific data of an API token entry. The token is the entry's password.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenDetails {
+    pub expires: String,
+}
+
+/// Kind-specific data of an SSH key entry. The private key is the entry's password.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SshKeyDetails {
+    pub public_key: String,
+    pub passphrase: SafeString,
+}
+
+/// Kind-specific data of an identity entry. The ID or passport number is the entry's password.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdentityDetails {
+    pub full_name: String,
+    pub birth_date: String,
+    pub address: String,
+    pub phone: String,
+    pub email: String,
+}
+
+/// The kind-specific part of a password entry, encrypted along with the rest of the entry.
+///
+/// Entries without a payload are logins, which is what every entry was before kinds
+/// existed.
+#[derive(Clone, Debug)]
+pub enum Payload {
+    Note,
+    Card(CardDetails),
+    Wifi(WifiDetails),
+    Token(TokenDetails),
+    SshKey(SshKeyDetails),
+    Identity(IdentityDetails),
+    /// A kind added by a newer version of Rooster, kept as it was read so that saving
+    /// with this version does not lose it.
+    Unknown(Value),
+}
+
+/// Payloads are written the way rustc-serialize wrote enums, which is how the
+/// password files written before serde have them: `"Note"` for notes, and
+/// `{"variant": "Card", "fields": [{...}]}` for the others.
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (variant, details) = match *self {
+            Payload::Note => return serializer.serialize_str("Note"),
+            Payload::Unknown(ref value) => return value.serialize(serializer),
+            Payload::Card(ref details) => ("Card", serde_json::to_value(details)),
+            Payload::Wifi(ref details) => ("Wifi", serde_json::to_value(details)),
+            Payload::Token(ref details) => ("Token", serde_json::to_value(details)),
+            Payload::SshKey(ref details) => ("SshKey", serde_json::to_value(details)),
+            Payload::Identity(ref details) => ("Identity", serde_json::to_value(details)),
+        };
+        let details = try!(details.map_err(SerError::custom));
+        let mut map = try!(serializer.serialize_map(Some(2)));
+        try!(map.serialize_entry("variant", variant));
+        try!(map.serialize_entry("fields", &vec![details]));
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Payload, D::Error> {
+        let value = try!(Value::deserialize(deserializer));
+        if value.as_str() == Some("Note") {
+            return Ok(Payload::Note);
+        }
+        let variant = match value.get("variant").and_then(|variant| variant.as_str()) {
+            Some(variant) => variant.to_string(),
+            None => { return Ok(Payload::Unknown(value)); },
+        };
+        let details = match value.get("fields").and_then(|fields| fields.get(0)).cloned() {
+            Some(details) => details,
+            None => { return Ok(Payload::Unknown(value)); },
+        };
+        let payload = match variant.deref() {
+            "Card" => serde_json::from_value(details).map(Payload::Card),
+            "Wifi" => serde_json::from_value(details).map(Payload::Wifi),
+            "Token" => serde_json::from_value(details).map(Payload::Token),
+            "SshKey" => serde_json::from_value(details).map(Payload::SshKey),
+            "Identity" => serde_json::from_value(details).map(Payload::Identity),
+            _ => { return Ok(Payload::Unknown(value)); },
+        };
+        payload.map_err(D::Error::custom)
+    }
+}
+
+/// A kind-specific field, other than the entry's password.
+pub struct Field {
+    pub name: &'static str,
+    /// Secret fields are typed without echo and never shown in listings.
+    pub secret: bool,
+}
+
+/// Describes how commands should display and edit entries of a given kind.
+pub struct Kind {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// What the entry's password is called for this kind, e.g. "card number".
+    pub secret_name: &'static str,
+    /// Whether the password spans several lines, like notes and private keys.
+    pub secret_multiline: bool,
+    /// Whether Rooster may generate the password for this kind.
+    pub generated: bool,
+    /// Whether the password is a sensitive number, only shown masked unless asked for.
+    pub masked: bool,
+    /// Fields stored in the payload, in the order `build` expects their values.
+    pub fields: &'static [Field],
+    build: fn(Vec<SafeString>) -> Option<Payload>,
+}
+
+impl Kind {
+    pub fn from_name(name: &str) -> Option<&'static Kind> {
+        for k in KINDS.iter() {
+            if k.name == name {
+                return Some(*k);
+            }
+        }
+        None
+    }
+
+    /// Builds the payload from the values of `self.fields`, in the same order.
+    pub fn build_payload(&self, values: Vec<SafeString>) -> Option<Payload> {
+        assert_eq!(values.len(), self.fields.len());
+        (self.build)(values)
+    }
+}
+
+pub static LOGIN: Kind = Kind {
+    name: "login",
+    description: "A username and a password",
+    secret_name: "password",
+    secret_multiline: false,
+    generated: true,
+    masked: false,
+    fields: &[],
+    build: build_login,
+};
+
+pub static NOTE: Kind = Kind {
+    name: "note",
+    description: "A secure note",
+    secret_name: "note",
+    secret_multiline: true,
+    generated: false,
+    masked: false,
+    fields: &[],
+    build: build_note,
+};
+
+pub static CARD: Kind = Kind {
+    name: "card",
+    description: "A credit or debit card",
+    secret_name: "card number",
+    secret_multiline: false,
+    generated: false,
+    masked: true,
+    fields: &[
+        Field { name: "holder", secret: false },
+        Field { name: "expiry", secret: false },
+        Field { name: "cvv", secret: true },
+    ],
+    build: build_card,
+};
+
+pub static WIFI: Kind = Kind {
+    name: "wifi",
+    description: "A wifi network",
+    secret_name: "passphrase",
+    secret_multiline: false,
+    generated: true,
+    masked: false,
+    fields: &[
+        Field { name: "ssid", secret: false },
+        Field { name: "security", secret: false },
+    ],
+    build: build_wifi,
+};
+
+pub static TOKEN: Kind = Kind {
+    name: "token",
+    description: "An API token",
+    secret_name: "token",
+    secret_multiline: false,
+    generated: false,
+    masked: false,
+    fields: &[
+        Field { name: "expires", secret: false },
+    ],
+    build: build_token,
+};
+
+pub static SSH_KEY: Kind = Kind {
+    name: "ssh-key",
+    description: "An SSH key pair",
+    secret_name: "private key",
+    secret_multiline: true,
+    generated: false,
+    masked: false,
+    fields: &[
+        Field { name: "public key", secret: false },
+        Field { name: "passphrase", secret: true },
+    ],
+    build: build_ssh_key,
+};
+
+pub static IDENTITY: Kind = Kind {
+    name: "identity",
+    description: "An identity document and personal details",
+    secret_name: "ID number",
+    secret_multiline: false,
+    generated: false,
+    masked: true,
+    fields: &[
+        Field { name: "full name", secret: false },
+        Field { name: "birth date", secret: false },
+        Field { name: "address", secret: false },
+        Field { name: "phone", secret: false },
+        Field { name: "email", secret: false },
+    ],
+    build: build_identity,
+};
+
+/// The kind of entries with a kind from a newer version of Rooster, see
+/// `Payload::Unknown`. It is not in `KINDS`, so no entry is added with it.
+pub static UNKNOWN: Kind = Kind {
+    name: "unknown",
+    description: "A kind from a newer version of Rooster",
+    secret_name: "password",
+    secret_multiline: false,
+    generated: false,
+    masked: false,
+    fields: &[],
+    build: build_unknown,
+};
+
+/// All kinds of entries, the first one being the default.
+pub static KINDS: &'static [&'static Kind] = &[&LOGIN, &NOTE, &CARD, &WIFI, &TOKEN, &SSH_KEY, &IDENTITY];
+
+/// Hides a sensitive value but its last 4 characters, like on receipts: "**** 4242".
+/// Short values are hidden entirely.
+pub fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() <= 4 {
+        return chars.iter().map(|_| '*').collect();
+    }
+    let last: String = chars[chars.len() - 4..].iter().cloned().collect();
+    format!("**** {}", last)
+}
+
+fn build_login(_values: Vec<SafeString>) -> Option<Payload> {
+    None
+}
+
+fn build_unknown(_values: Vec<SafeString>) -> Option<Payload> {
+    None
+}
+
+fn build_note(_values: Vec<SafeString>) -> Option<Payload> {
+    Some(Payload::Note)
+}
+
+fn build_card(values: Vec<SafeString>) -> Option<Payload> {
+    let mut values = values.into_iter();
+    let holder = values.next().unwrap().deref().to_string();
+    let expiry = values.next().unwrap().deref().to_string();
+    Some(Payload::Card(CardDetails {
+        holder: holder,
+        expiry: expiry,
+        cvv: values.next().unwrap(),
+    }))
+}
+
+fn build_wifi(values: Vec<SafeString>) -> Option<Payload> {
+    let mut values = values.into_iter();
+    let ssid = values.next().unwrap().deref().to_string();
+    let security = values.next().unwrap().deref().to_string();
+    Some(Payload::Wifi(WifiDetails {
+        ssid: ssid,
+        security: security,
+    }))
+}
+
+fn build_token(values: Vec<SafeString>) -> Option<Payload> {
+    let mut values = values.into_iter();
+    Some(Payload::Token(TokenDetails {
+        expires: values.next().unwrap().deref().to_string(),
+    }))
+}
+
+fn build_ssh_key(values: Vec<SafeString>) -> Option<Payload> {
+    let mut values = values.into_iter();
+    let public_key = values.next().unwrap().deref().to_string();
+    Some(Payload::SshKey(SshKeyDetails {
+        public_key: public_key,
+        passphrase: values.next().unwrap(),
+    }))
+}
+
+fn build_identity(values: Vec<SafeString>) -> Option<Payload> {
+    let mut values = values.into_iter().map(|v| v.deref().to_string());
+    Some(Payload::Identity(IdentityDetails {
+        full_name: values.next().unwrap(),
+        birth_date: values.next().unwrap(),
+        address: values.next().unwrap(),
+        phone: values.next().unwrap(),
+        email: values.next().unwrap(),
+    }))
+}
+
+impl Payload {
+    pub fn kind(&self) -> &'static Kind {
+        match *self {
+            Payload::Note => &NOTE,
+            Payload::Card(_) => &CARD,
+            Payload::Wifi(_) => &WIFI,
+            Payload::Token(_) => &TOKEN,
+            Payload::SshKey(_) => &SSH_KEY,
+            Payload::Identity(_) => &IDENTITY,
+            Payload::Unknown(_) => &UNKNOWN,
+        }
+    }
+
+    /// Values of the kind's fields, in the order of `Kind::fields`.
+    pub fn values(&self) -> Vec<SafeString> {
+        match *self {
+            Payload::Note | Payload::Unknown(_) => vec![],
+            Payload::Card(ref d) => vec![
+                SafeString::new(d.holder.clone()),
+                SafeString::new(d.expiry.clone()),
+                d.cvv.clone(),
+            ],
+            Payload::Wifi(ref d) => vec![
+                SafeString::new(d.ssid.clone()),
+                SafeString::new(d.security.clone()),
+            ],
+            Payload::Token(ref d) => vec![
+                SafeString::new(d.expires.clone()),
+            ],
+            Payload::SshKey(ref d) => vec![
+                SafeString::new(d.public_key.clone()),
+                d.passphrase.clone(),
+            ],
+            Payload::Identity(ref d) => vec![
+                SafeString::new(d.full_name.clone()),
+                SafeString::new(d.birth_date.clone()),
+                SafeString::new(d.address.clone()),
+                SafeString::new(d.phone.clone()),
+                SafeString::new(d.email.clone()),
+            ],
+        }
+    }
+}
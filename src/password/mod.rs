@@ -14,11 +14,15 @@
 
 pub mod v1;
 pub mod v2;
+pub mod kind;
+pub mod provider;
 
 use std::io::{Error as IoError, stdin, Write};
 use std::ops::Deref;
+use std::collections::BTreeMap;
 use super::safe_string::SafeString;
 use super::safe_vec::SafeVec;
+use super::timestamp::Timestamp;
 
 #[derive(Debug)]
 pub enum PasswordError {
@@ -28,8 +32,15 @@ pub enum PasswordError {
     AppExistsError,
     Io(IoError),
     WrongVersionError,
+    /// The header of the file is truncated or says something absurd.
+    HeaderError(String),
     InvalidJsonError,
+    /// The decrypted data is not UTF-8 from this byte offset.
+    Utf8Error(usize),
+    /// The decrypted data is not the JSON of a vault at this byte offset.
+    JsonError(usize),
     CorruptionError,
+    WrongMasterPasswordError,
 }
 
 fn upgrade_v1_v2(v1_passwords: &[v1::Password], v2_store: &mut v2::PasswordStore) -> Result<(), PasswordError> {
@@ -38,8 +49,25 @@ fn upgrade_v1_v2(v1_passwords: &[v1::Password], v2_store: &mut v2::PasswordStore
 			name: p.name.clone(),
 		    username: p.username.clone(),
 		    password: p.password.clone(),
-		    created_at: p.created_at,
-		    updated_at: p.updated_at,
+		    created_at: Timestamp::from_secs(p.created_at as u64),
+		    updated_at: Timestamp::from_secs(p.updated_at as u64),
+		    payload: None,
+		    notes: None,
+		    generation_policy: None,
+		    history: None,
+		    last_used_at: None,
+		    use_count: None,
+		    recovery_codes: None,
+		    otp: None,
+		    custom_fields: None,
+		    attachments: None,
+		    url: None,
+		    autotype: None,
+		    aliases: None,
+		    tags: None,
+		    protected: None,
+		    clocks: None,
+		    extra: BTreeMap::new(),
 		};
 		try!(v2_store.add_password(v2_password));
 	}
@@ -54,8 +82,8 @@ pub fn upgrade(master_password: SafeString, input: SafeVec) -> Result<v2::Passwo
 
     println_stderr!("Your Rooster file has version 1. You need to upgrade to version 2.");
     println_stderr!("");
-    println_stderr!("WARNING: If in doubt, it could mean you've been hacked. Only");
-    println_stderr!("proceed if you recently upgraded your Rooster installation.");
+    println_warn!("WARNING: If in doubt, it could mean you've been hacked. Only");
+    println_warn!("proceed if you recently upgraded your Rooster installation.");
     println_stderr!("");
     println_stderr!("Upgrade to version 2? [y/n]");
     loop {
@@ -0,0 +1,199 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::super::crypto::{scrypt, hmac, sha2, chacha20};
+use super::super::crypto::mac::{Mac, MacResult};
+use super::super::crypto::symmetriccipher::SynchronousStreamCipher;
+use super::super::aes;
+use super::super::safe_vec::SafeVec;
+use super::PasswordError;
+use std::ops::DerefMut;
+
+/// Parameters of the key derivation function, as stored in the file header.
+#[derive(Clone, Copy)]
+pub struct KdfParams {
+    pub log2_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+/// The cryptographic primitives a vault is built on.
+///
+/// From version 5, each vault says in its header which provider it uses (see
+/// `provider`), and the lengths of its IV, password check and signature come from
+/// that provider. A new cipher or a new crypto library then does not mean a new
+/// copy of the format code. Vaults are encrypted, then the encrypted data is
+/// signed along with the header. A provider with an AEAD cipher can do both in
+/// `encrypt` and have `mac` sign the header alone.
+///
+/// Providers are shared with the thread that derives keys, see `progress::run`.
+pub trait CryptoProvider: Sync {
+    /// Length of the keys `derive_key` returns.
+    fn key_len(&self) -> usize;
+
+    /// Length of the IVs (or nonces) `encrypt` expects.
+    fn iv_len(&self) -> usize;
+
+    /// Length of the signatures `mac` returns.
+    fn mac_len(&self) -> usize;
+
+    /// Length of what `encrypt` returns for data of this length.
+    fn encrypted_len(&self, len: usize) -> usize;
+
+    /// Derives an encryption key from the master password.
+    fn derive_key(&self, params: KdfParams, master_password: &str, salt: &[u8]) -> SafeVec;
+
+    fn encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, PasswordError>;
+
+    fn decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<SafeVec, PasswordError>;
+
+    fn mac(&self, key: &[u8], data: &[u8]) -> Vec<u8>;
+
+    /// Checks a signature from `mac`, in constant time.
+    fn verify_mac(&self, key: &[u8], data: &[u8], signature: &[u8]) -> bool;
+}
+
+/// scrypt, AES-256-CBC and HMAC-SHA512 from rust-crypto. Vaults older than
+/// version 5, which do not say which provider they use, all use this one.
+pub struct RustCrypto;
+
+impl CryptoProvider for RustCrypto {
+    fn key_len(&self) -> usize {
+        32
+    }
+
+    fn iv_len(&self) -> usize {
+        16
+    }
+
+    fn mac_len(&self) -> usize {
+        64
+    }
+
+    fn encrypted_len(&self, len: usize) -> usize {
+        // PKCS padding always adds between 1 byte and a whole block.
+        (len / 16 + 1) * 16
+    }
+
+    fn derive_key(&self, params: KdfParams, master_password: &str, salt: &[u8]) -> SafeVec {
+        let mut output = SafeVec::new(vec![0u8; self.key_len()]);
+        let scrypt_params = scrypt::ScryptParams::new(params.log2_n, params.r, params.p);
+        scrypt::scrypt(master_password.as_bytes(), salt, &scrypt_params, output.deref_mut());
+        output
+    }
+
+    fn encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, PasswordError> {
+        aes::encrypt(data, key, iv).map_err(|_| PasswordError::EncryptionError)
+    }
+
+    fn decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<SafeVec, PasswordError> {
+        aes::decrypt(data, key, iv).map_err(|_| PasswordError::DecryptionError)
+    }
+
+    fn mac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut hmac = hmac::Hmac::new(sha2::Sha512::new(), key);
+        hmac.input(data);
+        hmac.result().code().to_vec()
+    }
+
+    fn verify_mac(&self, key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+        let mut hmac = hmac::Hmac::new(sha2::Sha512::new(), key);
+        hmac.input(data);
+        // MacResult compares in constant time.
+        hmac.result() == MacResult::new(signature)
+    }
+}
+
+/// scrypt, XChaCha20 and HMAC-SHA512 from rust-crypto. The nonce of XChaCha20 is long
+/// enough to be picked at random, and the data needs no padding.
+pub struct XChaCha20;
+
+impl CryptoProvider for XChaCha20 {
+    fn key_len(&self) -> usize {
+        32
+    }
+
+    fn iv_len(&self) -> usize {
+        24
+    }
+
+    fn mac_len(&self) -> usize {
+        64
+    }
+
+    fn encrypted_len(&self, len: usize) -> usize {
+        len
+    }
+
+    fn derive_key(&self, params: KdfParams, master_password: &str, salt: &[u8]) -> SafeVec {
+        RustCrypto.derive_key(params, master_password, salt)
+    }
+
+    fn encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, PasswordError> {
+        // rust-crypto panics on a key or a nonce of the wrong length.
+        if key.len() != self.key_len() || iv.len() != self.iv_len() {
+            return Err(PasswordError::EncryptionError);
+        }
+        let mut output = vec![0u8; data.len()];
+        chacha20::ChaCha20::new_xchacha20(key, iv).process(data, &mut output);
+        Ok(output)
+    }
+
+    fn decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<SafeVec, PasswordError> {
+        if key.len() != self.key_len() || iv.len() != self.iv_len() {
+            return Err(PasswordError::DecryptionError);
+        }
+        let mut output = SafeVec::new(vec![0u8; data.len()]);
+        chacha20::ChaCha20::new_xchacha20(key, iv).process(data, output.deref_mut());
+        Ok(output)
+    }
+
+    fn mac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        RustCrypto.mac(key, data)
+    }
+
+    fn verify_mac(&self, key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+        RustCrypto.verify_mac(key, data, signature)
+    }
+}
+
+/// Identifier of `RustCrypto`, for file headers.
+pub const RUST_CRYPTO: u8 = 0;
+
+/// Identifier of `XChaCha20`, for file headers.
+pub const XCHACHA20: u8 = 1;
+
+/// The names of the providers for users, like in `rooster rekey --cipher`, after their
+/// cipher.
+pub const NAMES: &'static [(&'static str, u8)] = &[("aes", RUST_CRYPTO), ("xchacha20", XCHACHA20)];
+
+/// The provider a file header refers to, if this version of Rooster has it.
+pub fn provider(id: u8) -> Option<&'static CryptoProvider> {
+    match id {
+        RUST_CRYPTO => Some(&RustCrypto),
+        XCHACHA20 => Some(&XChaCha20),
+        _ => None,
+    }
+}
+
+/// The identifier of a provider from its name in `NAMES`.
+pub fn by_name(name: &str) -> Option<u8> {
+    NAMES.iter().find(|&&(known, _)| known == name).map(|&(_, id)| id)
+}
+
+/// The name of a provider in `NAMES`, or its identifier for unknown ones.
+pub fn name(id: u8) -> String {
+    NAMES.iter().find(|&&(_, known)| known == id).map(|&(name, _)| name.to_string()).unwrap_or(id.to_string())
+}
@@ -12,21 +12,37 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::super::ffi;
-use super::super::crypto::{scrypt, hmac, sha2};
-use super::super::crypto::digest::Digest;
-use super::super::crypto::mac::{Mac, MacResult};
-use super::super::aes;
+use super::super::timestamp::Timestamp;
 use super::super::rand::{Rng, OsRng};
 use super::super::byteorder::{ReadBytesExt, WriteBytesExt, BigEndian, Error as ByteorderError};
-use super::super::rustc_serialize::json;
+use super::super::serde::Serialize;
+use super::super::serde_json::{self, Value};
 use super::super::safe_string::SafeString;
 use super::super::safe_vec::SafeVec;
+use super::super::backend::VaultBackend;
+use super::super::progress;
+use super::super::gpg;
+use super::super::yubikey;
+use super::super::fido2;
+use super::super::keywrap;
+use super::super::base32;
+use super::super::crypto::digest::Digest;
+use super::super::crypto::sha2::Sha256;
+use super::super::crypto::util::fixed_time_eq;
+use super::super::crypto::chacha20::ChaCha20;
+use super::super::crypto::symmetriccipher::SynchronousStreamCipher;
+use super::super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::super::flate2::Compression;
+use super::super::flate2::read::DeflateDecoder;
+use super::super::flate2::write::DeflateEncoder;
 use super::PasswordError;
-use std::io::{Seek, SeekFrom, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind, Read, Write, Cursor};
-use std::fs::File;
-use std::ops::DerefMut;
+use super::provider::{self, CryptoProvider, KdfParams};
+use super::kind::{Kind, Payload, LOGIN};
+use std::io::{Result as IoResult, Error as IoError, ErrorKind as IoErrorKind, Read, Write, Cursor};
 use std::ops::Deref;
+use std::collections::BTreeMap;
+use std::cmp::max;
+use std::str;
 
 /// The schema of the JSON content in the password file.
 ///
@@ -38,43 +54,355 @@ use std::ops::Deref;
 ///         "password": "xxxxxxxx",
 ///         "created_at": 23145436,
 ///         "updated_at": 23145546,
-///     ]
+///         "payload": null,
+///         "notes": null,
+///         "generation_policy": null,
+///         "history": null,
+///         "last_used_at": null,
+///         "use_count": null,
+///         "recovery_codes": null,
+///         "otp": null,
+///         "custom_fields": null,
+///         "attachments": null,
+///         "url": null,
+///         "autotype": null,
+///         "aliases": null,
+///         "tags": null,
+///         "protected": null,
+///         "clocks": null,
+///     ],
+///     "sharing_key": null,
+///     "other_vaults": [{
+///         "slot": 1,
+///         "decoy_key": null,
+///         "decoy_master_password": null,
+///         "wrap_key": "...",
+///         "mirrored": "..."
+///     }],
+///     "metadata": {
+///         "created_at": 23145436,
+///         "rekeyed_at": null,
+///         "kdf_history": [{"at": 23145436, "log2_n": 12, "r": 8, "p": 1}]
+///     }
 /// }
 /// ```
 
-/// The IV is 128 bits long.
-///
-/// This should be enough for it to be unique. Also, the password storage file
-/// is normally unique, so if an attacker gets it, having access to the IV
-/// doesn't help much, since there is no other data that uses the same IV to
-/// compare with.
-const IV_LEN: usize = 16;
-
-/// Length of the key derived ffrom the user password.
-const KEY_LEN: usize = 32;
-
 /// Length of the salt passed to the key derivation function.
 const SALT_LEN: usize = 32;
 
-/// Length of the HMAC signature
-const SIGNATURE_LEN: usize = 64;
+const PASSWORD_CHECK_MESSAGE: &'static [u8] = b"rooster master password check";
+
+const WRAP_KEY_MESSAGE: &'static [u8] = b"rooster vault wrapping";
+
+/// Length of everything before the encrypted data in the smallest header, see
+/// `PasswordStore`: a version 2 file, with the 128 bits IV and the 512 bits signature
+/// of `provider::RustCrypto`.
+pub const HEADER_LEN: usize = 4 + 1 + 4 + 4 + SALT_LEN + 16 + 64;
 
 /// Scrypt parameters
 const SCRYPT_PARAM_LOG2_N: u8 = 12;
 const SCRYPT_PARAM_R: u32 = 8;
 const SCRYPT_PARAM_P: u32 = 1;
 
+/// After this many days with the same key, Rooster suggests `rooster rekey`.
+pub const REKEY_AFTER_DAYS: u64 = 365;
+
 /// The version of this lib
-const VERSION: u32 = 2;
+pub const VERSION: u32 = 5;
+
+/// Files of this version have no flags before each vault, which are then never
+/// compressed, but are read all the same.
+const VERSION_WITHOUT_FLAGS: u32 = 4;
+
+/// Files of this version hold a single vault, but are read all the same.
+const VERSION_SINGLE_VAULT: u32 = 3;
+
+/// Files of this version have no password check, but are read all the same.
+const VERSION_WITHOUT_PASSWORD_CHECK: u32 = 2;
+
+/// Vaults in a file: the one opened with the master password, and one that is either a
+/// decoy vault or random bytes. Every save gives the other vault the clear part and the
+/// size of the vault that was opened, and new random bytes, see `PasswordStore::write_to`,
+/// so that without its master password nobody can tell which is which.
+const SLOTS: usize = 2;
+
+/// Set in the flags of a vault when its JSON is compressed with deflate before it is
+/// encrypted. Vaults carried over from a version 4 file don't have it.
+const FLAG_DEFLATE: u8 = 1;
+
+/// Set in the flags of a vault when its data is padded before it is encrypted, see `pad`.
+const FLAG_PADDED: u8 = 2;
+
+/// Set in the flags of a vault when its data is encrypted with a random key, which its
+/// key slots wrap. See `KeySlot`.
+const FLAG_KEY_SLOTS: u8 = 4;
+
+/// Set in the flags of a vault when everything after its key slots is wrapped: a random
+/// nonce, then, encrypted with XChaCha20 and a key of its own, the length of the rest of
+/// the vault, the rest of the vault and random bytes up to the size of the other vaults.
+/// The other vaults of the file have that key, so that whichever is saved gives the
+/// others new random bytes and a new size without being able to read them.
+const FLAG_WRAPPED: u8 = 8;
+
+/// The flags of the vaults this version writes.
+const FLAGS: u8 = FLAG_DEFLATE | FLAG_PADDED | FLAG_WRAPPED;
+
+/// Length of the nonce of a wrapped vault, see `FLAG_WRAPPED`.
+const WRAP_NONCE_LEN: usize = 24;
+
+/// Length of the random key of a vault with key slots.
+const KEY_LEN: usize = 32;
+
+/// The key slot with the key wrapped by the master password, with the scrypt parameters
+/// and the salt of the vault.
+pub const KEY_SLOT_MASTER_PASSWORD: u8 = 0;
+
+/// A key slot with the key encrypted to a GPG key, see `gpg::encrypt`.
+pub const KEY_SLOT_GPG: u8 = 1;
+
+/// A key slot with the key wrapped by the hash of a file, see `secret_key`.
+pub const KEY_SLOT_KEYFILE: u8 = 2;
+
+/// A key slot with the key wrapped by a random recovery key, printed once for the user to
+/// keep somewhere safe.
+pub const KEY_SLOT_RECOVERY_KEY: u8 = 3;
+
+/// A key slot with a random challenge, then the key wrapped by the response of a YubiKey
+/// to it.
+pub const KEY_SLOT_YUBIKEY: u8 = 4;
+
+/// A key slot with the key encrypted to the GPG key of a member of a team vault. It opens
+/// like `KEY_SLOT_GPG`, but `rooster member remove` gives the vault a new key.
+pub const KEY_SLOT_MEMBER: u8 = 5;
+
+/// The master password key slot of a vault that also needs a YubiKey: a random
+/// challenge, then the key wrapped by both the key derived from the master password and
+/// the response of the YubiKey to the challenge. It takes the place of
+/// `KEY_SLOT_MASTER_PASSWORD`, so the master password alone no longer opens the vault.
+pub const KEY_SLOT_MASTER_PASSWORD_YUBIKEY: u8 = 6;
+
+/// A key slot with a credential of a FIDO2 authenticator and a random salt, then the key
+/// wrapped by the hmac-secret of the credential for the salt, see `fido2_prefix`.
+pub const KEY_SLOT_FIDO2: u8 = 7;
+
+/// A key slot with a random secret sealed by the TPM of a machine for a PIN, then the key
+/// wrapped by the secret, see `tpm_prefix`.
+pub const KEY_SLOT_TPM: u8 = 8;
+
+/// Length of a recovery key, 48 characters in base32.
+const RECOVERY_KEY_LEN: usize = 30;
+
+/// Length of the challenge sent to a YubiKey.
+const YUBIKEY_CHALLENGE_LEN: usize = 32;
+
+/// The smallest size the data of a vault is padded to.
+const PADDED_LEN_MIN: usize = 1024;
+
+/// Limits on what a file may say, so that a damaged or crafted file can't make Rooster
+/// run out of memory or time. They are far above what Rooster writes.
+const MAX_FILE_LEN: usize = 256 * 1024 * 1024;
+const MAX_DATA_LEN: usize = 256 * 1024 * 1024;
+const MAX_SLOTS: usize = 16;
+const MAX_KEY_SLOTS: usize = 16;
+const MAX_SCRYPT_PARAM_LOG2_N: u8 = 22;
+const MAX_SCRYPT_PARAM_R: u32 = 64;
+const MAX_SCRYPT_PARAM_P: u32 = 16;
+
+/// The crypto provider of new vaults.
+const CRYPTO_PROVIDER: u8 = provider::RUST_CRYPTO;
+
+/// The crypto provider a vault header names, if this version of Rooster has it.
+fn crypto(id: u8) -> Result<&'static CryptoProvider, PasswordError> {
+    provider::provider(id).ok_or_else(|| {
+        PasswordError::HeaderError(format!("the vault uses crypto provider {}, which this version of Rooster does not have", id))
+    })
+}
 
 // Create a random IV.
-fn generate_random_iv() -> IoResult<[u8; IV_LEN]> {
-    let mut bytes: [u8; IV_LEN] = [0; IV_LEN];
+fn generate_random_iv(crypto: &CryptoProvider) -> IoResult<Vec<u8>> {
+    let mut bytes = vec![0u8; crypto.iv_len()];
+    let mut rng = try!(OsRng::new());
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+/// Random bytes, for salts, nonces and fillers.
+fn random_bytes(len: usize) -> IoResult<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
     let mut rng = try!(OsRng::new());
     rng.fill_bytes(&mut bytes);
     Ok(bytes)
 }
 
+/// The other vaults of a new file, random bytes drawn on the first save, see
+/// `PasswordStore::generate_filler`.
+fn fillers(slot: usize) -> Vec<OtherVault> {
+    (0..SLOTS).filter(|&i| i != slot).map(|i| OtherVault {
+        slot: i,
+        decoy_key: None,
+        decoy_master_password: None,
+        wrap_key: None,
+        mirrored: None,
+    }).collect()
+}
+
+/// The key a vault is wrapped with, see `FLAG_WRAPPED`. Other vaults of the file have
+/// it, so it must not give them the key of the vault.
+fn wrap_key(key: &[u8]) -> SafeVec {
+    let mut hasher = Sha256::new();
+    hasher.input(WRAP_KEY_MESSAGE);
+    hasher.input(key);
+    let mut wrap_key = SafeVec::new(vec![0u8; hasher.output_bytes()]);
+    hasher.result(&mut wrap_key);
+    wrap_key
+}
+
+/// Encrypts or decrypts what a vault wraps, see `FLAG_WRAPPED`.
+fn wrap_stream(wrap_key: &[u8], nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0u8; data.len()];
+    ChaCha20::new_xchacha20(wrap_key, nonce).process(data, &mut output);
+    output
+}
+
+/// A wrapped vault: the clear part, a new nonce, then what it wraps.
+fn wrap(header: &[u8], wrap_key: &[u8], plain: &[u8]) -> Result<Vec<u8>, PasswordError> {
+    let nonce = try!(random_bytes(WRAP_NONCE_LEN).map_err(|io_err| PasswordError::Io(io_err)));
+    let mut vault = header.to_vec();
+    vault.extend_from_slice(&nonce);
+    vault.extend(wrap_stream(wrap_key, &nonce, plain));
+    Ok(vault)
+}
+
+/// A wrapped vault as it was before `wrap`: its clear part and the rest of the vault,
+/// without the random bytes after it. `None` if the wrap key is not the right one, as far
+/// as the length tells.
+fn unwrap_vault(vault: &[u8], clear_len: usize, wrap_key: &[u8]) -> Option<Vec<u8>> {
+    if vault.len() < clear_len + WRAP_NONCE_LEN + 4 {
+        return None;
+    }
+    let (clear, rest) = vault.split_at(clear_len);
+    let (nonce, wrapped) = rest.split_at(WRAP_NONCE_LEN);
+    let plain = wrap_stream(wrap_key, nonce, wrapped);
+    let len = match Cursor::new(&plain).read_u32::<BigEndian>() {
+        Ok(len) => len as usize,
+        Err(_) => { return None; },
+    };
+    if len > plain.len() - 4 {
+        return None;
+    }
+    let mut unwrapped = clear.to_vec();
+    unwrapped.extend_from_slice(&plain[4..4 + len]);
+    Some(unwrapped)
+}
+
+/// A vault this one can't read, with a new nonce and more random bytes at the end to
+/// make it `len` bytes long, see `FLAG_WRAPPED`. What it wraps does not change. Vaults
+/// that are not wrapped are returned as they are.
+fn rewrap(bytes: &[u8], wrap_key: &[u8], len: usize) -> Result<Vec<u8>, PasswordError> {
+    if bytes.len() < 2 || bytes[0] & FLAG_WRAPPED == 0 {
+        return Ok(bytes.to_vec());
+    }
+    let slot = Slot {
+        flags: bytes[0],
+        provider: bytes[1],
+        vault: &bytes[2..],
+    };
+    let header_len = match read_vault(VERSION, &slot) {
+        Ok(header) => 2 + clear_len(&header),
+        Err(_) => { return Ok(bytes.to_vec()); },
+    };
+    if bytes.len() < header_len + WRAP_NONCE_LEN {
+        return Ok(bytes.to_vec());
+    }
+    let (header, rest) = bytes.split_at(header_len);
+    let (nonce, wrapped) = rest.split_at(WRAP_NONCE_LEN);
+    let mut plain = wrap_stream(wrap_key, nonce, wrapped);
+    plain.extend(try!(random_bytes(len.saturating_sub(bytes.len())).map_err(|io_err| PasswordError::Io(io_err))));
+    wrap(header, wrap_key, &plain)
+}
+
+/// Length of the clear part of a vault, up to its IV or, when it is wrapped, its nonce.
+fn clear_len(header: &VaultHeader) -> usize {
+    1 + 4 + 4 + SALT_LEN + write_key_slots(&header.key_slots).len()
+}
+
+/// A vault ready to be wrapped, see `PasswordStore::seal_vault`.
+struct SealedVault {
+    /// The flags, the crypto provider and the clear part of the vault.
+    header: Vec<u8>,
+    /// The IV, the password check, the signature and the encrypted data.
+    body: Vec<u8>,
+    wrap_key: SafeVec,
+}
+
+impl SealedVault {
+    /// Length of the vault once wrapped, without random bytes at the end.
+    fn len(&self) -> usize {
+        self.header.len() + WRAP_NONCE_LEN + 4 + self.body.len()
+    }
+
+    /// The vault wrapped to `len` bytes, see `FLAG_WRAPPED`.
+    fn wrap(&self, len: usize) -> Result<Vec<u8>, PasswordError> {
+        let mut plain = Vec::new();
+        try!(plain.write_u32::<BigEndian>(self.body.len() as u32).map_err(|_| PasswordError::EncryptionError));
+        plain.extend_from_slice(&self.body);
+        plain.extend(try!(random_bytes(len.saturating_sub(self.len())).map_err(|io_err| PasswordError::Io(io_err))));
+        wrap(&self.header, self.wrap_key.deref(), &plain)
+    }
+}
+
+/// Another vault of the file, ready to be written back, see `PasswordStore::other_vault`.
+enum OtherContent {
+    /// As it was read.
+    Unchanged(Vec<u8>),
+    /// Rewrapped with this key, see `rewrap`.
+    Rewrapped(Vec<u8>, SafeVec),
+    /// Random bytes that look like the vault that was opened, wrapped with this key.
+    Filler(SafeVec),
+    Decoy(SealedVault),
+}
+
+impl OtherContent {
+    /// The fewest bytes it can be written with.
+    fn len(&self) -> usize {
+        match *self {
+            OtherContent::Unchanged(ref bytes) | OtherContent::Rewrapped(ref bytes, _) => bytes.len(),
+            OtherContent::Filler(_) => 0,
+            OtherContent::Decoy(ref vault) => vault.len(),
+        }
+    }
+}
+
+fn write_kdf_params<W: Write>(file: &mut W, scrypt_log2_n: u8, scrypt_r: u32, scrypt_p: u32) -> Result<(), PasswordError> {
+    try!(match file.write_u8(scrypt_log2_n) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            match err {
+                ByteorderError::Io(err) => Err(PasswordError::Io(err)),
+                _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+            }
+        }
+    });
+    try!(match file.write_u32::<BigEndian>(scrypt_r) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            match err {
+                ByteorderError::Io(err) => Err(PasswordError::Io(err)),
+                _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+            }
+        }
+    });
+    match file.write_u32::<BigEndian>(scrypt_p) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            match err {
+                ByteorderError::Io(err) => Err(PasswordError::Io(err)),
+                _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+            }
+        }
+    }
+}
+
 // Create a random salt.
 fn generate_random_salt() -> IoResult<[u8; SALT_LEN]> {
     let mut bytes: [u8; SALT_LEN] = [0; SALT_LEN];
@@ -83,22 +411,134 @@ fn generate_random_salt() -> IoResult<[u8; SALT_LEN]> {
     Ok(bytes)
 }
 
-/// Derives a 256 bits encryption key from the password.
-fn generate_encryption_key(scrypt_params: scrypt::ScryptParams, master_password: &str, salt: [u8; SALT_LEN]) -> SafeVec {
-    let mut vec = Vec::<u8>::with_capacity(KEY_LEN);
-    for _ in 0..KEY_LEN {
-        vec.push(0u8);
+fn compress(data: &[u8]) -> Result<SafeVec, PasswordError> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    try!(encoder.write_all(data).map_err(|io_err| PasswordError::Io(io_err)));
+    encoder.finish().map(SafeVec::new).map_err(|io_err| PasswordError::Io(io_err))
+}
+
+fn decompress(data: &[u8]) -> Result<SafeVec, PasswordError> {
+    let mut decompressed = Vec::new();
+    let mut decoder = DeflateDecoder::new(data).take(MAX_DATA_LEN as u64 + 1);
+    try!(decoder.read_to_end(&mut decompressed).map_err(|_| PasswordError::DecryptionError));
+    let decompressed = SafeVec::new(decompressed);
+    if decompressed.len() > MAX_DATA_LEN {
+        return Err(PasswordError::DecryptionError);
+    }
+    Ok(decompressed)
+}
+
+/// Pads the data with zeros to the next power of two, from 1 KiB, so that the size of
+/// the file only changes when the vault doubles in size. The padded data starts with
+/// the length of the data.
+fn pad(data: &[u8]) -> Result<SafeVec, PasswordError> {
+    let padded_len = max(PADDED_LEN_MIN, (4 + data.len()).next_power_of_two());
+    let mut padded = Vec::with_capacity(padded_len);
+    try!(padded.write_u32::<BigEndian>(data.len() as u32).map_err(|_| PasswordError::EncryptionError));
+    padded.extend_from_slice(data);
+    padded.resize(padded_len, 0);
+    Ok(SafeVec::new(padded))
+}
+
+fn unpad(padded: &[u8]) -> Result<&[u8], PasswordError> {
+    let len = try!(Cursor::new(padded).read_u32::<BigEndian>().map_err(|_| PasswordError::DecryptionError)) as usize;
+    if len > padded.len() - 4 {
+        return Err(PasswordError::DecryptionError);
     }
-    let mut output = SafeVec::new(vec);
+    Ok(&padded[4..4 + len])
+}
 
-    scrypt::scrypt(master_password.as_bytes(), &salt, &scrypt_params, output.deref_mut());
+/// The byte offset of a line and column as serde_json counts them, from 1. The error
+/// itself is not kept, since it may quote the passwords.
+fn byte_offset(text: &str, line: usize, column: usize) -> usize {
+    let before: usize = text.split('\n').take(line.saturating_sub(1)).map(|line| line.len() + 1).sum();
+    before + column.saturating_sub(1)
+}
 
-    output
+fn default_kdf_params() -> KdfParams {
+    KdfParams {
+        log2_n: SCRYPT_PARAM_LOG2_N,
+        r: SCRYPT_PARAM_R,
+        p: SCRYPT_PARAM_P,
+    }
+}
+
+/// Derives a 256 bits encryption key from the password. This takes a while with high
+/// scrypt parameters, so it shows a spinner in the meantime.
+fn generate_encryption_key(crypto: &'static CryptoProvider, kdf_params: KdfParams, master_password: &str, salt: [u8; SALT_LEN]) -> SafeVec {
+    let master_password = SafeString::new(master_password.to_string());
+    progress::run("Deriving the key", move || crypto.derive_key(kdf_params, master_password.deref(), &salt))
+}
+
+/// Feeds the JSON written to it into a hash, so that values holding secrets can be
+/// compared without being serialized into a string nobody wipes.
+struct DigestWriter<'a>(&'a mut Sha256);
+
+impl<'a> Write for DigestWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.0.input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// Hashes the JSON of a value, see `DigestWriter`.
+fn hash_json<T: Serialize>(hasher: &mut Sha256, value: &T) {
+    // Writing to a hash can't fail, and what a vault holds always serializes.
+    let _ = serde_json::to_writer(DigestWriter(hasher), value);
+}
+
+/// SHA-256 of the JSON of a value: two values with the same JSON have the same digest.
+pub fn digest<T: Serialize>(value: &T) -> SafeVec {
+    let mut hasher = Sha256::new();
+    hash_json(&mut hasher, value);
+    let mut digest = SafeVec::new(vec![0u8; hasher.output_bytes()]);
+    hasher.result(&mut digest);
+    digest
+}
+
+/// The version in the header of a password file, if it is long enough to have one.
+pub fn version_of(input: &[u8]) -> Option<u32> {
+    Cursor::new(input).read_u32::<BigEndian>().ok()
+}
+
+/// Whether the input has the header of a password file of version 2 or later, with each
+/// vault in one piece. It needs no key, so it can't tell a real vault from a filler.
+pub fn is_password_file(input: &[u8]) -> bool {
+    match version_of(input) {
+        Some(version) if version >= VERSION_WITHOUT_PASSWORD_CHECK && version <= VERSION => {},
+        _ => return false,
+    }
+    match read_slots(input) {
+        Ok((version, slots)) => slots.iter().all(|slot| read_vault(version, slot).is_ok()),
+        Err(_) => false,
+    }
+}
+
+/// The version a vault was signed with: the version of the file it was written to. One
+/// without flags comes from a file older than version 5.
+fn signed_version(version: u32, slot: &Slot) -> u32 {
+    if version < VERSION_WITHOUT_FLAGS || slot.flags != 0 {
+        version
+    } else {
+        VERSION_WITHOUT_FLAGS
+    }
+}
+
+/// A value only the right master password gives, so that a wrong master password
+/// and a damaged file can be told apart. It is as hard to brute force as the
+/// signature, since both need the key.
+fn password_check(crypto: &CryptoProvider, key: &[u8]) -> Vec<u8> {
+    crypto.mac(key, PASSWORD_CHECK_MESSAGE)
 }
 
-/// Creates a HMAC struct
-fn digest(key: &[u8], version: u32, scrypt_log2_n: u8, scrypt_r: u32, scrypt_p: u32, iv: &[u8], salt: &[u8], blob: &[u8]) -> IoResult<hmac::Hmac<sha2::Sha512>> {
-    let mut digest = hmac::Hmac::new(sha2::Sha512::new(), key);
+/// The data covered by the file signature: the header and the encrypted data. From
+/// version 5, the header starts with the flags and the crypto provider.
+fn signed_data(version: u32, flags: u8, provider: u8, scrypt_log2_n: u8, scrypt_r: u32, scrypt_p: u32, iv: &[u8], salt: &[u8], key_slots: &[u8], check: &[u8], blob: &[u8]) -> IoResult<Vec<u8>> {
+    let mut data = Vec::new();
 
     let mut version_bytes_cursor: Cursor<Vec<u8>> = Cursor::new(Vec::new());
     try!(version_bytes_cursor.write_u32::<BigEndian>(version).map_err(|be| {
@@ -129,277 +569,1256 @@ fn digest(key: &[u8], version: u32, scrypt_log2_n: u8, scrypt_r: u32, scrypt_p:
     }));
 
     let version_bytes = version_bytes_cursor.into_inner();
-    digest.input(version_bytes.deref());
+    data.extend_from_slice(version_bytes.deref());
+
+    if version >= VERSION {
+        data.push(flags);
+        data.push(provider);
+    }
 
     let scrypt_bytes = scrypt_bytes_cursor.into_inner();
-    digest.input(scrypt_bytes.deref());
+    data.extend_from_slice(scrypt_bytes.deref());
 
-    digest.input(&iv);
-    digest.input(&salt);
-    digest.input(blob.deref());
+    data.extend_from_slice(&iv);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(key_slots);
+    data.extend_from_slice(check);
+    data.extend_from_slice(blob.deref());
 
-    Ok(digest)
+    Ok(data)
 }
 
+/// Encrypts and signs data with a key, see `PasswordStore::seal`.
+fn seal_with(crypto: &CryptoProvider, key: &[u8], data: &[u8]) -> Result<Vec<u8>, PasswordError> {
+    let iv = try!(generate_random_iv(crypto).map_err(|io_err| PasswordError::Io(io_err)));
+    let mut sealed = iv.clone();
+    sealed.extend(try!(crypto.encrypt(key, &iv, data)));
+    let signature = crypto.mac(key, &sealed);
+    sealed.extend(signature);
+    Ok(sealed)
+}
 
-/// The format of the encrypted JSON content in the password file v1.
-#[derive(RustcDecodable, RustcEncodable, Clone)]
-pub struct Schema {
-    passwords: Vec<Password>,
+/// Checks and decrypts data from `seal_with`.
+fn unseal_with(crypto: &CryptoProvider, key: &[u8], sealed: &[u8]) -> Result<SafeVec, PasswordError> {
+    if sealed.len() < crypto.iv_len() + crypto.mac_len() {
+        return Err(PasswordError::CorruptionError);
+    }
+    let (signed, signature) = sealed.split_at(sealed.len() - crypto.mac_len());
+    if !crypto.verify_mac(key, signed, signature) {
+        return Err(PasswordError::CorruptionError);
+    }
+    crypto.decrypt(key, &signed[..crypto.iv_len()], &signed[crypto.iv_len()..])
 }
 
-impl Schema {
-    fn new() -> Schema {
-        Schema {
-            passwords: Vec::new(),
-        }
+/// A key to wrap a vault key with, from a secret with enough entropy not to need scrypt:
+/// a key file, a recovery key, the response of a YubiKey, the hmac-secret of a FIDO2
+/// authenticator or a secret sealed by a TPM.
+fn secret_key(secret: &[u8]) -> SafeVec {
+    let mut hasher = Sha256::new();
+    hasher.input(secret);
+    let mut key = SafeVec::new(vec![0u8; hasher.output_bytes()]);
+    hasher.result(&mut key);
+    key
+}
+
+/// A key to wrap a vault key with that needs both the master password and the response
+/// of a YubiKey.
+fn second_factor_key(derived: &[u8], response: &[u8]) -> SafeVec {
+    let mut hasher = Sha256::new();
+    hasher.input(derived);
+    hasher.input(response);
+    let mut key = SafeVec::new(vec![0u8; hasher.output_bytes()]);
+    hasher.result(&mut key);
+    key
+}
+
+/// Wraps the key of a vault for `KEY_SLOT_MASTER_PASSWORD_YUBIKEY`, with a new random
+/// challenge.
+fn wrap_with_second_factor(crypto: &CryptoProvider, derived: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
+    let mut challenge = [0u8; YUBIKEY_CHALLENGE_LEN];
+    try!(OsRng::new().map_err(|err| format!("I could not make a challenge ({})", err))).fill_bytes(&mut challenge);
+    let response = try!(yubikey::challenge_response(&challenge));
+    let mut wrapped = challenge.to_vec();
+    wrapped.extend(try!(seal_with(crypto, second_factor_key(derived, response.deref()).deref(), key).map_err(|err| format!("I could not wrap the key ({:?})", err))));
+    Ok(wrapped)
+}
+
+/// Appends the length of `bytes` as a big endian u16, then `bytes`.
+fn push_len_prefixed(prefix: &mut Vec<u8>, bytes: &[u8]) {
+    let len = bytes.len() as u16;
+    prefix.push((len >> 8) as u8);
+    prefix.push(len as u8);
+    prefix.extend_from_slice(bytes);
+}
+
+/// Splits what `push_len_prefixed` appended from the bytes after it.
+fn split_len_prefixed(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    if bytes.len() < 2 {
+        return None;
     }
+    let len = ((bytes[0] as usize) << 8) | bytes[1] as usize;
+    if bytes.len() < 2 + len {
+        return None;
+    }
+    Some(bytes[2..].split_at(len))
 }
 
-#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
-pub struct Password {
-    pub name: String,
-    pub username: String,
-    pub password: SafeString,
-    pub created_at: ffi::time_t,
-    pub updated_at: ffi::time_t
+/// The start of a `KEY_SLOT_FIDO2` key slot: the credential ID, see `push_len_prefixed`,
+/// then the salt.
+fn fido2_prefix(credential_id: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::new();
+    push_len_prefixed(&mut prefix, credential_id);
+    prefix.extend_from_slice(salt);
+    prefix
 }
 
-impl Password {
-    pub fn new(name: String, username: String, password: SafeString) -> Password {
-        let timestamp = ffi::time();
-        Password {
-            name: name,
-            username: username,
-            password: password,
-            created_at: timestamp,
-            updated_at: timestamp
-        }
+/// The credential ID, the salt and the wrapped key of a `KEY_SLOT_FIDO2` key slot.
+fn split_fido2_key_slot(wrapped: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let (credential_id, rest) = match split_len_prefixed(wrapped) {
+        Some(split) => split,
+        None => { return None; }
+    };
+    if rest.len() <= fido2::SALT_LEN {
+        return None;
     }
+    let (salt, wrapped) = rest.split_at(fido2::SALT_LEN);
+    Some((credential_id, salt, wrapped))
 }
 
-pub struct PasswordStore {
-    key: SafeVec,
-    scrypt_log2_n: u8,
-    scrypt_r: u32,
-    scrypt_p: u32,
-    salt: [u8; SALT_LEN],
-    schema: Schema,
+/// The start of a `KEY_SLOT_TPM` key slot: the public blob, then the private blob of the
+/// sealed secret, each after its length, see `push_len_prefixed`.
+fn tpm_prefix(sealed: &keywrap::Sealed) -> Vec<u8> {
+    let mut prefix = Vec::new();
+    push_len_prefixed(&mut prefix, &sealed.public);
+    push_len_prefixed(&mut prefix, &sealed.private);
+    prefix
 }
 
-/// Read and writes to a Rooster file
-///
-/// The Rooster file has the following format:
-/// - rooster version: u32, big endian
-/// - scrypt log2_n:  u8
-/// - scrypt r:       u32, big endian
-/// - scrypt p:       u32, big endian
-/// - salt:           256 bits
-/// - iv:             256 bits
-/// - signature:      512 bits HMAC-SHA512
-/// - encrypted blob: variable length
-impl PasswordStore {
-    pub fn new(master_password: SafeString) -> IoResult<PasswordStore> {
-        let salt = try!(generate_random_salt());
+/// The sealed secret and the wrapped key of a `KEY_SLOT_TPM` key slot.
+fn split_tpm_key_slot(wrapped: &[u8]) -> Option<(keywrap::Sealed, &[u8])> {
+    let (public, rest) = match split_len_prefixed(wrapped) {
+        Some(split) => split,
+        None => { return None; }
+    };
+    let (private, wrapped) = match split_len_prefixed(rest) {
+        Some(split) => split,
+        None => { return None; }
+    };
+    let sealed = keywrap::Sealed {
+        public: public.to_vec(),
+        private: private.to_vec(),
+    };
+    Some((sealed, wrapped))
+}
 
-        let scrypt_params = scrypt::ScryptParams::new(
-            SCRYPT_PARAM_LOG2_N,
-            SCRYPT_PARAM_R,
-            SCRYPT_PARAM_P
-        );
+/// A random key for a vault with key slots.
+fn generate_random_key() -> IoResult<SafeVec> {
+    let mut key = SafeVec::new(vec![0u8; KEY_LEN]);
+    let mut rng = try!(OsRng::new());
+    rng.fill_bytes(&mut key);
+    Ok(key)
+}
 
-        let key = generate_encryption_key(scrypt_params.clone(), master_password.deref(), salt);
+/// One way to open a vault with key slots: the random key of the vault, wrapped.
+#[derive(Clone, PartialEq)]
+pub struct KeySlot {
+    /// See `KEY_SLOT_MASTER_PASSWORD` and the others.
+    pub kind: u8,
+    /// What unwraps the key, like the ID of a GPG key, so that users can tell key slots
+    /// apart.
+    pub label: String,
+    pub wrapped: Vec<u8>,
+}
 
-        Ok(PasswordStore {
-            key: key,
-            scrypt_log2_n: SCRYPT_PARAM_LOG2_N,
-            scrypt_r: SCRYPT_PARAM_R,
-            scrypt_p: SCRYPT_PARAM_P,
-            salt: salt,
-            schema: Schema::new(),
-        })
+/// Key slots as they are written in a vault:
+/// - key slot count: u8
+/// - for each key slot:
+///   - kind:         u8
+///   - label length: u8
+///   - label:        UTF-8
+///   - length:       u32, big endian
+///   - wrapped key:  variable length
+///
+/// Vaults without key slots have none of it.
+fn write_key_slots(key_slots: &[KeySlot]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if key_slots.is_empty() {
+        return bytes;
     }
+    bytes.push(key_slots.len() as u8);
+    for key_slot in key_slots.iter() {
+        bytes.push(key_slot.kind);
+        bytes.push(key_slot.label.len() as u8);
+        bytes.extend_from_slice(key_slot.label.as_bytes());
+        let len = key_slot.wrapped.len() as u32;
+        bytes.extend_from_slice(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8]);
+        bytes.extend_from_slice(&key_slot.wrapped);
+    }
+    bytes
+}
 
-    pub fn from_input(master_password: SafeString, input: SafeVec) -> Result<PasswordStore, PasswordError> {
-        let mut reader = Cursor::new(input.deref());
+/// Reads the bytes of a file in order, with errors that say which part is missing.
+struct SliceReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
 
-        // Version taken from network byte order (big endian).
-        let version = match reader.read_u32::<BigEndian>() {
-            Ok(version) => version,
-            Err(err) => {
-                let err = match err {
-                   ByteorderError::UnexpectedEOF => PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")),
-                   ByteorderError::Io(io_err) => PasswordError::Io(io_err)
-                };
-                return Err(err);
-            }
-        };
-        if version != VERSION {
-            return Err(PasswordError::WrongVersionError);
+impl<'a> SliceReader<'a> {
+    fn new(input: &'a [u8]) -> SliceReader<'a> {
+        SliceReader {
+            input: input,
+            position: 0,
         }
+    }
 
-        // Read the scrypt params.
-        let scrypt_log2_n = match reader.read_u8() {
-            Ok(n) => n,
-            Err(err) => {
-                let err = match err {
-                   ByteorderError::UnexpectedEOF => PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")),
-                   ByteorderError::Io(io_err) => PasswordError::Io(io_err)
-                };
-                return Err(err);
-            }
-        };
-        let scrypt_r = match reader.read_u32::<BigEndian>() {
-            Ok(n) => n,
-            Err(err) => {
-                let err = match err {
-                   ByteorderError::UnexpectedEOF => PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")),
-                   ByteorderError::Io(io_err) => PasswordError::Io(io_err)
-                };
-                return Err(err);
-            }
-        };
-        let scrypt_p = match reader.read_u32::<BigEndian>() {
-            Ok(n) => n,
-            Err(err) => {
-                let err = match err {
-                   ByteorderError::UnexpectedEOF => PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")),
-                   ByteorderError::Io(io_err) => PasswordError::Io(io_err)
-                };
-                return Err(err);
-            }
-        };
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], PasswordError> {
+        let left = self.input.len() - self.position;
+        if len > left {
+            return Err(PasswordError::HeaderError(format!("the {} at byte {} needs {} bytes, but only {} are left", what, self.position, len, left)));
+        }
+        let bytes = &self.input[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
 
-        // Read the old salt.
-        let mut salt: [u8; SALT_LEN] = [0u8; SALT_LEN];
-        try!(reader.read(&mut salt).map_err(|io_err| PasswordError::Io(io_err)).and_then(|num_bytes| {
-            if num_bytes == SALT_LEN {
-                Ok(())
-            } else {
-                Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")))
-            }
-        }));
+    fn read_u8(&mut self, what: &str) -> Result<u8, PasswordError> {
+        Ok(try!(self.take(1, what))[0])
+    }
 
-        // Read the old IV.
-        let mut iv: [u8; IV_LEN] = [0u8; IV_LEN];
-        try!(reader.read(&mut iv).map_err(|io_err| PasswordError::Io(io_err)).and_then(|num_bytes| {
-            if num_bytes == IV_LEN {
-                Ok(())
-            } else {
-                Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")))
-            }
-        }));
+    fn read_u32(&mut self, what: &str) -> Result<u32, PasswordError> {
+        let bytes = try!(self.take(4, what));
+        Ok((bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32)
+    }
 
-        // Read the HMAC signature.
-        let mut signature: [u8; SIGNATURE_LEN] = [0u8; SIGNATURE_LEN];
-        try!(reader.read(&mut signature).map_err(|io_err| PasswordError::Io(io_err)).and_then(|num_bytes| {
-            if num_bytes == SIGNATURE_LEN {
-                Ok(())
-            } else {
-                Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unexpected eof")))
-            }
-        }));
+    fn rest(&mut self) -> &'a [u8] {
+        let bytes = &self.input[self.position..];
+        self.position = self.input.len();
+        bytes
+    }
+}
 
-        // The encrypted password data.
-        let mut blob: Vec<u8> = Vec::new();
-        try!(reader.read_to_end(&mut blob).map_err(|io_err| PasswordError::Io(io_err)));
+/// One vault of a file, as `read_slots` finds it.
+pub struct Slot<'a> {
+    /// 0 for files older than version 5.
+    pub flags: u8,
+    /// `provider::RUST_CRYPTO` for files older than version 5.
+    pub provider: u8,
+    pub vault: &'a [u8],
+}
 
-        // Derive a 256 bits encryption key from the password.
-        let scrypt_params = scrypt::ScryptParams::new(
-            scrypt_log2_n,
-            scrypt_r,
-            scrypt_p
-        );
-        let key = generate_encryption_key(scrypt_params, master_password.deref(), salt);
+/// The clear part of a vault, as `read_vault` finds it.
+pub struct VaultHeader<'a> {
+    pub scrypt_log2_n: u8,
+    pub scrypt_r: u32,
+    pub scrypt_p: u32,
+    pub salt: [u8; SALT_LEN],
+    /// Empty for vaults without the `FLAG_KEY_SLOTS` flag.
+    pub key_slots: Vec<KeySlot>,
+    /// As long as the crypto provider of the vault says, like the password check and
+    /// the signature.
+    pub iv: &'a [u8],
+    /// Empty for files of version 2.
+    pub check: &'a [u8],
+    pub signature: &'a [u8],
+    pub blob: &'a [u8],
+}
 
-        // Check the signature against what it should be.
-        let new_signature_mac = try!(digest(key.deref(), version, scrypt_log2_n, scrypt_r, scrypt_p, &iv, &salt, blob.deref()).map_err(|io_err| {
-            PasswordError::Io(io_err)
-        })).result();
-        let old_signature_mac = MacResult::new(&signature);
-        if new_signature_mac != old_signature_mac {
-            return Err(PasswordError::CorruptionError);
-        }
+/// Splits a file into its version and its vaults. Like `read_vault`, it needs no key and
+/// only looks at the bytes it is given, so it can be fuzzed on its own.
+pub fn read_slots(input: &[u8]) -> Result<(u32, Vec<Slot>), PasswordError> {
+    if input.len() > MAX_FILE_LEN {
+        return Err(PasswordError::HeaderError(format!("the file has {} bytes, more than the {} a Rooster file may have", input.len(), MAX_FILE_LEN)));
+    }
+    let mut reader = SliceReader::new(input);
 
-        // Decrypt the data.
-        let passwords = match aes::decrypt(blob.deref(), key.as_ref(), iv.as_ref()) {
-            Ok(decrypted) => {
-                let encoded = SafeString::new(String::from_utf8_lossy(decrypted.as_ref()).into_owned());
-                match json::decode::<Schema>(encoded.deref()) {
-                    Ok(json) => json.passwords,
-                    Err(_) => {
-                        return Err(PasswordError::InvalidJsonError);
-                    }
-                }
-            },
-            Err(_) => {
-                return Err(PasswordError::DecryptionError);
-            }
-        };
+    // Version taken from network byte order (big endian).
+    let version = try!(reader.read_u32("version"));
 
-        Ok(PasswordStore {
-            key: key,
-            scrypt_log2_n: scrypt_log2_n,
-            scrypt_r: scrypt_r,
-            scrypt_p: scrypt_p,
-            salt: salt,
-            schema: Schema {
-                passwords: passwords,
-            },
-        })
+    if version == VERSION_WITHOUT_PASSWORD_CHECK || version == VERSION_SINGLE_VAULT {
+        return Ok((version, vec![Slot { flags: 0, provider: provider::RUST_CRYPTO, vault: reader.rest() }]));
+    }
+    if version != VERSION && version != VERSION_WITHOUT_FLAGS {
+        return Err(PasswordError::WrongVersionError);
     }
 
-    pub fn sync(&self, file: &mut File) -> Result<(), PasswordError> {
-        // This should never fail. The structs are all encodable.
-        let json_schema = match json::encode(&self.schema) {
-            Ok(json_schema) => json_schema,
-            Err(_) => {
-                return Err(PasswordError::InvalidJsonError);
-            }
+    let count = try!(reader.read_u8("vault count")) as usize;
+    if count == 0 || count > MAX_SLOTS {
+        return Err(PasswordError::HeaderError(format!("the file says it has {} vaults, it should have 1 to {}", count, MAX_SLOTS)));
+    }
+    let mut slots = Vec::new();
+    for i in 0..count {
+        let len = try!(reader.read_u32(&format!("length of vault {}", i + 1))) as usize;
+        let mut slot = SliceReader::new(try!(reader.take(len, &format!("vault {}", i + 1))));
+        let flags = if version == VERSION_WITHOUT_FLAGS {
+            0
+        } else {
+            try!(slot.read_u8(&format!("flags of vault {}", i + 1)))
         };
-        let json_schema = SafeString::new(json_schema);
-
-        // Encrypt the data with a new salt and a new IV.
-        let iv = try!(generate_random_iv().map_err(|io_err| PasswordError::Io(io_err)));
-        let encrypted = match aes::encrypt(json_schema.deref().as_bytes(), self.key.as_ref(), iv.as_ref()) {
-            Ok(val) => { val },
-            Err(_) => { return Err(PasswordError::EncryptionError) }
+        if flags & !(FLAG_DEFLATE | FLAG_PADDED | FLAG_KEY_SLOTS | FLAG_WRAPPED) != 0 {
+            return Err(PasswordError::WrongVersionError);
+        }
+        let provider = if version == VERSION_WITHOUT_FLAGS {
+            provider::RUST_CRYPTO
+        } else {
+            try!(slot.read_u8(&format!("crypto provider of vault {}", i + 1)))
         };
-
-        // Reset the file pointer.
-        try!(file.seek(SeekFrom::Start(0)).and_then(|_| file.set_len(0)).map_err(|err| PasswordError::Io(err)));
-
-        // Write the file version.
-        try!(match file.write_u32::<BigEndian>(VERSION) {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                match err {
-                    ByteorderError::Io(err) => Err(PasswordError::Io(err)),
-                    _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+        slots.push(Slot {
+            flags: flags,
+            provider: provider,
+            vault: slot.rest(),
+        });
+    }
+    let left = reader.rest().len();
+    if left > 0 {
+        return Err(PasswordError::HeaderError(format!("there are {} bytes after the last vault", left)));
+    }
+
+    Ok((version, slots))
+}
+
+/// Checks that scrypt parameters are within what Rooster reads, see `MAX_SCRYPT_PARAM_LOG2_N`.
+pub fn check_kdf_params(params: KdfParams) -> Result<(), String> {
+    if params.log2_n == 0 || params.log2_n > MAX_SCRYPT_PARAM_LOG2_N {
+        return Err(format!("scrypt log2_n is {}, it should be 1 to {}", params.log2_n, MAX_SCRYPT_PARAM_LOG2_N));
+    }
+    if params.r == 0 || params.r > MAX_SCRYPT_PARAM_R {
+        return Err(format!("scrypt r is {}, it should be 1 to {}", params.r, MAX_SCRYPT_PARAM_R));
+    }
+    if params.p == 0 || params.p > MAX_SCRYPT_PARAM_P {
+        return Err(format!("scrypt p is {}, it should be 1 to {}", params.p, MAX_SCRYPT_PARAM_P));
+    }
+    Ok(())
+}
+
+/// Reads the clear part of a vault from a file of the given version, and checks that
+/// its scrypt parameters are sane before anything is derived with them.
+pub fn read_vault<'a>(version: u32, slot: &Slot<'a>) -> Result<VaultHeader<'a>, PasswordError> {
+    let crypto = try!(crypto(slot.provider));
+    let mut reader = SliceReader::new(slot.vault);
+
+    let scrypt_log2_n = try!(reader.read_u8("scrypt log2_n"));
+    let scrypt_r = try!(reader.read_u32("scrypt r"));
+    let scrypt_p = try!(reader.read_u32("scrypt p"));
+    try!(check_kdf_params(KdfParams { log2_n: scrypt_log2_n, r: scrypt_r, p: scrypt_p }).map_err(PasswordError::HeaderError));
+
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(try!(reader.take(SALT_LEN, "salt")));
+    let mut key_slots = Vec::new();
+    if slot.flags & FLAG_KEY_SLOTS != 0 {
+        let count = try!(reader.read_u8("key slot count")) as usize;
+        if count == 0 || count > MAX_KEY_SLOTS {
+            return Err(PasswordError::HeaderError(format!("the vault says it has {} key slots, it should have 1 to {}", count, MAX_KEY_SLOTS)));
+        }
+        for i in 0..count {
+            let kind = try!(reader.read_u8(&format!("kind of key slot {}", i + 1)));
+            let label_len = try!(reader.read_u8(&format!("label length of key slot {}", i + 1))) as usize;
+            let label = try!(reader.take(label_len, &format!("label of key slot {}", i + 1)));
+            let label = try!(str::from_utf8(label).map_err(|_| {
+                PasswordError::HeaderError(format!("the label of key slot {} is not UTF-8", i + 1))
+            }));
+            let len = try!(reader.read_u32(&format!("length of key slot {}", i + 1))) as usize;
+            let wrapped = try!(reader.take(len, &format!("key slot {}", i + 1)));
+            key_slots.push(KeySlot {
+                kind: kind,
+                label: label.to_string(),
+                wrapped: wrapped.to_vec(),
+            });
+        }
+    }
+    let iv = try!(reader.take(crypto.iv_len(), "IV"));
+    let check_len = if version == VERSION_WITHOUT_PASSWORD_CHECK { 0 } else { crypto.mac_len() };
+    let check = try!(reader.take(check_len, "password check"));
+    let signature = try!(reader.take(crypto.mac_len(), "signature"));
+    let blob = reader.rest();
+
+    Ok(VaultHeader {
+        scrypt_log2_n: scrypt_log2_n,
+        scrypt_r: scrypt_r,
+        scrypt_p: scrypt_p,
+        salt: salt,
+        key_slots: key_slots,
+        iv: iv,
+        check: check,
+        signature: signature,
+        blob: blob,
+    })
+}
+
+/// The format of the encrypted JSON content in the password file v1.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Schema {
+    passwords: Vec<Password>,
+    /// X25519 private key to receive shared passwords, see `rooster receive`.
+    sharing_key: Option<SafeString>,
+    /// The other vaults of the file, when this vault set them up. A decoy vault knows
+    /// nothing of the vault that set it up, and neither do vaults from files older
+    /// than version 5.
+    other_vaults: Option<Vec<OtherVault>>,
+    /// Where the vault comes from. Vaults written before it was added don't have it
+    /// until they are opened.
+    metadata: Option<Metadata>,
+    /// Fields added by newer versions of Rooster, kept as they are so that saving
+    /// with this version does not lose them.
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
+}
+
+impl Schema {
+    fn new() -> Schema {
+        Schema {
+            passwords: Vec::new(),
+            sharing_key: None,
+            other_vaults: None,
+            metadata: Some(Metadata::new(Some(Timestamp::now()), default_kdf_params())),
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+/// Another vault of the file, and how to write it back so that it changes along with the
+/// vault that was opened, see `PasswordStore::write_to`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OtherVault {
+    /// Where it is in the file.
+    slot: usize,
+    /// The key of the decoy vault there, base64 encoded, or none for random bytes and
+    /// for vaults this one can't read.
+    decoy_key: Option<SafeString>,
+    /// The master password of the decoy vault, to derive its key again when the clear
+    /// part of this vault changes. Decoy vaults set up before it was added don't have it,
+    /// and keep their own scrypt parameters.
+    decoy_master_password: Option<SafeString>,
+    /// The key the vault there is wrapped with, base64 encoded, see `FLAG_WRAPPED`. Not
+    /// needed for a decoy vault, whose key gives it.
+    wrap_key: Option<SafeString>,
+    /// The hex SHA-256 of the clear part of this vault when the vault there last took it
+    /// up, see `PasswordStore::other_vault`.
+    mirrored: Option<String>,
+}
+
+/// The history of the vault, inside the encrypted data so that it can be trusted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Unknown for vaults created before the metadata was added.
+    pub created_at: Option<Timestamp>,
+    /// When the vault last got a new key, see `rooster rekey`.
+    pub rekeyed_at: Option<Timestamp>,
+    /// The scrypt parameters of the vault, oldest first.
+    pub kdf_history: Vec<KdfChange>,
+}
+
+/// Scrypt parameters, and when the vault got them or, for vaults created before the
+/// metadata was added, when they were first seen.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KdfChange {
+    pub at: Timestamp,
+    pub log2_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Metadata {
+    fn new(created_at: Option<Timestamp>, kdf_params: KdfParams) -> Metadata {
+        Metadata {
+            created_at: created_at,
+            rekeyed_at: None,
+            kdf_history: vec![KdfChange::new(kdf_params)],
+        }
+    }
+
+    /// Since when the vault has had its key, as far as we know.
+    pub fn key_since(&self) -> Timestamp {
+        self.rekeyed_at
+            .or(self.created_at)
+            .or(self.kdf_history.first().map(|change| change.at))
+            .unwrap_or(Timestamp::now())
+    }
+}
+
+impl KdfChange {
+    fn new(kdf_params: KdfParams) -> KdfChange {
+        KdfChange {
+            at: Timestamp::now(),
+            log2_n: kdf_params.log2_n,
+            r: kdf_params.r,
+            p: kdf_params.p,
+        }
+    }
+}
+
+/// How new passwords are generated for an app, for sites with rules about passwords.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenerationPolicy {
+    pub alnum: bool,
+    pub length: usize,
+    /// When set, passphrases of this many words are generated instead of passwords.
+    pub words: Option<usize>,
+    pub separator: Option<String>,
+    /// Path to the word list for passphrases, the built-in list if not set.
+    pub wordlist: Option<String>,
+    #[serde(default)]
+    pub no_ambiguous: bool,
+    /// Characters to pick from, instead of the default ones.
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub require_each_class: bool,
+}
+
+/// A password that was replaced, kept in case the new one does not work out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreviousPassword {
+    pub password: SafeString,
+    pub replaced_at: Timestamp,
+}
+
+/// A named value attached to an entry, like an API key or a security answer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CustomField {
+    pub name: String,
+    pub value: SafeString,
+}
+
+/// A small file attached to an entry, like a certificate or a PDF of backup codes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    /// The file content, base64 encoded so that it fits in the JSON.
+    pub content: SafeString,
+    pub size: u64,
+    pub added_at: Timestamp,
+}
+
+/// A second factor secret for one-time passwords, time-based (TOTP) or counter-based (HOTP).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OtpSecret {
+    /// Base32 encoded, as sites show it.
+    pub secret: SafeString,
+    /// SHA1, SHA256 or SHA512.
+    pub algorithm: String,
+    pub digits: u32,
+    /// Seconds each TOTP code is valid for.
+    pub period: u64,
+    /// The counter of the next HOTP code, `None` for TOTP.
+    pub counter: Option<u64>,
+}
+
+/// Secrets of an entry encrypted with a passphrase of its own, on top of the master
+/// password, see `rooster protect`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Protected {
+    /// Base64 encoded, for the key derivation.
+    pub salt: String,
+    /// The encrypted secrets and their authentication tag, base64 encoded.
+    pub data: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Password {
+    pub name: String,
+    pub username: String,
+    pub password: SafeString,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+    /// Kind-specific data, `None` for logins.
+    pub payload: Option<Payload>,
+    pub notes: Option<SafeString>,
+    pub generation_policy: Option<GenerationPolicy>,
+    /// Previous passwords, oldest first.
+    pub history: Option<Vec<PreviousPassword>>,
+    /// When the password was last read, only recorded if usage tracking is enabled.
+    pub last_used_at: Option<Timestamp>,
+    pub use_count: Option<u64>,
+    /// One-time backup codes, a code is removed once used.
+    pub recovery_codes: Option<Vec<SafeString>>,
+    pub otp: Option<OtpSecret>,
+    pub custom_fields: Option<Vec<CustomField>>,
+    pub attachments: Option<Vec<Attachment>>,
+    /// Where to log in, for `rooster open`.
+    pub url: Option<String>,
+    /// What `rooster autotype` types, the default sequence if not set.
+    pub autotype: Option<String>,
+    /// Other names the app can be looked up with, see `rooster alias`.
+    pub aliases: Option<Vec<String>>,
+    /// Labels like "work" or "bank", for `rooster list --group-by tag`.
+    pub tags: Option<Vec<String>>,
+    /// When set, the password, history, notes, custom fields, recovery codes and OTP
+    /// secret are in there, and empty in the entry.
+    pub protected: Option<Protected>,
+    /// Lamport clock of each field in `MERGED_FIELDS`, bumped when the field changes,
+    /// so that copies edited on different devices merge field by field.
+    pub clocks: Option<BTreeMap<String, u64>>,
+    /// Fields added by newer versions of Rooster, kept as they are.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// Fields merged one by one when two copies of the password file are merged.
+pub const MERGED_FIELDS: &'static [&'static str] = &[
+    "username",
+    "password",
+    "payload",
+    "notes",
+    "url",
+    "generation_policy",
+    "history",
+    "recovery_codes",
+    "otp",
+    "custom_fields",
+    "attachments",
+    "autotype",
+    "aliases",
+    "tags",
+    "protected",
+];
+
+impl Password {
+    pub fn new(name: String, username: String, password: SafeString) -> Password {
+        let timestamp = Timestamp::now();
+        Password {
+            name: name,
+            username: username,
+            password: password,
+            created_at: timestamp,
+            updated_at: timestamp,
+            payload: None,
+            notes: None,
+            generation_policy: None,
+            history: None,
+            last_used_at: None,
+            use_count: None,
+            recovery_codes: None,
+            otp: None,
+            custom_fields: None,
+            attachments: None,
+            url: None,
+            autotype: None,
+            aliases: None,
+            tags: None,
+            protected: None,
+            clocks: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    pub fn custom_field(&self, name: &str) -> Option<&CustomField> {
+        match self.custom_fields {
+            Some(ref fields) => fields.iter().find(|field| field.name == name),
+            None => None,
+        }
+    }
+
+    /// Sets a custom field, replacing the field with the same name if any.
+    pub fn set_custom_field(&mut self, name: String, value: SafeString) {
+        let mut fields = self.custom_fields.take().unwrap_or(Vec::new());
+        match fields.iter().position(|field| field.name == name) {
+            Some(i) => { fields[i].value = value; },
+            None => { fields.push(CustomField { name: name, value: value }); }
+        }
+        self.custom_fields = Some(fields);
+    }
+
+    /// Removes a custom field, returns whether it existed.
+    pub fn remove_custom_field(&mut self, name: &str) -> bool {
+        let mut fields = self.custom_fields.take().unwrap_or(Vec::new());
+        let count = fields.len();
+        fields.retain(|field| field.name != name);
+        let removed = fields.len() != count;
+        self.custom_fields = if fields.is_empty() { None } else { Some(fields) };
+        removed
+    }
+
+    /// Records that the password was just read.
+    pub fn record_use(&mut self) {
+        self.last_used_at = Some(Timestamp::now());
+        self.use_count = Some(self.use_count.unwrap_or(0) + 1);
+    }
+
+    /// Replaces the password, keeping the previous one in the history.
+    pub fn replace_password(&mut self, password: SafeString) {
+        let timestamp = Timestamp::now();
+        let previous = PreviousPassword {
+            password: self.password.clone(),
+            replaced_at: timestamp,
+        };
+        match self.history {
+            Some(ref mut history) => history.push(previous),
+            None => { self.history = Some(vec![previous]); }
+        }
+        self.password = password;
+        self.updated_at = timestamp;
+    }
+
+    /// The digest of one of `MERGED_FIELDS`, to compare fields since passwords have no
+    /// PartialEq.
+    pub fn field_digest(&self, field: &str) -> Option<SafeVec> {
+        match field {
+            "username" => Some(digest(&self.username)),
+            "password" => Some(digest(&self.password)),
+            "payload" => Some(digest(&self.payload)),
+            "notes" => Some(digest(&self.notes)),
+            "url" => Some(digest(&self.url)),
+            "generation_policy" => Some(digest(&self.generation_policy)),
+            "history" => Some(digest(&self.history)),
+            "recovery_codes" => Some(digest(&self.recovery_codes)),
+            "otp" => Some(digest(&self.otp)),
+            "custom_fields" => Some(digest(&self.custom_fields)),
+            "attachments" => Some(digest(&self.attachments)),
+            "autotype" => Some(digest(&self.autotype)),
+            "aliases" => Some(digest(&self.aliases)),
+            "tags" => Some(digest(&self.tags)),
+            "protected" => Some(digest(&self.protected)),
+            _ => None,
+        }
+    }
+
+    fn copy_field(&mut self, from: &Password, field: &str) {
+        match field {
+            "username" => { self.username = from.username.clone(); },
+            "password" => { self.password = from.password.clone(); },
+            "payload" => { self.payload = from.payload.clone(); },
+            "notes" => { self.notes = from.notes.clone(); },
+            "url" => { self.url = from.url.clone(); },
+            "generation_policy" => { self.generation_policy = from.generation_policy.clone(); },
+            "history" => { self.history = from.history.clone(); },
+            "recovery_codes" => { self.recovery_codes = from.recovery_codes.clone(); },
+            "otp" => { self.otp = from.otp.clone(); },
+            "custom_fields" => { self.custom_fields = from.custom_fields.clone(); },
+            "attachments" => { self.attachments = from.attachments.clone(); },
+            "autotype" => { self.autotype = from.autotype.clone(); },
+            "aliases" => { self.aliases = from.aliases.clone(); },
+            "tags" => { self.tags = from.tags.clone(); },
+            "protected" => { self.protected = from.protected.clone(); },
+            _ => {},
+        }
+    }
+
+    /// Takes the fields of `other` that this app leaves empty, and the custom fields it
+    /// does not have, for an imported app with the same name. Returns the fields taken.
+    pub fn fill_from(&mut self, other: &Password) -> Vec<&'static str> {
+        let empty = [digest(&Value::Null), digest(&"")];
+        let is_empty = |field_digest: Option<SafeVec>| field_digest.map_or(true, |field_digest| empty.contains(&field_digest));
+        let mut taken = Vec::new();
+        for field in MERGED_FIELDS.iter() {
+            // Protected secrets only make sense as a whole, and so does the history.
+            if *field == "protected" || *field == "history" || *field == "custom_fields" {
+                continue;
+            }
+            if is_empty(self.field_digest(field)) && !is_empty(other.field_digest(field)) {
+                self.copy_field(other, field);
+                taken.push(*field);
+            }
+        }
+
+        if let Some(ref fields) = other.custom_fields {
+            let mut added = false;
+            for field in fields.iter() {
+                if self.custom_field(&field.name).is_none() {
+                    self.set_custom_field(field.name.clone(), field.value.clone());
+                    added = true;
                 }
             }
-        });
+            if added {
+                taken.push("custom_fields");
+            }
+        }
+        taken
+    }
 
-        // Write the scrypt params.
-        try!(match file.write_u8(self.scrypt_log2_n) {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                match err {
-                    ByteorderError::Io(err) => Err(PasswordError::Io(err)),
-                    _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+    fn clock(&self, field: &str) -> u64 {
+        self.clocks.as_ref().and_then(|clocks| clocks.get(field).cloned()).unwrap_or(0)
+    }
+
+    fn set_clock(&mut self, field: &str, clock: u64) {
+        let mut clocks = self.clocks.take().unwrap_or(BTreeMap::new());
+        clocks.insert(field.to_string(), clock);
+        self.clocks = Some(clocks);
+    }
+
+    /// Bumps the clock of each field that is different from `before`.
+    pub fn tick(&mut self, before: &Password) {
+        let latest = MERGED_FIELDS.iter()
+            .map(|field| ::std::cmp::max(self.clock(field), before.clock(field)))
+            .max()
+            .unwrap_or(0);
+        for field in MERGED_FIELDS.iter() {
+            if self.field_digest(field) != before.field_digest(field) {
+                self.set_clock(field, latest + 1);
+            }
+        }
+    }
+
+    /// Takes each field of `other` with a newer clock. Returns whether a field had
+    /// the same clock on both sides but a different value, in which case the value
+    /// whose digest sorts last wins, so that every device picks the same one.
+    pub fn merge_fields(&mut self, other: &Password) -> bool {
+        let mut tie = false;
+        for field in MERGED_FIELDS.iter() {
+            let (ours, theirs) = (self.clock(field), other.clock(field));
+            let (our_digest, their_digest) = (self.field_digest(field), other.field_digest(field));
+            if theirs > ours || (theirs == ours && their_digest > our_digest) {
+                self.copy_field(other, field);
+                self.set_clock(field, theirs);
+            }
+            if theirs == ours && their_digest != our_digest {
+                tie = true;
+            }
+        }
+
+        self.created_at = ::std::cmp::min(self.created_at, other.created_at);
+        self.updated_at = ::std::cmp::max(self.updated_at, other.updated_at);
+        self.last_used_at = ::std::cmp::max(self.last_used_at, other.last_used_at);
+        self.use_count = ::std::cmp::max(self.use_count, other.use_count);
+        tie
+    }
+
+    pub fn kind(&self) -> &'static Kind {
+        match self.payload {
+            Some(ref payload) => payload.kind(),
+            None => &LOGIN,
+        }
+    }
+
+    /// Looks up a field by name: "name", "username", "password", "notes", "kind", "url", one
+    /// of the kind-specific fields, with spaces written as underscores ("public_key"),
+    /// or a custom field.
+    pub fn field(&self, name: &str) -> Option<SafeString> {
+        match name {
+            "name" => Some(SafeString::new(self.name.clone())),
+            "username" => Some(SafeString::new(self.username.clone())),
+            "password" => Some(self.password.clone()),
+            "notes" => Some(self.notes.clone().unwrap_or(SafeString::new(String::new()))),
+            "kind" => Some(SafeString::new(self.kind().name.to_string())),
+            "url" => Some(SafeString::new(self.url.clone().unwrap_or(String::new()))),
+            _ => {
+                if let Some(ref payload) = self.payload {
+                    let kind = payload.kind();
+                    for (field, value) in kind.fields.iter().zip(payload.values().into_iter()) {
+                        if field.name.replace(" ", "_") == name {
+                            return Some(value);
+                        }
+                    }
                 }
+                self.custom_field(name).map(|field| field.value.clone())
             }
-        });
-        try!(match file.write_u32::<BigEndian>(self.scrypt_r) {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                match err {
-                    ByteorderError::Io(err) => Err(PasswordError::Io(err)),
-                    _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+        }
+    }
+}
+
+/// What opens a vault.
+#[derive(Clone)]
+pub enum Unlock {
+    MasterPassword(SafeString),
+    /// One of the GPG key slots of the vault, with whichever secret key gpg has.
+    Gpg,
+    /// The content of a key file.
+    Keyfile(SafeVec),
+    /// A recovery key, as the user typed it.
+    RecoveryKey(SafeString),
+    /// The YubiKey plugged in.
+    YubiKey,
+    /// The FIDO2 authenticator plugged in, see `fido2::device`.
+    Fido2,
+    /// The TPM of this machine, with the PIN the user typed.
+    Tpm(SafeString),
+    /// The key of the vault itself, as a session keeps it.
+    Key(SafeVec),
+}
+
+/// Unwraps the key from the first key slot of this kind that `wrapping_key` opens.
+fn unwrap_key(crypto: &CryptoProvider, kind: u8, wrapping_key: &[u8], key_slots: &[KeySlot]) -> Result<SafeVec, PasswordError> {
+    for key_slot in key_slots.iter().filter(|key_slot| key_slot.kind == kind) {
+        if let Ok(key) = unseal_with(crypto, wrapping_key, &key_slot.wrapped) {
+            return Ok(key);
+        }
+    }
+    Err(PasswordError::WrongMasterPasswordError)
+}
+
+/// The key of a vault: derived from the master password, or unwrapped from one of its
+/// key slots when it has some.
+fn unlock_key(crypto: &'static CryptoProvider, unlock: &Unlock, kdf_params: KdfParams, salt: [u8; SALT_LEN], key_slots: &[KeySlot]) -> Result<SafeVec, PasswordError> {
+    match *unlock {
+        Unlock::MasterPassword(ref master_password) => {
+            let derived = generate_encryption_key(crypto, kdf_params, master_password.deref(), salt);
+            if key_slots.is_empty() {
+                return Ok(derived);
+            }
+            let second_factor = key_slots.iter().find(|key_slot| key_slot.kind == KEY_SLOT_MASTER_PASSWORD_YUBIKEY && key_slot.wrapped.len() > YUBIKEY_CHALLENGE_LEN);
+            if let Some(key_slot) = second_factor {
+                let (challenge, wrapped) = key_slot.wrapped.split_at(YUBIKEY_CHALLENGE_LEN);
+                let response = try!(yubikey::challenge_response(challenge).map_err(|err| {
+                    println_stderr!("Your password file also needs your YubiKey: {}.", err);
+                    PasswordError::WrongMasterPasswordError
+                }));
+                return unseal_with(crypto, second_factor_key(derived.deref(), response.deref()).deref(), wrapped).map_err(|_| PasswordError::WrongMasterPasswordError);
+            }
+            unwrap_key(crypto, KEY_SLOT_MASTER_PASSWORD, derived.deref(), key_slots)
+        },
+        Unlock::Keyfile(ref content) => unwrap_key(crypto, KEY_SLOT_KEYFILE, secret_key(content.deref()).deref(), key_slots),
+        Unlock::RecoveryKey(ref recovery_key) => {
+            match base32::decode(recovery_key.deref()) {
+                Some(decoded) => unwrap_key(crypto, KEY_SLOT_RECOVERY_KEY, secret_key(&SafeVec::new(decoded)).deref(), key_slots),
+                None => Err(PasswordError::WrongMasterPasswordError),
+            }
+        },
+        Unlock::YubiKey => {
+            for key_slot in key_slots.iter().filter(|key_slot| key_slot.kind == KEY_SLOT_YUBIKEY && key_slot.wrapped.len() > YUBIKEY_CHALLENGE_LEN) {
+                let (challenge, wrapped) = key_slot.wrapped.split_at(YUBIKEY_CHALLENGE_LEN);
+                match yubikey::challenge_response(challenge) {
+                    Ok(response) => {
+                        if let Ok(key) = unseal_with(crypto, secret_key(response.deref()).deref(), wrapped) {
+                            return Ok(key);
+                        }
+                    },
+                    Err(err) => { println_stderr!("The YubiKey key slot did not open: {}.", err); }
                 }
             }
-        });
-        try!(match file.write_u32::<BigEndian>(self.scrypt_p) {
+            Err(PasswordError::WrongMasterPasswordError)
+        },
+        Unlock::Fido2 => {
+            let device = try!(fido2::device().map_err(|err| {
+                println_stderr!("The FIDO2 key slots did not open: {}.", err);
+                PasswordError::WrongMasterPasswordError
+            }));
+            // Each authenticator only knows its own credentials, so the key slots of the
+            // others fail.
+            for key_slot in key_slots.iter().filter(|key_slot| key_slot.kind == KEY_SLOT_FIDO2) {
+                if let Some((credential_id, salt, wrapped)) = split_fido2_key_slot(&key_slot.wrapped) {
+                    match fido2::hmac_secret(&device, credential_id, salt) {
+                        Ok(secret) => {
+                            if let Ok(key) = unseal_with(crypto, secret_key(secret.deref()).deref(), wrapped) {
+                                return Ok(key);
+                            }
+                        },
+                        Err(err) => { println_stderr!("The FIDO2 key slot {} did not open: {}.", key_slot.label, err); }
+                    }
+                }
+            }
+            Err(PasswordError::WrongMasterPasswordError)
+        },
+        Unlock::Tpm(ref pin) => {
+            // The TPMs of other machines do not load the blobs of this one.
+            for key_slot in key_slots.iter().filter(|key_slot| key_slot.kind == KEY_SLOT_TPM) {
+                if let Some((sealed, wrapped)) = split_tpm_key_slot(&key_slot.wrapped) {
+                    match keywrap::unseal(&sealed, pin) {
+                        Ok(secret) => {
+                            if let Ok(key) = unseal_with(crypto, secret_key(secret.deref()).deref(), wrapped) {
+                                return Ok(key);
+                            }
+                        },
+                        Err(err) => { println_stderr!("The TPM key slot {} did not open: {}.", key_slot.label, err); }
+                    }
+                }
+            }
+            Err(PasswordError::WrongMasterPasswordError)
+        },
+        Unlock::Gpg => {
+            for key_slot in key_slots.iter().filter(|key_slot| key_slot.kind == KEY_SLOT_GPG || key_slot.kind == KEY_SLOT_MEMBER) {
+                match gpg::decrypt(&key_slot.wrapped) {
+                    Ok(key) => { return Ok(key); },
+                    Err(err) => { println_stderr!("The GPG key slot for {} did not open: {}.", key_slot.label, err); }
+                }
+            }
+            Err(PasswordError::WrongMasterPasswordError)
+        },
+        Unlock::Key(ref key) => Ok(key.clone()),
+    }
+}
+
+pub struct PasswordStore {
+    /// Derived from the master password, or random for vaults with key slots.
+    key: SafeVec,
+    scrypt_log2_n: u8,
+    scrypt_r: u32,
+    scrypt_p: u32,
+    salt: [u8; SALT_LEN],
+    /// See `provider::provider`.
+    provider: u8,
+    schema: Schema,
+    /// Which vault of the file this is.
+    slot: usize,
+    /// Every vault of the file as it was read, with its flags and crypto provider. See
+    /// `other_vault` for how the others are written back.
+    slots: Vec<Vec<u8>>,
+    /// Empty when the key is derived from the master password.
+    key_slots: Vec<KeySlot>,
+}
+
+/// Read and writes to a Rooster file
+///
+/// The Rooster file has the following format:
+/// - rooster version: u32, big endian
+/// - vault count:     u8, only from version 4
+/// - for each vault, from version 4:
+///   - vault length:  u32, big endian, with the flags from version 5
+///   - flags:         u8, only from version 5, see `FLAG_DEFLATE` and the others
+///   - provider:      u8, only from version 5, see `provider::provider`
+///   - vault:         see below
+///
+/// Until version 3, the file has a single vault right after the version. A vault is:
+/// - scrypt log2_n:  u8
+/// - scrypt r:       u32, big endian
+/// - scrypt p:       u32, big endian
+/// - salt:           256 bits
+/// - key slots:      only with `FLAG_KEY_SLOTS`, see `write_key_slots`
+/// - nonce:          192 bits, only with `FLAG_WRAPPED`, which wraps everything below
+/// - length:         u32, big endian, of everything below but the random bytes, only
+///                   with `FLAG_WRAPPED`
+/// - iv:             as long as the crypto provider says, 128 bits for AES-256-CBC
+/// - password check: as long as a signature, only from version 3
+/// - signature:      as long as the crypto provider says, 512 bits for HMAC-SHA512
+/// - encrypted blob: variable length
+/// - random bytes:   only with `FLAG_WRAPPED`, up to the size of the other vaults
+impl PasswordStore {
+    pub fn new(master_password: SafeString) -> IoResult<PasswordStore> {
+        let salt = try!(generate_random_salt());
+
+        let kdf_params = KdfParams {
+            log2_n: SCRYPT_PARAM_LOG2_N,
+            r: SCRYPT_PARAM_R,
+            p: SCRYPT_PARAM_P,
+        };
+
+        let crypto = try!(crypto(CRYPTO_PROVIDER).map_err(|_| IoError::new(IoErrorKind::Other, "unknown crypto provider")));
+        let key = generate_encryption_key(crypto, kdf_params, master_password.deref(), salt);
+
+        // Where the vault goes is random, so that it says nothing about a decoy vault.
+        let slots = vec![Vec::new(); SLOTS];
+        let slot = try!(OsRng::new()).gen_range(0, SLOTS);
+
+        let mut schema = Schema::new();
+        schema.other_vaults = Some(fillers(slot));
+
+        Ok(PasswordStore {
+            key: key,
+            scrypt_log2_n: SCRYPT_PARAM_LOG2_N,
+            scrypt_r: SCRYPT_PARAM_R,
+            scrypt_p: SCRYPT_PARAM_P,
+            salt: salt,
+            provider: CRYPTO_PROVIDER,
+            schema: schema,
+            slot: slot,
+            slots: slots,
+            key_slots: Vec::new(),
+        })
+    }
+
+    pub fn from_input(master_password: SafeString, input: SafeVec) -> Result<PasswordStore, PasswordError> {
+        PasswordStore::open(&Unlock::MasterPassword(master_password), input)
+    }
+
+    /// Opens the vault of the file that `unlock` opens.
+    pub fn open(unlock: &Unlock, input: SafeVec) -> Result<PasswordStore, PasswordError> {
+        PasswordStore::open_with(input.deref(), |version, slot| PasswordStore::open_slot(version, unlock, slot))
+    }
+
+    /// Opens the vault of another copy of the file with the key of this one, like the
+    /// file as a sync left it, without asking for the master password again.
+    pub fn reopen(&self, input: &[u8]) -> Result<PasswordStore, PasswordError> {
+        PasswordStore::open_with(input, |version, slot| {
+            PasswordStore::open_slot_with_key(version, signed_version(version, slot), slot, self.key.clone())
+        })
+    }
+
+    /// Opens the vault of the file that `open_slot` opens.
+    fn open_with<F: Fn(u32, &Slot) -> Result<PasswordStore, PasswordError>>(input: &[u8], open_slot: F) -> Result<PasswordStore, PasswordError> {
+        let (version, slots) = try!(read_slots(input));
+
+        // Every vault is tried, even after the right one, so that the time it takes does
+        // not tell which one was opened.
+        let mut opened = Err(PasswordError::WrongMasterPasswordError);
+        for (i, slot) in slots.iter().enumerate() {
+            match open_slot(version, slot) {
+                Err(PasswordError::WrongMasterPasswordError) => {},
+                Ok(store) => {
+                    if opened.is_err() {
+                        opened = Ok((i, store));
+                    }
+                },
+                Err(err) => {
+                    if opened.is_err() {
+                        opened = Err(err);
+                    }
+                }
+            }
+        }
+        let (slot, mut store) = try!(opened);
+
+        if version == VERSION_WITHOUT_PASSWORD_CHECK || version == VERSION_SINGLE_VAULT {
+            // Saved in the current version, with random bytes where a decoy vault could be.
+            store.slot = 0;
+            store.slots = vec![Vec::new(); SLOTS];
+            store.schema.other_vaults = Some(fillers(0));
+            return Ok(store);
+        }
+
+        // Vaults are kept with their flags and crypto provider, the way they are written
+        // back.
+        store.slot = slot;
+        store.slots = slots.iter().map(|slot| {
+            let mut bytes = vec![slot.flags, slot.provider];
+            bytes.extend_from_slice(slot.vault);
+            bytes
+        }).collect();
+        Ok(store)
+    }
+
+    /// Opens one vault of the file, which has the given version.
+    fn open_slot(version: u32, unlock: &Unlock, slot: &Slot) -> Result<PasswordStore, PasswordError> {
+        let header = try!(read_vault(version, slot));
+        let crypto = try!(crypto(slot.provider));
+
+        // Derive a 256 bits encryption key from the password.
+        let kdf_params = KdfParams {
+            log2_n: header.scrypt_log2_n,
+            r: header.scrypt_r,
+            p: header.scrypt_p,
+        };
+        let key = try!(unlock_key(crypto, unlock, kdf_params, header.salt, &header.key_slots));
+        let opened = PasswordStore::open_slot_with_key(version, signed_version(version, slot), slot, key);
+        match (unlock, opened) {
+            // The key of another vault, or of this one before `rekey`, is just a wrong key.
+            (&Unlock::Key(_), Err(PasswordError::CorruptionError)) => Err(PasswordError::WrongMasterPasswordError),
+            (_, opened) => opened,
+        }
+    }
+
+    /// Opens one vault of the file with its key, see `open_slot`.
+    fn open_slot_with_key(version: u32, signed_version: u32, slot: &Slot, key: SafeVec) -> Result<PasswordStore, PasswordError> {
+        let header = try!(read_vault(version, slot));
+        if slot.flags & FLAG_WRAPPED == 0 {
+            return PasswordStore::open_unwrapped(signed_version, slot, &header, key);
+        }
+
+        // A wrong key unwraps random bytes, which most likely have no sane length, and
+        // otherwise fail the password check.
+        let wrong_key = || if header.key_slots.is_empty() { PasswordError::WrongMasterPasswordError } else { PasswordError::CorruptionError };
+        let unwrapped = try!(unwrap_vault(slot.vault, clear_len(&header), wrap_key(key.deref()).deref()).ok_or_else(|| wrong_key()));
+        let unwrapped_slot = Slot {
+            flags: slot.flags,
+            provider: slot.provider,
+            vault: &unwrapped,
+        };
+        let unwrapped_header = try!(read_vault(version, &unwrapped_slot).map_err(|_| wrong_key()));
+        PasswordStore::open_unwrapped(signed_version, &unwrapped_slot, &unwrapped_header, key)
+    }
+
+    /// Opens a vault that is not wrapped, or no longer, see `open_slot_with_key`.
+    fn open_unwrapped(signed_version: u32, slot: &Slot, header: &VaultHeader, key: SafeVec) -> Result<PasswordStore, PasswordError> {
+        let crypto = try!(crypto(slot.provider));
+        let kdf_params = KdfParams {
+            log2_n: header.scrypt_log2_n,
+            r: header.scrypt_r,
+            p: header.scrypt_p,
+        };
+
+        // With a password check, we know for sure whether the master password is wrong.
+        // With key slots, the key was unwrapped, so it can only be wrong if the file is
+        // damaged.
+        if !header.check.is_empty() && !crypto.verify_mac(key.deref(), PASSWORD_CHECK_MESSAGE, header.check) {
+            if header.key_slots.is_empty() {
+                return Err(PasswordError::WrongMasterPasswordError);
+            }
+            return Err(PasswordError::CorruptionError);
+        }
+
+        // Check the signature against what it should be.
+        let signed = try!(signed_data(signed_version, slot.flags, slot.provider, header.scrypt_log2_n, header.scrypt_r, header.scrypt_p, header.iv, &header.salt, &write_key_slots(&header.key_slots), header.check, header.blob).map_err(|io_err| {
+            PasswordError::Io(io_err)
+        }));
+        if !crypto.verify_mac(key.deref(), &signed, header.signature) {
+            return Err(PasswordError::CorruptionError);
+        }
+
+        // Decrypt the data.
+        let schema = match crypto.decrypt(key.as_ref(), header.iv, header.blob) {
+            Ok(decrypted) => {
+                let decrypted = if slot.flags & FLAG_PADDED != 0 {
+                    SafeVec::new(try!(unpad(decrypted.deref())).to_vec())
+                } else {
+                    decrypted
+                };
+                let decrypted = if slot.flags & FLAG_DEFLATE != 0 {
+                    try!(decompress(decrypted.deref()))
+                } else {
+                    decrypted
+                };
+                let encoded = match str::from_utf8(decrypted.deref()) {
+                    Ok(encoded) => encoded,
+                    Err(err) => {
+                        return Err(PasswordError::Utf8Error(err.valid_up_to()));
+                    }
+                };
+                match serde_json::from_str::<Schema>(encoded) {
+                    Ok(json) => json,
+                    Err(err) => {
+                        return Err(PasswordError::JsonError(byte_offset(encoded, err.line(), err.column())));
+                    }
+                }
+            },
+            Err(_) => {
+                return Err(PasswordError::DecryptionError);
+            }
+        };
+
+        let mut schema = schema;
+        if schema.metadata.is_none() {
+            schema.metadata = Some(Metadata::new(None, kdf_params));
+        }
+
+        Ok(PasswordStore {
+            key: key,
+            scrypt_log2_n: header.scrypt_log2_n,
+            scrypt_r: header.scrypt_r,
+            scrypt_p: header.scrypt_p,
+            salt: header.salt,
+            provider: slot.provider,
+            schema: schema,
+            slot: 0,
+            slots: Vec::new(),
+            key_slots: header.key_slots.clone(),
+        })
+    }
+
+    pub fn sync(&self, backend: &mut VaultBackend) -> Result<(), PasswordError> {
+        let mut content = Vec::new();
+        try!(self.write_to(&mut content));
+
+        try!(backend.write(&content).map_err(|err| PasswordError::Io(err)));
+        Ok(())
+    }
+
+    /// Encrypts the passwords and writes them in the Rooster file format. Every vault is
+    /// wrapped to the same size, the next power of two of the largest one, so that the
+    /// file says nothing about which vault grew.
+    pub fn write_to<W: Write>(&self, file: &mut W) -> Result<(), PasswordError> {
+        // The other vaults first, since this vault says how to write them back next time.
+        let header_digest = try!(self.header_digest());
+        let mut others = Vec::new();
+        let mut entries = Vec::new();
+        for (i, bytes) in self.slots.iter().enumerate() {
+            if i == self.slot {
+                continue;
+            }
+            let entry = self.schema.other_vaults.as_ref().and_then(|others| others.iter().find(|other| other.slot == i));
+            match entry {
+                Some(entry) => {
+                    let (content, entry) = try!(self.other_vault(entry, bytes, &header_digest));
+                    others.push(content);
+                    entries.push(entry);
+                },
+                None => others.push(OtherContent::Unchanged(bytes.clone())),
+            }
+        }
+        let mut schema = self.schema.clone();
+        if schema.other_vaults.is_some() {
+            schema.other_vaults = Some(entries);
+        }
+        let vault = try!(self.seal_vault(&schema));
+        let len = others.iter().map(|other| other.len()).fold(vault.len(), max).next_power_of_two();
+
+        // Write the file version.
+        try!(match file.write_u32::<BigEndian>(VERSION) {
             Ok(_) => Ok(()),
             Err(err) => {
                 match err {
@@ -409,26 +1828,290 @@ impl PasswordStore {
             }
         });
 
+        // Write every vault, ours in its place and the others as `other_vault` says.
+        try!(file.write_all(&[self.slots.len() as u8]).map_err(|err| PasswordError::Io(err)));
+        let mut others = others.into_iter();
+        for i in 0..self.slots.len() {
+            let content = if i == self.slot {
+                try!(vault.wrap(len))
+            } else {
+                match others.next() {
+                    Some(OtherContent::Unchanged(bytes)) => bytes,
+                    Some(OtherContent::Rewrapped(bytes, wrap_key)) => try!(rewrap(&bytes, wrap_key.deref(), len)),
+                    Some(OtherContent::Filler(wrap_key)) => try!(self.generate_filler(wrap_key.deref(), len)),
+                    Some(OtherContent::Decoy(decoy)) => try!(decoy.wrap(len)),
+                    None => unreachable!(),
+                }
+            };
+            try!(match file.write_u32::<BigEndian>(content.len() as u32) {
+                Ok(_) => Ok(()),
+                Err(err) => {
+                    match err {
+                        ByteorderError::Io(err) => Err(PasswordError::Io(err)),
+                        _ => Err(PasswordError::Io(IoError::new(IoErrorKind::Other, "unknown")))
+                    }
+                }
+            });
+            try!(file.write_all(&content).map_err(|err| PasswordError::Io(err)));
+        }
+
+        Ok(())
+    }
+
+    /// How another vault of the file is written back, so that comparing two copies of
+    /// the file does not tell which vault was opened, and what this vault then says about
+    /// it. The other vault takes up the clear part of this one: when it changes, with a
+    /// new key, new key slots or new scrypt parameters, random bytes are drawn again to
+    /// look like it, and a decoy vault gets a key derived the same way. Otherwise random
+    /// bytes and vaults this one can't read get a new wrapping, and a decoy vault is
+    /// encrypted again. A decoy vault whose key changed since it was set up is written
+    /// back as it was read.
+    ///
+    /// Since a decoy vault can't tell the vault that set it up from random bytes, it draws
+    /// random bytes in its place if its own clear part changes: a decoy vault must not get
+    /// a new key or new key slots.
+    fn other_vault(&self, entry: &OtherVault, bytes: &[u8], header_digest: &str) -> Result<(OtherContent, OtherVault), PasswordError> {
+        let mirrored = entry.mirrored.as_ref().map_or(false, |mirrored| mirrored == header_digest);
+        let decoy_key = match entry.decoy_key {
+            Some(ref decoy_key) => decoy_key,
+            None => {
+                let wrap_key = entry.wrap_key.as_ref().and_then(|wrap_key| wrap_key.from_base64().ok()).map(SafeVec::new);
+                if let (true, Some(wrap_key)) = (mirrored, wrap_key) {
+                    return Ok((OtherContent::Rewrapped(bytes.to_vec(), wrap_key), entry.clone()));
+                }
+                let wrap_key = try!(generate_random_key().map_err(|io_err| PasswordError::Io(io_err)));
+                let entry = OtherVault {
+                    slot: entry.slot,
+                    decoy_key: None,
+                    decoy_master_password: None,
+                    wrap_key: Some(SafeString::new(wrap_key.to_base64(STANDARD))),
+                    mirrored: Some(header_digest.to_string()),
+                };
+                return Ok((OtherContent::Filler(wrap_key), entry));
+            },
+        };
+
+        let mut decoy = match PasswordStore::open_decoy(decoy_key, bytes) {
+            Some(decoy) => decoy,
+            None => { return Ok((OtherContent::Unchanged(bytes.to_vec()), entry.clone())); },
+        };
+        let mut entry = entry.clone();
+        if !mirrored {
+            if let Some(master_password) = entry.decoy_master_password.clone() {
+                try!(self.mirror(&mut decoy, &master_password));
+                entry.decoy_key = Some(SafeString::new(decoy.key.to_base64(STANDARD)));
+                entry.mirrored = Some(header_digest.to_string());
+            }
+        }
+
+        // The decoy vault rewraps the others in turn, like random bytes would be by a
+        // vault without a decoy.
+        let decoy_digest = try!(decoy.header_digest());
+        let mut decoy_entries = Vec::new();
+        for i in (0..self.slots.len()).filter(|&i| i != entry.slot) {
+            let wrap_key = if i == self.slot {
+                Some(SafeString::new(wrap_key(self.key.deref()).to_base64(STANDARD)))
+            } else {
+                self.schema.other_vaults.as_ref()
+                    .and_then(|others| others.iter().find(|other| other.slot == i))
+                    .and_then(|other| other.wrap_key.clone())
+            };
+            decoy_entries.push(OtherVault {
+                slot: i,
+                decoy_key: None,
+                decoy_master_password: None,
+                wrap_key: wrap_key,
+                mirrored: Some(decoy_digest.clone()),
+            });
+        }
+        decoy.schema.other_vaults = Some(decoy_entries);
+        Ok((OtherContent::Decoy(try!(decoy.seal_vault(&decoy.schema))), entry))
+    }
+
+    /// Opens the decoy vault in these bytes with its key, see `other_vault`.
+    fn open_decoy(decoy_key: &SafeString, bytes: &[u8]) -> Option<PasswordStore> {
+        let key = match decoy_key.from_base64() {
+            Ok(key) => SafeVec::new(key),
+            Err(_) => { return None; },
+        };
+        if bytes.len() < 2 {
+            return None;
+        }
+        let slot = Slot {
+            flags: bytes[0],
+            provider: bytes[1],
+            vault: &bytes[2..],
+        };
+        PasswordStore::open_slot_with_key(VERSION, VERSION, &slot, key).ok()
+    }
+
+    /// Gives a decoy vault the clear part of this one: its crypto provider and scrypt
+    /// parameters, a new salt, and key slots of the same kinds and sizes, of which only
+    /// the master password ones open it. Its key is then the one derived from its master
+    /// password.
+    fn mirror(&self, decoy: &mut PasswordStore, master_password: &SafeString) -> Result<(), PasswordError> {
+        let crypto = self.crypto();
+        let salt = try!(generate_random_salt().map_err(|io_err| PasswordError::Io(io_err)));
+        let derived = generate_encryption_key(crypto, self.kdf_params(), master_password.deref(), salt);
+
+        let opens = |key_slot: &KeySlot| key_slot.kind == KEY_SLOT_MASTER_PASSWORD || key_slot.kind == KEY_SLOT_MASTER_PASSWORD_YUBIKEY;
+        let mut key_slots = Vec::new();
+        if self.key_slots.iter().any(&opens) {
+            for key_slot in self.key_slots.iter() {
+                let wrapped = match key_slot.kind {
+                    KEY_SLOT_MASTER_PASSWORD => try!(seal_with(crypto, derived.deref(), derived.deref())),
+                    KEY_SLOT_MASTER_PASSWORD_YUBIKEY => try!(wrap_with_second_factor(crypto, derived.deref(), derived.deref()).map_err(|err| {
+                        PasswordError::Io(IoError::new(IoErrorKind::Other, err))
+                    })),
+                    _ => try!(random_bytes(key_slot.wrapped.len()).map_err(|io_err| PasswordError::Io(io_err))),
+                };
+                key_slots.push(KeySlot {
+                    kind: key_slot.kind,
+                    label: key_slot.label.clone(),
+                    wrapped: wrapped,
+                });
+            }
+        }
+
+        decoy.key = derived;
+        decoy.scrypt_log2_n = self.scrypt_log2_n;
+        decoy.scrypt_r = self.scrypt_r;
+        decoy.scrypt_p = self.scrypt_p;
+        decoy.salt = salt;
+        decoy.provider = self.provider;
+        decoy.key_slots = key_slots;
+        // So that the decoy vault suggests a new key when this one does.
+        decoy.schema.metadata = self.schema.metadata.clone();
+        Ok(())
+    }
+
+    /// The flags of this vault.
+    fn flags(&self) -> u8 {
+        if self.key_slots.is_empty() { FLAGS } else { FLAGS | FLAG_KEY_SLOTS }
+    }
 
-        // Write the key derivation salt.
-        try!(file.write_all(&self.salt).map_err(|err| PasswordError::Io(err)));
+    /// The flags, the crypto provider and the clear part of this vault, with the given
+    /// salt and key slots.
+    fn clear_header(&self, salt: &[u8], key_slots: &[KeySlot]) -> Result<Vec<u8>, PasswordError> {
+        let mut header = vec![self.flags(), self.provider];
+        try!(write_kdf_params(&mut header, self.scrypt_log2_n, self.scrypt_r, self.scrypt_p));
+        header.extend_from_slice(salt);
+        header.extend(write_key_slots(key_slots));
+        Ok(header)
+    }
 
-        // Write the encryption IV.
-        try!(file.write_all(&iv).map_err(|err| PasswordError::Io(err)));
+    /// The hex SHA-256 of the clear part of this vault, see `OtherVault::mirrored`.
+    fn header_digest(&self) -> Result<String, PasswordError> {
+        let header = try!(self.clear_header(&self.salt, &self.key_slots));
+        let mut hasher = Sha256::new();
+        hasher.input(&header);
+        Ok(hasher.result_str())
+    }
 
-        // Write the file signature.
-        let signature = try!(digest(self.key.deref(), VERSION, self.scrypt_log2_n, self.scrypt_r, self.scrypt_p, &iv, &self.salt, encrypted.as_ref()).map_err(|io_err| {
+    /// Random bytes that look like this vault to anyone without its key: the same flags,
+    /// crypto provider, scrypt parameters and key slots, with random bytes in place of
+    /// the salt and the wrapped keys, then `len` bytes in all, wrapped with `wrap_key`
+    /// like a vault with the data of a small one.
+    fn generate_filler(&self, wrap_key: &[u8], len: usize) -> Result<Vec<u8>, PasswordError> {
+        let crypto = self.crypto();
+        let mut key_slots = Vec::new();
+        for key_slot in self.key_slots.iter() {
+            key_slots.push(KeySlot {
+                kind: key_slot.kind,
+                label: key_slot.label.clone(),
+                wrapped: try!(random_bytes(key_slot.wrapped.len()).map_err(|io_err| PasswordError::Io(io_err))),
+            });
+        }
+        let salt = try!(random_bytes(SALT_LEN).map_err(|io_err| PasswordError::Io(io_err)));
+        let header = try!(self.clear_header(&salt, &key_slots));
+
+        // The encrypted data is padded data, see `pad`.
+        let mut rng = try!(OsRng::new().map_err(|io_err| PasswordError::Io(io_err)));
+        let body_len = crypto.iv_len() + 2 * crypto.mac_len() + crypto.encrypted_len(PADDED_LEN_MIN << rng.gen_range(0, 4));
+        let room = len.saturating_sub(header.len() + WRAP_NONCE_LEN + 4);
+        let mut plain = Vec::new();
+        try!(plain.write_u32::<BigEndian>(::std::cmp::min(body_len, room) as u32).map_err(|_| PasswordError::EncryptionError));
+        plain.extend(try!(random_bytes(room).map_err(|io_err| PasswordError::Io(io_err))));
+        wrap(&header, wrap_key, &plain)
+    }
+
+    /// Encrypts the passwords, with the given schema, as one vault of the file.
+    fn seal_vault(&self, schema: &Schema) -> Result<SealedVault, PasswordError> {
+        // This should never fail. The structs are all encodable.
+        let json_schema = match serde_json::to_string(schema) {
+            Ok(json_schema) => json_schema,
+            Err(_) => {
+                return Err(PasswordError::InvalidJsonError);
+            }
+        };
+        let json_schema = SafeString::new(json_schema);
+        let compressed = try!(compress(json_schema.deref().as_bytes()));
+        let padded = try!(pad(compressed.deref()));
+
+        // Encrypt the data with a new IV.
+        let crypto = self.crypto();
+        let iv = try!(generate_random_iv(crypto).map_err(|io_err| PasswordError::Io(io_err)));
+        let encrypted = try!(crypto.encrypt(self.key.as_ref(), iv.as_ref(), padded.deref()));
+
+        // The flags, the crypto provider, the scrypt params, the salt and the key slots.
+        let header = try!(self.clear_header(&self.salt, &self.key_slots));
+
+        // The IV, the password check, the signature and the encrypted password data.
+        let check = password_check(crypto, self.key.deref());
+        let signed = try!(signed_data(VERSION, self.flags(), self.provider, self.scrypt_log2_n, self.scrypt_r, self.scrypt_p, &iv, &self.salt, &write_key_slots(&self.key_slots), &check, encrypted.as_ref()).map_err(|io_err| {
             PasswordError::Io(io_err)
-        })).result();
-        try!(file.write_all(signature.code()).map_err(|err| PasswordError::Io(err)));
+        }));
+        let mut body = iv;
+        body.extend_from_slice(&check);
+        body.extend(crypto.mac(self.key.deref(), &signed));
+        body.extend_from_slice(encrypted.as_ref());
 
-        // Write the encrypted password data.
-        try!(file.write_all(&encrypted.as_ref()).map_err(|err| PasswordError::Io(err)));
+        Ok(SealedVault {
+            header: header,
+            body: body,
+            wrap_key: wrap_key(self.key.deref()),
+        })
+    }
+
+    /// Puts a new, empty vault for this master password in place of the other vault of
+    /// the file, whether it was a decoy vault or random bytes. It takes up the clear part
+    /// of this vault on the next save, see `other_vault`.
+    pub fn set_decoy(&mut self, master_password: SafeString) -> Result<(), PasswordError> {
+        let decoy = try!(PasswordStore::new(master_password.clone()).map_err(|io_err| PasswordError::Io(io_err)));
+        let vault = try!(decoy.seal_vault(&decoy.schema));
+        let other = (self.slot + 1) % self.slots.len();
+        self.slots[other] = try!(vault.wrap(vault.len()));
 
-        try!(file.sync_all().map_err(|err| PasswordError::Io(err)));
+        // Kept so that the decoy vault changes along with this one.
+        let slot = self.slot;
+        let others = self.schema.other_vaults.get_or_insert_with(|| fillers(slot));
+        others.retain(|vault| vault.slot != other);
+        others.push(OtherVault {
+            slot: other,
+            decoy_key: Some(SafeString::new(decoy.key.to_base64(STANDARD))),
+            decoy_master_password: Some(master_password),
+            wrap_key: None,
+            mirrored: None,
+        });
         Ok(())
     }
 
+    /// Encrypts and signs data with the key of the password file, for files that go
+    /// along with it, like the journal.
+    pub fn seal(&self, data: &[u8]) -> Result<Vec<u8>, PasswordError> {
+        seal_with(self.crypto(), self.key.deref(), data)
+    }
+
+    /// Checks and decrypts data from `seal`.
+    pub fn unseal(&self, sealed: &[u8]) -> Result<SafeVec, PasswordError> {
+        unseal_with(self.crypto(), self.key.deref(), sealed)
+    }
+
+    /// The crypto provider of the vault, which was checked when it was opened.
+    fn crypto(&self) -> &'static CryptoProvider {
+        crypto(self.provider).expect("the crypto provider of an open vault is always available")
+    }
+
     pub fn get_all_passwords(&self) -> &[Password] {
         self.schema.passwords.deref()
     }
@@ -474,19 +2157,445 @@ impl PasswordStore {
             }
             return Some(p.clone());
         }
-        None
+
+        // Names come first, so an alias can never hide an app.
+        let name = name.to_lowercase();
+        self.schema.passwords.iter()
+            .find(|p| p.aliases.as_ref().map_or(false, |aliases| aliases.iter().any(|alias| alias.to_lowercase() == name)))
+            .cloned()
+    }
+
+    /// Records that the password for this app was just read.
+    pub fn record_use(&mut self, name: &str) -> Result<(), PasswordError> {
+        let p = try!(self.get_password(name).ok_or(PasswordError::NoSuchAppError));
+
+        for password in self.schema.passwords.iter_mut() {
+            if password.name == p.name {
+                password.record_use();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sharing_key(&self) -> Option<&SafeString> {
+        self.schema.sharing_key.as_ref()
+    }
+
+    pub fn set_sharing_key(&mut self, key: SafeString) {
+        self.schema.sharing_key = Some(key);
     }
 
     pub fn has_password(&self, name: &str) -> bool {
         self.get_password(name).is_some()
     }
 
-    pub fn change_master_password(&mut self, master_password: &str) {
-        let scrypt_params = scrypt::ScryptParams::new(
-            self.scrypt_log2_n,
-            self.scrypt_r,
-            self.scrypt_p
-        );
-        self.key = generate_encryption_key(scrypt_params, master_password, self.salt);
+    /// Bumps the field clocks of the apps that changed since `base`.
+    pub fn tick(&mut self, base: &[Password]) {
+        for password in self.schema.passwords.iter_mut() {
+            if let Some(before) = base.iter().find(|p| p.name.to_lowercase() == password.name.to_lowercase()) {
+                password.tick(before);
+            }
+        }
+    }
+
+    /// Merges the changes made to `theirs` since `base` into this store, which also
+    /// started from `base`. Apps both sides changed are merged field by field, and an
+    /// app deleted on one side but changed on the other is kept. Returns the names of
+    /// the apps where both sides changed the same field.
+    pub fn merge(&mut self, base: &[Password], theirs: &PasswordStore) -> Vec<String> {
+        fn find(passwords: &[Password], name: &str) -> Option<Password> {
+            passwords.iter().find(|p| p.name.to_lowercase() == name.to_lowercase()).cloned()
+        }
+
+        // Passwords have no PartialEq, the digest of their JSON will do.
+        fn same(a: &Option<Password>, b: &Option<Password>) -> bool {
+            digest(a) == digest(b)
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for p in base.iter().chain(self.schema.passwords.iter()).chain(theirs.schema.passwords.iter()) {
+            if !names.iter().any(|name| name.to_lowercase() == p.name.to_lowercase()) {
+                names.push(p.name.clone());
+            }
+        }
+
+        let mut merged = Vec::new();
+        let mut conflicts = Vec::new();
+        for name in names.iter() {
+            let base_p = find(base, name);
+            let our_p = find(&self.schema.passwords, name);
+            let their_p = find(&theirs.schema.passwords, name);
+
+            if same(&our_p, &base_p) {
+                merged.extend(their_p);
+            } else if same(&their_p, &base_p) || same(&our_p, &their_p) {
+                merged.extend(our_p);
+            } else {
+                match (our_p, their_p) {
+                    (Some(mut ours), Some(theirs)) => {
+                        if ours.merge_fields(&theirs) {
+                            conflicts.push(name.clone());
+                        }
+                        merged.push(ours);
+                    },
+                    (ours, theirs) => merged.extend(ours.or(theirs)),
+                }
+            }
+        }
+
+        self.schema.passwords = merged;
+        if self.schema.sharing_key.is_none() {
+            self.schema.sharing_key = theirs.schema.sharing_key.clone();
+        }
+        // A decoy vault may have been set in the meantime.
+        if theirs.slot == self.slot && theirs.slots.len() == self.slots.len() {
+            for (i, other) in theirs.slots.iter().enumerate() {
+                if i != self.slot {
+                    self.slots[i] = other.clone();
+                }
+            }
+            self.schema.other_vaults = theirs.schema.other_vaults.clone();
+        }
+        conflicts
+    }
+
+    /// Whether this is the master password of this vault.
+    pub fn is_master_password(&self, master_password: &str) -> bool {
+        let unlock = Unlock::MasterPassword(SafeString::new(master_password.to_string()));
+        match unlock_key(self.crypto(), &unlock, self.kdf_params(), self.salt, &self.key_slots) {
+            Ok(key) => key.len() == self.key.len() && fixed_time_eq(key.deref(), self.key.deref()),
+            Err(_) => false,
+        }
+    }
+
+    /// Wraps the key with a key derived from the master password, in the master password
+    /// key slot, along with the YubiKey if the vault needs it.
+    fn wrap_for_master_password(&mut self, master_password: &str) -> Result<(), PasswordError> {
+        let second_factor = self.requires_yubikey();
+        self.wrap_master_password_key_slot(master_password, second_factor)
+    }
+
+    fn wrap_master_password_key_slot(&mut self, master_password: &str, second_factor: bool) -> Result<(), PasswordError> {
+        let derived = generate_encryption_key(self.crypto(), self.kdf_params(), master_password, self.salt);
+        let key_slot = if second_factor {
+            KeySlot {
+                kind: KEY_SLOT_MASTER_PASSWORD_YUBIKEY,
+                label: "master password and yubikey".to_string(),
+                wrapped: try!(wrap_with_second_factor(self.crypto(), derived.deref(), self.key.deref()).map_err(|err| PasswordError::Io(IoError::new(IoErrorKind::Other, err)))),
+            }
+        } else {
+            KeySlot {
+                kind: KEY_SLOT_MASTER_PASSWORD,
+                label: "master password".to_string(),
+                wrapped: try!(seal_with(self.crypto(), derived.deref(), self.key.deref())),
+            }
+        };
+        self.key_slots.retain(|key_slot| key_slot.kind != KEY_SLOT_MASTER_PASSWORD && key_slot.kind != KEY_SLOT_MASTER_PASSWORD_YUBIKEY);
+        self.key_slots.insert(0, key_slot);
+        Ok(())
+    }
+
+    /// Whether the master password only opens the vault along with the YubiKey.
+    pub fn requires_yubikey(&self) -> bool {
+        self.key_slots.iter().any(|key_slot| key_slot.kind == KEY_SLOT_MASTER_PASSWORD_YUBIKEY)
+    }
+
+    /// Makes the master password only open the vault along with the YubiKey plugged in, as a
+    /// second factor. Other key slots still open it on their own.
+    pub fn require_yubikey(&mut self, master_password: &str) -> Result<(), String> {
+        if self.requires_yubikey() {
+            return Err("your password file already needs your YubiKey".to_string());
+        }
+        try!(self.enable_key_slots(master_password).map_err(|err| format!("I could not add key slots to the vault ({:?})", err)));
+        self.wrap_master_password_key_slot(master_password, true).map_err(|err| format!("I could not wrap the key ({:?})", err))
+    }
+
+    /// Makes the master password open the vault on its own again.
+    pub fn stop_requiring_yubikey(&mut self, master_password: &str) -> Result<(), String> {
+        if !self.requires_yubikey() {
+            return Err("your password file does not need your YubiKey".to_string());
+        }
+        self.wrap_master_password_key_slot(master_password, false).map_err(|err| format!("I could not wrap the key ({:?})", err))
+    }
+
+    pub fn key_slots(&self) -> &[KeySlot] {
+        &self.key_slots
+    }
+
+    /// Gives the vault key slots, if it has none yet. The data is then encrypted with a new
+    /// random key, which the master password wraps.
+    fn enable_key_slots(&mut self, master_password: &str) -> Result<(), PasswordError> {
+        if !self.key_slots.is_empty() {
+            return Ok(());
+        }
+        self.key = try!(generate_random_key().map_err(|io_err| PasswordError::Io(io_err)));
+        self.salt = try!(generate_random_salt().map_err(|io_err| PasswordError::Io(io_err)));
+        self.wrap_for_master_password(master_password)
+    }
+
+    /// Adds a key slot with the key encrypted to this GPG key, so that it can open the
+    /// vault without the master password.
+    pub fn add_gpg_key_slot(&mut self, master_password: &str, recipient: &str) -> Result<(), String> {
+        self.add_encrypted_key_slot(master_password, KEY_SLOT_GPG, recipient)
+    }
+
+    /// Adds a member to a team vault: a key slot with the key encrypted to their GPG key.
+    pub fn add_member(&mut self, master_password: &str, recipient: &str) -> Result<(), String> {
+        if self.key_slots.iter().any(|key_slot| key_slot.kind == KEY_SLOT_MEMBER && key_slot.label == recipient) {
+            return Err(format!("{} is already a member", recipient));
+        }
+        self.add_encrypted_key_slot(master_password, KEY_SLOT_MEMBER, recipient)
+    }
+
+    /// Removes a member from a team vault. Since they may have kept the key, the vault
+    /// gets a new one, see `rotate_key`, and the key slots that could not wrap it are
+    /// returned.
+    pub fn remove_member(&mut self, master_password: &str, recipient: &str) -> Result<Vec<KeySlot>, String> {
+        let before = self.key_slots.len();
+        self.key_slots.retain(|key_slot| key_slot.kind != KEY_SLOT_MEMBER || key_slot.label != recipient);
+        if self.key_slots.len() == before {
+            return Err(format!("{} is not a member", recipient));
+        }
+        self.rotate_key(master_password)
+    }
+
+    pub fn members(&self) -> Vec<&str> {
+        self.key_slots.iter().filter(|key_slot| key_slot.kind == KEY_SLOT_MEMBER).map(|key_slot| key_slot.label.deref()).collect()
+    }
+
+    /// Adds a key slot with the key encrypted to a GPG key.
+    fn add_encrypted_key_slot(&mut self, master_password: &str, kind: u8, recipient: &str) -> Result<(), String> {
+        if recipient.len() > 255 {
+            return Err("the GPG key ID is too long".to_string());
+        }
+        if self.key_slots.len() >= MAX_KEY_SLOTS {
+            return Err(format!("the vault already has {} key slots, the most it may have", MAX_KEY_SLOTS));
+        }
+        try!(self.enable_key_slots(master_password).map_err(|err| format!("I could not add key slots to the vault ({:?})", err)));
+        let wrapped = try!(gpg::encrypt(recipient, self.key.deref()));
+        self.key_slots.push(KeySlot {
+            kind: kind,
+            label: recipient.to_string(),
+            wrapped: wrapped,
+        });
+        Ok(())
+    }
+
+    /// Adds a key slot that wraps the key with a secret, after `prefix`.
+    fn add_secret_key_slot(&mut self, master_password: &str, kind: u8, label: &str, secret: &[u8], prefix: &[u8]) -> Result<(), String> {
+        if label.len() > 255 {
+            return Err("the name of the key slot is too long".to_string());
+        }
+        if self.key_slots.len() >= MAX_KEY_SLOTS {
+            return Err(format!("the vault already has {} key slots, the most it may have", MAX_KEY_SLOTS));
+        }
+        try!(self.enable_key_slots(master_password).map_err(|err| format!("I could not add key slots to the vault ({:?})", err)));
+        let mut wrapped = prefix.to_vec();
+        wrapped.extend(try!(seal_with(self.crypto(), secret_key(secret).deref(), self.key.deref()).map_err(|err| format!("I could not wrap the key ({:?})", err))));
+        self.key_slots.push(KeySlot {
+            kind: kind,
+            label: label.to_string(),
+            wrapped: wrapped,
+        });
+        Ok(())
+    }
+
+    /// Adds a key slot that the content of a key file opens. The label says which file.
+    pub fn add_keyfile_key_slot(&mut self, master_password: &str, label: &str, content: &[u8]) -> Result<(), String> {
+        self.add_secret_key_slot(master_password, KEY_SLOT_KEYFILE, label, content, &[])
+    }
+
+    /// Adds a key slot with a new random recovery key, and returns it for the user to
+    /// write down. It is not kept anywhere else.
+    pub fn add_recovery_key_slot(&mut self, master_password: &str) -> Result<SafeString, String> {
+        let mut recovery_key = SafeVec::new(vec![0u8; RECOVERY_KEY_LEN]);
+        try!(OsRng::new().map_err(|err| format!("I could not make a recovery key ({})", err))).fill_bytes(&mut recovery_key);
+        try!(self.add_secret_key_slot(master_password, KEY_SLOT_RECOVERY_KEY, "recovery key", recovery_key.deref(), &[]));
+
+        let encoded = SafeString::new(base32::encode(recovery_key.deref()));
+        let groups: Vec<String> = encoded.as_bytes().chunks(6).map(|group| String::from_utf8_lossy(group).into_owned()).collect();
+        Ok(SafeString::new(groups.join("-")))
+    }
+
+    /// Adds a key slot that the YubiKey plugged in opens, with a random challenge.
+    pub fn add_yubikey_key_slot(&mut self, master_password: &str) -> Result<(), String> {
+        let mut challenge = [0u8; YUBIKEY_CHALLENGE_LEN];
+        try!(OsRng::new().map_err(|err| format!("I could not make a challenge ({})", err))).fill_bytes(&mut challenge);
+        let response = try!(yubikey::challenge_response(&challenge));
+        self.add_secret_key_slot(master_password, KEY_SLOT_YUBIKEY, "yubikey", response.deref(), &challenge)
+    }
+
+    /// Adds a key slot that a FIDO2 authenticator opens, with a new credential on it and a
+    /// random salt. Each authenticator gets its own key slot.
+    pub fn add_fido2_key_slot(&mut self, master_password: &str, device: &fido2::Device) -> Result<(), String> {
+        let credential_id = try!(fido2::make_credential(device));
+        if credential_id.len() > u16::max_value() as usize {
+            return Err("the authenticator made a credential ID too long to keep".to_string());
+        }
+        let mut salt = [0u8; fido2::SALT_LEN];
+        try!(OsRng::new().map_err(|err| format!("I could not make a salt ({})", err))).fill_bytes(&mut salt);
+        let secret = try!(fido2::hmac_secret(device, &credential_id, &salt));
+        self.add_secret_key_slot(master_password, KEY_SLOT_FIDO2, &device.name, secret.deref(), &fido2_prefix(&credential_id, &salt))
+    }
+
+    /// Adds a key slot that the TPM of this machine opens for the PIN, with a random
+    /// secret sealed by it. The label says which machine.
+    pub fn add_tpm_key_slot(&mut self, master_password: &str, label: &str, pin: &SafeString) -> Result<(), String> {
+        let mut secret = SafeVec::new(vec![0u8; KEY_LEN]);
+        try!(OsRng::new().map_err(|err| format!("I could not make a secret ({})", err))).fill_bytes(&mut secret);
+        let sealed = try!(keywrap::seal(secret.deref(), pin));
+        if sealed.public.len() > u16::max_value() as usize || sealed.private.len() > u16::max_value() as usize {
+            return Err("the TPM sealed the secret in blobs too long to keep".to_string());
+        }
+        self.add_secret_key_slot(master_password, KEY_SLOT_TPM, label, secret.deref(), &tpm_prefix(&sealed))
+    }
+
+    /// Encrypts the vault with a new random key, which every key slot wraps again. GPG and
+    /// member key slots only need the public key for it. The others need their secret,
+    /// which the vault does not keep, so they are removed and returned.
+    fn rotate_key(&mut self, master_password: &str) -> Result<Vec<KeySlot>, String> {
+        let second_factor = self.requires_yubikey();
+        self.key = try!(generate_random_key().map_err(|err| format!("I could not make a new key ({})", err)));
+        let mut key_slots = Vec::new();
+        let mut removed = Vec::new();
+        for key_slot in self.key_slots.iter() {
+            match key_slot.kind {
+                KEY_SLOT_MASTER_PASSWORD | KEY_SLOT_MASTER_PASSWORD_YUBIKEY => {},
+                KEY_SLOT_GPG | KEY_SLOT_MEMBER => {
+                    key_slots.push(KeySlot {
+                        kind: key_slot.kind,
+                        label: key_slot.label.clone(),
+                        wrapped: try!(gpg::encrypt(&key_slot.label, self.key.deref())),
+                    });
+                },
+                _ => removed.push(key_slot.clone()),
+            }
+        }
+        self.key_slots = key_slots;
+        try!(self.wrap_master_password_key_slot(master_password, second_factor).map_err(|err| format!("I could not wrap the new key ({:?})", err)));
+        Ok(removed)
+    }
+
+    /// Removes a key slot, by its index in `key_slots`. The master password key slot stays,
+    /// and members are removed with `remove_member`.
+    pub fn remove_key_slot(&mut self, index: usize) -> Result<KeySlot, String> {
+        match self.key_slots.get(index) {
+            None => { return Err(format!("there is no key slot {}", index + 1)); },
+            Some(key_slot) if key_slot.kind == KEY_SLOT_MASTER_PASSWORD => {
+                return Err("the master password key slot cannot be removed".to_string());
+            },
+            Some(key_slot) if key_slot.kind == KEY_SLOT_MASTER_PASSWORD_YUBIKEY => {
+                return Err("the master password key slot cannot be removed, stop requiring the YubiKey with `rooster key remove-second-factor`".to_string());
+            },
+            // Removing a member gives the vault a new key, which `remove_member` does.
+            Some(key_slot) if key_slot.kind == KEY_SLOT_MEMBER => {
+                return Err(format!("{} is a member of the vault, remove them with `rooster member remove`", key_slot.label));
+            },
+            Some(_) => {},
+        }
+        Ok(self.key_slots.remove(index))
+    }
+
+    /// See `provider::provider`.
+    pub fn provider(&self) -> u8 {
+        self.provider
+    }
+
+    /// The key the vault is encrypted with, which `Unlock::Key` opens it with again.
+    pub fn key(&self) -> &SafeVec {
+        &self.key
+    }
+
+    pub fn kdf_params(&self) -> KdfParams {
+        KdfParams {
+            log2_n: self.scrypt_log2_n,
+            r: self.scrypt_r,
+            p: self.scrypt_p,
+        }
+    }
+
+    /// Derives a new key from the master password, with a new random salt and the given
+    /// scrypt parameters and crypto provider. The IV is new every time the file is
+    /// written anyway. Returns the key slots that could not wrap the new key, and were
+    /// removed.
+    pub fn rekey(&mut self, master_password: &str, kdf_params: KdfParams, provider: u8) -> IoResult<Vec<KeySlot>> {
+        try!(crypto(provider).map_err(|_| IoError::new(IoErrorKind::Other, "unknown crypto provider")));
+        let salt = try!(generate_random_salt());
+        self.salt = salt;
+        self.provider = provider;
+        self.scrypt_log2_n = kdf_params.log2_n;
+        self.scrypt_r = kdf_params.r;
+        self.scrypt_p = kdf_params.p;
+        let removed = if self.key_slots.is_empty() {
+            self.key = generate_encryption_key(self.crypto(), kdf_params, master_password, salt);
+            Vec::new()
+        } else {
+            try!(self.rotate_key(master_password).map_err(|err| IoError::new(IoErrorKind::Other, err)))
+        };
+
+        let metadata = self.schema.metadata.get_or_insert_with(|| Metadata::new(None, kdf_params));
+        metadata.rekeyed_at = Some(Timestamp::now());
+        let changed = metadata.kdf_history.last().map_or(true, |last| {
+            (last.log2_n, last.r, last.p) != (kdf_params.log2_n, kdf_params.r, kdf_params.p)
+        });
+        if changed {
+            metadata.kdf_history.push(KdfChange::new(kdf_params));
+        }
+        Ok(removed)
+    }
+
+    /// A hash of everything that gets written but when apps were last used, to tell
+    /// whether a command changed the vault.
+    pub fn fingerprint(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for password in self.schema.passwords.iter() {
+            let mut password = password.clone();
+            password.last_used_at = None;
+            password.use_count = None;
+            hash_json(&mut hasher, &password);
+        }
+        hash_json(&mut hasher, &(&self.schema.sharing_key, &self.schema.metadata));
+        hasher.input(self.key.deref());
+        hasher.input(&self.salt);
+        hasher.input(&[self.scrypt_log2_n, self.provider]);
+        hasher.input(format!("{} {}", self.scrypt_r, self.scrypt_p).as_bytes());
+        hasher.input(&write_key_slots(&self.key_slots));
+        for slot in self.slots.iter() {
+            hasher.input(slot);
+        }
+        let mut fingerprint = vec![0u8; hasher.output_bytes()];
+        hasher.result(&mut fingerprint);
+        fingerprint
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.schema.metadata.as_ref()
+    }
+
+    /// Why the vault should get a new key with `rooster rekey`, if it should.
+    pub fn rekey_reasons(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let default = default_kdf_params();
+        if self.scrypt_log2_n < default.log2_n || self.scrypt_r < default.r || self.scrypt_p < default.p {
+            reasons.push(format!(
+                "its scrypt parameters (log2_n {}, r {}, p {}) are weaker than the default ones (log2_n {}, r {}, p {})",
+                self.scrypt_log2_n, self.scrypt_r, self.scrypt_p, default.log2_n, default.r, default.p
+            ));
+        }
+        if let Some(ref metadata) = self.schema.metadata {
+            let days = metadata.key_since().days_ago();
+            if days > REKEY_AFTER_DAYS {
+                reasons.push(format!("it has had the same key for {} days", days));
+            }
+        }
+        reasons
+    }
+
+    pub fn change_master_password(&mut self, master_password: &str) -> Result<(), PasswordError> {
+        if self.key_slots.is_empty() {
+            self.key = generate_encryption_key(self.crypto(), self.kdf_params(), master_password, self.salt);
+            return Ok(());
+        }
+        self.wrap_for_master_password(master_password)
     }
 }
@@ -0,0 +1,79 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Plugins are programs named `rooster-<command>` on the PATH, like git and cargo have.
+//! `rooster <command>` runs them when Rooster has no such command, so importers and
+//! integrations can be shipped on their own.
+//!
+//! A plugin gets the password file in $ROOSTER_FILE and the Rooster program in
+//! $ROOSTER_BIN. To read or change passwords, it runs `$ROOSTER_BIN ipc`, which asks for
+//! the master password. $ROOSTER_SESSION is not passed along, a session only serves the
+//! shell that started it.
+
+use super::session;
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Environment variable holding the path to the Rooster program, for plugins.
+pub const BIN_ENV_VAR: &'static str = "ROOSTER_BIN";
+
+#[cfg(unix)]
+fn executable_names(command: &str) -> Vec<String> {
+    vec![format!("rooster-{}", command)]
+}
+
+#[cfg(windows)]
+fn executable_names(command: &str) -> Vec<String> {
+    vec![format!("rooster-{}.exe", command), format!("rooster-{}.bat", command), format!("rooster-{}.cmd", command)]
+}
+
+/// The plugin for this command, if there is one on the PATH.
+pub fn find(command: &str) -> Option<PathBuf> {
+    // Names with a path separator could point anywhere, they are not plugins.
+    if command.is_empty() || command.contains('/') || command.contains('\\') {
+        return None;
+    }
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => { return None; }
+    };
+    for dir in env::split_paths(&path) {
+        for name in executable_names(command) {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+/// Runs the plugin with the arguments that followed the command name, and returns its
+/// exit code.
+pub fn run(plugin: &PathBuf, args: &[String], password_file: &str) -> i32 {
+    let mut command = session::command(plugin);
+    command.args(args).env("ROOSTER_FILE", password_file);
+    if let Ok(bin) = env::current_exe() {
+        command.env(BIN_ENV_VAR, bin);
+    }
+    match command.status() {
+        // Plugins killed by a signal have no exit code.
+        Ok(status) => status.code().unwrap_or(1),
+        Err(err) => {
+            println_err!("Woops, I could not run {} ({}).", plugin.display(), err);
+            1
+        }
+    }
+}
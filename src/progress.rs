@@ -0,0 +1,74 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ffi;
+use super::output;
+use std::io::{stderr, Write};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Work that is done sooner than this shows nothing.
+const DELAY_MS: u64 = 300;
+
+/// How often the spinner turns.
+const TICK_MS: u64 = 100;
+
+const FRAMES: &'static [char] = &['|', '/', '-', '\\'];
+
+/// Runs the work on another thread. Meanwhile, if stderr is a terminal, a spinner and
+/// the time spent show after the message, so that nobody thinks Rooster hung. The main
+/// thread only waits, so Ctrl+C stops Rooster right away.
+pub fn run<T, F>(message: &str, work: F) -> T
+    where T: Send + 'static, F: FnOnce() -> T + Send + 'static {
+    if output::is_quiet() || !ffi::is_terminal(2) {
+        return work();
+    }
+
+    let (sender, receiver) = channel();
+    let worker = thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+
+    let started = Instant::now();
+    let mut shown = false;
+    let mut frame = 0;
+    let result;
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(if shown { TICK_MS } else { DELAY_MS })) {
+            Ok(done) => {
+                result = done;
+                break;
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                let elapsed = started.elapsed();
+                let _ = write!(stderr(), "\r{} {} {}.{}s", message, FRAMES[frame % FRAMES.len()], elapsed.as_secs(), elapsed.subsec_nanos() / 100_000_000);
+                let _ = stderr().flush();
+                shown = true;
+                frame += 1;
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                // The work panicked, the same happens here.
+                let _ = worker.join();
+                panic!("{} failed", message);
+            }
+        }
+    }
+
+    if shown {
+        // Erase the line, so the spinner does not stay among the output.
+        let _ = write!(stderr(), "\r{}\r", " ".repeat(message.len() + 16));
+    }
+    result
+}
@@ -0,0 +1,125 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::crypto::aead::{AeadDecryptor, AeadEncryptor};
+use super::crypto::chacha20poly1305::ChaCha20Poly1305;
+use super::crypto::scrypt::{scrypt, ScryptParams};
+use super::password::v2::{CustomField, OtpSecret, Password, PreviousPassword, Protected};
+use super::progress;
+use super::rand::{Rng, OsRng};
+use super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use super::serde_json;
+use std::ops::Deref;
+
+const SALT_LEN: usize = 32;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// Each encryption uses a new salt, hence a new key, so a fixed nonce is safe.
+const NONCE: [u8; 8] = [0u8; 8];
+
+/// Harder than for the master password, since only a few entries are protected and
+/// they are opened one at a time.
+const SCRYPT_PARAM_LOG2_N: u8 = 15;
+const SCRYPT_PARAM_R: u32 = 8;
+const SCRYPT_PARAM_P: u32 = 1;
+
+/// The fields of an entry that the passphrase protects.
+#[derive(Serialize, Deserialize)]
+struct Secrets {
+    password: SafeString,
+    history: Option<Vec<PreviousPassword>>,
+    notes: Option<SafeString>,
+    custom_fields: Option<Vec<CustomField>>,
+    recovery_codes: Option<Vec<SafeString>>,
+    otp: Option<OtpSecret>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> SafeVec {
+    let passphrase = SafeString::new(passphrase.to_string());
+    let salt = salt.to_vec();
+    progress::run("Deriving the key of the passphrase", move || {
+        let mut key = SafeVec::new(vec![0u8; KEY_LEN]);
+        scrypt(passphrase.as_bytes(), &salt, &ScryptParams::new(SCRYPT_PARAM_LOG2_N, SCRYPT_PARAM_R, SCRYPT_PARAM_P), &mut key);
+        key
+    })
+}
+
+/// Encrypts the secrets of the entry with the passphrase. They are left empty in the
+/// entry until `unprotect` brings them back.
+pub fn protect(password: &mut Password, passphrase: &str) -> Result<(), String> {
+    if password.protected.is_some() {
+        return Err("it is already protected".to_string());
+    }
+    let secrets = Secrets {
+        password: password.password.clone(),
+        history: password.history.take(),
+        notes: password.notes.take(),
+        custom_fields: password.custom_fields.take(),
+        recovery_codes: password.recovery_codes.take(),
+        otp: password.otp.take(),
+    };
+    password.password = SafeString::new(String::new());
+    let encoded = SafeString::new(try!(serde_json::to_string(&secrets).map_err(|err| err.to_string())));
+
+    let mut salt = [0u8; SALT_LEN];
+    try!(OsRng::new().map_err(|err| err.to_string())).fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt);
+    let mut data = vec![0u8; encoded.len()];
+    let mut tag = [0u8; TAG_LEN];
+    ChaCha20Poly1305::new(&key, &NONCE, password.name.as_bytes()).encrypt(encoded.as_bytes(), &mut data, &mut tag);
+    data.extend_from_slice(&tag);
+
+    password.protected = Some(Protected {
+        salt: salt.to_base64(STANDARD),
+        data: data.to_base64(STANDARD),
+    });
+    Ok(())
+}
+
+/// A copy of the entry with its secrets decrypted, or the entry as is if it is not
+/// protected.
+pub fn unprotect(password: &Password, passphrase: &str) -> Result<Password, String> {
+    let protected = match password.protected {
+        Some(ref protected) => protected,
+        None => { return Ok(password.clone()); }
+    };
+    let salt = try!(protected.salt.from_base64().map_err(|_| "the protected data is damaged".to_string()));
+    let data = try!(protected.data.from_base64().map_err(|_| "the protected data is damaged".to_string()));
+    if data.len() < TAG_LEN {
+        return Err("the protected data is damaged".to_string());
+    }
+    let (ciphertext, tag) = data.split_at(data.len() - TAG_LEN);
+
+    let key = derive_key(passphrase, &salt);
+    let mut decrypted = SafeVec::new(vec![0u8; ciphertext.len()]);
+    // The name is authenticated too, so that secrets can't be moved to another entry.
+    if !ChaCha20Poly1305::new(&key, &NONCE, password.name.as_bytes()).decrypt(ciphertext, &mut decrypted, tag) {
+        return Err("the passphrase is wrong".to_string());
+    }
+    let encoded = SafeString::new(try!(String::from_utf8(decrypted.deref().to_vec()).map_err(|_| "the protected data is damaged".to_string())));
+    let secrets: Secrets = try!(serde_json::from_str(encoded.deref()).map_err(|_| "the protected data is damaged".to_string()));
+
+    let mut unprotected = password.clone();
+    unprotected.password = secrets.password;
+    unprotected.history = secrets.history;
+    unprotected.notes = secrets.notes;
+    unprotected.custom_fields = secrets.custom_fields;
+    unprotected.recovery_codes = secrets.recovery_codes;
+    unprotected.otp = secrets.otp;
+    unprotected.protected = None;
+    Ok(unprotected)
+}
@@ -0,0 +1,90 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::crypto::digest::Digest;
+use super::crypto::sha1::Sha1;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Result as IoResult, Seek, SeekFrom};
+use std::path::Path;
+
+/// A local copy of the Have I Been Pwned password list, in the "ordered by hash"
+/// format: one `SHA1:COUNT` line per password, sorted by SHA-1 in uppercase hex.
+///
+/// The file is searched in place with a binary search, so nothing is sent over the
+/// network and the file, tens of gigabytes, is never loaded in memory.
+pub struct PwnedDatabase {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl PwnedDatabase {
+    pub fn open(path: &Path) -> IoResult<PwnedDatabase> {
+        let file = try!(File::open(path));
+        let len = try!(file.metadata()).len();
+        Ok(PwnedDatabase {
+            reader: BufReader::new(file),
+            len: len,
+        })
+    }
+
+    /// Reads the first full line that starts at or after `offset`, and returns where
+    /// it starts, or `None` past the end of the file.
+    fn line_at(&mut self, offset: u64) -> IoResult<Option<(u64, String)>> {
+        // Starting one byte early finds the line that starts exactly at the offset.
+        let mut start = offset;
+        if offset > 0 {
+            try!(self.reader.seek(SeekFrom::Start(offset - 1)));
+            let mut skipped = Vec::new();
+            start = offset - 1 + try!(self.reader.read_until(b'\n', &mut skipped)) as u64;
+        } else {
+            try!(self.reader.seek(SeekFrom::Start(0)));
+        }
+        let mut line = String::new();
+        if try!(self.reader.read_line(&mut line)) == 0 {
+            return Ok(None);
+        }
+        Ok(Some((start, line)))
+    }
+
+    /// How many times the password appeared in a breach, 0 if it never did.
+    pub fn count(&mut self, password: &str) -> IoResult<u64> {
+        let mut hasher = Sha1::new();
+        hasher.input_str(password);
+        let hash = hasher.result_str().to_uppercase();
+
+        // The line we look for, if any, starts between `low` and `high`.
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let (start, line) = match try!(self.line_at(middle)) {
+                Some((start, ref line)) if start < high => (start, line.clone()),
+                _ => {
+                    high = middle;
+                    continue;
+                }
+            };
+            let mut parts = line.trim_right().splitn(2, ':');
+            let line_hash = parts.next().unwrap_or("").to_uppercase();
+            match line_hash.cmp(&hash) {
+                Ordering::Equal => {
+                    return Ok(parts.next().and_then(|count| count.parse().ok()).unwrap_or(1));
+                },
+                Ordering::Less => { low = start + line.len() as u64; },
+                Ordering::Greater => { high = middle; },
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::qrcode::QrCode;
+use super::safe_string::SafeString;
+
+/// Number of light modules around the code, so scanners can find its edges.
+const QUIET_ZONE: usize = 2;
+
+/// Renders data as a QR code that can be printed in a terminal.
+///
+/// Each character holds two modules, one above the other, using half blocks. Light
+/// modules are drawn and dark modules are left blank, which reads correctly on the
+/// usual light-on-dark terminal.
+pub fn render(data: &[u8]) -> Result<SafeString, String> {
+    let code = try!(QrCode::new(data).map_err(|err| format!("{:?}", err)));
+    let width = code.width();
+    let modules = code.to_vec();
+    let size = width + 2 * QUIET_ZONE;
+
+    let is_light = |x: usize, y: usize| -> bool {
+        if x < QUIET_ZONE || y < QUIET_ZONE || x >= width + QUIET_ZONE || y >= width + QUIET_ZONE {
+            true
+        } else {
+            !modules[(y - QUIET_ZONE) * width + (x - QUIET_ZONE)]
+        }
+    };
+
+    let mut output = String::with_capacity((size + 1) * (size / 2 + 1) * 3);
+    let mut y = 0;
+    while y < size {
+        for x in 0..size {
+            let top = is_light(x, y);
+            let bottom = y + 1 >= size || is_light(x, y + 1);
+            output.push(match (top, bottom) {
+                (true, true) => '\u{2588}',
+                (true, false) => '\u{2580}',
+                (false, true) => '\u{2584}',
+                (false, false) => ' ',
+            });
+        }
+        output.push('\n');
+        y += 2;
+    }
+    Ok(SafeString::new(output))
+}
@@ -14,12 +14,37 @@
 
 use std::ops::Drop;
 use std::ops::Deref;
-use super::rustc_serialize::Encodable;
-use super::rustc_serialize::Decodable;
-use super::rustc_serialize::Encoder;
-use super::rustc_serialize::Decoder;
+use super::serde::{Serialize, Serializer, Deserialize, Deserializer};
+use super::safe_vec::SafeVec;
+use std::io::{Read, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::fs::OpenOptions;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use super::ffi;
+#[cfg(windows)]
+use super::rpassword::read_password;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// The longest password `read_password_safe` reads. Its buffer is allocated for this
+/// many bytes up front and reading stops there, so it is never reallocated and never
+/// leaves a copy of the password behind.
+#[cfg(unix)]
+const MAX_PASSWORD_LEN: usize = 4096;
+
+/// Whether passwords are read from the terminal rather than stdin, see
+/// `prompt_from_terminal`.
+static FROM_TERMINAL: AtomicBool = AtomicBool::new(false);
+
+/// Makes `read_password_safe` read from the terminal, for when stdin holds something
+/// else, like the password file.
+pub fn prompt_from_terminal() {
+    FROM_TERMINAL.store(true, Ordering::Relaxed);
+}
+
+#[derive(Clone, Debug)]
 pub struct SafeString {
     inner: String,
 }
@@ -41,6 +66,20 @@ impl Drop for SafeString {
     }
 }
 
+/// Compares in constant time, so that comparing secrets, like a password and its
+/// confirmation, does not tell how much of them matched.
+impl PartialEq for SafeString {
+    fn eq(&self, other: &SafeString) -> bool {
+        let (a, b) = (self.inner.as_bytes(), other.inner.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+}
+
+impl Eq for SafeString {}
+
 impl Deref for SafeString {
     type Target = str;
 
@@ -49,14 +88,97 @@ impl Deref for SafeString {
     }
 }
 
-impl Encodable for SafeString {
-    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
-        String::encode(&self.inner, s)
+impl Serialize for SafeString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.inner.deref())
+    }
+}
+
+impl<'de> Deserialize<'de> for SafeString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SafeString, D::Error> {
+        String::deserialize(deserializer).map(|s| SafeString::new(s))
     }
 }
 
-impl Decodable for SafeString {
-    fn decode<D: Decoder>(d: &mut D) -> Result<SafeString, D::Error> {
-        String::decode(d).map(|s| SafeString::new(s))
+/// Reads everything into a `SafeString`. The buffer is never left to `Vec` to grow:
+/// when it is full, its bytes are copied into a new one twice as large and it is
+/// wiped. The chunks read go through a `SafeVec`, wiped at the end.
+pub fn read_to_end_safe<R: Read>(reader: &mut R) -> IoResult<SafeString> {
+    let mut bytes = Vec::with_capacity(1024);
+    let mut chunk = SafeVec::new(vec![0u8; 256]);
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(ref err) if err.kind() == IoErrorKind::Interrupted => continue,
+            Err(err) => {
+                drop(SafeVec::new(bytes));
+                return Err(err);
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        if bytes.len() + read > bytes.capacity() {
+            let mut bigger = Vec::with_capacity(2 * (bytes.len() + read));
+            bigger.extend_from_slice(&bytes);
+            drop(SafeVec::new(mem::replace(&mut bytes, bigger)));
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+    }
+    String::from_utf8(bytes).map(SafeString::new).map_err(|err| {
+        drop(SafeVec::new(err.into_bytes()));
+        IoError::new(IoErrorKind::InvalidData, "the text is not valid UTF-8")
+    })
+}
+
+/// Reads a line from stdin without echo if it is a terminal, straight into a
+/// `SafeString`, without any other copy of it in memory. After `prompt_from_terminal`,
+/// reads from the terminal instead.
+#[cfg(unix)]
+pub fn read_password_safe() -> IoResult<SafeString> {
+    if FROM_TERMINAL.load(Ordering::Relaxed) {
+        let terminal = try!(OpenOptions::new().read(true).write(true).open("/dev/tty"));
+        let fd = terminal.as_raw_fd();
+        return try!(ffi::without_echo(fd, || read_line_safe(fd)));
+    }
+    if ffi::is_terminal(0) {
+        try!(ffi::without_echo(0, || read_line_safe(0)))
+    } else {
+        read_line_safe(0)
+    }
+}
+
+/// On Windows, the password goes through the buffers of rpassword.
+#[cfg(windows)]
+pub fn read_password_safe() -> IoResult<SafeString> {
+    read_password().map(SafeString::new)
+}
+
+#[cfg(unix)]
+fn read_line_safe(fd: i32) -> IoResult<SafeString> {
+    let mut bytes = Vec::with_capacity(MAX_PASSWORD_LEN);
+    loop {
+        match ffi::read_byte(fd) {
+            Ok(Some(b'\n')) | Ok(None) => break,
+            Ok(Some(byte)) => {
+                if bytes.len() == MAX_PASSWORD_LEN {
+                    drop(SafeVec::new(bytes));
+                    return Err(IoError::new(IoErrorKind::InvalidInput, "the password is too long"));
+                }
+                bytes.push(byte);
+            },
+            Err(err) => {
+                drop(SafeVec::new(bytes));
+                return Err(err);
+            }
+        }
+    }
+    if bytes.last() == Some(&b'\r') {
+        bytes.pop();
     }
+    // Both keep the buffer as is, so it is wiped either way.
+    String::from_utf8(bytes).map(SafeString::new).map_err(|err| {
+        drop(SafeVec::new(err.into_bytes()));
+        IoError::new(IoErrorKind::InvalidData, "the password is not valid UTF-8")
+    })
 }
@@ -0,0 +1,324 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::crypto::aead::{AeadDecryptor, AeadEncryptor};
+use super::crypto::chacha20poly1305::ChaCha20Poly1305;
+#[cfg(windows)]
+use super::ffi;
+#[cfg(target_os = "macos")]
+use super::keychain;
+use super::rand::{Rng, OsRng};
+use super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::safe_vec::SafeVec;
+use super::timestamp::Timestamp;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Environment variable holding the wrapped vault key of the session.
+pub const SESSION_ENV_VAR: &'static str = "ROOSTER_SESSION";
+
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// Each session has its own key and wraps a single vault key, so a fixed nonce is safe.
+const NONCE: [u8; 8] = [0u8; 8];
+
+/// A session is the key of the vault, encrypted with a key kept in a file that only the
+/// user can read. The vault key opens the vault it was taken from, but unlike the master
+/// password it is no use for any other file or site. The encrypted key goes in
+/// $ROOSTER_SESSION, which rooster removes from the programs it runs, so only the shell
+/// that started the session can use it. Deleting the key file ends the session, even
+/// for copies of the variable.
+struct Session {
+    id: String,
+    expires_at: Timestamp,
+    /// Encrypted "<password file>\0<vault key>", then the authentication tag.
+    wrapped: Vec<u8>,
+}
+
+impl Session {
+    fn parse(value: &str) -> Option<Session> {
+        let parts: Vec<&str> = value.splitn(3, '.').collect();
+        if parts.len() != 3 || parts[0].is_empty() || !parts[0].chars().all(|c| c.is_digit(16)) {
+            return None;
+        }
+        let expires_at = match parts[1].parse::<u64>() {
+            Ok(seconds) => Timestamp::from_secs(seconds),
+            Err(_) => { return None; }
+        };
+        match parts[2].from_base64() {
+            Ok(ref wrapped) if wrapped.len() >= TAG_LEN => Some(Session {
+                id: parts[0].to_string(),
+                expires_at: expires_at,
+                wrapped: wrapped.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Authenticated with the password, so the expiry can't be pushed back.
+    fn header(&self) -> String {
+        format!("{}.{}", self.id, self.expires_at.as_secs())
+    }
+
+    /// The expiry is in the name, so that `sweep` can delete the keys of sessions nobody
+    /// used again.
+    fn key_path(&self) -> PathBuf {
+        key_dir().join(format!("{}{}-{}", KEY_FILE_PREFIX, self.id, self.expires_at.as_secs()))
+    }
+}
+
+const KEY_FILE_PREFIX: &'static str = "rooster-session-";
+
+/// Where the keys are kept, in memory when possible.
+fn key_dir() -> PathBuf {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => env::temp_dir(),
+    }
+}
+
+#[cfg(unix)]
+fn key_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true).mode(0o600);
+    options
+}
+
+/// Files in the user's temporary directory are only readable by the user already.
+#[cfg(windows)]
+fn key_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// The key as it goes in its file, which only the user can read.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn protect_key(_path: &PathBuf, key: &SafeVec) -> IoResult<SafeVec> {
+    Ok(key.clone())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn unprotect_key(_path: &PathBuf, content: &SafeVec) -> IoResult<SafeVec> {
+    Ok(content.clone())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn forget_key(_path: &PathBuf) {
+}
+
+/// Keychain service of the keys of sessions on macOS, see `protect_key`.
+#[cfg(target_os = "macos")]
+const KEYCHAIN_SERVICE: &'static str = "rooster-session";
+
+#[cfg(target_os = "macos")]
+fn keychain_account(path: &PathBuf) -> String {
+    path.file_name().and_then(|name| name.to_str()).unwrap_or("").to_string()
+}
+
+/// On macOS, the temporary directory is on the disk, so the key goes in the login
+/// keychain instead, and the file is empty. It only tells `sweep` when the session
+/// expires. The keychain encrypts the key on the disk, and keeps it from Rooster while
+/// it is locked, like after sleep when the keychain is set up that way.
+#[cfg(target_os = "macos")]
+fn protect_key(path: &PathBuf, key: &SafeVec) -> IoResult<SafeVec> {
+    try!(keychain::store(KEYCHAIN_SERVICE, &keychain_account(path), key));
+    Ok(SafeVec::new(Vec::new()))
+}
+
+#[cfg(target_os = "macos")]
+fn unprotect_key(path: &PathBuf, _content: &SafeVec) -> IoResult<SafeVec> {
+    keychain::find(KEYCHAIN_SERVICE, &keychain_account(path))
+}
+
+#[cfg(target_os = "macos")]
+fn forget_key(path: &PathBuf) {
+    let _ = keychain::delete(KEYCHAIN_SERVICE, &keychain_account(path));
+}
+
+/// The temporary directory is on the disk on Windows, so the key is encrypted with DPAPI
+/// for the user's account. A copy of the file is useless to other accounts and on other
+/// computers.
+#[cfg(windows)]
+fn protect_key(_path: &PathBuf, key: &SafeVec) -> IoResult<SafeVec> {
+    ffi::protect_data(key)
+}
+
+#[cfg(windows)]
+fn unprotect_key(_path: &PathBuf, content: &SafeVec) -> IoResult<SafeVec> {
+    ffi::unprotect_data(content)
+}
+
+fn write_key(path: &PathBuf, key: &SafeVec) -> IoResult<()> {
+    let content = try!(protect_key(path, key));
+    key_file_options().open(path).and_then(|mut file| file.write_all(&content))
+}
+
+fn read_key(path: &PathBuf) -> IoResult<SafeVec> {
+    // Large enough for the key as DPAPI wraps it, so that the buffer is never moved.
+    let mut content = Vec::with_capacity(1024);
+    try!(File::open(path).and_then(|mut file| file.read_to_end(&mut content)));
+    let key = try!(unprotect_key(path, &SafeVec::new(content)));
+    if key.len() != KEY_LEN {
+        return Err(IoError::new(IoErrorKind::InvalidData, "the key of the session has the wrong length"));
+    }
+    Ok(key)
+}
+
+/// Overwrites the key before deleting it, so that it does not stay on the disk.
+fn delete_key(path: &PathBuf) -> IoResult<()> {
+    let _ = fs::metadata(path).and_then(|metadata| OpenOptions::new().write(true).open(path)
+        .and_then(|mut file| file.write_all(&vec![0u8; metadata.len() as usize]).and_then(|_| file.sync_all())));
+    forget_key(path);
+    fs::remove_file(path)
+}
+
+/// The keys of every session of the user, whichever shell started them, with when they
+/// expire.
+fn key_files() -> Vec<(PathBuf, u64)> {
+    let entries = match fs::read_dir(key_dir()) {
+        Ok(entries) => entries,
+        Err(_) => { return Vec::new(); }
+    };
+    entries.filter_map(|entry| entry.ok()).filter_map(|entry| {
+        let expires_at = entry.file_name().to_str()
+            .and_then(|name| if name.starts_with(KEY_FILE_PREFIX) { name.rsplit('-').next() } else { None })
+            .and_then(|seconds| seconds.parse::<u64>().ok());
+        expires_at.map(|expires_at| (entry.path(), expires_at))
+    }).collect()
+}
+
+/// Deletes the keys of the sessions that have expired.
+fn sweep() {
+    let now = Timestamp::now().as_secs();
+    for (path, expires_at) in key_files() {
+        if expires_at <= now {
+            let _ = delete_key(&path);
+        }
+    }
+}
+
+/// How many sessions are open, in every shell.
+pub fn count() -> usize {
+    sweep();
+    key_files().len()
+}
+
+/// Ends every session, in every shell, and returns how many there were.
+pub fn end_all() -> IoResult<usize> {
+    sweep();
+    let key_files = key_files();
+    for &(ref path, _) in key_files.iter() {
+        try!(delete_key(path));
+    }
+    Ok(key_files.len())
+}
+
+/// The current session, if there is one. Expired keys are deleted on the way.
+fn current() -> Option<Session> {
+    sweep();
+    env::var(SESSION_ENV_VAR).ok().and_then(|value| Session::parse(&value))
+}
+
+/// A program for rooster to run. It does not get $ROOSTER_SESSION, the session is for
+/// the commands typed in the shell, not for the programs they start.
+pub fn command<S: AsRef<OsStr>>(program: S) -> Command {
+    let mut command = Command::new(program);
+    command.env_remove(SESSION_ENV_VAR);
+    command
+}
+
+/// Starts a session for the password file, returns the value of $ROOSTER_SESSION.
+pub fn start(password_file: &str, vault_key: &SafeVec, seconds: u64) -> IoResult<String> {
+    sweep();
+    let mut rng = try!(OsRng::new());
+    let mut id_bytes = [0u8; 8];
+    rng.fill_bytes(&mut id_bytes);
+    let id: String = id_bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let mut key = SafeVec::new(vec![0u8; KEY_LEN]);
+    rng.fill_bytes(&mut key);
+
+    let mut session = Session {
+        id: id,
+        expires_at: Timestamp::from_secs(Timestamp::now().as_secs().saturating_add(seconds)),
+        wrapped: Vec::new(),
+    };
+    try!(write_key(&session.key_path(), &key));
+
+    let mut plain = Vec::new();
+    plain.extend_from_slice(password_file.as_bytes());
+    plain.push(0);
+    plain.extend_from_slice(vault_key);
+    let plain = SafeVec::new(plain);
+    let mut ciphertext = vec![0u8; plain.len()];
+    let mut tag = [0u8; TAG_LEN];
+    ChaCha20Poly1305::new(&key, &NONCE, session.header().as_bytes()).encrypt(&plain, &mut ciphertext, &mut tag);
+    session.wrapped = ciphertext;
+    session.wrapped.extend_from_slice(&tag);
+
+    Ok(format!("{}.{}", session.header(), session.wrapped.to_base64(STANDARD)))
+}
+
+/// The vault key of the current session, if there is one for this password file and it
+/// has not expired or ended.
+pub fn key(password_file: &str) -> Option<SafeVec> {
+    let session = match current() {
+        Some(session) => session,
+        None => { return None; }
+    };
+    if Timestamp::now().as_secs() >= session.expires_at.as_secs() {
+        return None;
+    }
+
+    let key = match read_key(&session.key_path()) {
+        Ok(key) => key,
+        Err(_) => { return None; }
+    };
+    let (ciphertext, tag) = session.wrapped.split_at(session.wrapped.len() - TAG_LEN);
+    let mut plain = SafeVec::new(vec![0u8; ciphertext.len()]);
+    if !ChaCha20Poly1305::new(&key, &NONCE, session.header().as_bytes()).decrypt(ciphertext, &mut plain, tag) {
+        return None;
+    }
+
+    let separator = match plain.iter().position(|b| *b == 0) {
+        Some(separator) => separator,
+        None => { return None; }
+    };
+    if &plain[..separator] != password_file.as_bytes() {
+        return None;
+    }
+    Some(SafeVec::new(plain[separator + 1..].to_vec()))
+}
+
+/// When the current session ends, or None without a session.
+pub fn expires_at() -> Option<Timestamp> {
+    current().and_then(|session| {
+        if session.key_path().exists() { Some(session.expires_at) } else { None }
+    })
+}
+
+/// Ends the current session by deleting its key.
+pub fn end() -> IoResult<()> {
+    match current() {
+        Some(session) => delete_key(&session.key_path()),
+        None => Err(IoError::new(IoErrorKind::NotFound, "there is no session in this shell")),
+    }
+}
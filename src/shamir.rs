@@ -0,0 +1,104 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::rand::{Rng, OsRng};
+use super::safe_vec::SafeVec;
+use std::io::Result as IoResult;
+
+/// Shamir's secret sharing over GF(256), byte by byte: each byte of the secret is the
+/// constant term of a random polynomial of degree `threshold - 1`, and share `x` holds
+/// the value of each polynomial at `x`. Any `threshold` shares give the polynomials
+/// back by Lagrange interpolation, fewer say nothing about the secret.
+pub struct Share {
+    /// Where the polynomials were evaluated, from 1 to 255.
+    pub x: u8,
+    pub y: SafeVec,
+}
+
+/// Multiplication in GF(256) with the AES polynomial, by shifting and adding.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// The multiplicative inverse, as a^254 since a^255 = 1 for non-zero a.
+fn inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = mul(result, a);
+    }
+    result
+}
+
+/// Splits the secret in `count` shares, any `threshold` of which give it back.
+pub fn split(secret: &[u8], count: u8, threshold: u8) -> IoResult<Vec<Share>> {
+    assert!(threshold >= 1 && threshold <= count);
+    let mut rng = try!(OsRng::new());
+
+    let mut values: Vec<Vec<u8>> = (0..count).map(|_| Vec::with_capacity(secret.len())).collect();
+    let mut coefficients = SafeVec::new(vec![0u8; threshold as usize]);
+    for &byte in secret.iter() {
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+
+        for (i, y) in values.iter_mut().enumerate() {
+            let x = i as u8 + 1;
+            // Horner's method, from the highest degree down.
+            let mut value = 0;
+            for &c in coefficients.iter().rev() {
+                value = mul(value, x) ^ c;
+            }
+            y.push(value);
+        }
+    }
+
+    Ok(values.into_iter().enumerate().map(|(i, y)| Share {
+        x: i as u8 + 1,
+        y: SafeVec::new(y),
+    }).collect())
+}
+
+/// Gives the secret back from at least `threshold` distinct shares. With fewer shares,
+/// the result is garbage rather than an error, there is no way to tell.
+pub fn combine(shares: &[Share]) -> SafeVec {
+    let len = shares.iter().map(|share| share.y.len()).min().unwrap_or(0);
+    let mut secret = Vec::with_capacity(len);
+    for i in 0..len {
+        // Lagrange interpolation at x = 0. In GF(256), subtraction is xor.
+        let mut byte = 0;
+        for (j, share) in shares.iter().enumerate() {
+            let mut basis = 1;
+            for (m, other) in shares.iter().enumerate() {
+                if m != j {
+                    basis = mul(basis, mul(other.x, inv(other.x ^ share.x)));
+                }
+            }
+            byte ^= mul(share.y[i], basis);
+        }
+        secret.push(byte);
+    }
+    SafeVec::new(secret)
+}
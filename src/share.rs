@@ -0,0 +1,137 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::crypto::aead::{AeadDecryptor, AeadEncryptor};
+use super::crypto::chacha20poly1305::ChaCha20Poly1305;
+use super::crypto::curve25519::{curve25519, curve25519_base};
+use super::crypto::hkdf::{hkdf_expand, hkdf_extract};
+use super::crypto::sha2::Sha256;
+use super::rand::{Rng, OsRng};
+use super::rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use std::io::Result as IoResult;
+
+/// Prefix of public keys, so they are not mistaken for anything else.
+const PUBLIC_KEY_PREFIX: &'static str = "rooster-public-key:";
+
+const BEGIN: &'static str = "-----BEGIN ROOSTER SHARE-----";
+const END: &'static str = "-----END ROOSTER SHARE-----";
+
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// Each share uses a new ephemeral key, hence a new encryption key, so a fixed nonce
+/// is safe.
+const NONCE: [u8; 8] = [0u8; 8];
+
+const HKDF_INFO: &'static [u8] = b"rooster share v1";
+
+/// Creates an X25519 private key, base64 encoded.
+pub fn generate_private_key() -> IoResult<SafeString> {
+    let mut rng = try!(OsRng::new());
+    let mut key = SafeVec::new(vec![0u8; KEY_LEN]);
+    rng.fill_bytes(&mut key);
+    Ok(SafeString::new(key.to_base64(STANDARD)))
+}
+
+fn decode_key(key: &str) -> Result<SafeVec, String> {
+    match key.trim().from_base64() {
+        Ok(ref bytes) if bytes.len() == KEY_LEN => Ok(SafeVec::new(bytes.clone())),
+        _ => Err("the key is not valid".to_string()),
+    }
+}
+
+/// The public key to give to people who want to share passwords with you.
+pub fn public_key(private_key: &str) -> Result<String, String> {
+    let private_key = try!(decode_key(private_key));
+    Ok(format!("{}{}", PUBLIC_KEY_PREFIX, curve25519_base(&private_key).to_base64(STANDARD)))
+}
+
+fn parse_public_key(public_key: &str) -> Result<SafeVec, String> {
+    let public_key = public_key.trim();
+    if !public_key.starts_with(PUBLIC_KEY_PREFIX) {
+        return Err(format!("public keys start with \"{}\"", PUBLIC_KEY_PREFIX));
+    }
+    decode_key(&public_key[PUBLIC_KEY_PREFIX.len()..])
+}
+
+/// Derives the encryption key from the X25519 shared secret and both public keys.
+fn derive_key(shared: &[u8], ephemeral_public: &[u8], recipient_public: &[u8]) -> SafeVec {
+    let mut salt = Vec::with_capacity(2 * KEY_LEN);
+    salt.extend_from_slice(ephemeral_public);
+    salt.extend_from_slice(recipient_public);
+    let mut prk = SafeVec::new(vec![0u8; KEY_LEN]);
+    hkdf_extract(Sha256::new(), &salt, shared, &mut prk);
+    let mut key = SafeVec::new(vec![0u8; KEY_LEN]);
+    hkdf_expand(Sha256::new(), &prk, HKDF_INFO, &mut key);
+    key
+}
+
+/// Encrypts data for the owner of the public key, as armored text.
+///
+/// The format is ECIES with X25519, HKDF-SHA256 and ChaCha20-Poly1305: an ephemeral
+/// public key, then the ciphertext, then the authentication tag.
+pub fn encrypt(data: &[u8], recipient_public_key: &str) -> Result<String, String> {
+    let recipient_public = try!(parse_public_key(recipient_public_key));
+
+    let ephemeral_private = try!(generate_private_key().map_err(|err| err.to_string()));
+    let ephemeral_private = try!(decode_key(&ephemeral_private));
+    let ephemeral_public = curve25519_base(&ephemeral_private);
+    let shared = SafeVec::new(curve25519(&ephemeral_private, &recipient_public).to_vec());
+    let key = derive_key(&shared, &ephemeral_public, &recipient_public);
+
+    let mut ciphertext = vec![0u8; data.len()];
+    let mut tag = [0u8; TAG_LEN];
+    ChaCha20Poly1305::new(&key, &NONCE, &[]).encrypt(data, &mut ciphertext, &mut tag);
+
+    let mut blob = Vec::with_capacity(KEY_LEN + data.len() + TAG_LEN);
+    blob.extend_from_slice(&ephemeral_public);
+    blob.extend_from_slice(&ciphertext);
+    blob.extend_from_slice(&tag);
+
+    let encoded = blob.to_base64(STANDARD);
+    let lines: Vec<String> = encoded.as_bytes().chunks(64).map(|line| String::from_utf8_lossy(line).into_owned()).collect();
+    Ok(format!("{}\n{}\n{}\n", BEGIN, lines.join("\n"), END))
+}
+
+/// Decrypts armored text made by `encrypt` with the private key.
+pub fn decrypt(armored: &str, private_key: &str) -> Result<SafeVec, String> {
+    let start = try!(armored.find(BEGIN).ok_or("no share found".to_string())) + BEGIN.len();
+    let end = try!(armored.find(END).ok_or("the share is cut short".to_string()));
+    if end < start {
+        return Err("the share is malformed".to_string());
+    }
+    let encoded: String = armored[start..end].chars().filter(|c| !c.is_whitespace()).collect();
+    let blob = try!(encoded.from_base64().map_err(|_| "the share is not valid base64".to_string()));
+    if blob.len() < KEY_LEN + TAG_LEN {
+        return Err("the share is cut short".to_string());
+    }
+
+    let private_key = try!(decode_key(private_key));
+    let recipient_public = curve25519_base(&private_key);
+    let ephemeral_public = &blob[..KEY_LEN];
+    let ciphertext = &blob[KEY_LEN..blob.len() - TAG_LEN];
+    let tag = &blob[blob.len() - TAG_LEN..];
+
+    let shared = SafeVec::new(curve25519(&private_key, ephemeral_public).to_vec());
+    let key = derive_key(&shared, ephemeral_public, &recipient_public);
+
+    let mut data = SafeVec::new(vec![0u8; ciphertext.len()]);
+    if !ChaCha20Poly1305::new(&key, &NONCE, &[]).decrypt(ciphertext, &mut data, tag) {
+        return Err("the share was not made for you, or it was modified".to_string());
+    }
+    Ok(data)
+}
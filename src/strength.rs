@@ -0,0 +1,331 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::config;
+use super::getopts;
+use super::generate::WORDLIST;
+use super::commands::CommandError;
+use std::collections::HashSet;
+use std::env;
+use std::io::Write;
+
+/// Environment variable holding the default minimum score, for all commands.
+pub const ENFORCE_STRENGTH_ENV_VAR: &'static str = "ROOSTER_ENFORCE_STRENGTH";
+
+/// Guesses per second of an offline attack against a slow hash, as in zxcvbn.
+const GUESSES_PER_SECOND_LOG10: f64 = 4.0;
+
+/// Passwords that attackers try first, roughly by popularity.
+static COMMON_PASSWORDS: &'static [&'static str] = &[
+    "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111",
+    "1234567", "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein",
+    "shadow", "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890",
+    "michael", "654321", "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx",
+    "123qwe", "killer", "trustno1", "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter",
+    "buster", "soccer", "harley", "batman", "andrew", "tigger", "sunshine", "iloveyou",
+    "2000", "charlie", "robert", "thomas", "hockey", "ranger", "daniel", "starwars",
+    "klaster", "112233", "george", "computer", "michelle", "jessica", "pepper", "1111",
+    "zxcvbn", "555555", "11111111", "131313", "freedom", "777777", "pass", "maggie",
+    "159753", "aaaaaa", "ginger", "princess", "joshua", "cheese", "amanda", "summer",
+    "love", "ashley", "nicole", "chelsea", "biteme", "matthew", "access", "yankees",
+    "987654321", "dallas", "austin", "thunder", "taylor", "matrix", "admin", "welcome",
+    "login", "passw0rd", "password1", "hello", "whatever", "secret", "root", "letmein1",
+];
+
+static KEYBOARD_ROWS: &'static [&'static str] = &[
+    "1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm", "azertyuiop", "qsdfghjklm", "wxcvbn",
+];
+
+pub struct Strength {
+    /// From 0 (too guessable) to 4 (very unguessable), like zxcvbn.
+    pub score: u8,
+    /// Estimated number of guesses to find the password, as a power of 10.
+    pub guesses_log10: f64,
+}
+
+impl Strength {
+    pub fn label(&self) -> &'static str {
+        match self.score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "strong",
+            _ => "very strong",
+        }
+    }
+
+    /// Human readable time to crack the password offline.
+    pub fn crack_time(&self) -> String {
+        let seconds_log10 = self.guesses_log10 - GUESSES_PER_SECOND_LOG10;
+        let seconds = 10f64.powf(seconds_log10);
+        let minute = 60.0;
+        let hour = 60.0 * minute;
+        let day = 24.0 * hour;
+        let units: &[(f64, &str, &str)] = &[
+            (100.0 * 365.0 * day, "century", "centuries"),
+            (365.0 * day, "year", "years"),
+            (30.0 * day, "month", "months"),
+            (day, "day", "days"),
+            (hour, "hour", "hours"),
+            (minute, "minute", "minutes"),
+            (1.0, "second", "seconds"),
+        ];
+        for &(unit_seconds, singular, plural) in units.iter() {
+            if seconds >= unit_seconds {
+                let count = (seconds / unit_seconds).round();
+                if count > 1000.0 && singular == "century" {
+                    return "more than a thousand centuries".to_string();
+                }
+                return format!("{} {}", count, if count > 1.0 { plural } else { singular });
+            }
+        }
+        "less than a second".to_string()
+    }
+}
+
+/// Groups characters into lowercase, uppercase, digits, ASCII symbols and the rest.
+fn char_class(c: char) -> u8 {
+    if c.is_ascii_lowercase() {
+        0
+    } else if c.is_ascii_uppercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else if c.is_ascii() {
+        3
+    } else {
+        4
+    }
+}
+
+/// Size of the character set an attacker must try for this character.
+fn char_pool(c: char) -> f64 {
+    match char_class(c) {
+        0 | 1 => 26.0,
+        2 => 10.0,
+        3 => 33.0,
+        _ => 100.0,
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        '8' => 'b',
+        '9' => 'g',
+        c => c.to_ascii_lowercase(),
+    }
+}
+
+/// Length and guesses of the cheapest pattern starting at `start`, if any.
+fn match_at(chars: &[char], start: usize, dictionary: &HashSet<String>, common: &HashSet<&str>) -> Option<(usize, f64)> {
+    let rest = &chars[start..];
+    let mut best: Option<(usize, f64)> = None;
+    {
+        let mut consider = |len: usize, guesses_log10: f64| {
+            // Prefer the pattern that covers the most characters, then the cheapest one.
+            best = match best {
+                Some((best_len, best_guesses)) if best_len > len || (best_len == len && best_guesses <= guesses_log10) => {
+                    Some((best_len, best_guesses))
+                },
+                _ => Some((len, guesses_log10)),
+            };
+        };
+
+        // Common passwords and dictionary words, as typed or with l33t substitutions.
+        let lower: String = rest.iter().map(|c| c.to_ascii_lowercase()).collect();
+        let unleeted: String = rest.iter().map(|c| unleet(*c)).collect();
+        for len in 3..(rest.len() + 1) {
+            let typed = &lower[..lower.char_indices().nth(len).map(|(i, _)| i).unwrap_or(lower.len())];
+            let plain = &unleeted[..unleeted.char_indices().nth(len).map(|(i, _)| i).unwrap_or(unleeted.len())];
+            let uppercase = rest[..len].iter().any(|c| c.is_uppercase());
+            // Capitalization and substitutions each roughly double the guesses.
+            let variations = (if uppercase { 2f64 } else { 1f64 }).log10()
+                + (if typed != plain { 2f64 } else { 1f64 }).log10();
+            if common.contains(typed) || common.contains(plain) {
+                consider(len, (COMMON_PASSWORDS.len() as f64).log10() + variations);
+            } else if dictionary.contains(typed) || dictionary.contains(plain) {
+                consider(len, (dictionary.len() as f64).log10() + variations);
+            }
+        }
+
+        // The same character repeated.
+        let repeated = rest.iter().take_while(|c| **c == rest[0]).count();
+        if repeated >= 3 {
+            consider(repeated, (char_pool(rest[0]) * repeated as f64).log10());
+        }
+
+        // Sequences like "abcd" or "9876".
+        if rest.len() >= 3 {
+            let step = rest[1] as i64 - rest[0] as i64;
+            if step == 1 || step == -1 {
+                let mut len = 2;
+                while len < rest.len() && rest[len] as i64 - rest[len - 1] as i64 == step {
+                    len += 1;
+                }
+                if len >= 3 {
+                    consider(len, (2.0 * char_pool(rest[0]) * len as f64).log10());
+                }
+            }
+        }
+
+        // Runs along a keyboard row, like "qwerty" or "asdf".
+        for row in KEYBOARD_ROWS.iter() {
+            let mut len = 0;
+            while len < rest.len() && len < row.len() {
+                let candidate: String = rest[..len + 1].iter().map(|c| c.to_ascii_lowercase()).collect();
+                if row.contains(candidate.as_str()) {
+                    len += 1;
+                } else {
+                    break;
+                }
+            }
+            if len >= 4 {
+                consider(len, (2.0 * KEYBOARD_ROWS.len() as f64 * 10.0 * len as f64).log10());
+            }
+        }
+
+        // Years, which people love to put at the end of passwords.
+        if rest.len() >= 4 && rest[..4].iter().all(|c| c.is_ascii_digit()) {
+            let year: String = rest[..4].iter().cloned().collect();
+            match year.parse::<u32>() {
+                Ok(year) if year >= 1900 && year < 2050 => consider(4, 150f64.log10()),
+                _ => {}
+            }
+        }
+    }
+    best
+}
+
+/// Estimates how hard the password is to guess, in the spirit of zxcvbn.
+///
+/// The password is split, from left to right, into the cheapest patterns an attacker
+/// would try: common passwords, dictionary words (also with l33t substitutions),
+/// repeated characters, sequences, keyboard rows and years. Whatever is left is
+/// counted as brute force. The guesses for each part are multiplied to get an
+/// estimate for the whole password.
+pub fn estimate(password: &str) -> Strength {
+    let dictionary: HashSet<String> = WORDLIST.lines().map(|word| word.to_string()).collect();
+    let common: HashSet<&str> = COMMON_PASSWORDS.iter().cloned().collect();
+    let chars: Vec<char> = password.chars().collect();
+
+    // Brute force uses the character sets found in the whole password.
+    let mut pool = 0f64;
+    let mut seen_classes: Vec<u8> = Vec::new();
+    for c in chars.iter() {
+        if !seen_classes.contains(&char_class(*c)) {
+            seen_classes.push(char_class(*c));
+            pool += char_pool(*c);
+        }
+    }
+    let brute_force_log10 = if pool > 0.0 { pool.log10() } else { 0.0 };
+
+    let mut guesses_log10 = 0f64;
+    let mut patterns = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        match match_at(&chars, i, &dictionary, &common) {
+            Some((len, pattern_guesses_log10)) => {
+                guesses_log10 += pattern_guesses_log10;
+                patterns += 1;
+                i += len;
+            },
+            None => {
+                guesses_log10 += brute_force_log10;
+                i += 1;
+            }
+        }
+    }
+    // The attacker also has to guess how the patterns are put together.
+    if patterns > 1 {
+        guesses_log10 += (patterns as f64).log10();
+    }
+
+    let score = if guesses_log10 < 3.0 {
+        0
+    } else if guesses_log10 < 6.0 {
+        1
+    } else if guesses_log10 < 8.0 {
+        2
+    } else if guesses_log10 < 10.0 {
+        3
+    } else {
+        4
+    };
+
+    Strength {
+        score: score,
+        guesses_log10: guesses_log10,
+    }
+}
+
+/// Registers the option to refuse weak passwords.
+pub fn add_options(opts: &mut getopts::Options) {
+    opts.optopt("", "enforce-strength", "Refuse passwords with a strength score below this, from 0 to 4", "N");
+}
+
+/// The minimum score from the options, then from the environment, then from the
+/// configuration file.
+pub fn required_score(matches: &getopts::Matches) -> Result<Option<u8>, CommandError> {
+    let value = match matches.opt_str("enforce-strength") {
+        Some(value) => value,
+        None => { return default_score(); }
+    };
+    parse_score(&value)
+}
+
+/// The minimum score for commands without `--enforce-strength`, like `rooster ipc`.
+pub fn default_score() -> Result<Option<u8>, CommandError> {
+    if let Ok(value) = env::var(ENFORCE_STRENGTH_ENV_VAR) {
+        return parse_score(&value);
+    }
+    match config::load().ok().and_then(|config| config.enforce_strength) {
+        Some(score) if score > 4 => {
+            Err(CommandError::Failed(format!("the minimum strength in the configuration file must be from 0 to 4, not {}", score)))
+        },
+        score => Ok(score),
+    }
+}
+
+fn parse_score(value: &str) -> Result<Option<u8>, CommandError> {
+    match value.parse::<u8>() {
+        Ok(score) if score <= 4 => Ok(Some(score)),
+        _ => {
+            Err(CommandError::Failed(format!("the minimum strength must be a number from 0 to 4, not \"{}\"", value)))
+        }
+    }
+}
+
+/// Prints the strength of the password, and fails if it is below the required score.
+pub fn check(password: &str, required_score: Option<u8>) -> Result<(), CommandError> {
+    let strength = estimate(password);
+    println_info!(
+        "Password strength: {}/4 ({}), it would take about {} to crack.",
+        strength.score,
+        strength.label(),
+        strength.crack_time()
+    );
+    match required_score {
+        Some(required) if strength.score < required => {
+            Err(CommandError::Failed(format!("this password is too weak. Its score must be at least {}/4", required)))
+        },
+        _ => Ok(()),
+    }
+}
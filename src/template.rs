@@ -0,0 +1,91 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::config::{Config, TemplateConfig};
+use super::password::kind::{Kind, LOGIN};
+
+/// A field that a template asks for, stored as a custom field.
+pub struct TemplateField {
+    pub name: String,
+    /// Secret fields are typed without echo, like the password.
+    pub secret: bool,
+}
+
+/// The fields an entry should have, for `rooster add --template`.
+pub struct Template {
+    pub name: String,
+    pub kind: &'static Kind,
+    /// Asked for in this order, after the fields of the kind.
+    pub fields: Vec<TemplateField>,
+}
+
+/// Templates that are there without any configuration, as (name, kind, fields). Secret
+/// fields are marked with a leading '*'.
+const BUILTIN: &'static [(&'static str, &'static str, &'static [&'static str])] = &[
+    ("bank", "login", &["account number", "routing number", "*PIN"]),
+    ("server", "login", &["hostname", "port", "*root password"]),
+    ("wifi", "wifi", &[]),
+];
+
+impl Template {
+    fn from_config(name: &str, config: &TemplateConfig) -> Result<Template, String> {
+        let kind = match config.kind {
+            Some(ref kind_name) => {
+                match Kind::from_name(kind_name) {
+                    Some(kind) => kind,
+                    None => { return Err(format!("the template \"{}\" has an unknown kind \"{}\"", name, kind_name)); }
+                }
+            },
+            None => &LOGIN,
+        };
+        Ok(Template {
+            name: name.to_string(),
+            kind: kind,
+            fields: config.fields.iter().map(|field| TemplateField {
+                name: field.name.clone(),
+                secret: field.secret.unwrap_or(false),
+            }).collect(),
+        })
+    }
+}
+
+fn builtin() -> Vec<Template> {
+    BUILTIN.iter().map(|&(name, kind_name, fields)| Template {
+        name: name.to_string(),
+        kind: Kind::from_name(kind_name).unwrap(),
+        fields: fields.iter().map(|field| TemplateField {
+            name: field.trim_left_matches('*').to_string(),
+            secret: field.starts_with('*'),
+        }).collect(),
+    }).collect()
+}
+
+/// All the templates, sorted by name. Templates from the configuration file replace
+/// the built-in ones with the same name.
+pub fn all(config: &Config) -> Result<Vec<Template>, String> {
+    let mut templates = builtin();
+    if let Some(ref configured) = config.templates {
+        for (name, template_config) in configured.iter() {
+            let template = try!(Template::from_config(name, template_config));
+            templates.retain(|t| t.name != template.name);
+            templates.push(template);
+        }
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+pub fn find(config: &Config, name: &str) -> Result<Option<Template>, String> {
+    Ok(try!(all(config)).into_iter().find(|t| t.name == name))
+}
@@ -0,0 +1,95 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::timestamp::Timestamp;
+use std::cmp::min;
+use std::fs::{self, File};
+use std::io::{ErrorKind as IoErrorKind, Read, Result as IoResult, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Wrong master passwords allowed in a row before Rooster starts waiting.
+const FREE_ATTEMPTS: u32 = 3;
+
+/// The longest wait between two attempts, in seconds.
+const MAX_DELAY: u64 = 15 * 60;
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// With `--no-delay`, Rooster does not wait after wrong master passwords, but still
+/// counts them.
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// The count of wrong master passwords sits next to the password file.
+pub fn path(password_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.attempts", password_file))
+}
+
+/// Wrong master passwords in a row, and when the last one was typed. The file holds
+/// both as numbers on one line.
+fn read(path: &PathBuf) -> (u32, Timestamp) {
+    let mut content = String::new();
+    if File::open(path).and_then(|mut file| file.read_to_string(&mut content)).is_err() {
+        return (0, Timestamp::from_secs(0));
+    }
+    let mut numbers = content.split_whitespace().map(|n| n.parse::<u64>().ok());
+    match (numbers.next(), numbers.next()) {
+        (Some(Some(failures)), Some(Some(last))) => (failures as u32, Timestamp::from_secs(last)),
+        _ => (0, Timestamp::from_secs(0)),
+    }
+}
+
+/// Seconds to wait after this many wrong master passwords: nothing for the first
+/// few, then doubling from 1 second up to `MAX_DELAY`.
+fn delay(failures: u32) -> u64 {
+    if failures < FREE_ATTEMPTS {
+        return 0;
+    }
+    min(1u64.checked_shl(failures - FREE_ATTEMPTS).unwrap_or(MAX_DELAY), MAX_DELAY)
+}
+
+/// Waits until the password file may be decrypted again, after wrong master
+/// passwords. Only the time left since the last wrong one is waited.
+pub fn wait(path: &PathBuf) {
+    if DISABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let (failures, last) = read(path);
+    let elapsed = Timestamp::now().as_secs().saturating_sub(last.as_secs());
+    let left = delay(failures).saturating_sub(elapsed);
+    if left > 0 {
+        println_warn!("After {} wrong master passwords, I'll check this one in {} seconds.", failures, left);
+        thread::sleep(Duration::from_secs(left));
+    }
+}
+
+/// Counts a wrong master password.
+pub fn failed(path: &PathBuf) -> IoResult<()> {
+    let (failures, _) = read(path);
+    File::create(path).and_then(|mut file| {
+        file.write_all(format!("{} {}\n", failures.saturating_add(1), Timestamp::now().as_secs()).as_bytes())
+    })
+}
+
+/// Forgets wrong master passwords, once the right one was typed.
+pub fn succeeded(path: &PathBuf) -> IoResult<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == IoErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
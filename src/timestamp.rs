@@ -0,0 +1,88 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::ffi;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// A point in time, in seconds since the Unix epoch (UTC). It is a plain number in
+/// the password file, as the 32 bits `time_t` it replaces was, so files written
+/// before read the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => Timestamp(duration.as_secs()),
+            // The clock is set before 1970, there is no sensible time to use.
+            Err(_) => Timestamp(0),
+        }
+    }
+
+    pub fn from_secs(seconds: u64) -> Timestamp {
+        Timestamp(seconds)
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.0
+    }
+
+    /// Number of whole days since then, 0 if it is in the future.
+    pub fn days_ago(&self) -> u64 {
+        Timestamp::now().0.saturating_sub(self.0) / SECONDS_PER_DAY
+    }
+
+    /// The date and time in the local time zone, like "2017-03-25 14:02".
+    pub fn to_local_string(&self) -> String {
+        match ffi::localtime(self.0) {
+            Some((year, month, day, hour, minute)) => format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute),
+            None => format!("{} seconds after 1970", self.0),
+        }
+    }
+}
+
+/// Parses a date like "2024-03-01" into the start of that day in the local time zone.
+pub fn parse_date(date: &str) -> Option<Timestamp> {
+    let parts: Vec<&str> = date.trim().split('-').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    match (parts[0].parse::<i32>(), parts[1].parse::<u32>(), parts[2].parse::<u32>()) {
+        (Ok(year), Ok(month), Ok(day)) if month >= 1 && month <= 12 && day >= 1 && day <= 31 => {
+            ffi::mktime(year, month, day).map(Timestamp)
+        },
+        _ => None,
+    }
+}
+
+/// Parses a duration like "15m", "12h", "30d", "6w" or "1y" into seconds.
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+    if duration.len() < 2 || !duration.is_char_boundary(duration.len() - 1) {
+        return None;
+    }
+    let (count, unit) = duration.split_at(duration.len() - 1);
+    let unit_seconds = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => SECONDS_PER_DAY,
+        "w" => 7 * SECONDS_PER_DAY,
+        "y" => 365 * SECONDS_PER_DAY,
+        _ => return None,
+    };
+    count.parse::<u64>().ok().and_then(|count| count.checked_mul(unit_seconds))
+}
@@ -0,0 +1,27 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::env;
+
+/// Environment variable to record when passwords are read. This is off by default,
+/// because recording it means writing to the password file on every read.
+pub const TRACK_USAGE_ENV_VAR: &'static str = "ROOSTER_TRACK_USAGE";
+
+pub fn tracking_enabled() -> bool {
+    match env::var(TRACK_USAGE_ENV_VAR) {
+        Ok(value) => value == "true",
+        Err(_) => false,
+    }
+}
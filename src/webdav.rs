@@ -0,0 +1,110 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::session;
+use std::path::Path;
+use std::process::Stdio;
+
+/// Where curl writes what we don't need.
+#[cfg(unix)]
+const NULL_DEVICE: &'static str = "/dev/null";
+
+/// Where curl writes what we don't need.
+#[cfg(windows)]
+const NULL_DEVICE: &'static str = "NUL";
+
+/// What matters to us in an HTTP response.
+pub struct Response {
+    pub status: u32,
+    pub etag: Option<String>,
+}
+
+/// WebDAV is plain HTTP for our needs, GET and PUT on a single file. Requests are
+/// made with curl, which deals with TLS, proxies and ~/.netrc.
+pub struct WebDav {
+    url: String,
+    username: Option<String>,
+}
+
+impl WebDav {
+    pub fn new(url: String, username: Option<String>) -> WebDav {
+        WebDav {
+            url: url,
+            username: username,
+        }
+    }
+
+    fn request(&self, args: &[&str]) -> Result<Response, String> {
+        let mut command = session::command("curl");
+        command.args(&["--silent", "--show-error", "--netrc-optional", "--dump-header", "-", "--write-out", "\n%{http_code}"]);
+        if let Some(ref username) = self.username {
+            // Without a password after the colon, curl asks for it on the terminal.
+            command.arg("--user").arg(username);
+        }
+        command.args(args).arg(&self.url);
+
+        let output = try!(
+            command.stdin(Stdio::inherit()).stderr(Stdio::inherit()).output()
+                .map_err(|err| format!("could not run curl, is it installed? ({})", err))
+        );
+        if !output.status.success() {
+            return Err(format!("curl exited with {}", output.status));
+        }
+
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        let mut lines: Vec<&str> = text.lines().collect();
+        let status = try!(
+            lines.pop().and_then(|code| code.trim().parse::<u32>().ok()).ok_or("curl gave no HTTP status".to_string())
+        );
+        // Headers of redirects come first, the last ETag is the one that matters.
+        let etag = lines.iter().rev()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(name), Some(value)) if name.trim().to_lowercase() == "etag" => Some(value.trim().to_string()),
+                    _ => None,
+                }
+            })
+            .next();
+
+        Ok(Response {
+            status: status,
+            etag: etag,
+        })
+    }
+
+    /// Downloads the file to `output`, unless its ETag is still `if_none_match`.
+    pub fn get(&self, output: &Path, if_none_match: Option<&str>) -> Result<Response, String> {
+        let output = try!(output.to_str().ok_or("the path is not valid UTF-8".to_string()));
+        let header = if_none_match.map(|etag| format!("If-None-Match: {}", etag));
+        let mut args = vec!["--output", output];
+        if let Some(ref header) = header {
+            args.push("--header");
+            args.push(header);
+        }
+        self.request(&args)
+    }
+
+    /// Uploads the file at `input`, only if the remote file still has the ETag
+    /// `if_match`, or does not exist when there is none.
+    pub fn put(&self, input: &Path, if_match: Option<&str>) -> Result<Response, String> {
+        let input = try!(input.to_str().ok_or("the path is not valid UTF-8".to_string()));
+        let header = match if_match {
+            Some(etag) => format!("If-Match: {}", etag),
+            None => "If-None-Match: *".to_string(),
+        };
+        self.request(&["--upload-file", input, "--output", NULL_DEVICE, "--header", &header])
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright 2014 The Rooster Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HMAC-SHA1 challenge-response with a YubiKey, through the `ykchalresp` program of
+//! yubikey-personalization. The YubiKey slot must be set up for it, for example with:
+//!
+//! ```
+//! ykpersonalize -2 -ochal-resp -ochal-hmac -ohmac-lt64 -oserial-api-visible
+//! ```
+
+use super::rustc_serialize::hex::{FromHex, ToHex};
+use super::safe_string::SafeString;
+use super::safe_vec::SafeVec;
+use super::session;
+use std::env;
+use std::io::Write;
+use std::process::Stdio;
+
+/// Environment variable with the YubiKey slot to use, 2 by default.
+pub const SLOT_ENV_VAR: &'static str = "ROOSTER_YUBIKEY_SLOT";
+
+/// Sends the challenge to the YubiKey and returns its response. The YubiKey may wait for
+/// a touch.
+pub fn challenge_response(challenge: &[u8]) -> Result<SafeVec, String> {
+    let slot = env::var(SLOT_ENV_VAR).unwrap_or_else(|_| "2".to_string());
+    if slot != "1" && slot != "2" {
+        return Err(format!("${} must be 1 or 2, not \"{}\"", SLOT_ENV_VAR, slot));
+    }
+    println_stderr!("Touch your YubiKey if it blinks.");
+    let output = try!(session::command("ykchalresp")
+        .arg(format!("-{}", slot))
+        .arg("-x")
+        .arg(challenge.to_hex())
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("I could not run ykchalresp ({}), is yubikey-personalization installed?", err)));
+    if !output.status.success() {
+        return Err(format!("ykchalresp failed ({}), is your YubiKey plugged in?", output.status));
+    }
+    let response = SafeString::new(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    response.from_hex().map(SafeVec::new).map_err(|_| "ykchalresp gave an invalid response".to_string())
+}